@@ -1,7 +1,10 @@
 use std::error::Error;
+use crate::quorum_info::quorum_info::QuorumInfo;
+use atlas_common::crypto::threshold_crypto::{PrivateKeyPart, PublicKeySet};
 use atlas_common::node_id::NodeId;
 use atlas_common::serialization_helper::SerMsg;
 use atlas_communication::message::StoredMessage;
+use getset::Getters;
 
 /// A trait representing an asynchronous binary agreement protocol.
 ///
@@ -12,10 +15,23 @@ use atlas_communication::message::StoredMessage;
 /// See the [`AsyncBinaryAgreementResult`] enum for possible outcomes of the protocol a message.
 pub trait ABAProtocol {
 
-    type AsyncBinaryMessage: SerMsg;
-    type ABAError: Error + Send + Sync;
+    type AsyncBinaryMessage: SerMsg + VerifiableABAMessage;
+    type ABAError: ABAErrorKind + Error + Send + Sync + 'static;
 
-    fn new(input_bit: bool) -> Self;
+    /// Create an agreement run by the members of the given quorum, with our input to it
+    /// and the keys its common coin is flipped with
+    fn new(input_bit: bool, quorum_info: QuorumInfo, coin_keys: CoinKeys) -> Self;
+
+    /// The round the agreement is currently in
+    fn round(&self) -> usize;
+
+    /// Broadcast our estimate of the current round to the quorum.
+    ///
+    /// The agreement does not open its rounds by itself, so the orchestrator calls this once it
+    /// has created the agreement, and again whenever processing a message moved it to a new round.
+    fn broadcast_estimate<NT>(&self, network: &NT) -> Result<(), Self::ABAError>
+    where
+        NT: AsyncBinaryAgreementSendNode<Self::AsyncBinaryMessage>;
 
     /// Polls the protocol for new messages or decisions.
     /// Returns Some(AsyncBinaryAgreementResult) if there is a new message to send or
@@ -38,11 +54,61 @@ pub trait ABAProtocol {
     fn finalize(self) -> Result<bool, Self::ABAError>;
 }
 
+/// The threshold keys the common coin of an agreement is flipped with:
+/// the key set of the quorum and the key shares dealt to us,
+/// in the order given by [`QuorumInfo::key_shares`]
+#[derive(Debug, Clone, Getters)]
+pub struct CoinKeys {
+    #[get = "pub"]
+    public_key_set: PublicKeySet,
+    #[get = "pub"]
+    key_shares: Vec<PrivateKeyPart>,
+}
+
+impl CoinKeys {
+    pub fn new(public_key_set: PublicKeySet, key_shares: Vec<PrivateKeyPart>) -> Self {
+        Self {
+            public_key_set,
+            key_shares,
+        }
+    }
+}
+
+/// The kinds of failure of an agreement, as seen by the orchestrator running it.
+pub trait ABAErrorKind {
+    /// Whether the message was fully processed, and only broadcasting our resulting vote failed.
+    /// The agreement remains usable, so the orchestrator should carry on with it.
+    fn is_broadcast_failure(&self) -> bool;
+}
+
+/// Stateless checks of agreement messages, which can be performed before the message
+/// reaches the protocol instance (and therefore without any of its state).
+pub trait VerifiableABAMessage {
+    /// Whether the message is well formed, regardless of the state of the agreement
+    fn is_well_formed(&self) -> bool;
+
+    /// Whether the coin shares carried by the message, if any, were produced by the sender
+    /// with the key shares it holds under the given key set of the quorum.
+    /// Messages without coin shares are always valid.
+    fn has_valid_coin_share(
+        &self,
+        sender: NodeId,
+        quorum_info: &QuorumInfo,
+        key_set: &PublicKeySet,
+    ) -> bool;
+
+    /// Whether a node which sent this message contradicts itself by also sending the given one,
+    /// that is, both messages are different votes which may only be cast once
+    fn conflicts_with(&self, other: &Self) -> bool;
+}
+
 /// Represents the result of processing a message in the asynchronous binary agreement protocol.
 /// Indicates whether the message was queued, ignored, processed, or led to a decision.
 pub enum AsyncBinaryAgreementResult {
     MessageQueued,
     MessageIgnored,
+    /// The message was sent by a node outside the quorum, so it was dropped
+    NonMember,
     Processed,
     Decided,
 }
@@ -1,24 +1,26 @@
-use crate::aba::{ABAProtocol, AsyncBinaryAgreementResult, AsyncBinaryAgreementSendNode};
+use crate::aba::{
+    ABAErrorKind, ABAProtocol, AsyncBinaryAgreementResult, AsyncBinaryAgreementSendNode, CoinKeys,
+};
 use crate::async_bin_agreement::async_bin_agreement_round::{RoundData, RoundDataVoteAcceptResult};
 use crate::async_bin_agreement::messages::{
-    AsyncBinaryAgreementMessage, AsyncBinaryAgreementMessageType,
+    AsyncBinaryAgreementMessage, AsyncBinaryAgreementMessageType, is_valid_coin_share,
 };
 use crate::async_bin_agreement::pending_messages::PendingMessages;
+use crate::metrics::{ABA_ROUND_LATENCY_ID, ABA_ROUNDS_TO_DECIDE_ID};
 use crate::quorum_info::quorum_info::QuorumInfo;
 use atlas_common::crypto::threshold_crypto::{PartialSignature, PrivateKeyPart, PublicKeySet};
 use atlas_communication::message::StoredMessage;
+use atlas_metrics::metrics::{metric_duration, metric_store_count};
 use getset::{CopyGetters, Getters};
+use std::time::Instant;
 use thiserror::Error;
-
-/// Represents the keys used in the threshold cryptography for the asynchronous binary agreement.
-#[derive(Debug)]
-pub(super) struct ThresholdKeys(PublicKeySet, PrivateKeyPart);
+use tracing::{debug, debug_span};
 
 /// Represents the state of an asynchronous binary agreement protocol.
 /// It contains the current round, the input bit, the quorum information,
 /// the current round data, the previous rounds, and the pending messages.
 #[derive(Debug, Getters, CopyGetters)]
-pub(super) struct AsyncBinaryAgreement {
+pub(crate) struct AsyncBinaryAgreement {
     #[get_copy = "pub"]
     round: usize,
     input_bit: bool,
@@ -27,44 +29,103 @@ pub(super) struct AsyncBinaryAgreement {
     current_round: RoundData,
     previous_rounds: Vec<RoundData>,
     pending_messages: PendingMessages,
-    threshold_key: ThresholdKeys,
+    threshold_key: CoinKeys,
+    // The value decided by the protocol, once it has finalized
+    decision: Option<bool>,
+    // When the current round started, to measure its latency
+    round_started_at: Instant,
 }
 
 impl AsyncBinaryAgreement {
+    /// Create an agreement with our input to it, along with the key shares dealt to us,
+    /// in the order given by [`QuorumInfo::key_shares`]
     pub fn new(
         input_bit: bool,
         quorum_info: QuorumInfo,
         public_key_set: PublicKeySet,
-        threshold_key: PrivateKeyPart,
+        key_shares: Vec<PrivateKeyPart>,
     ) -> Self {
-        let f = quorum_info.f();
+        Self::with_coin_keys(
+            input_bit,
+            quorum_info,
+            CoinKeys::new(public_key_set, key_shares),
+        )
+    }
 
+    fn with_coin_keys(input_bit: bool, quorum_info: QuorumInfo, coin_keys: CoinKeys) -> Self {
         Self {
             round: 0,
             input_bit,
+            current_round: RoundData::new(
+                quorum_info.clone(),
+                coin_keys.public_key_set().clone(),
+                input_bit,
+            ),
             quorum_info,
-            current_round: RoundData::new(f, public_key_set.clone(), input_bit),
             previous_rounds: Vec::new(),
             pending_messages: PendingMessages::default(),
-            threshold_key: ThresholdKeys(public_key_set, threshold_key),
+            threshold_key: coin_keys,
+            decision: None,
+            round_started_at: Instant::now(),
         }
     }
 
     pub(super) fn advance_round(&mut self, next_estimate: bool) {
-        let f = self.quorum_info.f();
-
-        let new_round = RoundData::new(f, self.threshold_key.0.clone(), next_estimate);
+        let new_round = RoundData::new(
+            self.quorum_info.clone(),
+            self.threshold_key.public_key_set().clone(),
+            next_estimate,
+        );
         let old_round = std::mem::replace(&mut self.current_round, new_round);
 
         self.previous_rounds.push(old_round);
 
+        metric_duration(ABA_ROUND_LATENCY_ID, self.round_started_at.elapsed());
+
         self.round += 1;
+        self.round_started_at = Instant::now();
+
+        debug!(round = self.round, estimate = next_estimate, "Binary agreement moved to the next round");
+    }
+
+    /// Broadcast our vote of the current round to the quorum.
+    ///
+    /// The message which led to the vote has been fully processed by the time this fails,
+    /// so the agreement remains usable, but the members which missed our vote may need
+    /// to receive it from us again to make progress.
+    fn broadcast<NT>(
+        &self,
+        message_type: AsyncBinaryAgreementMessageType,
+        network: &NT,
+    ) -> Result<(), ABAError>
+    where
+        NT: AsyncBinaryAgreementSendNode<AsyncBinaryAgreementMessage>,
+    {
+        let vote = match &message_type {
+            AsyncBinaryAgreementMessageType::Val { .. } => "estimate",
+            AsyncBinaryAgreementMessageType::Aux { .. } => "auxiliary",
+            AsyncBinaryAgreementMessageType::Conf { .. } => "confirmation",
+            AsyncBinaryAgreementMessageType::Finish { .. } => "finish",
+        };
+
+        let message = AsyncBinaryAgreementMessage::new(message_type, self.round);
+
+        network
+            .broadcast_message(message, self.quorum_info.quorum_members().iter().cloned())
+            .map_err(|source| ABAError::BroadcastFailed {
+                round: self.round,
+                vote,
+                source,
+            })
     }
 
-    fn calculate_threshold_signature_for_round(&self, round: usize) -> PartialSignature {
+    /// Our coin shares of the given round, one for each of our key shares
+    fn calculate_threshold_signature_for_round(&self, round: usize) -> Vec<PartialSignature> {
         self.threshold_key
-            .1
-            .partially_sign(&round.to_le_bytes()[..])
+            .key_shares()
+            .iter()
+            .map(|key_share| key_share.partially_sign(&round.to_le_bytes()[..]))
+            .collect()
     }
 }
 
@@ -72,8 +133,21 @@ impl ABAProtocol for AsyncBinaryAgreement {
     type AsyncBinaryMessage = AsyncBinaryAgreementMessage;
     type ABAError = ABAError;
 
-    fn new(input_bit: bool) -> Self {
-        unimplemented!("Use the new function with quorum info and threshold keys")
+    fn new(input_bit: bool, quorum_info: QuorumInfo, coin_keys: CoinKeys) -> Self {
+        Self::with_coin_keys(input_bit, quorum_info, coin_keys)
+    }
+
+    fn round(&self) -> usize {
+        self.round
+    }
+
+    fn broadcast_estimate<NT>(&self, network: &NT) -> Result<(), ABAError>
+    where
+        NT: AsyncBinaryAgreementSendNode<Self::AsyncBinaryMessage>,
+    {
+        let estimate = *self.current_round.estimate();
+
+        self.broadcast(AsyncBinaryAgreementMessageType::Val { estimate }, network)
     }
 
     fn poll(&mut self) -> Option<StoredMessage<Self::AsyncBinaryMessage>> {
@@ -88,6 +162,13 @@ impl ABAProtocol for AsyncBinaryAgreement {
     where
         NT: AsyncBinaryAgreementSendNode<Self::AsyncBinaryMessage>,
     {
+        let _span = debug_span!("round", round = self.round).entered();
+
+        if !self.quorum_info.is_member(message.header().from()) {
+            // Checked before queueing, so nodes outside the quorum can not fill our future rounds
+            return Ok(AsyncBinaryAgreementResult::NonMember);
+        }
+
         let round = message.message().round();
 
         if round > self.round {
@@ -115,10 +196,22 @@ impl ABAProtocol for AsyncBinaryAgreement {
                 .accept_auxiliary(sender, accepted_estimates),
             AsyncBinaryAgreementMessageType::Conf {
                 feasible_values,
-                partial_signature,
-            } => self
-                .current_round
-                .accept_confirmation(sender, feasible_values, partial_signature),
+                partial_signatures,
+            } => {
+                if !is_valid_coin_share(
+                    self.threshold_key.public_key_set(),
+                    &self.quorum_info,
+                    sender,
+                    round,
+                    &partial_signatures,
+                ) {
+                    // A share which does not verify would make the coin impossible to combine
+                    return Ok(AsyncBinaryAgreementResult::MessageIgnored);
+                }
+
+                self.current_round
+                    .accept_confirmation(sender, feasible_values, partial_signatures)
+            }
             AsyncBinaryAgreementMessageType::Finish { value } => {
                 self.current_round.accept_finish(sender, value)
             }
@@ -132,74 +225,45 @@ impl ABAProtocol for AsyncBinaryAgreement {
                 AsyncBinaryAgreementResult::Processed
             }
             RoundDataVoteAcceptResult::Finalized(result) => {
+                if self.decision.is_none() {
+                    debug!(value = result, "Binary agreement decided");
+
+                    metric_duration(ABA_ROUND_LATENCY_ID, self.round_started_at.elapsed());
+                    metric_store_count(ABA_ROUNDS_TO_DECIDE_ID, self.round + 1);
+                }
+
+                self.decision = Some(result);
+
                 AsyncBinaryAgreementResult::Decided
             }
             RoundDataVoteAcceptResult::BroadcastEst(estimate) => {
-                // If we are collecting echoes, we broadcast the estimate
-                let est_message = AsyncBinaryAgreementMessage::new(
-                    AsyncBinaryAgreementMessageType::Val { estimate },
-                    self.round,
-                );
-
-                network
-                    .broadcast_message(
-                        est_message,
-                        self.quorum_info.quorum_members().iter().cloned(),
-                    )
-                    .expect("Failed to broadcast estimate message");
+                self.broadcast(AsyncBinaryAgreementMessageType::Val { estimate }, network)?;
 
                 AsyncBinaryAgreementResult::Processed
             }
             RoundDataVoteAcceptResult::BroadcastAux(accepted_estimates) => {
-                // If we are collecting echoes, we broadcast the estimate
-                let est_message = AsyncBinaryAgreementMessage::new(
+                self.broadcast(
                     AsyncBinaryAgreementMessageType::Aux { accepted_estimates },
-                    self.round,
-                );
-
-                network
-                    .broadcast_message(
-                        est_message,
-                        self.quorum_info.quorum_members().iter().cloned(),
-                    )
-                    .expect("Failed to broadcast estimate message");
+                    network,
+                )?;
 
                 AsyncBinaryAgreementResult::Processed
             }
             RoundDataVoteAcceptResult::BroadcastConf(feasible_values) => {
-                // If we are collecting echoes, we broadcast the estimate
-                let partial_signature = self.calculate_threshold_signature_for_round(self.round);
+                let partial_signatures = self.calculate_threshold_signature_for_round(self.round);
 
-                let conf_message = AsyncBinaryAgreementMessage::new(
+                self.broadcast(
                     AsyncBinaryAgreementMessageType::Conf {
                         feasible_values,
-                        partial_signature,
+                        partial_signatures,
                     },
-                    self.round,
-                );
-
-                network
-                    .broadcast_message(
-                        conf_message,
-                        self.quorum_info.quorum_members().iter().cloned(),
-                    )
-                    .expect("Failed to broadcast confirmation message");
+                    network,
+                )?;
 
                 AsyncBinaryAgreementResult::Processed
             }
             RoundDataVoteAcceptResult::BroadcastFinalized(value) => {
-                // If we are collecting echoes, we broadcast the estimate
-                let finish_message = AsyncBinaryAgreementMessage::new(
-                    AsyncBinaryAgreementMessageType::Finish { value },
-                    self.round,
-                );
-
-                network
-                    .broadcast_message(
-                        finish_message,
-                        self.quorum_info.quorum_members().iter().cloned(),
-                    )
-                    .expect("Failed to broadcast finalized message");
+                self.broadcast(AsyncBinaryAgreementMessageType::Finish { value }, network)?;
 
                 AsyncBinaryAgreementResult::Processed
             }
@@ -208,6 +272,7 @@ impl ABAProtocol for AsyncBinaryAgreement {
                 self.pending_messages.add_message(self.round, message);
                 AsyncBinaryAgreementResult::MessageQueued
             }
+            RoundDataVoteAcceptResult::NonMember => AsyncBinaryAgreementResult::NonMember,
             RoundDataVoteAcceptResult::Ignored | RoundDataVoteAcceptResult::AlreadyAccepted => {
                 AsyncBinaryAgreementResult::MessageIgnored
             }
@@ -215,12 +280,25 @@ impl ABAProtocol for AsyncBinaryAgreement {
     }
 
     fn finalize(self) -> Result<bool, Self::ABAError> {
-        todo!()
+        self.decision.ok_or(ABAError::FailedToFinalizeNotReady)
     }
 }
 
 #[derive(Error, Debug)]
 pub enum ABAError {
     #[error("The aba protocol has failed to finalize as it is not ready to do so")]
-    FailedToFinalizeNotReady
-}
\ No newline at end of file
+    FailedToFinalizeNotReady,
+    /// The message was processed, but our resulting vote did not reach the whole quorum
+    #[error("Failed to broadcast our {vote} vote of round {round}")]
+    BroadcastFailed {
+        round: usize,
+        vote: &'static str,
+        source: anyhow::Error,
+    },
+}
+
+impl ABAErrorKind for ABAError {
+    fn is_broadcast_failure(&self) -> bool {
+        matches!(self, ABAError::BroadcastFailed { .. })
+    }
+}
@@ -1,19 +1,23 @@
+use crate::metrics::ABA_COIN_FAILURES_ID;
+use crate::quorum_info::quorum_info::QuorumInfo;
 use atlas_common::collections::{HashMap, HashSet, LinkedHashMap};
 use atlas_common::crypto::hash::{Context, Digest};
 use atlas_common::crypto::threshold_crypto::{
     CombineSignatureError, PartialSignature, PublicKeySet,
 };
 use atlas_common::node_id::NodeId;
+use atlas_metrics::metrics::metric_increment;
 use getset::Getters;
+use tracing::{debug, warn};
 
 /// Represents the state of the asynchronous binary agreement round.
 /// It contains the current state, the quorum size, the estimate, and the received votes.
-#[derive(Debug, Getters)]
+#[derive(Debug, Clone, Getters)]
 pub(super) struct RoundData {
     #[get = "pub"]
     state: AsyncBinaryAgreementState,
-    // The quorum size 2f + 1, where f is the maximum number of faulty nodes for this round
-    f: usize,
+    // The quorum running this round, where f is the maximum number of faulty nodes
+    quorum_info: QuorumInfo,
     pub_key: PublicKeySet,
     #[get = "pub"]
     estimate: bool,
@@ -26,10 +30,10 @@ pub(super) struct RoundData {
 }
 
 impl RoundData {
-    pub fn new(f: usize, pub_key_set: PublicKeySet, estimate: bool) -> Self {
+    pub fn new(quorum_info: QuorumInfo, pub_key_set: PublicKeySet, estimate: bool) -> Self {
         Self {
             state: AsyncBinaryAgreementState::default(),
-            f,
+            quorum_info,
             pub_key: pub_key_set,
             estimate,
             values_r: HashSet::default(),
@@ -40,11 +44,95 @@ impl RoundData {
         }
     }
 
+    fn transition(&mut self, next: AsyncBinaryAgreementState) {
+        debug!(from = ?self.state, to = ?next, "Binary agreement phase change");
+
+        self.state = next;
+    }
+
+    /// Whether a message of the given type can no longer have any effect on this round
+    #[cfg(test)]
+    pub(super) fn ignores(
+        &self,
+        message_type: &crate::async_bin_agreement::messages::AsyncBinaryAgreementMessageType,
+    ) -> bool {
+        use crate::async_bin_agreement::messages::AsyncBinaryAgreementMessageType;
+
+        match message_type {
+            AsyncBinaryAgreementMessageType::Val { .. } => {
+                self.state != AsyncBinaryAgreementState::CollectingVal
+            }
+            AsyncBinaryAgreementMessageType::Aux { .. } => matches!(
+                self.state,
+                AsyncBinaryAgreementState::CollectingConf | AsyncBinaryAgreementState::Finishing
+            ),
+            AsyncBinaryAgreementMessageType::Conf { .. } => {
+                self.state == AsyncBinaryAgreementState::Finishing
+            }
+            AsyncBinaryAgreementMessageType::Finish { .. } => false,
+        }
+    }
+
+    /// Feed into `hasher` the part of the state which can still influence this round,
+    /// so the model checker can merge the schedules which lead to the same state.
+    /// The votes of the phases we are done with are left out, as they are never read again.
+    #[cfg(test)]
+    pub(super) fn fingerprint<H: std::hash::Hasher>(&self, hasher: &mut H) {
+        use std::hash::Hash;
+
+        fn sorted<T: Ord + Clone>(values: impl Iterator<Item = T>) -> Vec<T> {
+            let mut values = values.collect::<Vec<_>>();
+            values.sort();
+            values
+        }
+
+        fn votes<'a, K, V>(votes: impl Iterator<Item = (&'a K, V)>) -> Vec<(K, Vec<NodeId>)>
+        where
+            K: Ord + Clone + 'a,
+            V: IntoIterator<Item = &'a NodeId>,
+        {
+            sorted(
+                votes.map(|(value, senders)| (value.clone(), sorted(senders.into_iter().cloned()))),
+            )
+        }
+
+        self.state.hash(hasher);
+        self.estimate.hash(hasher);
+        sorted(self.values_r.iter().cloned()).hash(hasher);
+        sorted(self.finish_round_data.broadcast_finish.iter().cloned()).hash(hasher);
+
+        match self.state {
+            AsyncBinaryAgreementState::CollectingVal => {
+                votes(self.val_data.received_vals.iter()).hash(hasher);
+                sorted(self.val_data.broadcast_estimates.iter().cloned()).hash(hasher);
+            }
+            AsyncBinaryAgreementState::CollectingAux => {
+                votes(self.aux_round_data.received_aux.iter()).hash(hasher);
+            }
+            AsyncBinaryAgreementState::CollectingConf => {
+                votes(
+                    self.conf_round_data
+                        .received_conf
+                        .iter()
+                        .map(|(values, signatures)| (values, signatures.keys())),
+                )
+                .hash(hasher);
+            }
+            AsyncBinaryAgreementState::Finishing => {
+                votes(self.finish_round_data.received_finish.iter()).hash(hasher);
+            }
+        }
+    }
+
     pub(super) fn accept_estimate(
         &mut self,
         sender: NodeId,
         estimate: bool,
     ) -> RoundDataVoteAcceptResult {
+        if !self.quorum_info.is_member(sender) {
+            return RoundDataVoteAcceptResult::NonMember;
+        }
+
         match self.state {
             AsyncBinaryAgreementState::CollectingVal => self.insert_estimate(sender, estimate),
             // If we are collecting ready messages, we ignore the estimate as we already completed it
@@ -53,22 +141,28 @@ impl RoundData {
     }
 
     fn insert_estimate(&mut self, sender: NodeId, estimate: bool) -> RoundDataVoteAcceptResult {
-        let current_votes = match self.val_data.insert_estimate(sender, estimate) {
-            Ok(current_votes) => current_votes,
-            Err(_) => return RoundDataVoteAcceptResult::AlreadyAccepted,
-        };
+        let current_weight =
+            match self
+                .val_data
+                .insert_estimate(&self.quorum_info, sender, estimate)
+            {
+                Ok(current_weight) => current_weight,
+                Err(_) => return RoundDataVoteAcceptResult::AlreadyAccepted,
+            };
 
-        if current_votes > 2 * self.f {
+        if current_weight > 2 * self.quorum_info.f() {
             self.values_r.insert(estimate);
 
-            self.state = AsyncBinaryAgreementState::CollectingAux;
+            self.transition(AsyncBinaryAgreementState::CollectingAux);
 
             return RoundDataVoteAcceptResult::BroadcastAux(
                 self.values_r.clone().into_iter().collect(),
             );
         }
 
-        if current_votes > self.f && self.val_data.broadcast_estimates.insert(estimate) {
+        if current_weight > self.quorum_info.f()
+            && self.val_data.broadcast_estimates.insert(estimate)
+        {
             // Broadcast the estimate to all nodes
             return RoundDataVoteAcceptResult::BroadcastEst(estimate);
         }
@@ -81,6 +175,10 @@ impl RoundData {
         sender: NodeId,
         accepted_estimates: Vec<bool>,
     ) -> RoundDataVoteAcceptResult {
+        if !self.quorum_info.is_member(sender) {
+            return RoundDataVoteAcceptResult::NonMember;
+        }
+
         match self.state {
             AsyncBinaryAgreementState::CollectingAux => self.insert_aux(sender, accepted_estimates),
             AsyncBinaryAgreementState::CollectingVal => RoundDataVoteAcceptResult::Queue,
@@ -95,21 +193,22 @@ impl RoundData {
         sender: NodeId,
         accepted_estimates: Vec<bool>,
     ) -> RoundDataVoteAcceptResult {
-        let vote_count = match self
-            .aux_round_data
-            .insert_aux(sender, accepted_estimates.clone())
-        {
+        let vote_weight = match self.aux_round_data.insert_aux(
+            &self.quorum_info,
+            sender,
+            accepted_estimates.clone(),
+        ) {
             Ok(votes) => votes,
             Err(_) => return RoundDataVoteAcceptResult::AlreadyAccepted,
         };
 
         let accepted_estimates = accepted_estimates.into_iter().collect::<HashSet<_>>();
 
-        if vote_count > 2 * self.f
+        if vote_weight > 2 * self.quorum_info.f()
             && (self.values_r.is_superset(&accepted_estimates)
                 || self.values_r.eq(&accepted_estimates))
         {
-            self.state = AsyncBinaryAgreementState::CollectingConf;
+            self.transition(AsyncBinaryAgreementState::CollectingConf);
 
             return RoundDataVoteAcceptResult::BroadcastConf(
                 self.values_r.clone().into_iter().collect(),
@@ -123,11 +222,15 @@ impl RoundData {
         &mut self,
         sender: NodeId,
         feasible_values: Vec<bool>,
-        signature: PartialSignature,
+        signatures: Vec<PartialSignature>,
     ) -> RoundDataVoteAcceptResult {
+        if !self.quorum_info.is_member(sender) {
+            return RoundDataVoteAcceptResult::NonMember;
+        }
+
         match self.state {
             AsyncBinaryAgreementState::CollectingConf => {
-                self.insert_confirmation(sender, feasible_values, signature)
+                self.insert_confirmation(sender, feasible_values, signatures)
             }
             AsyncBinaryAgreementState::CollectingAux | AsyncBinaryAgreementState::CollectingVal => {
                 RoundDataVoteAcceptResult::Queue
@@ -140,29 +243,44 @@ impl RoundData {
         &mut self,
         sender: NodeId,
         feasible_values: Vec<bool>,
-        partial_signature: PartialSignature,
+        partial_signatures: Vec<PartialSignature>,
     ) -> RoundDataVoteAcceptResult {
-        let vote_count = match self.conf_round_data.insert_confirmation(
+        let vote_weight = match self.conf_round_data.insert_confirmation(
+            &self.quorum_info,
             sender,
             feasible_values.clone(),
-            partial_signature,
+            partial_signatures,
         ) {
             Ok(votes) => votes,
             Err(_) => return RoundDataVoteAcceptResult::AlreadyAccepted,
         };
 
-        if vote_count > 2 * self.f {
+        // Besides the weight of the confirmations, the coin needs enough shares to be combined.
+        // Each member sends a share for every key share it holds, so shares accumulate by weight
+        let share_count = self
+            .conf_round_data
+            .received_conf
+            .get(&feasible_values)
+            .map_or(0, |signatures| signatures.values().map(Vec::len).sum());
+
+        if vote_weight > 2 * self.quorum_info.f() && share_count > self.pub_key.threshold() {
             let feasible_value_set = feasible_values.iter().cloned().collect::<HashSet<_>>();
 
             if self.values_r.is_superset(&feasible_value_set) || self.values_r == feasible_value_set
             {
                 let signatures = self
                     .conf_round_data
-                    .get_signatures_for_values(&feasible_values);
+                    .get_signatures_for_values(&self.quorum_info, &feasible_values);
 
                 return self
                     .perform_coin_flip(&feasible_values, signatures)
-                    .unwrap_or(RoundDataVoteAcceptResult::Failed(self.estimate));
+                    .unwrap_or_else(|err| {
+                        warn!("Failed to combine the coin shares of the round: {err:?}");
+
+                        metric_increment(ABA_COIN_FAILURES_ID, Some(1));
+
+                        RoundDataVoteAcceptResult::Failed(self.estimate)
+                    });
             }
         }
 
@@ -172,28 +290,11 @@ impl RoundData {
     fn perform_coin_flip(
         &mut self,
         winning_set: &Vec<bool>,
-        partial_signature: Vec<(NodeId, PartialSignature)>,
+        signatures: Vec<(usize, PartialSignature)>,
     ) -> Result<RoundDataVoteAcceptResult, CombineSignatureError> {
-        let signatures = partial_signature
-            .iter()
-            .map(|(node, sig)| (node.0 as usize, sig));
-
-        let combined_signature = self.pub_key.combine_signatures(signatures)?;
-
-        // I want to hash the combined signature to get a deterministic value
-        // and then use that value to % 2 to get the coin flip result
-        let mut hash_ctx = Context::new();
+        let coin_flip_result = common_coin(&self.pub_key, &signatures)?;
 
-        // I will need to serialize the combined signature
-        let serialized_sig =
-            bincode::serde::encode_to_vec(&combined_signature, bincode::config::standard())
-                .expect("Failed to serialize combined signature");
-
-        hash_ctx.update(&serialized_sig);
-
-        let hash = hash_ctx.finish();
-
-        let coin_flip_result = hash.as_ref()[Digest::LENGTH - 1] % 2 == 0;
+        debug!(coin = coin_flip_result, ?winning_set, "Common coin flipped");
 
         if winning_set.len() != 1 {
             // If the winning set is not a single value, we ignore it,
@@ -203,7 +304,7 @@ impl RoundData {
 
         if winning_set[0] == coin_flip_result {
             // If the winning set is the same as the coin flip result, we finalize
-            self.state = AsyncBinaryAgreementState::Finishing;
+            self.transition(AsyncBinaryAgreementState::Finishing);
             self.estimate = coin_flip_result;
 
             if self.finish_round_data.try_register_broadcast(self.estimate) {
@@ -223,6 +324,10 @@ impl RoundData {
         sender: NodeId,
         final_value: bool,
     ) -> RoundDataVoteAcceptResult {
+        if !self.quorum_info.is_member(sender) {
+            return RoundDataVoteAcceptResult::NonMember;
+        }
+
         match self.state {
             AsyncBinaryAgreementState::Finishing => self.insert_finish(sender, final_value),
             AsyncBinaryAgreementState::CollectingAux
@@ -232,14 +337,19 @@ impl RoundData {
     }
 
     fn insert_finish(&mut self, sender: NodeId, final_value: bool) -> RoundDataVoteAcceptResult {
-        let vote_count = match self.finish_round_data.insert_finish(sender, final_value) {
-            Ok(votes) => votes,
-            Err(_) => return RoundDataVoteAcceptResult::AlreadyAccepted,
-        };
+        let vote_weight =
+            match self
+                .finish_round_data
+                .insert_finish(&self.quorum_info, sender, final_value)
+            {
+                Ok(votes) => votes,
+                Err(_) => return RoundDataVoteAcceptResult::AlreadyAccepted,
+            };
 
-        if vote_count > 2 * self.f {
+        if vote_weight > 2 * self.quorum_info.f() {
             return RoundDataVoteAcceptResult::Finalized(final_value);
-        } else if vote_count > self.f && self.finish_round_data.try_register_broadcast(final_value)
+        } else if vote_weight > self.quorum_info.f()
+            && self.finish_round_data.try_register_broadcast(final_value)
         {
             return RoundDataVoteAcceptResult::BroadcastFinalized(final_value);
         }
@@ -258,11 +368,17 @@ struct ValRoundData {
 }
 
 impl ValRoundData {
-    fn insert_estimate(&mut self, sender: NodeId, estimate: bool) -> Result<usize, ()> {
+    /// Register the estimate of `sender`, returning the weight which voted for it
+    fn insert_estimate(
+        &mut self,
+        quorum_info: &QuorumInfo,
+        sender: NodeId,
+        estimate: bool,
+    ) -> Result<usize, ()> {
         let entry = self.received_vals.entry(estimate).or_default();
 
         if entry.insert(sender) {
-            Ok(entry.len())
+            Ok(quorum_info.accumulated_weight(entry.iter()))
         } else {
             Err(())
         }
@@ -277,11 +393,17 @@ struct AuxRoundData {
 }
 
 impl AuxRoundData {
-    fn insert_aux(&mut self, sender: NodeId, accepted_estimates: Vec<bool>) -> Result<usize, ()> {
+    /// Register the auxiliary vote of `sender`, returning the weight which voted for the same values
+    fn insert_aux(
+        &mut self,
+        quorum_info: &QuorumInfo,
+        sender: NodeId,
+        accepted_estimates: Vec<bool>,
+    ) -> Result<usize, ()> {
         let entry = self.received_aux.entry(accepted_estimates).or_default();
 
         if entry.insert(sender) {
-            Ok(entry.len())
+            Ok(quorum_info.accumulated_weight(entry.iter()))
         } else {
             Err(())
         }
@@ -291,31 +413,38 @@ impl AuxRoundData {
 #[derive(Debug, Clone, Default, Getters)]
 struct ConfRoundData {
     #[get = "pub"]
-    received_conf: LinkedHashMap<Vec<bool>, HashMap<NodeId, PartialSignature>>,
+    received_conf: LinkedHashMap<Vec<bool>, HashMap<NodeId, Vec<PartialSignature>>>,
 }
 
 impl ConfRoundData {
+    /// Register the confirmation of `sender`, returning the weight which confirmed the same values
     fn insert_confirmation(
         &mut self,
+        quorum_info: &QuorumInfo,
         sender: NodeId,
         feasible_values: Vec<bool>,
-        partial_signature: PartialSignature,
+        partial_signatures: Vec<PartialSignature>,
     ) -> Result<usize, ()> {
         let entry = self.received_conf.entry(feasible_values).or_default();
 
         if let std::collections::hash_map::Entry::Vacant(e) = entry.entry(sender) {
-            e.insert(partial_signature);
-            Ok(entry.len())
+            e.insert(partial_signatures);
+            Ok(quorum_info.accumulated_weight(entry.keys()))
         } else {
             Err(())
         }
     }
 
-    fn get_signatures_for_values(&self, values: &Vec<bool>) -> Vec<(NodeId, PartialSignature)> {
+    /// The coin shares received along with the given values, by the index of their key share
+    fn get_signatures_for_values(
+        &self,
+        quorum_info: &QuorumInfo,
+        values: &Vec<bool>,
+    ) -> Vec<(usize, PartialSignature)> {
         if let Some(signatures) = self.received_conf.get(values) {
             signatures
                 .iter()
-                .map(|(node, sig)| (*node, sig.clone()))
+                .flat_map(|(node, sigs)| quorum_info.key_shares(*node).zip(sigs.iter().cloned()))
                 .collect()
         } else {
             vec![]
@@ -331,11 +460,17 @@ struct FinishRoundData {
 }
 
 impl FinishRoundData {
-    fn insert_finish(&mut self, sender: NodeId, final_value: bool) -> Result<usize, ()> {
+    /// Register the finish vote of `sender`, returning the weight which voted for the same value
+    fn insert_finish(
+        &mut self,
+        quorum_info: &QuorumInfo,
+        sender: NodeId,
+        final_value: bool,
+    ) -> Result<usize, ()> {
         let entry = self.received_finish.entry(final_value).or_default();
 
         if entry.insert(sender) {
-            Ok(entry.len())
+            Ok(quorum_info.accumulated_weight(entry.iter()))
         } else {
             Err(())
         }
@@ -346,6 +481,31 @@ impl FinishRoundData {
     }
 }
 
+/// Combine the coin shares of a round, keyed by the index of their key share, into its common coin
+pub(super) fn common_coin(
+    pub_key: &PublicKeySet,
+    partial_signatures: &[(usize, PartialSignature)],
+) -> Result<bool, CombineSignatureError> {
+    let signatures = partial_signatures.iter().map(|(index, sig)| (*index, sig));
+
+    let combined_signature = pub_key.combine_signatures(signatures)?;
+
+    // I want to hash the combined signature to get a deterministic value
+    // and then use that value to % 2 to get the coin flip result
+    let mut hash_ctx = Context::new();
+
+    // I will need to serialize the combined signature
+    let serialized_sig =
+        bincode::serde::encode_to_vec(&combined_signature, bincode::config::standard())
+            .expect("Failed to serialize combined signature");
+
+    hash_ctx.update(&serialized_sig);
+
+    let hash = hash_ctx.finish();
+
+    Ok(hash.as_ref()[Digest::LENGTH - 1] % 2 == 0)
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
 pub(super) enum AsyncBinaryAgreementState {
     #[default]
@@ -364,6 +524,8 @@ pub(super) enum RoundDataVoteAcceptResult {
     BroadcastConf(Vec<bool>),
     BroadcastFinalized(bool),
     Ignored,
+    /// The vote was sent by a node outside the quorum
+    NonMember,
     AlreadyAccepted,
     Queue,
     Failed(bool),
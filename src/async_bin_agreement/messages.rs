@@ -1,30 +1,33 @@
-use atlas_common::crypto::threshold_crypto::PartialSignature;
+use crate::aba::VerifiableABAMessage;
+use crate::quorum_info::quorum_info::QuorumInfo;
+use atlas_common::crypto::threshold_crypto::{PartialSignature, PublicKeySet};
+use atlas_common::node_id::NodeId;
 use getset::{CopyGetters, Getters};
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, PartialEq, Eq, Getters, CopyGetters, Serialize, Deserialize)]
-pub(super) struct AsyncBinaryAgreementMessage {
-    #[get_copy = "pub(super)"]
+pub(crate) struct AsyncBinaryAgreementMessage {
+    #[get_copy = "pub(crate)"]
     round: usize,
     #[get = "pub"]
     message_type: AsyncBinaryAgreementMessageType,
 }
 
 impl AsyncBinaryAgreementMessage {
-    pub(super) fn new(message_type: AsyncBinaryAgreementMessageType, round: usize) -> Self {
+    pub(crate) fn new(message_type: AsyncBinaryAgreementMessageType, round: usize) -> Self {
         Self {
             message_type,
             round,
         }
     }
 
-    pub(super) fn into_inner(self) -> (usize, AsyncBinaryAgreementMessageType) {
+    pub(crate) fn into_inner(self) -> (usize, AsyncBinaryAgreementMessageType) {
         (self.round, self.message_type)
     }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
-pub(super) enum AsyncBinaryAgreementMessageType {
+pub(crate) enum AsyncBinaryAgreementMessageType {
     Val {
         estimate: bool,
     },
@@ -33,9 +36,90 @@ pub(super) enum AsyncBinaryAgreementMessageType {
     },
     Conf {
         feasible_values: Vec<bool>,
-        partial_signature: PartialSignature,
+        /// The coin shares of the sender, one for each of its key shares
+        partial_signatures: Vec<PartialSignature>,
     },
     Finish {
         value: bool,
     },
 }
+
+impl VerifiableABAMessage for AsyncBinaryAgreementMessage {
+    fn is_well_formed(&self) -> bool {
+        match &self.message_type {
+            AsyncBinaryAgreementMessageType::Val { .. }
+            | AsyncBinaryAgreementMessageType::Finish { .. } => true,
+            AsyncBinaryAgreementMessageType::Aux { accepted_estimates } => {
+                is_valid_value_set(accepted_estimates)
+            }
+            AsyncBinaryAgreementMessageType::Conf {
+                feasible_values, ..
+            } => is_valid_value_set(feasible_values),
+        }
+    }
+
+    fn has_valid_coin_share(
+        &self,
+        sender: NodeId,
+        quorum_info: &QuorumInfo,
+        key_set: &PublicKeySet,
+    ) -> bool {
+        match &self.message_type {
+            AsyncBinaryAgreementMessageType::Conf {
+                partial_signatures, ..
+            } => is_valid_coin_share(key_set, quorum_info, sender, self.round, partial_signatures),
+            _ => true,
+        }
+    }
+
+    fn conflicts_with(&self, other: &Self) -> bool {
+        match (&self.message_type, &other.message_type) {
+            // A node only decides once
+            (
+                AsyncBinaryAgreementMessageType::Finish { .. },
+                AsyncBinaryAgreementMessageType::Finish { .. },
+            ) => self.message_type != other.message_type,
+            // A node only accepts and confirms once per round. It may relay both values
+            // in the same round, so estimates never conflict
+            (
+                AsyncBinaryAgreementMessageType::Aux { .. },
+                AsyncBinaryAgreementMessageType::Aux { .. },
+            )
+            | (
+                AsyncBinaryAgreementMessageType::Conf { .. },
+                AsyncBinaryAgreementMessageType::Conf { .. },
+            ) => self.round == other.round && self.message_type != other.message_type,
+            _ => false,
+        }
+    }
+}
+
+/// Whether the given coin shares were produced by the sender for the given round,
+/// with every key share it holds in the quorum
+pub(crate) fn is_valid_coin_share(
+    key_set: &PublicKeySet,
+    quorum_info: &QuorumInfo,
+    sender: NodeId,
+    round: usize,
+    partial_signatures: &[PartialSignature],
+) -> bool {
+    let key_shares = quorum_info.key_shares(sender);
+
+    !key_shares.is_empty()
+        && key_shares.len() == partial_signatures.len()
+        && key_shares.zip(partial_signatures).all(|(index, partial_signature)| {
+            key_set
+                .public_key_share(index)
+                .verify(&round.to_le_bytes()[..], partial_signature)
+                .is_ok()
+        })
+}
+
+/// A set of binary values must be non empty and contain each value at most once
+fn is_valid_value_set(values: &[bool]) -> bool {
+    match values {
+        [_] => true,
+        [first, second] => first != second,
+        _ => false,
+    }
+}
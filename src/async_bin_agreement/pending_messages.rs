@@ -46,7 +46,10 @@ impl PendingMessages {
             // Calculate how many rounds to skip
             let rounds_to_skip = round - self.current_round_base;
 
-            // Remove all rounds up to (but not including) the requested round
+            // Remove all rounds up to (but not including) the requested round,
+            // of which there may be fewer queued than skipped
+            let rounds_to_skip = rounds_to_skip.min(self.per_round_messages.len());
+
             self.per_round_messages
                 .drain(0..rounds_to_skip)
                 .for_each(|_| ()); // Just drain, we don't need the values
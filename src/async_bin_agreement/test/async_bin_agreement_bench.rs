@@ -0,0 +1,133 @@
+use crate::aba::{ABAProtocol, AsyncBinaryAgreementResult};
+use crate::async_bin_agreement::async_bin_agreement::AsyncBinaryAgreement;
+use crate::async_bin_agreement::async_bin_agreement_round::common_coin;
+use crate::async_bin_agreement::messages::{
+    AsyncBinaryAgreementMessage, AsyncBinaryAgreementMessageType,
+};
+use crate::async_bin_agreement::test::async_bin_agreement_test::{key_shares, quorum_info};
+use crate::quorum_info::quorum_info::QuorumInfo;
+use crate::simulator::network::Outbox;
+use crate::simulator::simulation::stored_message;
+use atlas_common::crypto::threshold_crypto::{PartialSignature, PrivateKeySet};
+use atlas_common::node_id::NodeId;
+use atlas_communication::message::StoredMessage;
+use std::hint::black_box;
+use test::Bencher;
+
+/// The coin shares of a node for the first round, one for each of its key shares
+fn coin_shares(key_set: &PrivateKeySet, qi: &QuorumInfo, id: usize) -> Vec<PartialSignature> {
+    key_shares(key_set, qi, NodeId::from(id))
+        .iter()
+        .map(|key_share| key_share.partially_sign(&0usize.to_le_bytes()[..]))
+        .collect()
+}
+
+/// The messages a node receives in a first round where every node votes for the same value,
+/// phase by phase, so each of them is processed as soon as it arrives
+fn round_messages(
+    qi: &QuorumInfo,
+    key_set: &PrivateKeySet,
+) -> Vec<StoredMessage<AsyncBinaryAgreementMessage>> {
+    let phases = |id: usize| {
+        [
+            AsyncBinaryAgreementMessageType::Val { estimate: true },
+            AsyncBinaryAgreementMessageType::Aux {
+                accepted_estimates: vec![true],
+            },
+            AsyncBinaryAgreementMessageType::Conf {
+                feasible_values: vec![true],
+                partial_signatures: coin_shares(key_set, qi, id),
+            },
+            AsyncBinaryAgreementMessageType::Finish { value: true },
+        ]
+    };
+
+    let votes = (0..qi.quorum_size()).map(phases).collect::<Vec<_>>();
+
+    (0..4)
+        .flat_map(|phase| {
+            votes.iter().enumerate().map(move |(id, votes)| {
+                let message = AsyncBinaryAgreementMessage::new(votes[phase].clone(), 0);
+
+                stored_message(NodeId::from(id), NodeId(0), message)
+            })
+        })
+        .collect()
+}
+
+/// Process the messages of a whole round, which decides unless the coin goes against
+/// the unanimous vote, so the cost per message is the time per iteration over the `4n` messages.
+/// This includes verifying every coin share and combining them.
+fn bench_round(bencher: &mut Bencher, n: usize) {
+    let f = (n - 1) / 3;
+    let qi = quorum_info(n, f);
+    let key_set = PrivateKeySet::gen_random(f);
+    let pk_set = key_set.public_key_set();
+
+    let messages = round_messages(&qi, &key_set);
+    let outbox = Outbox::default();
+
+    bencher.iter(|| {
+        let mut aba = AsyncBinaryAgreement::new(
+            true,
+            qi.clone(),
+            pk_set.clone(),
+            key_shares(&key_set, &qi, NodeId(0)),
+        );
+
+        for message in messages.iter().cloned() {
+            if let AsyncBinaryAgreementResult::Decided =
+                aba.process_message(message, &outbox).unwrap()
+            {
+                break;
+            }
+        }
+
+        black_box(outbox.take());
+        black_box(aba)
+    });
+}
+
+/// Combine the `2f + 1` coin shares which flip the common coin of a round
+fn bench_coin(bencher: &mut Bencher, n: usize) {
+    let f = (n - 1) / 3;
+    let qi = quorum_info(n, f);
+    let key_set = PrivateKeySet::gen_random(f);
+    let pk_set = key_set.public_key_set();
+
+    let shares = (0..=2 * f)
+        .flat_map(|id| qi.key_shares(NodeId::from(id)).zip(coin_shares(&key_set, &qi, id)))
+        .collect::<Vec<_>>();
+
+    bencher.iter(|| common_coin(&pk_set, black_box(&shares)).unwrap());
+}
+
+#[bench]
+fn bench_round_n4(bencher: &mut Bencher) {
+    bench_round(bencher, 4);
+}
+
+#[bench]
+fn bench_round_n16(bencher: &mut Bencher) {
+    bench_round(bencher, 16);
+}
+
+#[bench]
+fn bench_round_n64(bencher: &mut Bencher) {
+    bench_round(bencher, 64);
+}
+
+#[bench]
+fn bench_coin_n4(bencher: &mut Bencher) {
+    bench_coin(bencher, 4);
+}
+
+#[bench]
+fn bench_coin_n16(bencher: &mut Bencher) {
+    bench_coin(bencher, 16);
+}
+
+#[bench]
+fn bench_coin_n64(bencher: &mut Bencher) {
+    bench_coin(bencher, 64);
+}
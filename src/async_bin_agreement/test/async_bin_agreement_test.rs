@@ -1,10 +1,11 @@
 use crate::aba::{ABAProtocol, AsyncBinaryAgreementResult, AsyncBinaryAgreementSendNode};
-use crate::async_bin_agreement::async_bin_agreement::AsyncBinaryAgreement;
+use crate::async_bin_agreement::async_bin_agreement::{ABAError, AsyncBinaryAgreement};
 use crate::async_bin_agreement::async_bin_agreement_round::AsyncBinaryAgreementState;
 use crate::async_bin_agreement::messages::{
     AsyncBinaryAgreementMessage, AsyncBinaryAgreementMessageType,
 };
 use crate::quorum_info::quorum_info::QuorumInfo;
+use anyhow::anyhow;
 use atlas_common::crypto::hash::Digest;
 use atlas_common::crypto::threshold_crypto::{PrivateKeyPart, PrivateKeySet};
 use atlas_common::node_id::NodeId;
@@ -17,6 +18,17 @@ use std::collections::HashSet;
 #[derive(Default)]
 pub(super) struct MockNetwork {
     sent: RefCell<Vec<(AsyncBinaryAgreementMessage, Vec<NodeId>)>>,
+    // Whether every broadcast fails, as it would with the peers disconnected
+    failing: bool,
+}
+
+impl MockNetwork {
+    pub(super) fn failing() -> Self {
+        Self {
+            failing: true,
+            ..Self::default()
+        }
+    }
 }
 
 impl AsyncBinaryAgreementSendNode<AsyncBinaryAgreementMessage> for MockNetwork {
@@ -28,6 +40,10 @@ impl AsyncBinaryAgreementSendNode<AsyncBinaryAgreementMessage> for MockNetwork {
     where
         I: Iterator<Item = NodeId>,
     {
+        if self.failing {
+            return Err(anyhow!("Not connected to the quorum"));
+        }
+
         self.sent.borrow_mut().push((message, target.collect()));
 
         Ok(())
@@ -47,10 +63,22 @@ pub(super) fn stored_msg<T>(from: NodeId, to: NodeId, msg: T) -> StoredMessage<T
     StoredMessage::new(wire_msg.header().clone(), msg)
 }
 
-pub(super) fn quorum_info(n: usize, f: usize) -> QuorumInfo {
+pub(crate) fn quorum_info(n: usize, f: usize) -> QuorumInfo {
     QuorumInfo::new(n, f, (0..n).map(NodeId::from).collect())
 }
 
+/// The key shares of the given key set dealt to a member of the quorum
+pub(crate) fn key_shares(
+    key_set: &PrivateKeySet,
+    quorum_info: &QuorumInfo,
+    node: NodeId,
+) -> Vec<PrivateKeyPart> {
+    quorum_info
+        .key_shares(node)
+        .map(|index| key_set.private_key_part(index))
+        .collect()
+}
+
 const N: usize = 4;
 const F: usize = 1;
 
@@ -61,27 +89,34 @@ pub(super) struct TestData {
     pub(super) network: MockNetwork,
     #[get = "pub"]
     pub(super) key_set: PrivateKeySet,
+    #[get = "pub"]
+    pub(super) quorum_info: QuorumInfo,
     #[get_mut = "pub"]
     pub(super) aba: AsyncBinaryAgreement,
 }
 
 impl TestData {
     pub(super) fn new(id: NodeId, n: usize, f: usize, initial_estimate: bool) -> Self {
-        let qi = quorum_info(n, f);
-        let key_set = PrivateKeySet::gen_random(f);
+        Self::with_quorum_info(id, quorum_info(n, f), initial_estimate)
+    }
+
+    /// Test data for a node of the given quorum, whose coin key set has a threshold of `f`
+    pub(super) fn with_quorum_info(id: NodeId, qi: QuorumInfo, initial_estimate: bool) -> Self {
+        let key_set = PrivateKeySet::gen_random(qi.f());
         let pk_set = key_set.public_key_set();
 
         let aba = AsyncBinaryAgreement::new(
             initial_estimate,
             qi.clone(),
             pk_set.clone(),
-            key_set.private_key_part(id.0 as usize),
+            key_shares(&key_set, &qi, id),
         );
 
         Self {
             node_id: id,
             network: MockNetwork::default(),
             key_set,
+            quorum_info: qi,
             aba,
         }
     }
@@ -99,6 +134,15 @@ impl TestData {
         from: NodeId,
         msg: AsyncBinaryAgreementMessage,
     ) -> AsyncBinaryAgreementResult {
+        self.try_accept_message(from, msg)
+            .expect("Failed to process the message")
+    }
+
+    pub(super) fn try_accept_message(
+        &mut self,
+        from: NodeId,
+        msg: AsyncBinaryAgreementMessage,
+    ) -> Result<AsyncBinaryAgreementResult, ABAError> {
         let stored = stored_msg(from, self.node_id.clone(), msg);
 
         self.aba.process_message(stored, &self.network)
@@ -233,20 +277,22 @@ fn test_aux_round() {
     ));
 }
 
+/// A confirmation carrying the coin shares of `node`, one for each of its key shares
 pub(super) fn get_conf_message(
     feasible_values: Vec<bool>,
-    signature_set: &PrivateKeySet,
+    test_data: &TestData,
     node: NodeId,
     round: Option<usize>,
 ) -> AsyncBinaryAgreementMessage {
-    let signature = signature_set
-        .private_key_part(node.0 as usize)
-        .partially_sign(&round.unwrap_or(0).to_le_bytes()[..]);
+    let signatures = key_shares(&test_data.key_set, &test_data.quorum_info, node)
+        .iter()
+        .map(|key_share| key_share.partially_sign(&round.unwrap_or(0).to_le_bytes()[..]))
+        .collect();
 
     AsyncBinaryAgreementMessage::new(
         AsyncBinaryAgreementMessageType::Conf {
             feasible_values,
-            partial_signature: signature,
+            partial_signatures: signatures,
         },
         round.unwrap_or(0),
     )
@@ -258,12 +304,8 @@ pub(super) fn perform_full_conf_round(
     round: Option<usize>,
 ) {
     for replica in 0..(2 * F + 1) {
-        let conf_message = get_conf_message(
-            vec![initial_estimate],
-            &test_data.key_set,
-            NodeId::from(replica),
-            round,
-        );
+        let conf_message =
+            get_conf_message(vec![initial_estimate], test_data, NodeId::from(replica), round);
 
         let result = test_data.accept_message(NodeId::from(replica), conf_message);
 
@@ -3,9 +3,16 @@ use atlas_common::node_id::NodeId;
 
 // Import test utilities from the existing test file
 use super::async_bin_agreement_test::{
-    TestData, get_aux_message, get_conf_message, get_val_message,
-    perform_all_rounds_until_conf_success,
+    MockNetwork, TestData, get_aux_message, get_conf_message, get_val_message, perform_all_rounds_until_conf_success,
+    perform_full_aux_round, perform_full_val_round,
 };
+use crate::aba::VerifiableABAMessage;
+use crate::async_bin_agreement::async_bin_agreement::ABAError;
+use crate::async_bin_agreement::async_bin_agreement_round::AsyncBinaryAgreementState;
+use crate::async_bin_agreement::messages::{
+    AsyncBinaryAgreementMessage, AsyncBinaryAgreementMessageType,
+};
+use crate::quorum_info::quorum_info::QuorumInfo;
 
 const N: usize = 4;
 const F: usize = 1;
@@ -127,14 +134,226 @@ fn test_erroneous_messages_in_finishing_state() {
     assert!(matches!(result, AsyncBinaryAgreementResult::MessageIgnored));
 
     // Send a Conf message which should be ignored in Finishing state
-    let conf_message = get_conf_message(
-        vec![INITIAL_ESTIMATE],
-        &test_data.key_set,
-        NodeId(1),
-        Some(round),
-    );
+    let conf_message =
+        get_conf_message(vec![INITIAL_ESTIMATE], &test_data, NodeId(1), Some(round));
     let result = test_data.accept_message(NodeId(1), conf_message);
 
     // The message should be ignored
     assert!(matches!(result, AsyncBinaryAgreementResult::MessageIgnored));
 }
+
+/// Test that a confirmation carrying a coin share which was not produced by its sender is ignored
+#[test]
+fn test_invalid_coin_share_is_ignored() {
+    const INITIAL_ESTIMATE: bool = true;
+
+    let mut test_data = TestData::new(NodeId(0), N, F, INITIAL_ESTIMATE);
+
+    perform_full_val_round(&mut test_data, get_val_message(INITIAL_ESTIMATE, None));
+    perform_full_aux_round(&mut test_data, get_aux_message(vec![INITIAL_ESTIMATE], None));
+
+    // The share is produced with the key of replica 2, but sent by replica 1
+    let conf_message = get_conf_message(vec![INITIAL_ESTIMATE], &test_data, NodeId(2), None);
+
+    let result = test_data.accept_message(NodeId(1), conf_message);
+
+    assert!(matches!(result, AsyncBinaryAgreementResult::MessageIgnored));
+}
+
+/// Test the stateless checks performed on agreement messages
+#[test]
+fn test_malformed_value_sets() {
+    let aux = |accepted_estimates: Vec<bool>| {
+        AsyncBinaryAgreementMessage::new(
+            AsyncBinaryAgreementMessageType::Aux { accepted_estimates },
+            0,
+        )
+    };
+
+    assert!(aux(vec![true]).is_well_formed());
+    assert!(aux(vec![true, false]).is_well_formed());
+    assert!(!aux(vec![]).is_well_formed());
+    assert!(!aux(vec![true, true]).is_well_formed());
+    assert!(!aux(vec![true, false, true]).is_well_formed());
+}
+
+/// Test the check of the coin share carried by confirmations, against the key set of the quorum
+#[test]
+fn test_coin_share_verification() {
+    let test_data = TestData::new(NodeId(0), N, F, true);
+    let key_set = test_data.key_set.public_key_set();
+
+    let quorum_info = &test_data.quorum_info;
+
+    let conf = get_conf_message(vec![true], &test_data, NodeId(1), None);
+
+    assert!(conf.has_valid_coin_share(NodeId(1), quorum_info, &key_set));
+    // The share was produced by another member
+    assert!(!conf.has_valid_coin_share(NodeId(2), quorum_info, &key_set));
+
+    // Messages without a coin share have nothing to check
+    assert!(get_val_message(true, None).has_valid_coin_share(NodeId(3), quorum_info, &key_set));
+}
+
+/// Test that a vote which fails to be broadcast is reported instead of crashing the replica
+#[test]
+fn test_failed_broadcast_is_reported() {
+    const INITIAL_ESTIMATE: bool = true;
+
+    let mut test_data = TestData::new(NodeId(0), N, F, INITIAL_ESTIMATE);
+    test_data.network = MockNetwork::failing();
+
+    let val_message = get_val_message(INITIAL_ESTIMATE, None);
+
+    for replica in 0..F {
+        let result = test_data.try_accept_message(NodeId::from(replica), val_message.clone());
+
+        assert!(matches!(result, Ok(AsyncBinaryAgreementResult::Processed)));
+    }
+
+    // The f + 1 estimate makes us broadcast it, which fails
+    let result = test_data.try_accept_message(NodeId::from(F), val_message);
+
+    assert!(matches!(
+        result,
+        Err(ABAError::BroadcastFailed {
+            round: 0,
+            vote: "estimate",
+            ..
+        })
+    ));
+}
+
+/// Test that the agreement carries on once the network recovers from failed broadcasts
+#[test]
+fn test_agreement_progresses_after_failed_broadcast() {
+    const INITIAL_ESTIMATE: bool = true;
+
+    let mut test_data = TestData::new(NodeId(0), N, F, INITIAL_ESTIMATE);
+    test_data.network = MockNetwork::failing();
+
+    let val_message = get_val_message(INITIAL_ESTIMATE, None);
+
+    // Both the estimate and the auxiliary vote fail to be broadcast
+    for replica in 0..(2 * F + 1) {
+        let result = test_data.try_accept_message(NodeId::from(replica), val_message.clone());
+
+        assert!(matches!(
+            result,
+            Ok(AsyncBinaryAgreementResult::Processed) | Err(ABAError::BroadcastFailed { .. })
+        ));
+    }
+
+    test_data.network = MockNetwork::default();
+
+    perform_full_aux_round(&mut test_data, get_aux_message(vec![INITIAL_ESTIMATE], None));
+
+    assert!(matches!(
+        test_data.aba.current_round().state(),
+        AsyncBinaryAgreementState::CollectingConf
+    ));
+}
+
+/// Test that votes from nodes outside the quorum are rejected, even for future rounds
+#[test]
+fn test_non_member_votes_are_rejected() {
+    const INITIAL_ESTIMATE: bool = true;
+
+    let mut test_data = TestData::new(NodeId(0), N, F, INITIAL_ESTIMATE);
+
+    // Enough estimates from outside the quorum to make up 2f + 1
+    for replica in N..(N + 2 * F + 1) {
+        let result = test_data.accept_message(
+            NodeId::from(replica),
+            get_val_message(INITIAL_ESTIMATE, None),
+        );
+
+        assert!(matches!(result, AsyncBinaryAgreementResult::NonMember));
+    }
+
+    assert!(matches!(
+        test_data.aba.current_round().state(),
+        AsyncBinaryAgreementState::CollectingVal
+    ));
+
+    // Messages for future rounds are rejected instead of queued
+    let result =
+        test_data.accept_message(NodeId::from(N), get_val_message(INITIAL_ESTIMATE, Some(1)));
+
+    assert!(matches!(result, AsyncBinaryAgreementResult::NonMember));
+    assert!(test_data.aba.poll().is_none());
+}
+
+/// Test that a member holding more than 2f of the weight moves the round along on its own,
+/// as it holds a key share for each unit of its weight and so brings enough coin shares
+#[test]
+fn test_weighted_votes_reach_the_thresholds() {
+    const INITIAL_ESTIMATE: bool = true;
+
+    // A total weight of 6, where the first member alone holds more than 2f
+    let quorum_info = QuorumInfo::weighted(
+        F,
+        vec![
+            (NodeId(0), 3),
+            (NodeId(1), 1),
+            (NodeId(2), 1),
+            (NodeId(3), 1),
+        ],
+    );
+
+    let mut test_data = TestData::with_quorum_info(NodeId(1), quorum_info, INITIAL_ESTIMATE);
+
+    test_data.accept_message(NodeId(0), get_val_message(INITIAL_ESTIMATE, None));
+
+    assert!(matches!(
+        test_data.aba.current_round().state(),
+        AsyncBinaryAgreementState::CollectingAux
+    ));
+
+    test_data.accept_message(NodeId(0), get_aux_message(vec![INITIAL_ESTIMATE], None));
+
+    assert!(matches!(
+        test_data.aba.current_round().state(),
+        AsyncBinaryAgreementState::CollectingConf
+    ));
+
+    // A confirmation must carry a coin share for each of the key shares of its sender
+    let (round, message_type) =
+        get_conf_message(vec![INITIAL_ESTIMATE], &test_data, NodeId(0), None).into_inner();
+
+    let AsyncBinaryAgreementMessageType::Conf {
+        feasible_values,
+        mut partial_signatures,
+    } = message_type
+    else {
+        unreachable!("Not a confirmation")
+    };
+
+    assert_eq!(3, partial_signatures.len());
+
+    partial_signatures.truncate(1);
+
+    let conf_message = AsyncBinaryAgreementMessage::new(
+        AsyncBinaryAgreementMessageType::Conf {
+            feasible_values,
+            partial_signatures,
+        },
+        round,
+    );
+
+    let result = test_data.accept_message(NodeId(0), conf_message);
+
+    assert!(matches!(result, AsyncBinaryAgreementResult::MessageIgnored));
+
+    let conf_message = get_conf_message(vec![INITIAL_ESTIMATE], &test_data, NodeId(0), None);
+
+    test_data.accept_message(NodeId(0), conf_message);
+
+    assert!(
+        test_data.aba.round() == 1
+            || matches!(
+                test_data.aba.current_round().state(),
+                AsyncBinaryAgreementState::Finishing
+            )
+    );
+}
@@ -0,0 +1,276 @@
+use crate::async_bin_agreement::async_bin_agreement_round::{RoundData, RoundDataVoteAcceptResult};
+use crate::async_bin_agreement::messages::{
+    AsyncBinaryAgreementMessage, AsyncBinaryAgreementMessageType,
+};
+use crate::async_bin_agreement::test::async_bin_agreement_test::{key_shares, quorum_info};
+use crate::simulator::model_checker::{ModelChecker, ModelProtocol, Outputs, all_output};
+use crate::simulator::network::Outbox;
+use crate::simulator::simulation::SimProtocol;
+use atlas_common::crypto::threshold_crypto::{PartialSignature, PrivateKeySet};
+use atlas_common::node_id::NodeId;
+use atlas_communication::message::StoredMessage;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::sync::Arc;
+
+const N: usize = 4;
+const F: usize = 1;
+
+/// How a node leaves the round
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum RoundOutcome {
+    Decided(bool),
+    /// Moved on to the next round with the given estimate
+    Advanced(bool),
+}
+
+/// A node taking part in the first round of a binary agreement, driving
+/// the round data the way the agreement does
+#[derive(Clone)]
+struct RoundNode {
+    members: Vec<NodeId>,
+    round: RoundData,
+    coin_shares: Vec<PartialSignature>,
+    queued: Vec<StoredMessage<AsyncBinaryAgreementMessage>>,
+    outcome: Option<RoundOutcome>,
+}
+
+impl RoundNode {
+    fn broadcast(
+        &self,
+        message_type: AsyncBinaryAgreementMessageType,
+        outbox: &Outbox<AsyncBinaryAgreementMessage>,
+    ) {
+        outbox.push_all(
+            self.members.iter().cloned(),
+            AsyncBinaryAgreementMessage::new(message_type, 0),
+        );
+    }
+
+    /// Process a message, returning whether it made progress
+    fn process(
+        &mut self,
+        message: StoredMessage<AsyncBinaryAgreementMessage>,
+        outbox: &Outbox<AsyncBinaryAgreementMessage>,
+    ) -> bool {
+        let sender = message.header().from();
+
+        let result = match message.message().message_type().clone() {
+            AsyncBinaryAgreementMessageType::Val { estimate } => {
+                self.round.accept_estimate(sender, estimate)
+            }
+            AsyncBinaryAgreementMessageType::Aux { accepted_estimates } => {
+                self.round.accept_auxiliary(sender, accepted_estimates)
+            }
+            AsyncBinaryAgreementMessageType::Conf {
+                feasible_values,
+                partial_signatures,
+            } => self
+                .round
+                .accept_confirmation(sender, feasible_values, partial_signatures),
+            AsyncBinaryAgreementMessageType::Finish { value } => {
+                self.round.accept_finish(sender, value)
+            }
+        };
+
+        match result {
+            RoundDataVoteAcceptResult::Accepted => {}
+            RoundDataVoteAcceptResult::BroadcastEst(estimate) => {
+                self.broadcast(AsyncBinaryAgreementMessageType::Val { estimate }, outbox)
+            }
+            RoundDataVoteAcceptResult::BroadcastAux(accepted_estimates) => self.broadcast(
+                AsyncBinaryAgreementMessageType::Aux { accepted_estimates },
+                outbox,
+            ),
+            RoundDataVoteAcceptResult::BroadcastConf(feasible_values) => self.broadcast(
+                AsyncBinaryAgreementMessageType::Conf {
+                    feasible_values,
+                    partial_signatures: self.coin_shares.clone(),
+                },
+                outbox,
+            ),
+            RoundDataVoteAcceptResult::BroadcastFinalized(value) => {
+                self.broadcast(AsyncBinaryAgreementMessageType::Finish { value }, outbox)
+            }
+            RoundDataVoteAcceptResult::Failed(estimate) => {
+                self.outcome = Some(RoundOutcome::Advanced(estimate))
+            }
+            RoundDataVoteAcceptResult::Finalized(value) => {
+                self.outcome = Some(RoundOutcome::Decided(value))
+            }
+            RoundDataVoteAcceptResult::Queue => {
+                self.queued.push(message);
+
+                return false;
+            }
+            RoundDataVoteAcceptResult::Ignored
+            | RoundDataVoteAcceptResult::NonMember
+            | RoundDataVoteAcceptResult::AlreadyAccepted => {
+                return false;
+            }
+        }
+
+        true
+    }
+}
+
+impl SimProtocol for RoundNode {
+    type Message = AsyncBinaryAgreementMessage;
+    type Output = RoundOutcome;
+
+    fn start(&mut self, outbox: &Arc<Outbox<Self::Message>>) {
+        let estimate = *self.round.estimate();
+
+        self.broadcast(AsyncBinaryAgreementMessageType::Val { estimate }, outbox);
+    }
+
+    fn deliver(
+        &mut self,
+        message: StoredMessage<Self::Message>,
+        outbox: &Arc<Outbox<Self::Message>>,
+    ) {
+        let mut pending = vec![message];
+
+        while !pending.is_empty() && self.outcome.is_none() {
+            let mut progressed = false;
+
+            for message in pending {
+                if self.outcome.is_none() {
+                    progressed |= self.process(message, outbox);
+                }
+            }
+
+            pending = if progressed {
+                std::mem::take(&mut self.queued)
+            } else {
+                Vec::new()
+            };
+        }
+
+        let round = &self.round;
+
+        self.queued
+            .retain(|message| !round.ignores(message.message().message_type()));
+    }
+
+    fn output(&self) -> Option<Self::Output> {
+        self.outcome
+    }
+}
+
+impl ModelProtocol for RoundNode {
+    fn fingerprint<H: Hasher>(&self, hasher: &mut H) {
+        self.outcome.hash(hasher);
+
+        if self.outcome.is_some() {
+            return;
+        }
+
+        self.round.fingerprint(hasher);
+
+        let mut queued = self
+            .queued
+            .iter()
+            .map(|message| {
+                let mut message_hasher = DefaultHasher::new();
+
+                message.header().from().hash(&mut message_hasher);
+                Self::message_fingerprint(message.message(), &mut message_hasher);
+
+                message_hasher.finish()
+            })
+            .collect::<Vec<_>>();
+
+        queued.sort_unstable();
+        queued.hash(hasher);
+    }
+
+    fn message_fingerprint<H: Hasher>(message: &Self::Message, hasher: &mut H) {
+        message.round().hash(hasher);
+
+        // The coin share of a confirmation is determined by its sender
+        match message.message_type() {
+            AsyncBinaryAgreementMessageType::Val { estimate } => (0u8, estimate).hash(hasher),
+            AsyncBinaryAgreementMessageType::Aux { accepted_estimates } => {
+                (1u8, accepted_estimates).hash(hasher)
+            }
+            AsyncBinaryAgreementMessageType::Conf {
+                feasible_values, ..
+            } => (2u8, feasible_values).hash(hasher),
+            AsyncBinaryAgreementMessageType::Finish { value } => (3u8, value).hash(hasher),
+        }
+    }
+
+    fn ignores(&self, message: &Self::Message) -> bool {
+        self.outcome.is_some() || self.round.ignores(message.message_type())
+    }
+}
+
+/// The first round of an agreement between the first three nodes, with the last one crashed
+fn first_round(inputs: [bool; N - 1]) -> ModelChecker<RoundNode> {
+    let quorum_info = quorum_info(N, F);
+    let key_set = PrivateKeySet::gen_random(F);
+    let pk_set = key_set.public_key_set();
+
+    let nodes = inputs.into_iter().enumerate().map(|(id, input)| {
+        let node = RoundNode {
+            members: quorum_info.quorum_members().clone(),
+            round: RoundData::new(quorum_info.clone(), pk_set.clone(), input),
+            coin_shares: key_shares(&key_set, &quorum_info, NodeId::from(id))
+                .iter()
+                .map(|key_share| key_share.partially_sign(&0usize.to_le_bytes()[..]))
+                .collect(),
+            queued: Vec::new(),
+            outcome: None,
+        };
+
+        (NodeId::from(id), node)
+    });
+
+    ModelChecker::new(nodes)
+        .with_invariant("agreement", |outputs| decisions(outputs).all(|value| decisions(outputs).all(|other| other == value)))
+        .with_invariant("decision carried to the next round", |outputs| {
+            decisions(outputs).all(|decided| {
+                outputs
+                    .values()
+                    .flatten()
+                    .all(|outcome| !matches!(outcome, RoundOutcome::Advanced(estimate) if *estimate != decided))
+            })
+        })
+        .with_quiescent_invariant("no deadlock", all_output)
+}
+
+fn decisions(outputs: &Outputs<RoundOutcome>) -> impl Iterator<Item = bool> + '_ {
+    outputs
+        .values()
+        .flatten()
+        .filter_map(|outcome| match outcome {
+            RoundOutcome::Decided(value) => Some(*value),
+            RoundOutcome::Advanced(_) => None,
+        })
+}
+
+#[test]
+fn test_unanimous_round_keeps_the_input() {
+    for input in [false, true] {
+        first_round([input; N - 1])
+            .with_invariant("validity", move |outputs| {
+                outputs.values().flatten().all(|outcome| {
+                    matches!(outcome, RoundOutcome::Decided(value) | RoundOutcome::Advanced(value) if *value == input)
+                })
+            })
+            .explore()
+            .unwrap_or_else(|err| panic!("{err}"));
+    }
+}
+
+#[test]
+fn test_divided_round_is_safe_and_live() {
+    for inputs in [[true, true, false], [true, false, false]] {
+        let exploration = first_round(inputs)
+            .explore()
+            .unwrap_or_else(|err| panic!("{err}"));
+
+        assert!(exploration.quiescent_states > 0);
+    }
+}
@@ -0,0 +1,175 @@
+use crate::async_bin_agreement::messages::AsyncBinaryAgreementMessage;
+use crate::async_bin_agreement::test::async_bin_agreement_test::{key_shares, quorum_info};
+use crate::async_bin_agreement::test::simulation_test::AbaNode;
+use crate::quorum_info::quorum_info::QuorumInfo;
+use crate::simulator::byzantine::aba::{
+    CoinShareWithholder, FutureRoundFlooder, InvalidCoinShare, SplitVoter,
+};
+use crate::simulator::byzantine::strategy::{ByzantineStrategy, Replay, Silent};
+use crate::simulator::network::NetworkConfig;
+use crate::simulator::properties::PropertyViolation;
+use crate::simulator::simulation::{SimReport, Simulation};
+use atlas_common::crypto::threshold_crypto::PrivateKeySet;
+use atlas_common::node_id::NodeId;
+use proptest::collection::vec;
+use proptest::prelude::*;
+use proptest::sample::subsequence;
+use proptest::test_runner::TestCaseError;
+use std::fmt::Debug;
+
+/// The round by which every honest node must have decided.
+/// Each round decides with probability at least one half under fair scheduling,
+/// so reaching it is a liveness bug rather than bad luck.
+const MAX_ROUNDS: usize = 32;
+
+/// The behavior of a faulty node
+#[derive(Debug, Clone, Copy)]
+enum Fault {
+    Silent,
+    SplitVoter,
+    CoinShareWithholder,
+    InvalidCoinShare,
+    FutureRoundFlooder,
+    Replay,
+}
+
+impl Fault {
+    fn strategy(
+        self,
+        key_set: &PrivateKeySet,
+        quorum_info: &QuorumInfo,
+        id: usize,
+    ) -> Box<dyn ByzantineStrategy<AsyncBinaryAgreementMessage>> {
+        match self {
+            Fault::Silent => Box::new(Silent),
+            Fault::SplitVoter => Box::new(SplitVoter),
+            Fault::CoinShareWithholder => Box::new(CoinShareWithholder),
+            Fault::InvalidCoinShare => {
+                Box::new(InvalidCoinShare::new(key_shares(key_set, quorum_info, NodeId::from(id))))
+            }
+            Fault::FutureRoundFlooder => Box::new(FutureRoundFlooder::new(2)),
+            Fault::Replay => Box::new(Replay::new(2)),
+        }
+    }
+}
+
+fn fault() -> impl Strategy<Value = Fault> {
+    prop_oneof![
+        Just(Fault::Silent),
+        Just(Fault::SplitVoter),
+        Just(Fault::CoinShareWithholder),
+        Just(Fault::InvalidCoinShare),
+        Just(Fault::FutureRoundFlooder),
+        Just(Fault::Replay),
+    ]
+}
+
+/// A generated run: the input of every node, which of them are faulty and how,
+/// and the seed and delays which determine the message schedule
+#[derive(Debug, Clone)]
+struct Scenario {
+    f: usize,
+    inputs: Vec<bool>,
+    faulty: Vec<(usize, Fault)>,
+    seed: u64,
+    config: NetworkConfig,
+}
+
+impl Scenario {
+    fn is_faulty(&self, id: usize) -> bool {
+        self.faulty.iter().any(|(faulty, _)| *faulty == id)
+    }
+
+    /// Give every honest node the same input
+    fn with_honest_input(mut self, input: bool) -> Self {
+        for id in 0..self.inputs.len() {
+            if !self.is_faulty(id) {
+                self.inputs[id] = input;
+            }
+        }
+
+        self
+    }
+}
+
+fn scenario() -> impl Strategy<Value = Scenario> {
+    (1usize..=2).prop_flat_map(|f| {
+        let n = 3 * f + 1;
+
+        (
+            vec(any::<bool>(), n),
+            subsequence((0..n).collect::<Vec<_>>(), 0..=f),
+            vec(fault(), f),
+            any::<u64>(),
+            0u64..10,
+            10u64..1_000,
+        )
+            .prop_map(
+                move |(inputs, faulty, faults, seed, min_delay, max_delay)| Scenario {
+                    f,
+                    inputs,
+                    faulty: faulty.into_iter().zip(faults).collect(),
+                    seed,
+                    config: NetworkConfig {
+                        min_delay,
+                        max_delay,
+                    },
+                },
+            )
+    })
+}
+
+fn run(scenario: &Scenario) -> SimReport<bool> {
+    let n = scenario.inputs.len();
+    let qi = quorum_info(n, scenario.f);
+    let key_set = PrivateKeySet::gen_random(scenario.f);
+
+    let nodes = scenario.inputs.iter().enumerate().map(|(id, input)| {
+        let node = AbaNode::with_input(*input, &qi, &key_set, id).with_max_rounds(MAX_ROUNDS);
+
+        (NodeId::from(id), node)
+    });
+
+    let simulation = Simulation::new(scenario.seed, scenario.config, nodes);
+
+    scenario
+        .faulty
+        .iter()
+        .fold(simulation, |simulation, (id, fault)| {
+            simulation.with_byzantine(NodeId::from(*id), fault.strategy(&key_set, &qi, *id))
+        })
+        .run()
+}
+
+fn holds<O>(check: Result<(), PropertyViolation<O>>) -> Result<(), TestCaseError>
+where
+    O: Debug,
+{
+    check.map_err(|violation| TestCaseError::fail(violation.to_string()))
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(64))]
+
+    #[test]
+    fn prop_honest_nodes_agree(scenario in scenario()) {
+        let report = run(&scenario);
+
+        holds(report.check_agreement())?;
+    }
+
+    #[test]
+    fn prop_unanimous_input_is_decided(scenario in scenario(), input in any::<bool>()) {
+        let report = run(&scenario.with_honest_input(input));
+
+        holds(report.check_agreement())?;
+        holds(report.check_validity(|decided| *decided == input))?;
+    }
+
+    #[test]
+    fn prop_decides_within_bounded_rounds(scenario in scenario()) {
+        let report = run(&scenario);
+
+        holds(report.check_termination())?;
+    }
+}
@@ -0,0 +1,257 @@
+use crate::aba::{ABAProtocol, AsyncBinaryAgreementResult};
+use crate::async_bin_agreement::async_bin_agreement::AsyncBinaryAgreement;
+use crate::async_bin_agreement::messages::{
+    AsyncBinaryAgreementMessage, AsyncBinaryAgreementMessageType,
+};
+use crate::async_bin_agreement::test::async_bin_agreement_test::{key_shares, quorum_info};
+use crate::quorum_info::quorum_info::QuorumInfo;
+use crate::simulator::byzantine::aba::{
+    CoinShareWithholder, FutureRoundFlooder, InvalidCoinShare, SplitVoter,
+};
+use crate::simulator::byzantine::strategy::{ByzantineStrategy, Chain, Replay, Silent};
+use crate::simulator::network::{NetworkConfig, Outbox};
+use crate::simulator::simulation::{SimProtocol, SimReport, Simulation};
+use atlas_common::crypto::threshold_crypto::PrivateKeySet;
+use atlas_common::node_id::NodeId;
+use atlas_communication::message::StoredMessage;
+use std::sync::Arc;
+
+const N: usize = 4;
+const F: usize = 1;
+const SEEDS: u64 = 16;
+
+/// A node taking part in a single binary agreement.
+///
+/// The agreement does not send the estimate of a round by itself, so this
+/// broadcasts it when the agreement starts and whenever it moves to a new round.
+pub(crate) struct AbaNode {
+    members: Vec<NodeId>,
+    aba: Option<AsyncBinaryAgreement>,
+    // The round we have sent our estimate for
+    round: usize,
+    // The round at which we give up on deciding, if any
+    max_rounds: Option<usize>,
+    decided: Option<bool>,
+}
+
+impl AbaNode {
+    fn new(members: Vec<NodeId>, aba: AsyncBinaryAgreement) -> Self {
+        Self {
+            members,
+            aba: Some(aba),
+            round: 0,
+            max_rounds: None,
+            decided: None,
+        }
+    }
+
+    /// A node starting the agreement with the given input, holding the shares of the threshold
+    /// keys dealt to the node at `id`
+    pub(crate) fn with_input(
+        input: bool,
+        quorum_info: &QuorumInfo,
+        key_set: &PrivateKeySet,
+        id: usize,
+    ) -> Self {
+        let aba = AsyncBinaryAgreement::new(
+            input,
+            quorum_info.clone(),
+            key_set.public_key_set(),
+            key_shares(key_set, quorum_info, NodeId::from(id)),
+        );
+
+        Self::new(quorum_info.quorum_members().clone(), aba)
+    }
+
+    /// Give up on the agreement once it reaches the given round, so it is reported as undecided
+    pub(super) fn with_max_rounds(mut self, max_rounds: usize) -> Self {
+        self.max_rounds = Some(max_rounds);
+        self
+    }
+
+    fn broadcast_estimate(
+        &self,
+        aba: &AsyncBinaryAgreement,
+        outbox: &Outbox<AsyncBinaryAgreementMessage>,
+    ) {
+        let message = AsyncBinaryAgreementMessage::new(
+            AsyncBinaryAgreementMessageType::Val {
+                estimate: *aba.current_round().estimate(),
+            },
+            aba.round(),
+        );
+
+        outbox.push_all(self.members.iter().cloned(), message);
+    }
+}
+
+impl SimProtocol for AbaNode {
+    type Message = AsyncBinaryAgreementMessage;
+    type Output = bool;
+
+    fn start(&mut self, outbox: &Arc<Outbox<Self::Message>>) {
+        if let Some(aba) = &self.aba {
+            self.broadcast_estimate(aba, outbox);
+        }
+    }
+
+    fn deliver(
+        &mut self,
+        message: StoredMessage<Self::Message>,
+        outbox: &Arc<Outbox<Self::Message>>,
+    ) {
+        let Some(mut aba) = self.aba.take() else {
+            return;
+        };
+
+        let mut pending = vec![message];
+
+        while !pending.is_empty() {
+            let mut progressed = false;
+
+            for message in pending {
+                match aba.process_message(message, outbox.as_ref()).unwrap() {
+                    AsyncBinaryAgreementResult::Processed => progressed = true,
+                    AsyncBinaryAgreementResult::Decided => {
+                        self.decided = Some(aba.finalize().unwrap());
+
+                        return;
+                    }
+                    AsyncBinaryAgreementResult::MessageIgnored
+                    | AsyncBinaryAgreementResult::NonMember
+                    | AsyncBinaryAgreementResult::MessageQueued => {}
+                }
+
+                if self
+                    .max_rounds
+                    .is_some_and(|max_rounds| aba.round() >= max_rounds)
+                {
+                    return;
+                }
+
+                if aba.round() > self.round {
+                    self.round = aba.round();
+
+                    self.broadcast_estimate(&aba, outbox);
+                }
+            }
+
+            pending = if progressed {
+                std::iter::from_fn(|| aba.poll()).collect()
+            } else {
+                Vec::new()
+            };
+        }
+
+        self.aba = Some(aba);
+    }
+
+    fn output(&self) -> Option<Self::Output> {
+        self.decided
+    }
+}
+
+fn simulation(seed: u64, inputs: [bool; N], key_set: &PrivateKeySet) -> Simulation<AbaNode> {
+    let qi = quorum_info(N, F);
+
+    let nodes = (0..N).map(|id| {
+        (
+            NodeId::from(id),
+            AbaNode::with_input(inputs[id], &qi, key_set, id),
+        )
+    });
+
+    Simulation::new(seed, NetworkConfig::default(), nodes)
+}
+
+fn run(seed: u64, inputs: [bool; N]) -> SimReport<bool> {
+    simulation(seed, inputs, &PrivateKeySet::gen_random(F)).run()
+}
+
+#[test]
+fn test_unanimous_input_is_decided() {
+    for input in [false, true] {
+        for seed in 0..SEEDS {
+            let report = run(seed, [input; N]);
+
+            report.check_termination().unwrap();
+            report.check_agreement().unwrap();
+            report.check_validity(|decided| *decided == input).unwrap();
+        }
+    }
+}
+
+#[test]
+fn test_divided_input_reaches_agreement() {
+    for seed in 0..SEEDS {
+        let report = run(seed, [true, false, true, false]);
+
+        report.check_termination().unwrap();
+        report.check_agreement().unwrap();
+    }
+}
+
+/// Checks that the correct nodes, which all input `true`, decide it despite the Byzantine node
+fn assert_decides_despite<S>(byzantine: S)
+where
+    S: Fn(&PrivateKeySet) -> Box<dyn ByzantineStrategy<AsyncBinaryAgreementMessage>>,
+{
+    for seed in 0..SEEDS {
+        let key_set = PrivateKeySet::gen_random(F);
+
+        let report = simulation(seed, [true; N], &key_set)
+            .with_byzantine(NodeId(3), byzantine(&key_set))
+            .run();
+
+        report.check_termination().unwrap();
+        report.check_agreement().unwrap();
+        report.check_validity(|decided| *decided).unwrap();
+    }
+}
+
+#[test]
+fn test_split_voter_does_not_divide_correct_nodes() {
+    assert_decides_despite(|_| Box::new(SplitVoter));
+}
+
+#[test]
+fn test_withheld_coin_shares_are_tolerated() {
+    assert_decides_despite(|_| Box::new(CoinShareWithholder));
+}
+
+#[test]
+fn test_invalid_coin_shares_are_ignored() {
+    assert_decides_despite(|key_set| {
+        Box::new(InvalidCoinShare::new(key_shares(
+            key_set,
+            &quorum_info(N, F),
+            NodeId(3),
+        )))
+    });
+}
+
+#[test]
+fn test_future_round_flooding_is_tolerated() {
+    assert_decides_despite(|_| Box::new(FutureRoundFlooder::new(3)));
+}
+
+#[test]
+fn test_replayed_past_rounds_are_ignored() {
+    assert_decides_despite(|_| Box::new(Replay::new(2)));
+}
+
+#[test]
+fn test_combined_strategies_are_tolerated() {
+    assert_decides_despite(|_| {
+        Box::new(Chain::new(vec![
+            Box::new(SplitVoter),
+            Box::new(FutureRoundFlooder::new(2)),
+            Box::new(Replay::new(1)),
+        ]))
+    });
+}
+
+#[test]
+fn test_silent_node_is_tolerated() {
+    assert_decides_despite(|_| Box::new(Silent));
+}
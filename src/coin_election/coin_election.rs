@@ -0,0 +1,239 @@
+use crate::aba::CoinKeys;
+use crate::committee_election::{
+    CommitteeElectionProtocol, CommitteeElectionResult, CommitteeElectionSendNode,
+};
+use crate::quorum_info::quorum_info::QuorumInfo;
+use atlas_common::collections::HashMap;
+use atlas_common::crypto::hash::{Context, Digest};
+use atlas_common::crypto::threshold_crypto::{CombineSignatureError, PartialSignature};
+use atlas_common::node_id::NodeId;
+use atlas_communication::message::StoredMessage;
+use getset::Getters;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tracing::{debug, warn};
+
+/// The coin shares a member of the quorum contributes to a committee election
+#[derive(Debug, Clone, PartialEq, Eq, Getters, Serialize, Deserialize)]
+pub(crate) struct CoinElectionMessage {
+    /// The coin shares of the sender over the nonce of the election, one for each of its key shares
+    #[get = "pub(crate)"]
+    partial_signatures: Vec<PartialSignature>,
+}
+
+impl CoinElectionMessage {
+    pub(crate) fn new(partial_signatures: Vec<PartialSignature>) -> Self {
+        Self { partial_signatures }
+    }
+}
+
+/// A committee election which draws the committee from a threshold coin.
+///
+/// Every member broadcasts its coin shares over the nonce of the election, and once more shares
+/// than the threshold of the key set are gathered they are combined into the coin. As the combined
+/// signature is unique, every correct member draws the same committee from it, and as it requires
+/// the shares of at least one correct member, the faulty members can not predict it.
+#[derive(Debug)]
+pub(crate) struct CoinCommitteeElection {
+    quorum_info: QuorumInfo,
+    // The weight the elected committee must hold
+    committee_size: usize,
+    coin_keys: CoinKeys,
+    nonce: Vec<u8>,
+    // The verified coin shares of each member
+    received_shares: HashMap<NodeId, Vec<PartialSignature>>,
+    // The elected committee, once enough shares were gathered
+    committee: Option<Vec<NodeId>>,
+}
+
+impl CoinCommitteeElection {
+    /// Whether the coin shares were produced by the sender over the nonce of this election,
+    /// with the key shares it holds
+    fn is_valid_share(&self, sender: NodeId, partial_signatures: &[PartialSignature]) -> bool {
+        let key_shares = self.quorum_info.key_shares(sender);
+
+        !key_shares.is_empty()
+            && key_shares.len() == partial_signatures.len()
+            && key_shares
+                .zip(partial_signatures)
+                .all(|(index, partial_signature)| {
+                    self.coin_keys
+                        .public_key_set()
+                        .public_key_share(index)
+                        .verify(&self.nonce[..], partial_signature)
+                        .is_ok()
+                })
+    }
+
+    fn share_count(&self) -> usize {
+        self.received_shares.values().map(Vec::len).sum()
+    }
+
+    /// Combine the gathered shares into the coin, and draw the committee from it
+    fn draw_committee(&self) -> Result<Vec<NodeId>, CoinElectionError> {
+        let signatures = self.received_shares.iter().flat_map(|(node, signatures)| {
+            self.quorum_info.key_shares(*node).zip(signatures.iter())
+        });
+
+        let combined_signature = self
+            .coin_keys
+            .public_key_set()
+            .combine_signatures(signatures)
+            .map_err(CoinElectionError::FailedToCombine)?;
+
+        let serialized_sig =
+            bincode::serde::encode_to_vec(&combined_signature, bincode::config::standard())
+                .expect("Failed to serialize combined signature");
+
+        let mut context = Context::new();
+        context.update(&serialized_sig);
+        let coin = context.finish();
+
+        Ok(draw_committee(
+            &self.quorum_info,
+            self.committee_size,
+            &coin,
+        ))
+    }
+}
+
+/// Draw the committee from the given coin: the members are ranked by the digest of the coin
+/// along with their id, and taken in that order until they hold the required weight
+pub(crate) fn draw_committee(
+    quorum_info: &QuorumInfo,
+    committee_size: usize,
+    coin: &Digest,
+) -> Vec<NodeId> {
+    let mut ranked = quorum_info
+        .quorum_members()
+        .iter()
+        .map(|member| {
+            let mut context = Context::new();
+
+            context.update(coin.as_ref());
+            context.update(&member.0.to_le_bytes()[..]);
+
+            (context.finish().as_ref().to_vec(), *member)
+        })
+        .collect::<Vec<_>>();
+
+    ranked.sort();
+
+    let mut committee = Vec::new();
+
+    for (_, member) in ranked {
+        if quorum_info.accumulated_weight(&committee) >= committee_size {
+            break;
+        }
+
+        committee.push(member);
+    }
+
+    committee.sort();
+
+    committee
+}
+
+impl CommitteeElectionProtocol for CoinCommitteeElection {
+    type Message = CoinElectionMessage;
+    type CEError = CoinElectionError;
+
+    fn new(
+        quorum_info: QuorumInfo,
+        committee_size: usize,
+        coin_keys: CoinKeys,
+        nonce: Vec<u8>,
+    ) -> Self {
+        Self {
+            quorum_info,
+            committee_size,
+            coin_keys,
+            nonce,
+            received_shares: HashMap::default(),
+            committee: None,
+        }
+    }
+
+    fn start<NT>(&mut self, network: &NT) -> Result<(), Self::CEError>
+    where
+        NT: CommitteeElectionSendNode<Self::Message>,
+    {
+        let partial_signatures = self
+            .coin_keys
+            .key_shares()
+            .iter()
+            .map(|key_share| key_share.partially_sign(&self.nonce[..]))
+            .collect();
+
+        if let Err(failed) = network.broadcast(
+            CoinElectionMessage::new(partial_signatures),
+            self.quorum_info.quorum_members().iter().cloned(),
+        ) {
+            warn!("Failed to broadcast our coin shares of the committee election to {failed:?}");
+        }
+
+        Ok(())
+    }
+
+    fn poll(&mut self) -> Option<StoredMessage<Self::Message>> {
+        // Shares are never held back, so there is nothing to poll
+        None
+    }
+
+    fn process_message<NT>(
+        &mut self,
+        message: StoredMessage<Self::Message>,
+        _network: &NT,
+    ) -> Result<CommitteeElectionResult, Self::CEError>
+    where
+        NT: CommitteeElectionSendNode<Self::Message>,
+    {
+        let sender = message.header().from();
+
+        if !self.quorum_info.is_member(sender) {
+            return Ok(CommitteeElectionResult::NonMember);
+        }
+
+        if self.committee.is_some() || self.received_shares.contains_key(&sender) {
+            return Ok(CommitteeElectionResult::MessageIgnored);
+        }
+
+        let (_, message) = message.into_inner();
+
+        if !self.is_valid_share(sender, message.partial_signatures()) {
+            warn!(
+                ?sender,
+                "Received invalid coin shares for the committee election, ignoring."
+            );
+
+            return Ok(CommitteeElectionResult::MessageIgnored);
+        }
+
+        self.received_shares
+            .insert(sender, message.partial_signatures);
+
+        if self.share_count() <= self.coin_keys.public_key_set().threshold() {
+            return Ok(CommitteeElectionResult::Processed);
+        }
+
+        let committee = self.draw_committee()?;
+
+        debug!(?committee, "Committee drawn from the coin");
+
+        self.committee = Some(committee);
+
+        Ok(CommitteeElectionResult::Decided)
+    }
+
+    fn finalize(self) -> Result<Vec<NodeId>, Self::CEError> {
+        self.committee.ok_or(CoinElectionError::NotDecided)
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum CoinElectionError {
+    #[error("The committee election has not decided yet")]
+    NotDecided,
+    #[error("Failed to combine the coin shares of the committee election: {0:?}")]
+    FailedToCombine(CombineSignatureError),
+}
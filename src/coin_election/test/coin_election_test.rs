@@ -0,0 +1,190 @@
+use crate::aba::CoinKeys;
+use crate::async_bin_agreement::test::async_bin_agreement_test::{key_shares, quorum_info};
+use crate::coin_election::coin_election::{CoinCommitteeElection, CoinElectionMessage};
+use crate::committee_election::{CommitteeElectionProtocol, CommitteeElectionResult};
+use crate::quorum_info::quorum_info::QuorumInfo;
+use crate::simulator::byzantine::strategy::Silent;
+use crate::simulator::network::{NetworkConfig, Outbox};
+use crate::simulator::simulation::{SimProtocol, Simulation, stored_message};
+use atlas_common::crypto::threshold_crypto::PrivateKeySet;
+use atlas_common::node_id::NodeId;
+use atlas_communication::message::StoredMessage;
+use std::sync::Arc;
+
+const N: usize = 4;
+const F: usize = 1;
+const SEEDS: u64 = 16;
+
+/// A node taking part in a single committee election
+struct ElectionNode {
+    election: Option<CoinCommitteeElection>,
+    committee: Option<Vec<NodeId>>,
+}
+
+impl ElectionNode {
+    fn new(quorum_info: &QuorumInfo, key_set: &PrivateKeySet, id: NodeId, nonce: &[u8]) -> Self {
+        Self {
+            election: Some(election(quorum_info, key_set, id, nonce)),
+            committee: None,
+        }
+    }
+}
+
+impl SimProtocol for ElectionNode {
+    type Message = CoinElectionMessage;
+    type Output = Vec<NodeId>;
+
+    fn start(&mut self, outbox: &Arc<Outbox<Self::Message>>) {
+        if let Some(election) = &mut self.election {
+            election.start(outbox.as_ref()).unwrap();
+        }
+    }
+
+    fn deliver(
+        &mut self,
+        message: StoredMessage<Self::Message>,
+        outbox: &Arc<Outbox<Self::Message>>,
+    ) {
+        let Some(mut election) = self.election.take() else {
+            return;
+        };
+
+        if let CommitteeElectionResult::Decided =
+            election.process_message(message, outbox.as_ref()).unwrap()
+        {
+            self.committee = Some(election.finalize().unwrap());
+
+            return;
+        }
+
+        self.election = Some(election);
+    }
+
+    fn output(&self) -> Option<Self::Output> {
+        self.committee.clone()
+    }
+}
+
+fn election(
+    quorum_info: &QuorumInfo,
+    key_set: &PrivateKeySet,
+    id: NodeId,
+    nonce: &[u8],
+) -> CoinCommitteeElection {
+    let coin_keys = CoinKeys::new(
+        key_set.public_key_set(),
+        key_shares(key_set, quorum_info, id),
+    );
+
+    CoinCommitteeElection::new(quorum_info.clone(), F + 1, coin_keys, nonce.to_vec())
+}
+
+fn simulation(seed: u64, key_set: &PrivateKeySet) -> Simulation<ElectionNode> {
+    let qi = quorum_info(N, F);
+
+    let nodes = (0..N).map(|id| {
+        let id = NodeId::from(id);
+
+        (id, ElectionNode::new(&qi, key_set, id, b"epoch"))
+    });
+
+    Simulation::new(seed, NetworkConfig::default(), nodes)
+}
+
+#[test]
+fn test_members_elect_the_same_committee() {
+    for seed in 0..SEEDS {
+        let qi = quorum_info(N, F);
+        let report = simulation(seed, &PrivateKeySet::gen_random(F)).run();
+
+        report.check_termination().unwrap();
+        report.check_agreement().unwrap();
+        report
+            .check_validity(|committee| {
+                qi.accumulated_weight(committee) > F
+                    && committee.iter().all(|member| qi.is_member(*member))
+            })
+            .unwrap();
+    }
+}
+
+#[test]
+fn test_committee_is_elected_despite_a_silent_member() {
+    for seed in 0..SEEDS {
+        let report = simulation(seed, &PrivateKeySet::gen_random(F))
+            .with_byzantine(NodeId(3), Silent)
+            .run();
+
+        report.check_termination().unwrap();
+        report.check_agreement().unwrap();
+    }
+}
+
+/// The shares of `from` in the election run with the given nonce
+fn shares(key_set: &PrivateKeySet, from: NodeId, nonce: &[u8]) -> CoinElectionMessage {
+    let qi = quorum_info(N, F);
+
+    CoinElectionMessage::new(
+        key_shares(key_set, &qi, from)
+            .iter()
+            .map(|key_share| key_share.partially_sign(nonce))
+            .collect(),
+    )
+}
+
+#[test]
+fn test_shares_of_another_election_are_ignored() {
+    let qi = quorum_info(N, F);
+    let key_set = PrivateKeySet::gen_random(F);
+    let outbox = Outbox::default();
+
+    let mut election = election(&qi, &key_set, NodeId(0), b"epoch 1");
+
+    let message = stored_message(
+        NodeId(1),
+        NodeId(0),
+        shares(&key_set, NodeId(1), b"epoch 2"),
+    );
+
+    assert!(matches!(
+        election.process_message(message, &outbox).unwrap(),
+        CommitteeElectionResult::MessageIgnored
+    ));
+}
+
+#[test]
+fn test_repeated_shares_are_ignored() {
+    let qi = quorum_info(N, F);
+    let key_set = PrivateKeySet::gen_random(F);
+    let outbox = Outbox::default();
+
+    let mut election = election(&qi, &key_set, NodeId(0), b"epoch");
+
+    let message = || stored_message(NodeId(1), NodeId(0), shares(&key_set, NodeId(1), b"epoch"));
+
+    assert!(matches!(
+        election.process_message(message(), &outbox).unwrap(),
+        CommitteeElectionResult::Processed
+    ));
+    assert!(matches!(
+        election.process_message(message(), &outbox).unwrap(),
+        CommitteeElectionResult::MessageIgnored
+    ));
+    assert!(election.finalize().is_err());
+}
+
+#[test]
+fn test_shares_of_non_member_are_dropped() {
+    let qi = quorum_info(N, F);
+    let key_set = PrivateKeySet::gen_random(F);
+    let outbox = Outbox::default();
+
+    let mut election = election(&qi, &key_set, NodeId(0), b"epoch");
+
+    let message = stored_message(NodeId(7), NodeId(0), CoinElectionMessage::new(Vec::new()));
+
+    assert!(matches!(
+        election.process_message(message, &outbox).unwrap(),
+        CommitteeElectionResult::NonMember
+    ));
+}
@@ -1,4 +1,5 @@
 use std::error::Error;
+use crate::aba::CoinKeys;
 use crate::quorum_info::quorum_info::QuorumInfo;
 use atlas_common::node_id::NodeId;
 use atlas_common::serialization_helper::SerMsg;
@@ -11,7 +12,18 @@ pub trait CommitteeElectionProtocol {
     type Message: SerMsg;
     type CEError: Error + Send + Sync + 'static;
 
-    fn new(quorum_info: QuorumInfo, committee_size: usize) -> Self;
+    /// Start an election for a committee holding at least `committee_size` of the weight
+    /// of the quorum, as given by [`QuorumInfo::weight_of`].
+    ///
+    /// The committee is drawn from a coin flipped with the given keys over the nonce,
+    /// which must be unique to this election so no two elections draw the same committee.
+    fn new(quorum_info: QuorumInfo, committee_size: usize, coin_keys: CoinKeys, nonce: Vec<u8>) -> Self;
+
+    /// Broadcast our contribution to the election.
+    /// Called once by the orchestrator, right after it has created the election.
+    fn start<NT>(&mut self, network: &NT) -> Result<(), Self::CEError>
+    where
+        NT: CommitteeElectionSendNode<Self::Message>;
 
     /// Poll this protocol to check if there are any pending messages stored
     /// That can now be processed
@@ -47,6 +59,9 @@ where
 pub enum CommitteeElectionResult {
     MessageQueued,
     MessageIgnored,
+    /// The message was sent by a node outside the quorum, so it was dropped.
+    /// Implementations must check the sender before counting any vote.
+    NonMember,
     Processed,
     Decided,
 }
@@ -0,0 +1,215 @@
+use crate::dumbo1::reconfiguration::Reconfiguration;
+use crate::reliable_broadcast::reliable_broadcast::BroadcastPayload;
+use crate::rq_aggregator::rq_aggregator::batch_digest;
+use crate::tpke::{TPKEError, ThresholdEncryptionScheme};
+use atlas_common::crypto::hash::{Context, Digest};
+use atlas_common::serialization_helper::SerMsg;
+use atlas_communication::message::StoredMessage;
+use atlas_communication::reconfiguration::NetworkInformationProvider;
+use atlas_core::ordering_protocol::networking::serialize::{
+    OrderProtocolVerificationHelper, OrderingProtocolMessage,
+};
+use getset::Getters;
+use serde::{Deserialize, Serialize};
+use std::fmt::Debug;
+use std::marker::PhantomData;
+use std::sync::Arc;
+use thiserror::Error;
+
+/// The payload reliably broadcast by each node in a Dumbo epoch.
+///
+/// When threshold encryption is enabled, the batch is only revealed
+/// after the agreement of the epoch has decided.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum ProposedBatch<RQ> {
+    Plain(Vec<StoredMessage<RQ>>),
+    Encrypted(EncryptedBatch),
+    /// A batch along with a reconfiguration of the quorum, which is decided
+    /// if and only if the batch is included in the epoch
+    Reconfigure {
+        batch: Box<ProposedBatch<RQ>>,
+        reconfiguration: Reconfiguration,
+    },
+}
+
+impl<RQ> ProposedBatch<RQ>
+where
+    RQ: SerMsg,
+{
+    /// Encrypt the given requests with the threshold encryption scheme of the quorum
+    pub fn encrypt(
+        requests: Vec<StoredMessage<RQ>>,
+        scheme: &dyn ThresholdEncryptionScheme,
+    ) -> Result<Self, BatchError> {
+        let plaintext = bincode::serde::encode_to_vec(&requests, bincode::config::standard())
+            .map_err(|err| BatchError::FailedToSerialize(err.to_string()))?;
+
+        let ciphertext = scheme.encrypt(&plaintext)?;
+
+        Ok(ProposedBatch::Encrypted(EncryptedBatch::new(ciphertext)))
+    }
+
+    /// Propose the given reconfiguration along with this batch
+    pub fn with_reconfiguration(self, reconfiguration: Reconfiguration) -> Self {
+        ProposedBatch::Reconfigure {
+            batch: Box::new(self.into_parts().0),
+            reconfiguration,
+        }
+    }
+
+    pub fn is_encrypted(&self) -> bool {
+        match self {
+            ProposedBatch::Plain(_) => false,
+            ProposedBatch::Encrypted(_) => true,
+            ProposedBatch::Reconfigure { batch, .. } => batch.is_encrypted(),
+        }
+    }
+
+    /// The requests of this batch, if they are not encrypted
+    pub fn plain_requests(&self) -> Option<&Vec<StoredMessage<RQ>>> {
+        match self {
+            ProposedBatch::Plain(requests) => Some(requests),
+            ProposedBatch::Encrypted(_) => None,
+            ProposedBatch::Reconfigure { batch, .. } => batch.plain_requests(),
+        }
+    }
+
+    pub fn reconfiguration(&self) -> Option<&Reconfiguration> {
+        match self {
+            ProposedBatch::Reconfigure {
+                reconfiguration, ..
+            } => Some(reconfiguration),
+            _ => None,
+        }
+    }
+
+    /// Split the reconfiguration proposed along with this batch from the batch itself.
+    /// The batch returned is never a [`ProposedBatch::Reconfigure`].
+    pub fn into_parts(self) -> (ProposedBatch<RQ>, Option<Reconfiguration>) {
+        match self {
+            ProposedBatch::Reconfigure {
+                batch,
+                reconfiguration,
+            } => (batch.into_parts().0, Some(reconfiguration)),
+            batch => (batch, None),
+        }
+    }
+
+    /// The digest identifying this batch, over the requests or over the ciphertext
+    pub fn digest(&self) -> Digest {
+        match self {
+            ProposedBatch::Plain(requests) => batch_digest(requests),
+            ProposedBatch::Encrypted(batch) => *batch.digest(),
+            ProposedBatch::Reconfigure {
+                batch,
+                reconfiguration,
+            } => {
+                let mut context = Context::new();
+
+                context.update(batch.digest().as_ref());
+                context.update(reconfiguration.digest().as_ref());
+
+                context.finish()
+            }
+        }
+    }
+}
+
+/// A proposed batch, identified by the digest of its requests or of its ciphertext
+impl<RQ> BroadcastPayload for ProposedBatch<RQ>
+where
+    RQ: SerMsg,
+{
+    fn payload_digest(&self) -> Digest {
+        self.digest()
+    }
+}
+
+/// A batch which has been encrypted under the threshold public key of the quorum.
+#[derive(Debug, Clone, PartialEq, Eq, Getters, Serialize, Deserialize)]
+pub struct EncryptedBatch {
+    #[get = "pub"]
+    ciphertext: Vec<u8>,
+    #[get = "pub"]
+    digest: Digest,
+}
+
+impl EncryptedBatch {
+    pub fn new(ciphertext: Vec<u8>) -> Self {
+        let mut context = Context::new();
+
+        context.update(&ciphertext);
+
+        Self {
+            digest: context.finish(),
+            ciphertext,
+        }
+    }
+
+    /// Decode the plaintext obtained by combining the decryption shares of this batch
+    pub(super) fn decode_plaintext<RQ>(plaintext: &[u8]) -> Result<Vec<StoredMessage<RQ>>, BatchError>
+    where
+        RQ: SerMsg,
+    {
+        bincode::serde::decode_from_slice(plaintext, bincode::config::standard())
+            .map(|(requests, _)| requests)
+            .map_err(|err| BatchError::FailedToDeserialize(err.to_string()))
+    }
+}
+
+/// Verifies the requests of encrypted batches once they are revealed.
+///
+/// Plain batches are verified along with the message that proposes them, but the requests
+/// of an encrypted batch are only known after the agreement of the epoch has decided it.
+pub trait RequestVerifier<RQ>: Debug + Send + Sync {
+    fn verify_request(&self, request: &StoredMessage<RQ>) -> atlas_common::error::Result<()>;
+}
+
+/// A [`RequestVerifier`] which checks requests with the verification helper of the
+/// ordering protocol, the same way the requests of plain batches are checked.
+pub struct HelperRequestVerifier<NI, OPM, OPVH> {
+    network_info: Arc<NI>,
+    _phantom: PhantomData<fn() -> (OPM, OPVH)>,
+}
+
+impl<NI, OPM, OPVH> HelperRequestVerifier<NI, OPM, OPVH> {
+    pub fn new(network_info: Arc<NI>) -> Self {
+        Self {
+            network_info,
+            _phantom: PhantomData,
+        }
+    }
+}
+
+impl<RQ, NI, OPM, OPVH> RequestVerifier<RQ> for HelperRequestVerifier<NI, OPM, OPVH>
+where
+    RQ: SerMsg,
+    NI: NetworkInformationProvider + Send + Sync,
+    OPM: OrderingProtocolMessage<RQ>,
+    OPVH: OrderProtocolVerificationHelper<RQ, OPM, NI>,
+{
+    fn verify_request(&self, request: &StoredMessage<RQ>) -> atlas_common::error::Result<()> {
+        OPVH::verify_request_message(
+            &self.network_info,
+            request.header(),
+            request.message().clone(),
+        )
+        .map(|_| ())
+    }
+}
+
+impl<NI, OPM, OPVH> Debug for HelperRequestVerifier<NI, OPM, OPVH> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HelperRequestVerifier").finish()
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum BatchError {
+    #[error("Failed to serialize the batch: {0}")]
+    FailedToSerialize(String),
+    #[error("Failed to deserialize the batch: {0}")]
+    FailedToDeserialize(String),
+    #[error("Threshold encryption error: {0}")]
+    Encryption(#[from] TPKEError),
+}
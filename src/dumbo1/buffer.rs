@@ -0,0 +1,133 @@
+use atlas_common::ordering::SeqNo;
+use either::Either;
+use getset::CopyGetters;
+use std::collections::BTreeMap;
+
+/// Messages received for epochs which we have not yet started.
+///
+/// Other nodes may be ahead of us, so messages for epochs past the ones we are running
+/// are stored until the epoch is created and then replayed into it.
+/// Only epochs within `window` epochs of the current one are buffered, and each epoch
+/// holds at most `capacity` messages, so a faulty node cannot make us store an unbounded
+/// amount of messages.
+pub(super) struct FutureEpochBuffer<M> {
+    window: usize,
+    capacity: usize,
+    epochs: BTreeMap<SeqNo, Vec<M>>,
+    statistics: BufferStatistics,
+}
+
+/// The outcome of attempting to buffer a message
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum BufferResult {
+    Buffered,
+    /// The epoch of the message has already been finalized
+    DroppedFinalized,
+    /// The epoch of the message is too far ahead of the current one
+    DroppedOutOfWindow,
+    /// The buffer of the epoch of the message is full
+    DroppedFull,
+}
+
+/// Counters of the messages which went through the future epoch buffer.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, CopyGetters)]
+#[get_copy = "pub"]
+pub struct BufferStatistics {
+    /// Messages which were stored in the buffer
+    buffered: usize,
+    /// Messages which were replayed into their epoch once it was created
+    replayed: usize,
+    /// Messages dropped because their epoch had already been finalized
+    dropped_finalized: usize,
+    /// Messages dropped because their epoch was outside of the window
+    dropped_out_of_window: usize,
+    /// Messages dropped because the buffer of their epoch was full
+    dropped_full: usize,
+}
+
+impl BufferStatistics {
+    /// The total amount of messages which were dropped
+    pub fn dropped(&self) -> usize {
+        self.dropped_finalized + self.dropped_out_of_window + self.dropped_full
+    }
+}
+
+impl<M> FutureEpochBuffer<M> {
+    pub(super) fn new(window: usize, capacity: usize) -> Self {
+        Self {
+            window,
+            capacity,
+            epochs: BTreeMap::new(),
+            statistics: BufferStatistics::default(),
+        }
+    }
+
+    pub(super) fn statistics(&self) -> BufferStatistics {
+        self.statistics
+    }
+
+    /// The amount of messages currently held in the buffer
+    pub(super) fn len(&self) -> usize {
+        self.epochs.values().map(Vec::len).sum()
+    }
+
+    /// Buffer a message for the given epoch, given the epoch we are currently at
+    pub(super) fn buffer(&mut self, current: SeqNo, epoch: SeqNo, message: M) -> BufferResult {
+        let result = match epoch.index(current) {
+            Either::Left(_) => BufferResult::DroppedFinalized,
+            Either::Right(distance) if distance >= self.window => {
+                BufferResult::DroppedOutOfWindow
+            }
+            Either::Right(_) => {
+                let messages = self.epochs.entry(epoch).or_default();
+
+                if messages.len() >= self.capacity {
+                    BufferResult::DroppedFull
+                } else {
+                    messages.push(message);
+
+                    BufferResult::Buffered
+                }
+            }
+        };
+
+        match result {
+            BufferResult::Buffered => self.statistics.buffered += 1,
+            BufferResult::DroppedFinalized => self.statistics.dropped_finalized += 1,
+            BufferResult::DroppedOutOfWindow => self.statistics.dropped_out_of_window += 1,
+            BufferResult::DroppedFull => self.statistics.dropped_full += 1,
+        }
+
+        result
+    }
+
+    /// Take the messages buffered for the given epoch, in the order they were received,
+    /// so they can be replayed into the newly created epoch
+    pub(super) fn take(&mut self, epoch: SeqNo) -> Vec<M> {
+        let messages = self.epochs.remove(&epoch).unwrap_or_default();
+
+        self.statistics.replayed += messages.len();
+
+        messages
+    }
+
+    /// Discard the messages of every epoch before `current`, which have been finalized
+    pub(super) fn discard_finalized(&mut self, current: SeqNo) {
+        let retained = self.epochs.split_off(&current);
+
+        let discarded = std::mem::replace(&mut self.epochs, retained);
+
+        self.statistics.dropped_finalized += discarded.values().map(Vec::len).sum::<usize>();
+    }
+}
+
+impl<M> std::fmt::Debug for FutureEpochBuffer<M> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FutureEpochBuffer")
+            .field("window", &self.window)
+            .field("epochs", &self.epochs.len())
+            .field("messages", &self.len())
+            .field("statistics", &self.statistics)
+            .finish()
+    }
+}
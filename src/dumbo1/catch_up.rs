@@ -0,0 +1,331 @@
+use crate::dumbo1::certificate::DecisionCertificate;
+use crate::quorum_info::quorum_info::QuorumInfo;
+use crate::rq_aggregator::rq_aggregator::batch_digest;
+use atlas_common::collections::{HashMap, HashSet};
+use atlas_common::crypto::hash::Digest;
+use atlas_common::crypto::threshold_crypto::PublicKeySet;
+use atlas_common::node_id::NodeId;
+use atlas_common::ordering::{Orderable, SeqNo};
+use atlas_communication::message::StoredMessage;
+use atlas_core::ordering_protocol::{BatchedDecision, ProtocolConsensusDecision};
+use getset::Getters;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, VecDeque};
+use tracing::{debug, info};
+
+/// The messages of the catch up sub protocol, used by replicas which have fallen
+/// too far behind the rest of the quorum to rejoin through the normal message flow.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum CatchUpMessage<RQ> {
+    /// Request the decided epochs in `[from, to)`
+    Request { from: SeqNo, to: SeqNo },
+    /// The decided epochs we know of, within the requested range, in epoch order
+    Response(Vec<DecidedEpoch<RQ>>),
+}
+
+/// An epoch which has been decided, along with the certificate proving it.
+#[derive(Debug, Clone, Getters, Serialize, Deserialize)]
+pub struct DecidedEpoch<RQ> {
+    #[get = "pub"]
+    certificate: DecisionCertificate,
+    /// The ordered output of the epoch
+    #[get = "pub"]
+    requests: Vec<StoredMessage<RQ>>,
+}
+
+impl<RQ> DecidedEpoch<RQ> {
+    pub fn new(certificate: DecisionCertificate, requests: Vec<StoredMessage<RQ>>) -> Self {
+        Self {
+            certificate,
+            requests,
+        }
+    }
+
+    /// Whether the requests match the output digest certified for the epoch
+    pub fn matches_certificate(&self) -> bool {
+        batch_digest(&self.requests) == *self.certificate.output_digest()
+    }
+
+    /// Map this epoch into the decision handed to the Atlas replica, along with its certificate
+    pub fn into_protocol_decision(self) -> (DecisionCertificate, ProtocolConsensusDecision<RQ>) {
+        let epoch = self.certificate.epoch();
+        let digest = *self.certificate.output_digest();
+
+        let batch = BatchedDecision::new(epoch, self.requests, None);
+
+        (
+            self.certificate,
+            ProtocolConsensusDecision::new(epoch, batch, Some(digest)),
+        )
+    }
+
+    pub fn into_inner(self) -> (DecisionCertificate, Vec<StoredMessage<RQ>>) {
+        (self.certificate, self.requests)
+    }
+}
+
+impl<RQ> Orderable for DecidedEpoch<RQ> {
+    fn sequence_number(&self) -> SeqNo {
+        self.certificate.epoch()
+    }
+}
+
+/// The catch up state of a Dumbo replica.
+///
+/// Keeps the latest decided epochs, to serve the requests of lagging peers, and
+/// detects when we are lagging ourselves: once members holding more than `f` of the weight
+/// have sent us messages for epochs past our buffering window, at least one correct replica
+/// is that far ahead. We then request the missing epochs, and accept each of them once it is
+/// either certified by the threshold signature of the quorum, or we have received matching
+/// answers for it from members holding more than `f` of the weight.
+pub(super) struct CatchUp<RQ> {
+    quorum_info: QuorumInfo,
+    certificate_keys: Option<PublicKeySet>,
+    // The latest decided epochs, in epoch order
+    history: VecDeque<DecidedEpoch<RQ>>,
+    max_history: usize,
+    // The highest epoch each member has sent us a message for, when past our window
+    ahead: HashMap<NodeId, SeqNo>,
+    // The catch up we are currently performing, if any
+    ongoing: Option<OngoingCatchUp<RQ>>,
+}
+
+struct OngoingCatchUp<RQ> {
+    to: SeqNo,
+    // The answers received for each epoch, grouped by the payload certifying their output
+    // and reconfigurations
+    answers: BTreeMap<SeqNo, HashMap<Digest, (DecidedEpoch<RQ>, HashSet<NodeId>)>>,
+}
+
+impl<RQ> CatchUp<RQ>
+where
+    RQ: Clone,
+{
+    pub(super) fn new(
+        quorum_info: QuorumInfo,
+        certificate_keys: Option<PublicKeySet>,
+        max_history: usize,
+    ) -> Self {
+        Self {
+            quorum_info,
+            certificate_keys,
+            history: VecDeque::new(),
+            max_history,
+            ahead: HashMap::default(),
+            ongoing: None,
+        }
+    }
+
+    /// Move to the membership of a reconfiguration which has taken effect.
+    /// The epochs we serve and accept from then on are certified by the new quorum.
+    pub(super) fn set_membership(
+        &mut self,
+        quorum_info: QuorumInfo,
+        certificate_keys: Option<PublicKeySet>,
+    ) {
+        self.quorum_info = quorum_info;
+        self.certificate_keys = certificate_keys;
+        self.ahead.clear();
+    }
+
+    pub(super) fn is_catching_up(&self) -> bool {
+        self.ongoing.is_some()
+    }
+
+    /// Record an epoch we have decided, so we can serve it to lagging peers
+    pub(super) fn record_decided(&mut self, epoch: DecidedEpoch<RQ>) {
+        if self.history.len() >= self.max_history {
+            self.history.pop_front();
+        }
+
+        self.history.push_back(epoch);
+    }
+
+    /// Discard the decided epochs up to and including the given one, which are
+    /// covered by a stable checkpoint
+    pub(super) fn discard_history_up_to(&mut self, epoch: SeqNo) {
+        while self
+            .history
+            .front()
+            .is_some_and(|decided| decided.sequence_number() <= epoch)
+        {
+            self.history.pop_front();
+        }
+    }
+
+    /// The decided epochs we hold within `[from, to)`
+    pub(super) fn serve(&self, from: SeqNo, to: SeqNo) -> Vec<DecidedEpoch<RQ>> {
+        self.history
+            .iter()
+            .filter(|epoch| epoch.sequence_number() >= from && epoch.sequence_number() < to)
+            .cloned()
+            .collect()
+    }
+
+    /// Register that a member has sent us a message for an epoch past our buffering window.
+    ///
+    /// Returns the range of epochs to request when we should start catching up.
+    pub(super) fn observe_ahead(
+        &mut self,
+        sender: NodeId,
+        epoch: SeqNo,
+        current: SeqNo,
+    ) -> Option<(SeqNo, SeqNo)> {
+        if !self.quorum_info.is_member(sender) || self.ongoing.is_some() {
+            return None;
+        }
+
+        let highest = self.ahead.entry(sender).or_insert(epoch);
+
+        if epoch > *highest {
+            *highest = epoch;
+        }
+
+        let mut highest_epochs = self
+            .ahead
+            .iter()
+            .filter(|(_, highest)| **highest > current)
+            .map(|(member, highest)| (*highest, self.quorum_info.weight_of(*member)))
+            .collect::<Vec<_>>();
+
+        // At least one of the highest epochs reported by more than f of the weight
+        // was reported by a correct replica
+        highest_epochs.sort_by(|a, b| b.cmp(a));
+
+        let mut weight = 0;
+
+        let (to, _) = highest_epochs.into_iter().find(|(_, member_weight)| {
+            weight += member_weight;
+
+            weight > self.quorum_info.f()
+        })?;
+
+        self.begin(current, to)
+    }
+
+    /// Start catching up on the epochs in `[current, to)`, unless we are already catching up.
+    ///
+    /// Returns the range of epochs to request.
+    pub(super) fn begin(&mut self, current: SeqNo, to: SeqNo) -> Option<(SeqNo, SeqNo)> {
+        if self.ongoing.is_some() || to <= current {
+            return None;
+        }
+
+        info!("Lagging behind the quorum, catching up on epochs [{current:?}, {to:?})");
+
+        self.ahead.clear();
+        self.ongoing = Some(OngoingCatchUp {
+            to,
+            answers: BTreeMap::new(),
+        });
+
+        Some((current, to))
+    }
+
+    /// Process a response to our catch up request.
+    ///
+    /// Returns the epochs, starting at `current`, which can now be installed, in epoch order.
+    pub(super) fn process_response(
+        &mut self,
+        sender: NodeId,
+        epochs: Vec<DecidedEpoch<RQ>>,
+        current: SeqNo,
+    ) -> Vec<DecidedEpoch<RQ>> {
+        let Some(ongoing) = &mut self.ongoing else {
+            return Vec::new();
+        };
+
+        if !self.quorum_info.is_member(sender) {
+            return Vec::new();
+        }
+
+        let mut certified = BTreeMap::new();
+
+        for epoch in epochs {
+            let seq = epoch.sequence_number();
+
+            if seq < current || seq >= ongoing.to {
+                continue;
+            }
+
+            if !epoch.matches_certificate() || epoch.certificate().verify(&self.quorum_info).is_err() {
+                debug!("Received an invalid decided epoch {seq:?} from {sender:?}, ignoring it");
+                continue;
+            }
+
+            let is_signed = self
+                .certificate_keys
+                .as_ref()
+                .is_some_and(|keys| epoch.certificate().verify_signature(keys).is_ok());
+
+            if is_signed {
+                certified.insert(seq, epoch);
+                continue;
+            }
+
+            let (_, senders) = ongoing
+                .answers
+                .entry(seq)
+                .or_default()
+                .entry(epoch.certificate().signing_payload())
+                .or_insert_with(|| (epoch, HashSet::default()));
+
+            senders.insert(sender);
+        }
+
+        let quorum_info = &self.quorum_info;
+
+        let mut ready = Vec::new();
+        let mut next = current;
+
+        while next < ongoing.to {
+            let decided = certified.remove(&next).or_else(|| {
+                ongoing.answers.get(&next).and_then(|answers| {
+                    answers
+                        .values()
+                        .find(|(_, senders)| {
+                            quorum_info.accumulated_weight(senders) > quorum_info.f()
+                        })
+                        .map(|(epoch, _)| epoch.clone())
+                })
+            });
+
+            let Some(decided) = decided else {
+                break;
+            };
+
+            ongoing.answers.remove(&next);
+            ready.push(decided);
+            next = next.next();
+        }
+
+        if next >= ongoing.to {
+            info!("Finished catching up at epoch {next:?}");
+
+            self.ongoing = None;
+        }
+
+        ready
+    }
+
+    /// Abandon the ongoing catch up, as we have moved past the requested range by other means
+    pub(super) fn discard_before(&mut self, current: SeqNo) {
+        if self
+            .ongoing
+            .as_ref()
+            .is_some_and(|ongoing| ongoing.to <= current)
+        {
+            self.ongoing = None;
+        }
+    }
+}
+
+impl<RQ> std::fmt::Debug for CatchUp<RQ> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CatchUp")
+            .field("history", &self.history.len())
+            .field("ahead", &self.ahead)
+            .field("catching_up_to", &self.ongoing.as_ref().map(|ongoing| ongoing.to))
+            .finish()
+    }
+}
@@ -0,0 +1,270 @@
+use crate::dumbo1::reconfiguration::Reconfiguration;
+use crate::quorum_info::quorum_info::QuorumInfo;
+use crate::reliable_broadcast::index_rbc::{IndexRBCError, IndexVector};
+use atlas_common::collections::HashSet;
+use atlas_common::crypto::hash::{Context, Digest};
+use atlas_common::crypto::threshold_crypto::{CombinedSignature, PublicKeySet};
+use atlas_common::node_id::NodeId;
+use atlas_common::ordering::{Orderable, SeqNo};
+use getset::{CopyGetters, Getters};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use thiserror::Error;
+
+/// The outcome of the agreement phase of an epoch, as recorded by the epoch itself.
+#[derive(Debug, Clone, PartialEq, Eq, Getters, Serialize, Deserialize)]
+pub struct AgreementOutcome {
+    /// The decision of the binary agreement of each member of the committee, ordered by member
+    #[get = "pub"]
+    agreement_decisions: Vec<(NodeId, bool)>,
+    /// The index vector of each committee member whose agreement decided 1, ordered by member
+    #[get = "pub(crate)"]
+    index_vectors: Vec<(NodeId, IndexVector)>,
+    /// The digest of the reliably broadcast batch of each included member, ordered by member
+    #[get = "pub"]
+    batch_digests: Vec<(NodeId, Digest)>,
+}
+
+impl AgreementOutcome {
+    pub(super) fn new(
+        mut agreement_decisions: Vec<(NodeId, bool)>,
+        mut index_vectors: Vec<(NodeId, IndexVector)>,
+        mut batch_digests: Vec<(NodeId, Digest)>,
+    ) -> Self {
+        agreement_decisions.sort_by_key(|(node, _)| *node);
+        index_vectors.sort_by_key(|(node, _)| *node);
+        batch_digests.sort_by_key(|(node, _)| *node);
+
+        Self {
+            agreement_decisions,
+            index_vectors,
+            batch_digests,
+        }
+    }
+
+    /// The committee members whose index vector was accepted by their agreement
+    pub fn accepted(&self) -> impl Iterator<Item = NodeId> + '_ {
+        self.agreement_decisions
+            .iter()
+            .filter(|(_, decision)| *decision)
+            .map(|(node, _)| *node)
+    }
+
+    /// The members whose batch was included in the epoch,
+    /// which are the ones referenced by any of the accepted index vectors
+    pub fn included(&self) -> impl Iterator<Item = NodeId> {
+        self.index_vectors
+            .iter()
+            .flat_map(|(_, vector)| vector.indices().iter().cloned())
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+    }
+}
+
+/// The evidence that an epoch was decided, emitted along with every decision.
+///
+/// Holds everything needed to check that an epoch output follows from the agreement
+/// of the quorum, without replaying the messages of the epoch.
+/// The output signature is a threshold signature over [`DecisionCertificate::signing_payload`],
+/// which allows a single certificate to be trusted without gathering `f + 1` matching copies.
+#[derive(Debug, Clone, PartialEq, Eq, Getters, CopyGetters, Serialize, Deserialize)]
+pub struct DecisionCertificate {
+    #[get_copy = "pub"]
+    epoch: SeqNo,
+    /// The committee elected in the epoch
+    #[get = "pub"]
+    committee: Vec<NodeId>,
+    /// The members whose batch was included in the epoch
+    #[get = "pub(crate)"]
+    included: IndexVector,
+    #[get = "pub"]
+    outcome: AgreementOutcome,
+    /// The digest of the ordered output of the epoch
+    #[get = "pub"]
+    output_digest: Digest,
+    /// The reconfigurations proposed along with the included batches, so that a replica
+    /// which installs the epoch without deciding it schedules the same reconfiguration
+    #[get = "pub"]
+    reconfigurations: Vec<(NodeId, Reconfiguration)>,
+    #[get = "pub"]
+    output_signature: Option<CombinedSignature>,
+}
+
+impl DecisionCertificate {
+    pub(super) fn new(
+        epoch: SeqNo,
+        committee: Vec<NodeId>,
+        outcome: AgreementOutcome,
+        output_digest: Digest,
+    ) -> Self {
+        Self {
+            epoch,
+            committee,
+            included: IndexVector::new(outcome.included().collect()),
+            outcome,
+            output_digest,
+            reconfigurations: Vec::new(),
+            output_signature: None,
+        }
+    }
+
+    /// Attach the reconfigurations proposed along with the included batches, by their proposer
+    pub(super) fn with_reconfigurations(
+        mut self,
+        reconfigurations: Vec<(NodeId, Reconfiguration)>,
+    ) -> Self {
+        self.reconfigurations = reconfigurations;
+        self
+    }
+
+    /// Attach the threshold signature of the quorum over the output of the epoch
+    pub fn with_output_signature(mut self, signature: CombinedSignature) -> Self {
+        self.output_signature = Some(signature);
+        self
+    }
+
+    /// The payload signed by the quorum to certify the output of the epoch
+    pub fn signing_payload(&self) -> Digest {
+        signing_payload(self.epoch, &self.output_digest, &self.reconfigurations)
+    }
+
+    /// Check that this certificate is consistent with the given quorum.
+    ///
+    /// The committee must be made of distinct members of the quorum, each of which must have
+    /// exactly one agreement decision. Every member whose agreement decided 1 must have a valid
+    /// index vector, and the included batches must be exactly the ones referenced by those vectors,
+    /// each with a digest.
+    /// Reconfigurations can only have been proposed along with an included batch.
+    pub fn verify(&self, quorum_info: &QuorumInfo) -> Result<(), CertificateError> {
+        let mut committee = HashSet::default();
+
+        for member in &self.committee {
+            if !quorum_info.is_member(*member) || !committee.insert(*member) {
+                return Err(CertificateError::InvalidCommittee);
+            }
+        }
+
+        let mut decided = HashSet::default();
+
+        for (node, _) in self.outcome.agreement_decisions() {
+            if !committee.contains(node) {
+                return Err(CertificateError::UnknownMember(*node));
+            }
+
+            if !decided.insert(*node) {
+                return Err(CertificateError::DuplicateDecision(*node));
+            }
+        }
+
+        if decided.len() != committee.len() {
+            return Err(CertificateError::MissingDecisions(
+                decided.len(),
+                committee.len(),
+            ));
+        }
+
+        let vector_owners = self
+            .outcome
+            .index_vectors()
+            .iter()
+            .map(|(node, _)| *node)
+            .collect::<Vec<_>>();
+
+        if vector_owners != self.outcome.accepted().collect::<Vec<_>>() {
+            return Err(CertificateError::InconsistentInclusion);
+        }
+
+        for (_, vector) in self.outcome.index_vectors() {
+            vector.validate(quorum_info)?;
+        }
+
+        if self.included != IndexVector::new(self.outcome.included().collect()) {
+            return Err(CertificateError::InconsistentInclusion);
+        }
+
+        self.included.validate(quorum_info)?;
+
+        let digested = self
+            .outcome
+            .batch_digests()
+            .iter()
+            .map(|(node, _)| *node)
+            .collect::<Vec<_>>();
+
+        if &digested != self.included.indices() {
+            return Err(CertificateError::InconsistentInclusion);
+        }
+
+        let mut proposers = HashSet::default();
+
+        for (proposer, _) in &self.reconfigurations {
+            if !self.included.indices().contains(proposer) || !proposers.insert(*proposer) {
+                return Err(CertificateError::InvalidReconfiguration(*proposer));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Check the threshold signature of this certificate, if it carries one
+    pub fn verify_signature(&self, public_key_set: &PublicKeySet) -> Result<(), CertificateError> {
+        let Some(signature) = &self.output_signature else {
+            return Err(CertificateError::MissingSignature);
+        };
+
+        public_key_set
+            .verify(self.signing_payload().as_ref(), signature)
+            .map_err(|_| CertificateError::InvalidSignature)
+    }
+}
+
+impl Orderable for DecisionCertificate {
+    fn sequence_number(&self) -> SeqNo {
+        self.epoch
+    }
+}
+
+/// The payload signed by the quorum to certify the output of an epoch,
+/// along with the reconfigurations proposed in it
+pub fn signing_payload(
+    epoch: SeqNo,
+    output_digest: &Digest,
+    reconfigurations: &[(NodeId, Reconfiguration)],
+) -> Digest {
+    let mut context = Context::new();
+
+    let serialized_epoch = bincode::serde::encode_to_vec(epoch, bincode::config::standard())
+        .expect("Failed to serialize epoch number");
+
+    context.update(&serialized_epoch);
+    context.update(output_digest.as_ref());
+
+    for (proposer, reconfiguration) in reconfigurations {
+        context.update(&proposer.0.to_le_bytes()[..]);
+        context.update(reconfiguration.digest().as_ref());
+    }
+
+    context.finish()
+}
+
+#[derive(Error, Debug)]
+pub enum CertificateError {
+    #[error("The certificate contains a decision for {0:?}, which is not a member of the committee")]
+    UnknownMember(NodeId),
+    #[error("The certificate contains more than one decision for {0:?}")]
+    DuplicateDecision(NodeId),
+    #[error("The certificate contains {0} decisions, but the committee has {1} members")]
+    MissingDecisions(usize, usize),
+    #[error("The included batches do not match the accepted index vectors")]
+    InconsistentInclusion,
+    #[error("Invalid inclusion vector: {0}")]
+    InvalidInclusion(#[from] IndexRBCError),
+    #[error("The committee contains nodes which are not members of the quorum, or repeats a member")]
+    InvalidCommittee,
+    #[error("The certificate carries a reconfiguration of {0:?}, whose batch was not included or which proposed more than one")]
+    InvalidReconfiguration(NodeId),
+    #[error("The certificate carries no output signature")]
+    MissingSignature,
+    #[error("The output signature of the certificate is invalid")]
+    InvalidSignature,
+}
@@ -0,0 +1,417 @@
+use crate::quorum_info::quorum_info::QuorumInfo;
+use crate::rq_aggregator::rq_aggregator::DecidedRequests;
+use atlas_common::collections::{HashMap, HashSet};
+use atlas_common::crypto::hash::{Context, Digest};
+use atlas_common::node_id::NodeId;
+use atlas_common::ordering::{Orderable, SeqNo};
+use atlas_communication::message::StoredMessage;
+use getset::{CopyGetters, Getters};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use thiserror::Error;
+use tracing::{info, warn};
+
+/// A checkpoint vote, sent by every member once it has decided a checkpoint epoch.
+///
+/// The digest covers the output of every epoch since the previous checkpoint,
+/// so matching votes mean the members agree on the log up to the checkpoint.
+#[derive(Debug, Clone, PartialEq, Eq, Getters, CopyGetters, Serialize, Deserialize)]
+pub struct CheckpointMessage {
+    #[get_copy = "pub"]
+    epoch: SeqNo,
+    #[get = "pub"]
+    digest: Digest,
+}
+
+impl CheckpointMessage {
+    pub fn new(epoch: SeqNo, digest: Digest) -> Self {
+        Self { epoch, digest }
+    }
+}
+
+impl Orderable for CheckpointMessage {
+    fn sequence_number(&self) -> SeqNo {
+        self.epoch
+    }
+}
+
+/// The state of the protocol once the epoch of a checkpoint has been decided.
+///
+/// The decided epochs a stable checkpoint covers are discarded, so what they contributed
+/// to the state of the protocol is kept here, and restored along with the checkpoint.
+#[derive(Debug, Clone, PartialEq, Eq, Getters, CopyGetters, Serialize, Deserialize)]
+pub struct CheckpointState {
+    /// The sequence numbers decided for each client, which later epochs are deduplicated against
+    #[get = "pub(super)"]
+    decided: BTreeMap<NodeId, DecidedRequests>,
+    /// The quorum which ran the epoch of the checkpoint
+    #[get = "pub(super)"]
+    quorum_info: QuorumInfo,
+    /// Whether the batches of that quorum are threshold encrypted
+    #[get_copy = "pub(super)"]
+    encrypted: bool,
+    /// The reconfiguration which was decided but has not yet taken effect: the epoch it takes
+    /// effect in, its quorum and whether its batches are threshold encrypted
+    #[get = "pub(super)"]
+    scheduled: Option<(SeqNo, QuorumInfo, bool)>,
+}
+
+impl CheckpointState {
+    pub(super) fn new(
+        decided: BTreeMap<NodeId, DecidedRequests>,
+        quorum_info: QuorumInfo,
+        encrypted: bool,
+        scheduled: Option<(SeqNo, QuorumInfo, bool)>,
+    ) -> Self {
+        Self {
+            decided,
+            quorum_info,
+            encrypted,
+            scheduled,
+        }
+    }
+
+    fn digest(&self) -> Digest {
+        let serialized = bincode::serde::encode_to_vec(self, bincode::config::standard())
+            .expect("Failed to serialize checkpoint state");
+
+        let mut context = Context::new();
+        context.update(&serialized);
+        context.finish()
+    }
+}
+
+/// The digest members vote on for a checkpoint, which covers both the outputs
+/// of the epochs since the previous checkpoint and the state at the checkpoint
+fn checkpoint_digest(outputs_digest: &Digest, state: &CheckpointState) -> Digest {
+    let mut context = Context::new();
+
+    context.update(outputs_digest.as_ref());
+    context.update(state.digest().as_ref());
+
+    context.finish()
+}
+
+/// A checkpoint which members holding `n - f` of the weight have signed.
+///
+/// Everything up to and including its epoch can be discarded, and a replica lagging
+/// further behind than it has to obtain the state through state transfer.
+#[derive(Debug, Clone, Getters, CopyGetters, Serialize, Deserialize)]
+pub struct StableCheckpoint {
+    #[get_copy = "pub"]
+    epoch: SeqNo,
+    #[get = "pub"]
+    digest: Digest,
+    /// The digest of the outputs of the epochs since the previous checkpoint
+    #[get = "pub"]
+    outputs_digest: Digest,
+    #[get = "pub"]
+    state: CheckpointState,
+    /// The signed votes of the members which agreed on the checkpoint
+    #[get = "pub"]
+    proof: Vec<StoredMessage<CheckpointMessage>>,
+}
+
+impl StableCheckpoint {
+    /// Check that members of the given quorum holding `n - f` of the weight voted for this
+    /// checkpoint, and that the state it carries is the one they voted for.
+    ///
+    /// The signatures of the votes are those of the headers of the proof, which must be verified
+    /// along with the messages which carried the checkpoint to us.
+    pub fn verify(&self, quorum_info: &QuorumInfo) -> Result<(), CheckpointError> {
+        if checkpoint_digest(&self.outputs_digest, &self.state) != self.digest {
+            return Err(CheckpointError::DigestMismatch(self.epoch));
+        }
+
+        let mut voters = HashSet::default();
+
+        for vote in &self.proof {
+            let voter = vote.header().from();
+
+            if !quorum_info.is_member(voter) {
+                return Err(CheckpointError::NonMemberVote(voter));
+            }
+
+            if vote.message().epoch() != self.epoch || *vote.message().digest() != self.digest {
+                return Err(CheckpointError::MismatchedVote(voter));
+            }
+
+            if !voters.insert(voter) {
+                return Err(CheckpointError::DuplicateVote(voter));
+            }
+        }
+
+        let weight = quorum_info.accumulated_weight(&voters);
+
+        if weight < quorum_info.quorum_size() {
+            return Err(CheckpointError::NotEnoughVotes(
+                weight,
+                quorum_info.quorum_size(),
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+impl Orderable for StableCheckpoint {
+    fn sequence_number(&self) -> SeqNo {
+        self.epoch
+    }
+}
+
+/// A checkpoint we have taken ourselves, which is not yet stable
+#[derive(Debug)]
+struct OwnCheckpoint {
+    digest: Digest,
+    outputs_digest: Digest,
+    state: CheckpointState,
+}
+
+pub(super) enum CheckpointResult {
+    MessageIgnored,
+    MessageProcessed,
+    /// Members holding more than `f` of the weight agree on a checkpoint for an epoch
+    /// we have not yet decided, so at least one correct replica is ahead of us
+    Lagging(SeqNo),
+    /// A checkpoint has become stable, and the state up to its epoch can be discarded
+    Stable(StableCheckpoint),
+}
+
+/// The checkpoints of a Dumbo replica, taken every `period` epochs.
+pub(super) struct Checkpoints {
+    quorum_info: QuorumInfo,
+    period: usize,
+    // The epoch of the next checkpoint
+    next_checkpoint: SeqNo,
+    // The digest of the outputs decided since the last checkpoint.
+    // None when we did not decide every epoch of the period ourselves, in which case we do not vote.
+    period_digest: Option<Context>,
+    // Our own checkpoints which are not yet stable
+    own: BTreeMap<SeqNo, OwnCheckpoint>,
+    // The votes received for checkpoints which are not yet stable, grouped by digest
+    votes: BTreeMap<SeqNo, HashMap<Digest, Vec<StoredMessage<CheckpointMessage>>>>,
+    last_stable: Option<StableCheckpoint>,
+}
+
+impl Checkpoints {
+    pub(super) fn new(quorum_info: QuorumInfo, period: usize) -> Self {
+        Self {
+            quorum_info,
+            period,
+            next_checkpoint: advance(SeqNo::ZERO, period),
+            period_digest: Some(Context::new()),
+            own: BTreeMap::new(),
+            votes: BTreeMap::new(),
+            last_stable: None,
+        }
+    }
+
+    /// Move to the quorum of a reconfiguration which has taken effect.
+    /// Votes for checkpoints which are not yet stable are kept, as they were sent by members
+    /// of the quorum which decided the epochs they cover.
+    pub(super) fn set_quorum_info(&mut self, quorum_info: QuorumInfo) {
+        self.quorum_info = quorum_info;
+    }
+
+    pub(super) fn last_stable(&self) -> Option<&StableCheckpoint> {
+        self.last_stable.as_ref()
+    }
+
+    /// Record the output of a decided epoch.
+    ///
+    /// Returns our vote when the epoch is a checkpoint epoch, which covers the state of
+    /// the protocol at the epoch as well, so it is only taken then.
+    pub(super) fn record_decided<F>(
+        &mut self,
+        epoch: SeqNo,
+        output_digest: &Digest,
+        state: F,
+    ) -> Option<CheckpointMessage>
+    where
+        F: FnOnce() -> CheckpointState,
+    {
+        if epoch < self.next_checkpoint {
+            if let Some(context) = &mut self.period_digest {
+                context.update(output_digest.as_ref());
+            }
+
+            return None;
+        }
+
+        let context = std::mem::replace(&mut self.period_digest, Some(Context::new()));
+
+        self.next_checkpoint = advance(epoch, self.period);
+
+        let mut context = context?;
+        context.update(output_digest.as_ref());
+
+        let outputs_digest = context.finish();
+        let state = state();
+        let digest = checkpoint_digest(&outputs_digest, &state);
+
+        self.own.insert(
+            epoch,
+            OwnCheckpoint {
+                digest,
+                outputs_digest,
+                state,
+            },
+        );
+
+        Some(CheckpointMessage::new(epoch, digest))
+    }
+
+    /// We have moved to the given epoch without deciding the epochs before it
+    /// (through state transfer), so we can not vote for the checkpoint of the current period.
+    pub(super) fn skip_to(&mut self, epoch: SeqNo) {
+        self.period_digest = None;
+
+        while self.next_checkpoint < epoch {
+            self.next_checkpoint = advance(self.next_checkpoint, self.period);
+        }
+
+        if self.next_checkpoint == epoch {
+            // The new period starts right at the epoch we skipped to
+            self.next_checkpoint = advance(epoch, self.period);
+            self.period_digest = Some(Context::new());
+        }
+    }
+
+    /// Restore a stable checkpoint which was persisted before the replica stopped,
+    /// or which was obtained through state transfer
+    pub(super) fn restore(&mut self, checkpoint: StableCheckpoint) {
+        self.next_checkpoint = advance(checkpoint.epoch, self.period);
+        self.period_digest = Some(Context::new());
+        self.own.retain(|epoch, _| *epoch > checkpoint.epoch);
+        self.votes.retain(|epoch, _| *epoch > checkpoint.epoch);
+        self.last_stable = Some(checkpoint);
+    }
+
+    pub(super) fn process_message(
+        &mut self,
+        message: StoredMessage<CheckpointMessage>,
+        current: SeqNo,
+    ) -> CheckpointResult {
+        let sender = message.header().from();
+        let epoch = message.message().epoch();
+
+        if !self.quorum_info.is_member(sender)
+            || self
+                .last_stable
+                .as_ref()
+                .is_some_and(|stable| epoch <= stable.epoch)
+        {
+            return CheckpointResult::MessageIgnored;
+        }
+
+        let epoch_votes = self.votes.entry(epoch).or_default();
+
+        if epoch_votes
+            .values()
+            .flatten()
+            .any(|vote| vote.header().from() == sender)
+        {
+            return CheckpointResult::MessageIgnored;
+        }
+
+        let digest = *message.message().digest();
+
+        let votes = epoch_votes.entry(digest).or_default();
+        votes.push(message);
+
+        let vote_weight = self
+            .quorum_info
+            .accumulated_weight(votes.iter().map(|vote| vote.header().from()));
+
+        // Only report the epoch as lagging when this vote takes its weight past f
+        let crossed = vote_weight > self.quorum_info.f()
+            && vote_weight - self.quorum_info.weight_of(sender) <= self.quorum_info.f();
+
+        if epoch >= current && crossed {
+            return CheckpointResult::Lagging(epoch);
+        }
+
+        match self.check_stable(epoch) {
+            Some(stable) => CheckpointResult::Stable(stable),
+            None => CheckpointResult::MessageProcessed,
+        }
+    }
+
+    /// Check whether the checkpoint at the given epoch has become stable.
+    ///
+    /// This requires matching votes from members holding `n - f` of the weight,
+    /// and our own checkpoint to match them, as we only discard the state we have agreed on.
+    pub(super) fn check_stable(&mut self, epoch: SeqNo) -> Option<StableCheckpoint> {
+        let own = self.own.get(&epoch)?.digest;
+
+        let epoch_votes = self.votes.get_mut(&epoch)?;
+
+        let agreed = epoch_votes
+            .iter()
+            .find(|(_, votes)| {
+                let senders = votes.iter().map(|vote| vote.header().from());
+
+                self.quorum_info.accumulated_weight(senders) >= self.quorum_info.quorum_size()
+            })
+            .map(|(digest, _)| *digest)?;
+
+        if agreed != own {
+            warn!("The quorum agreed on a checkpoint at {epoch:?} which does not match ours");
+
+            return None;
+        }
+
+        let proof = epoch_votes.remove(&own)?;
+
+        let OwnCheckpoint {
+            digest,
+            outputs_digest,
+            state,
+        } = self.own.remove(&epoch)?;
+
+        let stable = StableCheckpoint {
+            epoch,
+            digest,
+            outputs_digest,
+            state,
+            proof,
+        };
+
+        self.votes = self.votes.split_off(&epoch.next());
+        self.own = self.own.split_off(&epoch.next());
+        self.last_stable = Some(stable.clone());
+
+        info!("Checkpoint at epoch {epoch:?} is stable");
+
+        Some(stable)
+    }
+}
+
+impl std::fmt::Debug for Checkpoints {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Checkpoints")
+            .field("next_checkpoint", &self.next_checkpoint)
+            .field("pending", &self.votes.keys().collect::<Vec<_>>())
+            .field("last_stable", &self.last_stable.as_ref().map(|stable| stable.epoch))
+            .finish()
+    }
+}
+
+fn advance(epoch: SeqNo, by: usize) -> SeqNo {
+    (0..by).fold(epoch, |epoch, _| epoch.next())
+}
+
+#[derive(Error, Debug)]
+pub enum CheckpointError {
+    #[error("The state of the checkpoint at {0:?} does not match the digest voted for")]
+    DigestMismatch(SeqNo),
+    #[error("The checkpoint carries a vote of {0:?}, which is not a member of the quorum")]
+    NonMemberVote(NodeId),
+    #[error("The checkpoint carries a vote of {0:?} for another checkpoint")]
+    MismatchedVote(NodeId),
+    #[error("The checkpoint carries more than one vote of {0:?}")]
+    DuplicateVote(NodeId),
+    #[error("The votes of the checkpoint only hold a weight of {0}, {1} is required")]
+    NotEnoughVotes(usize, usize),
+}
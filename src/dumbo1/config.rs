@@ -0,0 +1,155 @@
+use crate::dumbo1::ordering::OrderingStrategy;
+use crate::rq_aggregator::rq_aggregator::RequestAggregatorConfig;
+use crate::tpke::ThresholdEncryptionScheme;
+use atlas_common::crypto::threshold_crypto::{PrivateKeyPart, PublicKeySet};
+use getset::{CopyGetters, Getters};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+/// The configuration of the Dumbo1 protocol.
+#[derive(Debug, Clone, Getters, CopyGetters)]
+pub struct DumboConfig {
+    /// The threshold encryption scheme of the quorum.
+    /// When present, every batch is encrypted before being reliably broadcast
+    /// and only revealed after the agreement of the epoch has decided.
+    /// Like the quorum itself, it must be configured alike on every member, as encryption is
+    /// decided per membership: later memberships decide it along with their reconfiguration.
+    #[get = "pub"]
+    threshold_encryption: Option<Arc<dyn ThresholdEncryptionScheme>>,
+    /// How the requests of the batches decided in each epoch are ordered
+    #[get_copy = "pub"]
+    ordering_strategy: OrderingStrategy,
+    /// The limits used to cut the batch this node proposes in each epoch
+    #[get = "pub"]
+    request_aggregator: RequestAggregatorConfig,
+    /// The maximum amount of epochs that run concurrently.
+    /// Epoch `e + 1` can start its reliable broadcasts while epoch `e` is still in agreement,
+    /// but decisions are always delivered in epoch order.
+    #[get_copy = "pub"]
+    pipeline_depth: usize,
+    /// How many epochs ahead of the current one we buffer messages for
+    #[get_copy = "pub"]
+    future_epoch_window: usize,
+    /// The maximum amount of messages buffered for a single future epoch
+    #[get_copy = "pub"]
+    max_buffered_per_epoch: usize,
+    /// The maximum amount of messages held for a sub protocol instance of an epoch
+    /// which is not yet able to process them
+    #[get_copy = "pub"]
+    max_inbox_messages: usize,
+    /// How many of the latest decided epochs are kept to serve lagging replicas
+    #[get_copy = "pub"]
+    catch_up_history: usize,
+    /// The key set of the quorum, used to check threshold signed decision certificates and the
+    /// coin shares carried by agreement messages.
+    /// It is required, as the agreements flip their common coin with it and our key shares.
+    #[get = "pub"]
+    certificate_keys: Option<PublicKeySet>,
+    /// The key shares dealt to us under the certificate keys, in the order given by
+    /// [`crate::quorum_info::quorum_info::QuorumInfo::key_shares`].
+    /// The agreements flip their common coin with them, so they are required along with the key set.
+    #[get = "pub"]
+    coin_key_shares: Vec<PrivateKeyPart>,
+    /// How many epochs apart checkpoints are taken.
+    /// Once a checkpoint is stable, the decided epochs it covers are discarded.
+    #[get_copy = "pub"]
+    checkpoint_period: usize,
+    /// The file of the write ahead log, which allows a replica to recover its state after a crash.
+    /// Without it, nothing is persisted.
+    #[get = "pub"]
+    wal_path: Option<PathBuf>,
+    /// How many epochs after being decided a reconfiguration of the membership takes effect.
+    /// Must be at least the pipeline depth, so no running epoch changes membership.
+    #[get_copy = "pub"]
+    reconfiguration_delay: usize,
+}
+
+impl Default for DumboConfig {
+    fn default() -> Self {
+        Self {
+            threshold_encryption: None,
+            ordering_strategy: OrderingStrategy::default(),
+            request_aggregator: RequestAggregatorConfig::default(),
+            pipeline_depth: 2,
+            future_epoch_window: 8,
+            max_buffered_per_epoch: 16 * 1024,
+            max_inbox_messages: 1024,
+            catch_up_history: 64,
+            certificate_keys: None,
+            coin_key_shares: Vec::new(),
+            checkpoint_period: 128,
+            wal_path: None,
+            reconfiguration_delay: 4,
+        }
+    }
+}
+
+impl DumboConfig {
+    pub fn with_threshold_encryption(
+        mut self,
+        threshold_encryption: Arc<dyn ThresholdEncryptionScheme>,
+    ) -> Self {
+        self.threshold_encryption = Some(threshold_encryption);
+        self
+    }
+
+    pub fn with_ordering_strategy(mut self, ordering_strategy: OrderingStrategy) -> Self {
+        self.ordering_strategy = ordering_strategy;
+        self
+    }
+
+    pub fn with_request_aggregator(mut self, request_aggregator: RequestAggregatorConfig) -> Self {
+        self.request_aggregator = request_aggregator;
+        self
+    }
+
+    pub fn with_pipeline_depth(mut self, pipeline_depth: usize) -> Self {
+        assert!(pipeline_depth > 0, "The pipeline must run at least one epoch");
+
+        self.pipeline_depth = pipeline_depth;
+        self
+    }
+
+    pub fn with_future_epoch_buffer(mut self, window: usize, max_buffered_per_epoch: usize) -> Self {
+        self.future_epoch_window = window;
+        self.max_buffered_per_epoch = max_buffered_per_epoch;
+        self
+    }
+
+    pub fn with_max_inbox_messages(mut self, max_inbox_messages: usize) -> Self {
+        self.max_inbox_messages = max_inbox_messages;
+        self
+    }
+
+    pub fn with_catch_up_history(mut self, catch_up_history: usize) -> Self {
+        self.catch_up_history = catch_up_history;
+        self
+    }
+
+    pub fn with_certificate_keys(mut self, certificate_keys: PublicKeySet) -> Self {
+        self.certificate_keys = Some(certificate_keys);
+        self
+    }
+
+    pub fn with_coin_key_shares(mut self, coin_key_shares: Vec<PrivateKeyPart>) -> Self {
+        self.coin_key_shares = coin_key_shares;
+        self
+    }
+
+    pub fn with_checkpoint_period(mut self, checkpoint_period: usize) -> Self {
+        assert!(checkpoint_period > 0, "Checkpoints must be at least one epoch apart");
+
+        self.checkpoint_period = checkpoint_period;
+        self
+    }
+
+    pub fn with_wal_path(mut self, wal_path: impl Into<PathBuf>) -> Self {
+        self.wal_path = Some(wal_path.into());
+        self
+    }
+
+    pub fn with_reconfiguration_delay(mut self, reconfiguration_delay: usize) -> Self {
+        self.reconfiguration_delay = reconfiguration_delay;
+        self
+    }
+}
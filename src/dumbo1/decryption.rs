@@ -0,0 +1,228 @@
+use crate::dumbo1::batch::{EncryptedBatch, ProposedBatch, RequestVerifier};
+use crate::dumbo1::message::DecryptionShareMessage;
+use crate::quorum_info::quorum_info::QuorumInfo;
+use crate::tpke::{TPKEError, ThresholdEncryptionScheme};
+use atlas_common::collections::HashMap;
+use atlas_common::node_id::NodeId;
+use atlas_common::serialization_helper::SerMsg;
+use atlas_communication::message::StoredMessage;
+use std::collections::BTreeMap;
+use std::fmt::Debug;
+use std::sync::Arc;
+use thiserror::Error;
+use tracing::warn;
+
+/// The decryption phase of a Dumbo epoch.
+///
+/// Started once the agreement of the epoch has decided which batches are included.
+/// Each node broadcasts its decryption share for every encrypted batch that was decided,
+/// and a batch is revealed once the shares collected for it cover `threshold` key shares,
+/// where each member holds a key share for each unit of its weight.
+/// The requests of a revealed batch which fail verification are dropped.
+pub(super) struct DecryptionPhase<RQ> {
+    scheme: Arc<dyn ThresholdEncryptionScheme>,
+    request_verifier: Arc<dyn RequestVerifier<RQ>>,
+    quorum_info: QuorumInfo,
+    // The decided batches, ordered by their proposer
+    batches: BTreeMap<NodeId, BatchDecryption<RQ>>,
+}
+
+enum BatchDecryption<RQ> {
+    Pending {
+        batch: EncryptedBatch,
+        shares: HashMap<NodeId, Vec<u8>>,
+    },
+    Decrypted(Vec<StoredMessage<RQ>>),
+}
+
+impl<RQ> DecryptionPhase<RQ>
+where
+    RQ: SerMsg,
+{
+    pub(super) fn new(
+        scheme: Arc<dyn ThresholdEncryptionScheme>,
+        request_verifier: Arc<dyn RequestVerifier<RQ>>,
+        quorum_info: QuorumInfo,
+        decided: Vec<(NodeId, ProposedBatch<RQ>)>,
+    ) -> Self {
+        let batches = decided
+            .into_iter()
+            .map(|(proposer, batch)| {
+                let decryption = match batch.into_parts().0 {
+                    ProposedBatch::Plain(requests) => BatchDecryption::Decrypted(requests),
+                    ProposedBatch::Encrypted(batch)
+                        if scheme.verify_ciphertext(batch.ciphertext()) =>
+                    {
+                        BatchDecryption::Pending {
+                            batch,
+                            shares: HashMap::default(),
+                        }
+                    }
+                    ProposedBatch::Encrypted(_) | ProposedBatch::Reconfigure { .. } => {
+                        // Every correct node sees the same ciphertext, so every
+                        // correct node will discard this batch
+                        warn!("Batch proposed by {proposer:?} has an invalid ciphertext, discarding it");
+
+                        BatchDecryption::Decrypted(Vec::new())
+                    }
+                };
+
+                (proposer, decryption)
+            })
+            .collect();
+
+        Self {
+            scheme,
+            request_verifier,
+            quorum_info,
+            batches,
+        }
+    }
+
+    /// Produce our decryption shares for all of the batches which are still encrypted
+    pub(super) fn own_shares(&self) -> Result<Vec<DecryptionShareMessage>, TPKEError> {
+        self.batches
+            .iter()
+            .filter_map(|(proposer, decryption)| match decryption {
+                BatchDecryption::Pending { batch, .. } => Some((proposer, batch)),
+                BatchDecryption::Decrypted(_) => None,
+            })
+            .map(|(proposer, batch)| {
+                self.scheme
+                    .decryption_share(batch.ciphertext())
+                    .map(|share| DecryptionShareMessage::new(*proposer, share))
+            })
+            .collect()
+    }
+
+    /// Process a decryption share received from the given node
+    pub(super) fn process_share(
+        &mut self,
+        from: NodeId,
+        message: DecryptionShareMessage,
+    ) -> DecryptionResult {
+        if !self.quorum_info.is_member(from) {
+            return DecryptionResult::ShareIgnored;
+        }
+
+        let (proposer, share) = message.into_inner();
+
+        let Some(decryption) = self.batches.get_mut(&proposer) else {
+            return DecryptionResult::ShareIgnored;
+        };
+
+        let BatchDecryption::Pending { batch, shares } = decryption else {
+            return DecryptionResult::ShareIgnored;
+        };
+
+        if shares.contains_key(&from)
+            || !self
+                .scheme
+                .verify_decryption_share(from, batch.ciphertext(), &share)
+        {
+            return DecryptionResult::ShareIgnored;
+        }
+
+        shares.insert(from, share);
+
+        if self.quorum_info.accumulated_weight(shares.keys()) < self.scheme.threshold() {
+            return DecryptionResult::ShareAccepted;
+        }
+
+        let collected_shares = shares
+            .iter()
+            .map(|(node, share)| (*node, share.as_slice()))
+            .collect::<Vec<_>>();
+
+        let requests = match self
+            .scheme
+            .combine_shares(batch.ciphertext(), &collected_shares)
+            .map_err(DecryptionError::from)
+            .and_then(|plaintext| {
+                EncryptedBatch::decode_plaintext(&plaintext)
+                    .map_err(|err| DecryptionError::InvalidPlaintext(err.to_string()))
+            }) {
+            Ok(requests) => requests,
+            Err(err) => {
+                // The plaintext is the same for every correct node, so they all discard it
+                warn!("Failed to decrypt batch proposed by {proposer:?}: {err}, discarding it");
+
+                Vec::new()
+            }
+        };
+
+        // Every correct node reveals the same requests, so they all drop the same ones
+        let requests = requests
+            .into_iter()
+            .filter(|request| match self.request_verifier.verify_request(request) {
+                Ok(()) => true,
+                Err(err) => {
+                    warn!(
+                        "Dropping invalid request from {:?} in batch proposed by {proposer:?}: {err}",
+                        request.header().from()
+                    );
+
+                    false
+                }
+            })
+            .collect();
+
+        *decryption = BatchDecryption::Decrypted(requests);
+
+        if self.is_complete() {
+            DecryptionResult::Completed
+        } else {
+            DecryptionResult::BatchDecrypted(proposer)
+        }
+    }
+
+    /// Whether all of the decided batches have been revealed
+    pub(super) fn is_complete(&self) -> bool {
+        self.batches
+            .values()
+            .all(|decryption| matches!(decryption, BatchDecryption::Decrypted(_)))
+    }
+
+    /// Obtain the decrypted batches, ordered by their proposer
+    pub(super) fn finalize(self) -> Result<Vec<(NodeId, Vec<StoredMessage<RQ>>)>, DecryptionError> {
+        self.batches
+            .into_iter()
+            .map(|(proposer, decryption)| match decryption {
+                BatchDecryption::Decrypted(requests) => Ok((proposer, requests)),
+                BatchDecryption::Pending { .. } => Err(DecryptionError::NotReadyToFinalize),
+            })
+            .collect()
+    }
+}
+
+impl<RQ> Debug for DecryptionPhase<RQ> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let pending = self
+            .batches
+            .values()
+            .filter(|decryption| matches!(decryption, BatchDecryption::Pending { .. }))
+            .count();
+
+        f.debug_struct("DecryptionPhase")
+            .field("batches", &self.batches.len())
+            .field("pending", &pending)
+            .finish()
+    }
+}
+
+pub(super) enum DecryptionResult {
+    ShareIgnored,
+    ShareAccepted,
+    BatchDecrypted(NodeId),
+    Completed,
+}
+
+#[derive(Error, Debug)]
+pub enum DecryptionError {
+    #[error("The decryption phase is not ready to finalize")]
+    NotReadyToFinalize,
+    #[error("Threshold encryption error: {0}")]
+    Encryption(#[from] TPKEError),
+    #[error("The decrypted plaintext is not a valid batch: {0}")]
+    InvalidPlaintext(String),
+}
@@ -1,10 +1,23 @@
-use crate::aba::{ABAProtocol, AsyncBinaryAgreementResult};
+use crate::aba::{ABAErrorKind, ABAProtocol, AsyncBinaryAgreementResult, CoinKeys};
 use crate::committee_election::{CommitteeElectionProtocol, CommitteeElectionResult};
-use crate::dumbo1::message::DumboMessageType;
+use crate::dumbo1::batch::{ProposedBatch, RequestVerifier};
+use crate::dumbo1::certificate::AgreementOutcome;
+use crate::dumbo1::decryption::{DecryptionPhase, DecryptionResult};
+use crate::dumbo1::inbox::{Inbox, InstanceInboxes};
+use crate::dumbo1::message::{DecryptionShareMessage, DumboMessage, DumboMessageType};
 use crate::dumbo1::network::SendNodeWrapperRef;
-use crate::dumbo1::protocol::{DumboPMessage, DumboPSerialization};
+use crate::dumbo1::protocol::DumboPSerialization;
+use crate::dumbo1::reconfiguration::{Membership, Reconfiguration};
+use crate::dumbo1::wal::{DumboPWal, RecoveredEpoch, WalEntry, log_entry};
+use crate::metrics::{ABA_BROADCAST_FAILURES_ID, CE_LATENCY_ID, NON_MEMBER_MESSAGES_ID};
 use crate::quorum_info::quorum_info::QuorumInfo;
-use crate::rbc::{ReliableBroadcast, ReliableBroadcastResult};
+use crate::rbc::{ReliableBroadcast, ReliableBroadcastResult, ReliableBroadcastSendNode};
+use crate::reliable_broadcast::index_rbc::{DeliveredBatches, IndexBroadcastMessage, IndexVector};
+use crate::reliable_broadcast::reliable_broadcast::{
+    ReliableBroadcastInstance, ReliableBroadcastResult as IndexBroadcastResult,
+};
+use crate::tpke::ThresholdEncryptionScheme;
+use anyhow::anyhow;
 use atlas_common::collections::HashMap;
 use atlas_common::error::Result;
 use atlas_common::node_id::NodeId;
@@ -13,34 +26,469 @@ use atlas_common::serialization_helper::SerMsg;
 use atlas_communication::message::StoredMessage;
 use atlas_core::ordering_protocol::ShareableConsensusMessage;
 use atlas_core::ordering_protocol::networking::OrderProtocolSendNode;
+use atlas_metrics::metrics::{metric_duration, metric_increment};
+use std::collections::BTreeSet;
 use std::fmt::Debug;
 use std::sync::Arc;
+use std::time::Instant;
+use tracing::{debug, debug_span, warn};
 
-pub(super) struct DumboRound<CE, RQ, R, A> {
+/// A single epoch of the Dumbo protocol.
+///
+/// Every node reliably broadcasts its batch, and the committee election is started once
+/// `n - f` batches have been delivered. Every member of the elected committee then reliably
+/// broadcasts the index vector of the batches it has delivered, and we only echo an index vector
+/// once we have delivered every batch it refers to ourselves.
+///
+/// A binary agreement is run for each committee member to decide whether its index vector
+/// is part of the output of the epoch. We vote 1 in the agreement of every member whose index
+/// vector we have delivered, and once one agreement has decided 1, we vote 0 in all of the
+/// agreements we have not yet voted in. The epoch outputs every batch referred to by the
+/// index vectors which were decided 1.
+pub(super) struct DumboRound<CE, RQ, R, A>
+where
+    R: ReliableBroadcast<ProposedBatch<RQ>>,
+    A: ABAProtocol,
+    CE: CommitteeElectionProtocol,
+{
     // The current epoch number.
     epoch_num: SeqNo,
-    // The state of each node in the protocol.
-    node_states: HashMap<NodeId, NodeState<RQ, R, A>>,
+    // Our own id, as we broadcast our index vector when we are elected to the committee.
+    own_id: NodeId,
+    // The quorum participating in this epoch.
+    quorum_info: QuorumInfo,
+    // The reliable broadcast of the batch of each node.
+    batch_broadcasts: HashMap<NodeId, BatchState<RQ, R>>,
+    // The batches we have delivered, which decide whether we can echo an index vector.
+    delivered_batches: DeliveredBatches,
     // The state of the committee election protocol.
     committee_election: CommitteeState<CE>,
+    // The state of each committee member in the protocol, once the committee is elected.
+    node_states: HashMap<NodeId, NodeState<A>>,
+    // The keys the agreements of this epoch flip their common coin with.
+    coin_keys: CoinKeys,
+    // The inputs we had provided to the agreements before the replica stopped,
+    // provided again once the committee is elected.
+    recovered_inputs: HashMap<NodeId, bool>,
+    // Whether we have broadcast our own index vector.
+    index_proposed: bool,
+    // Index broadcast messages received before the committee was elected.
+    index_inboxes: InstanceInboxes<StoredMessage<IndexBroadcastMessage>>,
+    // Whether the membership running this epoch encrypts its batches.
+    encrypted: bool,
+    // The threshold encryption scheme we were handed for the membership, if any.
+    threshold_encryption: Option<Arc<dyn ThresholdEncryptionScheme>>,
+    // Verifies the requests of the encrypted batches once they are revealed.
+    request_verifier: Arc<dyn RequestVerifier<RQ>>,
+    // The phase of the epoch.
+    phase: EpochPhase<RQ>,
+    // Decryption shares received before the agreement of this epoch decided,
+    // keyed by their sender and the proposer of the batch they are for.
+    pending_decryption_shares: HashMap<(NodeId, NodeId), StoredMessage<DecryptionShareMessage>>,
+    // The maximum amount of decryption shares held before the agreement decides.
+    max_pending_decryption_shares: usize,
+    // Whether we have already voted 0 in the agreements of the members we have not delivered.
+    voted_remaining: bool,
+    // The latest round of each agreement we have broadcast our estimate in.
+    estimate_rounds: HashMap<NodeId, usize>,
+    // Agreement messages received before we provided our input to the agreement of their instance.
+    aba_inboxes: InstanceInboxes<StoredMessage<A::AsyncBinaryMessage>>,
+    // Committee election messages received before we started the election.
+    ce_inbox: Inbox<StoredMessage<CE::Message>>,
+    // The outcome of the agreement phase, once every agreement has decided.
+    agreement_outcome: Option<AgreementOutcome>,
+    // The reconfigurations proposed along with the batches included in this epoch.
+    reconfigurations: Vec<(NodeId, Reconfiguration)>,
+    // When this epoch was started, and when its committee election was started
+    started_at: Instant,
+    ce_started_at: Option<Instant>,
+    // The write ahead log our proposal, our votes and the messages we send are logged in.
+    wal: Option<DumboPWal<RQ, R, A, CE>>,
 }
 
 impl<CE, RQ, R, A> DumboRound<CE, RQ, R, A>
 where
     RQ: SerMsg,
-    R: ReliableBroadcast<RQ>,
+    R: ReliableBroadcast<ProposedBatch<RQ>>,
     A: ABAProtocol,
     CE: CommitteeElectionProtocol,
 {
-    pub fn new(epoch_num: SeqNo, quorum_info: QuorumInfo) -> Self {
-        let required_committee = quorum_info.f() + 1;
+    /// Start an epoch run by the given membership
+    pub fn new(
+        epoch_num: SeqNo,
+        own_id: NodeId,
+        membership: &Membership,
+        inbox_capacity: usize,
+        request_verifier: Arc<dyn RequestVerifier<RQ>>,
+        wal: Option<DumboPWal<RQ, R, A, CE>>,
+    ) -> Self {
+        let quorum_info = membership.quorum_info().clone();
+        let threshold_encryption = membership.keys().threshold_encryption().clone();
+        let coin_keys = membership
+            .keys()
+            .coin_keys()
+            .expect("The membership of an epoch always has the keys of its coin, checked when it is configured");
 
-        let committee_election_protocol = CE::new(quorum_info, required_committee);
+        let batch_broadcasts = quorum_info
+            .quorum_members()
+            .iter()
+            .map(|member| (*member, BatchState::Running(R::new(*member, quorum_info.clone()))))
+            .collect();
 
         Self {
             epoch_num,
+            own_id,
+            delivered_batches: DeliveredBatches::new(quorum_info.clone()),
+            quorum_info,
+            batch_broadcasts,
+            committee_election: CommitteeState::Pending,
             node_states: HashMap::default(),
-            committee_election: CommitteeState::RunningCE(committee_election_protocol),
+            coin_keys,
+            recovered_inputs: HashMap::default(),
+            index_proposed: false,
+            index_inboxes: InstanceInboxes::new(inbox_capacity),
+            encrypted: membership.encrypted(),
+            threshold_encryption,
+            request_verifier,
+            phase: EpochPhase::Agreement,
+            pending_decryption_shares: HashMap::default(),
+            max_pending_decryption_shares: inbox_capacity,
+            voted_remaining: false,
+            estimate_rounds: HashMap::default(),
+            aba_inboxes: InstanceInboxes::new(inbox_capacity),
+            ce_inbox: Inbox::new(inbox_capacity),
+            agreement_outcome: None,
+            reconfigurations: Vec::new(),
+            started_at: Instant::now(),
+            ce_started_at: None,
+            wal,
+        }
+    }
+
+    pub(super) fn epoch_num(&self) -> SeqNo {
+        self.epoch_num
+    }
+
+    pub(super) fn started_at(&self) -> Instant {
+        self.started_at
+    }
+
+    /// Start the reliable broadcast of our own batch in this epoch
+    pub(super) fn propose<NT>(
+        &mut self,
+        batch: ProposedBatch<RQ>,
+        network: &Arc<NT>,
+    ) -> Result<()>
+    where
+        NT: OrderProtocolSendNode<RQ, DumboPSerialization<RQ, R, A, CE>>,
+    {
+        let own_id = self.own_id;
+
+        if !self.batch_broadcasts.contains_key(&own_id) {
+            warn!("Attempted to propose in epoch {:?}, but we are not part of the quorum", self.epoch_num);
+
+            return Ok(());
+        }
+
+        log_entry(self.wal.as_ref(), || WalEntry::Proposal {
+            epoch: self.epoch_num,
+            batch: batch.clone(),
+        })?;
+
+        let network = SendNodeWrapperRef::new(self.epoch_num, own_id, network).with_wal(self.wal.as_ref());
+
+        let rbc = R::new_with_propose(own_id, self.quorum_info.clone(), batch, &network);
+
+        self.batch_broadcasts.insert(own_id, BatchState::Running(rbc));
+
+        Ok(())
+    }
+
+    /// Rebuild the state this epoch had before the replica stopped, from what we logged in it.
+    ///
+    /// We propose the same batch again and provide the same input to the agreements we had voted in,
+    /// so we can never contradict what we sent before stopping.
+    /// The messages we had sent are sent again, to ourselves as well, so the votes we had counted
+    /// are counted again and the peers which missed them get them. Peers which did not miss them
+    /// discard them as duplicates.
+    /// The committee election runs again, and the inputs we had provided are only provided again
+    /// once it has decided, as the agreements are only run for the members of the committee.
+    /// The agreements start over from their first round, so any vote they cast again which differs
+    /// from the one we logged is refused by the write ahead log instead of being sent.
+    /// Once we have broadcast our index vector we never broadcast another one, as the one
+    /// we had sent is sent again instead.
+    pub(super) fn recover<NT>(
+        &mut self,
+        recovered: RecoveredEpoch<RQ, R::ReliableBroadcastMessage, A::AsyncBinaryMessage, CE::Message>,
+        network: &Arc<NT>,
+    ) where
+        NT: OrderProtocolSendNode<RQ, DumboPSerialization<RQ, R, A, CE>>,
+    {
+        let own_id = self.own_id;
+
+        let RecoveredEpoch {
+            proposal,
+            agreement_inputs,
+            sent,
+        } = recovered;
+
+        if let Some(batch) = proposal {
+            if self.batch_broadcasts.contains_key(&own_id) {
+                // Everything sent here was already logged when we first proposed
+                let network_ref = SendNodeWrapperRef::new(self.epoch_num, own_id, network);
+
+                let rbc = R::new_with_propose(own_id, self.quorum_info.clone(), batch, &network_ref);
+
+                self.batch_broadcasts.insert(own_id, BatchState::Running(rbc));
+            }
+        }
+
+        for (instance, value) in agreement_inputs {
+            self.recovered_inputs.insert(instance, value);
+
+            if !value {
+                self.voted_remaining = true;
+            }
+        }
+
+        let targets = self.quorum_info.quorum_members().clone();
+
+        for entry in sent {
+            let message_type = match entry {
+                WalEntry::ReliableBroadcast {
+                    instance, message, ..
+                } => DumboMessageType::ReliableBroadcast(instance, message),
+                WalEntry::IndexBroadcast {
+                    instance, message, ..
+                } => {
+                    if instance == own_id && matches!(message, IndexBroadcastMessage::Send(_, _)) {
+                        self.index_proposed = true;
+                    }
+
+                    DumboMessageType::IndexBroadcast(instance, message)
+                }
+                WalEntry::AsyncBinaryAgreement {
+                    instance, message, ..
+                } => DumboMessageType::AsyncBinaryAgreement(instance, message),
+                WalEntry::CommitteeElection { message, .. } => {
+                    DumboMessageType::CommitteeElectionMessage(message)
+                }
+                _ => continue,
+            };
+
+            let message = DumboMessage::new(self.epoch_num, message_type);
+
+            if let Err(failed) = network.broadcast_signed(message, targets.iter().cloned()) {
+                warn!("Failed to resend recovered message of epoch {:?} to {failed:?}", self.epoch_num);
+            }
+        }
+
+        debug!("Recovered epoch {:?} from the write ahead log", self.epoch_num);
+    }
+
+    /// Log the input we provide to the agreement of the given instance, before providing it
+    fn log_agreement_input(&self, instance: NodeId, value: bool) -> Result<()> {
+        log_entry(self.wal.as_ref(), || WalEntry::AgreementInput {
+            epoch: self.epoch_num,
+            instance,
+            value,
+        })?;
+
+        Ok(())
+    }
+
+    /// Whether the batches of this epoch are known
+    pub(super) fn is_decided(&self) -> bool {
+        matches!(self.phase, EpochPhase::Decided(_))
+    }
+
+    /// Take the batches decided in this epoch, ordered by their proposer.
+    /// Returns None if the epoch has not yet been decided.
+    pub(super) fn take_decided(&mut self) -> Option<Vec<(NodeId, Vec<StoredMessage<RQ>>)>> {
+        match std::mem::replace(&mut self.phase, EpochPhase::Agreement) {
+            EpochPhase::Decided(batches) => Some(batches),
+            phase => {
+                self.phase = phase;
+                None
+            }
+        }
+    }
+
+    /// The committee elected in this epoch, if the election has already completed
+    pub(super) fn committee(&self) -> Option<&Vec<NodeId>> {
+        match &self.committee_election {
+            CommitteeState::Completed { committee } => Some(committee),
+            CommitteeState::Pending { .. } | CommitteeState::RunningCE(_) => None,
+        }
+    }
+
+    /// The outcome of the agreement phase of this epoch, once it is known
+    pub(super) fn agreement_outcome(&self) -> Option<&AgreementOutcome> {
+        self.agreement_outcome.as_ref()
+    }
+
+    /// The reconfigurations proposed along with the batches included in this epoch,
+    /// known once the agreement phase has finished
+    pub(super) fn reconfigurations(&self) -> &[(NodeId, Reconfiguration)] {
+        &self.reconfigurations
+    }
+
+    /// Start the decryption phase of this epoch, with the batches decided by the agreement.
+    ///
+    /// When no batch needs decrypting, the epoch is decided right away.
+    /// Fails when the membership encrypts its batches but we lack its scheme.
+    pub(super) fn begin_decryption<NT>(
+        &mut self,
+        decided: Vec<(NodeId, ProposedBatch<RQ>)>,
+        network: &Arc<NT>,
+    ) -> Result<EpochResult>
+    where
+        NT: OrderProtocolSendNode<RQ, DumboPSerialization<RQ, R, A, CE>>,
+    {
+        let decided = decided
+            .into_iter()
+            .map(|(proposer, batch)| {
+                let (batch, reconfiguration) = batch.into_parts();
+
+                if let Some(reconfiguration) = reconfiguration {
+                    self.reconfigurations.push((proposer, reconfiguration));
+                }
+
+                (proposer, batch)
+            })
+            .collect::<Vec<_>>();
+
+        if !self.encrypted {
+            let decided = decided
+                .into_iter()
+                .map(|(proposer, batch)| match batch {
+                    ProposedBatch::Plain(requests) => (proposer, requests),
+                    ProposedBatch::Encrypted(_) | ProposedBatch::Reconfigure { .. } => {
+                        // Every member agrees the membership does not encrypt its batches,
+                        // so every correct member discards this batch
+                        warn!("Batch proposed by {proposer:?} is encrypted, but its membership does not encrypt its batches, discarding it");
+
+                        (proposer, Vec::new())
+                    }
+                })
+                .collect();
+
+            self.phase = EpochPhase::Decided(decided);
+
+            debug!("Epoch decided");
+
+            return Ok(EpochResult::Finalized);
+        }
+
+        // Discarding the encrypted batches would have us decide a different epoch than the rest
+        // of the membership, so we can not carry on without the scheme
+        let scheme = self.threshold_encryption.clone().ok_or_else(|| {
+            anyhow!(
+                "The membership of epoch {:?} encrypts its batches, but we were not handed its threshold encryption scheme",
+                self.epoch_num
+            )
+        })?;
+
+        let decryption = DecryptionPhase::new(
+            scheme,
+            self.request_verifier.clone(),
+            self.quorum_info.clone(),
+            decided,
+        );
+
+        // Plain batches and batches with an invalid ciphertext need no shares,
+        // so there may be nothing left to reveal
+        if decryption.is_complete() {
+            self.phase = EpochPhase::Decided(decryption.finalize()?);
+
+            debug!("Epoch decided, no included batch needs decrypting");
+
+            return Ok(EpochResult::Finalized);
+        }
+
+        decryption
+            .own_shares()?
+            .into_iter()
+            .for_each(|share| {
+                let message = DumboMessage::new(
+                    self.epoch_num,
+                    DumboMessageType::DecryptionShare(share),
+                );
+
+                if let Err(failed) = network.broadcast_signed(
+                    message,
+                    self.quorum_info.quorum_members().iter().cloned(),
+                ) {
+                    warn!("Failed to broadcast decryption share to {failed:?}");
+                }
+            });
+
+        self.phase = EpochPhase::Decryption(decryption);
+
+        debug!("Agreement phase completed, revealing the included batches");
+
+        let mut result = EpochResult::MessageProcessed;
+
+        for share in std::mem::take(&mut self.pending_decryption_shares).into_values() {
+            if let EpochResult::Finalized = self.process_decryption_share(share)? {
+                result = EpochResult::Finalized;
+            }
+        }
+
+        Ok(result)
+    }
+
+    fn process_decryption_share(
+        &mut self,
+        message: StoredMessage<DecryptionShareMessage>,
+    ) -> Result<EpochResult> {
+        let _span = debug_span!("decryption", proposer = ?message.message().proposer()).entered();
+
+        match &mut self.phase {
+            EpochPhase::Agreement => {
+                let key = (message.header().from(), *message.message().proposer());
+
+                // A correct node sends a single share for each batch
+                if !self.quorum_info.is_member(key.1)
+                    || self.pending_decryption_shares.contains_key(&key)
+                {
+                    return Ok(EpochResult::MessageIgnored);
+                }
+
+                if self.pending_decryption_shares.len() >= self.max_pending_decryption_shares {
+                    warn!("Decryption share inbox of epoch {:?} is full, dropping message", self.epoch_num);
+
+                    return Ok(EpochResult::MessageIgnored);
+                }
+
+                self.pending_decryption_shares.insert(key, message);
+
+                Ok(EpochResult::MessageQueued)
+            }
+            EpochPhase::Decryption(decryption) => {
+                let (header, share) = message.into_inner();
+
+                match decryption.process_share(header.from(), share) {
+                    DecryptionResult::ShareIgnored => Ok(EpochResult::MessageIgnored),
+                    DecryptionResult::ShareAccepted | DecryptionResult::BatchDecrypted(_) => {
+                        Ok(EpochResult::MessageProcessed)
+                    }
+                    DecryptionResult::Completed => {
+                        let EpochPhase::Decryption(decryption) =
+                            std::mem::replace(&mut self.phase, EpochPhase::Agreement)
+                        else {
+                            unreachable!()
+                        };
+
+                        self.phase = EpochPhase::Decided(decryption.finalize()?);
+
+                        debug!("Epoch decided, every included batch was decrypted");
+
+                        Ok(EpochResult::Finalized)
+                    }
+                }
+            }
+            EpochPhase::Decided(_) => Ok(EpochResult::MessageIgnored),
         }
     }
 
@@ -52,117 +500,696 @@ where
     where
         NT: OrderProtocolSendNode<RQ, DumboPSerialization<RQ, R, A, CE>>,
     {
+        let _span = debug_span!("epoch", epoch = ?self.epoch_num).entered();
+
         match message.message().message_type() {
-            DumboMessageType::ReliableBroadcast(rbc_msg) => {
-                let node_state = self.node_states.get_mut(&message.header().from());
+            DumboMessageType::ReliableBroadcast(instance, rbc_msg) => {
+                let stored_message = StoredMessage::new(message.header().clone(), rbc_msg.clone());
+
+                self.process_rbc_message(*instance, stored_message, network)
+            }
+            DumboMessageType::IndexBroadcast(instance, index_msg) => {
+                let stored_message = StoredMessage::new(message.header().clone(), index_msg.clone());
+
+                self.process_index_message(*instance, stored_message, network)
+            }
+            DumboMessageType::AsyncBinaryAgreement(instance, aba_msg) => {
+                let stored_message = StoredMessage::new(message.header().clone(), aba_msg.clone());
+
+                self.process_aba_message(*instance, stored_message, network)
+            }
+            DumboMessageType::CommitteeElectionMessage(ce_msg) => {
+                let stored_message = StoredMessage::new(message.header().clone(), ce_msg.clone());
+
+                self.process_ce_message(stored_message, network)
+            }
+            DumboMessageType::DecryptionShare(share) => {
+                let stored_message = StoredMessage::new(message.header().clone(), share.clone());
 
-                if let Some(node_state) = node_state {
-                    match node_state {
-                        NodeState::RunningRBC(rbc) => {
-                            let stored_message =
-                                StoredMessage::new(message.header().clone(), rbc_msg.clone());
+                self.process_decryption_share(stored_message)
+            }
+            // Catch up and checkpoint messages are handled by the protocol, not by the epochs
+            DumboMessageType::CatchUp(_) | DumboMessageType::Checkpoint(_) => {
+                Ok(EpochResult::MessageIgnored)
+            }
+        }
+    }
 
-                            let network = SendNodeWrapperRef::new(self.epoch_num.clone(), network);
+    /// Poll the sub protocols of this epoch for queued messages that can now be processed,
+    /// processing them until no more progress can be made.
+    pub(super) fn poll<NT>(&mut self, network: &Arc<NT>) -> Result<EpochResult>
+    where
+        NT: OrderProtocolSendNode<RQ, DumboPSerialization<RQ, R, A, CE>>,
+    {
+        let _span = debug_span!("epoch", epoch = ?self.epoch_num).entered();
 
-                            let result = rbc.process_message(stored_message, &network);
+        let mut result = EpochResult::MessageIgnored;
 
-                            match result {
-                                ReliableBroadcastResult::MessageQueued => Ok(EpochResult::MessageQueued),
-                                ReliableBroadcastResult::MessageIgnored => Ok(EpochResult::MessageIgnored),
-                                ReliableBroadcastResult::Processed => Ok(EpochResult::MessageProcessed),
-                                ReliableBroadcastResult::Finalized => {
-                                    // Replace with a dummy variable so we can then swap into the new state
-                                    let rbc = std::mem::replace(rbc, R::new());
+        loop {
+            let mut progressed = false;
 
-                                    let mut next_state = NodeState::RunningABA {
-                                        completed_rbc: rbc.finalize(),
-                                        aba: A::new(true),
-                                    };
+            let instances = self.batch_broadcasts.keys().cloned().collect::<Vec<_>>();
 
-                                    std::mem::swap(node_state, &mut next_state);
+            for instance in instances {
+                let Some(BatchState::Running(rbc)) = self.batch_broadcasts.get_mut(&instance) else {
+                    continue;
+                };
 
-                                    Ok(EpochResult::MessageProcessed)
-                                }
-                            }
-                        }
-                        NodeState::RunningABA { aba, .. } => {
-                            Ok(EpochResult::MessageIgnored)
-                        }
-                        NodeState::Completed { .. } => {
-                            Ok(EpochResult::MessageIgnored)
-                        }
+                if let Some(message) = rbc.poll() {
+                    progressed = true;
+                    result = result.merge(self.process_rbc_message(instance, message, network)?);
+                }
+            }
+
+            let committee = self.node_states.keys().cloned().collect::<Vec<_>>();
+
+            for member in committee {
+                let polled = match self.node_states.get_mut(&member) {
+                    Some(NodeState::RunningRBC(rbc) | NodeState::AwaitingRBC { rbc }) => self
+                        .index_inboxes
+                        .pop(member)
+                        .or_else(|| rbc.poll())
+                        .map(PolledMessage::Index),
+                    Some(NodeState::RunningABA { aba, .. }) => self
+                        .aba_inboxes
+                        .pop(member)
+                        .or_else(|| aba.poll())
+                        .map(PolledMessage::Aba),
+                    Some(NodeState::RunningBoth { rbc, aba }) => self
+                        .index_inboxes
+                        .pop(member)
+                        .or_else(|| rbc.poll())
+                        .map(PolledMessage::Index)
+                        .or_else(|| {
+                            self.aba_inboxes
+                                .pop(member)
+                                .or_else(|| aba.poll())
+                                .map(PolledMessage::Aba)
+                        }),
+                    Some(NodeState::Completed { .. }) | None => None,
+                };
+
+                let polled_result = match polled {
+                    Some(PolledMessage::Index(message)) => {
+                        self.process_index_message(member, message, network)?
                     }
-                } else {
-                    Ok(EpochResult::MessageIgnored)
+                    Some(PolledMessage::Aba(message)) => {
+                        self.process_aba_message(member, message, network)?
+                    }
+                    None => continue,
+                };
+
+                progressed = true;
+                result = result.merge(polled_result);
+            }
+
+            if let CommitteeState::RunningCE(committee_election) = &mut self.committee_election {
+                if let Some(message) = self.ce_inbox.pop().or_else(|| committee_election.poll()) {
+                    progressed = true;
+                    result = result.merge(self.process_ce_message(message, network)?);
                 }
             }
-            DumboMessageType::AsyncBinaryAgreement(aba_msg) => {
-                let node_state = self.node_states.get_mut(&message.header().from());
-                
-                if let Some(node_state) = node_state {
-                    match node_state {
-                        NodeState::RunningABA { aba, completed_rbc } => {
-                            let stored_message =
-                                StoredMessage::new(message.header().clone(), aba_msg.clone());
-
-                            let network = SendNodeWrapperRef::new(self.epoch_num.clone(), network);
-
-                            let aba_result = aba.process_message(stored_message, &network)?;
-
-                            match aba_result {
-                                AsyncBinaryAgreementResult::MessageQueued => Ok(EpochResult::MessageQueued),
-                                AsyncBinaryAgreementResult::MessageIgnored => Ok(EpochResult::MessageIgnored),
-                                AsyncBinaryAgreementResult::Processed => Ok(EpochResult::MessageProcessed),
-                                AsyncBinaryAgreementResult::Decided => {
-                                    let protocol = std::mem::replace(aba, A::new(false));
-                                    
-                                    let result = protocol.finalize();
-                                    
-                                    todo!()
-                                }
+
+            if !progressed {
+                break Ok(result);
+            }
+        }
+    }
+
+    fn process_rbc_message<NT>(
+        &mut self,
+        instance: NodeId,
+        message: StoredMessage<R::ReliableBroadcastMessage>,
+        network: &Arc<NT>,
+    ) -> Result<EpochResult>
+    where
+        NT: OrderProtocolSendNode<RQ, DumboPSerialization<RQ, R, A, CE>>,
+    {
+        let _span = debug_span!("rbc", ?instance).entered();
+
+        let Some(BatchState::Running(rbc)) = self.batch_broadcasts.get_mut(&instance) else {
+            // Message for an instance of a node which is not part of the quorum,
+            // or whose batch we have already delivered
+            return Ok(EpochResult::MessageIgnored);
+        };
+
+        let network_ref =
+            SendNodeWrapperRef::new(self.epoch_num, instance, network).with_wal(self.wal.as_ref());
+
+        match rbc.process_message(message, &network_ref) {
+            ReliableBroadcastResult::MessageQueued => Ok(EpochResult::MessageQueued),
+            ReliableBroadcastResult::MessageIgnored => Ok(EpochResult::MessageIgnored),
+            ReliableBroadcastResult::NonMember => Ok(EpochResult::non_member()),
+            ReliableBroadcastResult::Processed => Ok(EpochResult::MessageProcessed),
+            ReliableBroadcastResult::Finalized => {
+                debug!("Reliable broadcast delivered");
+
+                let Some(BatchState::Running(rbc)) = self.batch_broadcasts.remove(&instance) else {
+                    unreachable!()
+                };
+
+                self.batch_broadcasts
+                    .insert(instance, BatchState::Delivered(rbc.finalize()));
+
+                self.delivered_batches.register_delivered(instance);
+                self.release_index_echoes(network);
+                self.check_index_progress(network);
+
+                self.check_agreement_progress(network)
+            }
+        }
+    }
+
+    fn process_aba_message<NT>(
+        &mut self,
+        instance: NodeId,
+        message: StoredMessage<A::AsyncBinaryMessage>,
+        network: &Arc<NT>,
+    ) -> Result<EpochResult>
+    where
+        NT: OrderProtocolSendNode<RQ, DumboPSerialization<RQ, R, A, CE>>,
+    {
+        let _span = debug_span!("aba", ?instance).entered();
+
+        let aba = match self.node_states.get_mut(&instance) {
+            Some(NodeState::RunningABA { aba, .. } | NodeState::RunningBoth { aba, .. }) => aba,
+            Some(NodeState::RunningRBC(_)) => {
+                // ABA message received before we have provided input to this agreement,
+                // it is drained from the inbox once we vote
+                return Ok(self.queue_aba_message(instance, message));
+            }
+            None if self.committee().is_none() && self.quorum_info.is_member(instance) => {
+                // The committee is not yet known to us, and the instance may be one of its members
+                return Ok(self.queue_aba_message(instance, message));
+            }
+            None => {
+                // Message for an instance of a node which is not part of the committee
+                return Ok(EpochResult::MessageIgnored);
+            }
+            Some(NodeState::AwaitingRBC { .. } | NodeState::Completed { .. }) => {
+                // The agreement of this member has already decided
+                return Ok(EpochResult::MessageIgnored);
+            }
+        };
+
+        let network_ref =
+            SendNodeWrapperRef::new(self.epoch_num, instance, network).with_wal(self.wal.as_ref());
+
+        let result = aba.process_message(message, &network_ref);
+
+        match self.agreement_result(instance, result)? {
+            AsyncBinaryAgreementResult::MessageQueued => Ok(EpochResult::MessageQueued),
+            AsyncBinaryAgreementResult::MessageIgnored => Ok(EpochResult::MessageIgnored),
+            AsyncBinaryAgreementResult::NonMember => Ok(EpochResult::non_member()),
+            AsyncBinaryAgreementResult::Processed => {
+                // The message may have moved the agreement to a new round
+                self.open_agreement_round(instance, network)?;
+
+                Ok(EpochResult::MessageProcessed)
+            }
+            AsyncBinaryAgreementResult::Decided => {
+                let node_state = self.node_states.remove(&instance).unwrap();
+
+                let next_state = match node_state {
+                    NodeState::RunningABA { completed_rbc, aba } => NodeState::Completed {
+                        completed_rbc: Some(completed_rbc),
+                        value: aba.finalize()?,
+                    },
+                    NodeState::RunningBoth { rbc, aba } => {
+                        if aba.finalize()? {
+                            // The index vector was included, so we must wait for its delivery
+                            NodeState::AwaitingRBC { rbc }
+                        } else {
+                            NodeState::Completed {
+                                completed_rbc: None,
+                                value: false,
                             }
                         }
-                        NodeState::RunningRBC(_) => {
-                            // ABA message received before RBC completion, ignore or queue
-                            //TODO: Queue message somewhere
-                            Ok(EpochResult::MessageQueued)
-                        }
-                        NodeState::Completed { .. } => {
-                            // Node already completed, ignore message
-                            Ok(EpochResult::MessageIgnored)
-                        }
                     }
+                    state => state,
+                };
+
+                if let NodeState::Completed { value, .. } = &next_state {
+                    debug!(value, "Agreement completed");
+                }
+
+                self.node_states.insert(instance, next_state);
+
+                let discarded = self.aba_inboxes.discard(instance);
+
+                if discarded > 0 {
+                    debug!("Discarded {discarded} queued messages of the decided agreement of {instance:?}");
+                }
+
+                self.check_agreement_progress(network)
+            }
+        }
+    }
+
+    /// The result of an agreement processing a message.
+    ///
+    /// A vote which failed to reach part of the quorum is handled like a failed reliable broadcast
+    /// send: the message was processed, so the agreement carries on without it.
+    pub(super) fn agreement_result(
+        &self,
+        instance: NodeId,
+        result: std::result::Result<AsyncBinaryAgreementResult, A::ABAError>,
+    ) -> Result<AsyncBinaryAgreementResult> {
+        match result {
+            Ok(result) => Ok(result),
+            Err(err) if err.is_broadcast_failure() => {
+                warn!("Agreement of {instance:?} in epoch {:?} failed to broadcast our vote: {err}", self.epoch_num);
+
+                metric_increment(ABA_BROADCAST_FAILURES_ID, Some(1));
+
+                Ok(AsyncBinaryAgreementResult::Processed)
+            }
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    fn queue_aba_message(
+        &mut self,
+        instance: NodeId,
+        message: StoredMessage<A::AsyncBinaryMessage>,
+    ) -> EpochResult {
+        if self.aba_inboxes.push(instance, message) {
+            EpochResult::MessageQueued
+        } else {
+            warn!("Inbox of the agreement of {instance:?} in epoch {:?} is full, dropping message", self.epoch_num);
+
+            EpochResult::MessageIgnored
+        }
+    }
+
+    fn process_ce_message<NT>(
+        &mut self,
+        message: StoredMessage<CE::Message>,
+        network: &Arc<NT>,
+    ) -> Result<EpochResult>
+    where
+        NT: OrderProtocolSendNode<RQ, DumboPSerialization<RQ, R, A, CE>>,
+    {
+        let _span = debug_span!("ce").entered();
+
+        let committee_election = match &mut self.committee_election {
+            CommitteeState::RunningCE(committee_election) => committee_election,
+            CommitteeState::Pending => {
+                // The election is drained from the inbox once we start it
+                return Ok(if self.ce_inbox.push(message) {
+                    EpochResult::MessageQueued
                 } else {
-                    // Message from unknown node, ignore or queue
-                    Ok(EpochResult::MessageIgnored)
+                    warn!("Committee election inbox of epoch {:?} is full, dropping message", self.epoch_num);
+
+                    EpochResult::MessageIgnored
+                });
+            }
+            CommitteeState::Completed { .. } => return Ok(EpochResult::MessageIgnored),
+        };
+
+        let network_ref = SendNodeWrapperRef::new(self.epoch_num, message.header().from(), network)
+            .with_wal(self.wal.as_ref());
+
+        match committee_election.process_message(message, &network_ref)? {
+            CommitteeElectionResult::MessageQueued => Ok(EpochResult::MessageQueued),
+            CommitteeElectionResult::MessageIgnored => Ok(EpochResult::MessageIgnored),
+            CommitteeElectionResult::NonMember => Ok(EpochResult::non_member()),
+            CommitteeElectionResult::Processed => Ok(EpochResult::MessageProcessed),
+            CommitteeElectionResult::Decided => {
+                let CommitteeState::RunningCE(committee_election) = std::mem::replace(
+                    &mut self.committee_election,
+                    CommitteeState::Completed { committee: vec![] },
+                ) else {
+                    unreachable!()
+                };
+
+                self.committee_election = CommitteeState::Completed {
+                    committee: committee_election.finalize()?,
+                };
+
+                if let Some(started_at) = self.ce_started_at {
+                    metric_duration(CE_LATENCY_ID, started_at.elapsed());
                 }
+
+                debug!(committee = ?self.committee(), "Committee election decided");
+
+                self.start_committee(network)?;
+                self.check_index_progress(network);
+
+                self.check_agreement_progress(network)
+            }
+        }
+    }
+
+    fn process_index_message<NT>(
+        &mut self,
+        instance: NodeId,
+        message: StoredMessage<IndexBroadcastMessage>,
+        network: &Arc<NT>,
+    ) -> Result<EpochResult>
+    where
+        NT: OrderProtocolSendNode<RQ, DumboPSerialization<RQ, R, A, CE>>,
+    {
+        let _span = debug_span!("index", ?instance).entered();
+
+        if self.committee().is_none() {
+            if !self.quorum_info.is_member(instance) {
+                return Ok(EpochResult::MessageIgnored);
             }
-            DumboMessageType::CommitteeElectionMessage(ce_msg) => {
 
-                let network = SendNodeWrapperRef::new(self.epoch_num.clone(), network);
+            // The committee is not yet known to us, the inbox is drained once it is
+            return Ok(if self.index_inboxes.push(instance, message) {
+                EpochResult::MessageQueued
+            } else {
+                warn!("Inbox of the index broadcast of {instance:?} in epoch {:?} is full, dropping message", self.epoch_num);
 
-                match &mut self.committee_election {
-                    CommitteeState::RunningCE(committee_election) => {
-                        let stored_message = StoredMessage::new(message.header().clone(), ce_msg.clone());
+                EpochResult::MessageIgnored
+            });
+        }
 
-                        let committee_result =  committee_election.process_message(stored_message, &network)?;
+        let rbc = match self.node_states.get_mut(&instance) {
+            Some(
+                NodeState::RunningRBC(rbc)
+                | NodeState::RunningBoth { rbc, .. }
+                | NodeState::AwaitingRBC { rbc },
+            ) => rbc,
+            Some(NodeState::RunningABA { .. } | NodeState::Completed { .. }) | None => {
+                // The node is not part of the committee, or its index vector was already delivered
+                return Ok(EpochResult::MessageIgnored);
+            }
+        };
 
-                        match committee_result {
-                            CommitteeElectionResult::MessageQueued => Ok(EpochResult::MessageQueued),
-                            CommitteeElectionResult::MessageIgnored => Ok(EpochResult::MessageIgnored),
-                            CommitteeElectionResult::Processed => Ok(EpochResult::MessageProcessed),
-                            CommitteeElectionResult::Decided => {
+        let network_ref = SendNodeWrapperRef::new(self.epoch_num, instance, network)
+            .with_wal(self.wal.as_ref())
+            .index_broadcast();
 
-                                todo!()
-                            },
-                        }
-                    }
-                    CommitteeState::Completed { .. } => Ok(EpochResult::MessageIgnored)
+        match rbc.process_message_with_predicate(message, &network_ref, &self.delivered_batches) {
+            IndexBroadcastResult::MessageQueued => Ok(EpochResult::MessageQueued),
+            IndexBroadcastResult::MessageIgnored => Ok(EpochResult::MessageIgnored),
+            IndexBroadcastResult::NonMember => Ok(EpochResult::non_member()),
+            IndexBroadcastResult::Progressed(_) => Ok(EpochResult::MessageProcessed),
+            IndexBroadcastResult::Finalized => {
+                debug!("Index broadcast delivered");
+
+                if let Some(NodeState::RunningRBC(_)) = self.node_states.get(&instance) {
+                    self.log_agreement_input(instance, true)?;
                 }
+
+                let node_state = self.node_states.remove(&instance).unwrap();
+
+                let next_state = match node_state {
+                    NodeState::RunningRBC(rbc) => NodeState::RunningABA {
+                        completed_rbc: rbc.finalize()?.0,
+                        aba: A::new(true, self.quorum_info.clone(), self.coin_keys.clone()),
+                    },
+                    NodeState::RunningBoth { rbc, aba } => NodeState::RunningABA {
+                        completed_rbc: rbc.finalize()?.0,
+                        aba,
+                    },
+                    NodeState::AwaitingRBC { rbc } => NodeState::Completed {
+                        completed_rbc: Some(rbc.finalize()?.0),
+                        value: true,
+                    },
+                    state => state,
+                };
+
+                self.node_states.insert(instance, next_state);
+                self.open_agreement_round(instance, network)?;
+
+                self.check_agreement_progress(network)
+            }
+        }
+    }
+
+    /// Start the index broadcast and the agreement of every member of the elected committee,
+    /// discarding the messages held for the nodes which were not elected.
+    ///
+    /// The inputs we had provided before the replica stopped are provided again right away.
+    fn start_committee<NT>(&mut self, network: &Arc<NT>) -> Result<()>
+    where
+        NT: OrderProtocolSendNode<RQ, DumboPSerialization<RQ, R, A, CE>>,
+    {
+        let committee = self
+            .committee()
+            .into_iter()
+            .flatten()
+            .filter(|member| self.quorum_info.is_member(**member))
+            .cloned()
+            .collect::<Vec<_>>();
+
+        for member in &committee {
+            let rbc = ReliableBroadcastInstance::new(*member, self.quorum_info.clone());
+
+            let state = match self.recovered_inputs.remove(member) {
+                // The index vector must be delivered again, regardless of our input
+                Some(value) => NodeState::RunningBoth {
+                    rbc,
+                    aba: A::new(value, self.quorum_info.clone(), self.coin_keys.clone()),
+                },
+                None => NodeState::RunningRBC(rbc),
+            };
+
+            self.node_states.insert(*member, state);
+        }
+
+        for node in self.quorum_info.quorum_members().clone() {
+            if !committee.contains(&node) {
+                self.index_inboxes.discard(node);
+                self.aba_inboxes.discard(node);
             }
         }
+
+        for member in committee {
+            self.open_agreement_round(member, network)?;
+        }
+
+        Ok(())
+    }
+
+    /// Broadcast our estimate in the agreement of the given committee member, once for every
+    /// round it reaches, as the agreement does not open its rounds by itself
+    fn open_agreement_round<NT>(&mut self, instance: NodeId, network: &Arc<NT>) -> Result<()>
+    where
+        NT: OrderProtocolSendNode<RQ, DumboPSerialization<RQ, R, A, CE>>,
+    {
+        let aba = match self.node_states.get(&instance) {
+            Some(NodeState::RunningABA { aba, .. } | NodeState::RunningBoth { aba, .. }) => aba,
+            _ => return Ok(()),
+        };
+
+        let round = aba.round();
+
+        if self
+            .estimate_rounds
+            .get(&instance)
+            .is_some_and(|opened| *opened >= round)
+        {
+            return Ok(());
+        }
+
+        self.estimate_rounds.insert(instance, round);
+
+        let network_ref =
+            SendNodeWrapperRef::new(self.epoch_num, instance, network).with_wal(self.wal.as_ref());
+
+        let result = aba
+            .broadcast_estimate(&network_ref)
+            .map(|_| AsyncBinaryAgreementResult::Processed);
+
+        self.agreement_result(instance, result)?;
+
+        Ok(())
     }
 
+    /// Echo the index vectors which were withheld until we delivered every batch they refer to
+    fn release_index_echoes<NT>(&mut self, network: &Arc<NT>)
+    where
+        NT: OrderProtocolSendNode<RQ, DumboPSerialization<RQ, R, A, CE>>,
+    {
+        for (member, state) in self.node_states.iter_mut() {
+            let (NodeState::RunningRBC(rbc)
+            | NodeState::RunningBoth { rbc, .. }
+            | NodeState::AwaitingRBC { rbc }) = state
+            else {
+                continue;
+            };
+
+            let network_ref = SendNodeWrapperRef::new(self.epoch_num, *member, network)
+                .with_wal(self.wal.as_ref())
+                .index_broadcast();
+
+            if rbc.try_release_echo(&network_ref, &self.delivered_batches) {
+                debug!(?member, "Released our echo of an index vector");
+            }
+        }
+    }
+
+    /// Broadcast our index vector, once we are part of the elected committee
+    /// and we have delivered the batches of nodes holding `n - f` of the weight
+    fn check_index_progress<NT>(&mut self, network: &Arc<NT>)
+    where
+        NT: OrderProtocolSendNode<RQ, DumboPSerialization<RQ, R, A, CE>>,
+    {
+        if self.index_proposed
+            || !self.node_states.contains_key(&self.own_id)
+            || self.delivered_weight() < self.quorum_info.quorum_size()
+        {
+            return;
+        }
+
+        self.index_proposed = true;
+
+        let vector = IndexVector::new(self.delivered_batches.delivered().iter().cloned().collect());
+        let digest = vector.digest();
+
+        debug!(indices = ?vector.indices(), "Broadcasting our index vector");
+
+        let network_ref = SendNodeWrapperRef::new(self.epoch_num, self.own_id, network)
+            .with_wal(self.wal.as_ref())
+            .index_broadcast();
+
+        if let Err(failed) = network_ref.broadcast_signed(
+            IndexBroadcastMessage::Send(vector, digest),
+            self.quorum_info.quorum_members().iter().cloned(),
+        ) {
+            warn!("Failed to broadcast our index vector to {failed:?}");
+        }
+    }
+
+    /// Check whether the agreements of this epoch allow us to make progress.
+    ///
+    /// Once the agreement of a committee member has decided 1 we vote 0 in every agreement
+    /// we have not voted in, and once every agreement has decided (and every included index vector
+    /// and every batch they refer to has been delivered) we move on to revealing the included batches.
+    fn check_agreement_progress<NT>(&mut self, network: &Arc<NT>) -> Result<EpochResult>
+    where
+        NT: OrderProtocolSendNode<RQ, DumboPSerialization<RQ, R, A, CE>>,
+    {
+        if matches!(self.committee_election, CommitteeState::Pending)
+            && self.delivered_weight() >= self.quorum_info.quorum_size()
+        {
+            // The committee must hold more weight than the faulty nodes, so it has a correct member
+            let required_committee = self.quorum_info.f() + 1;
+
+            // Every epoch elects its own committee
+            let nonce = bincode::serde::encode_to_vec(self.epoch_num, bincode::config::standard())
+                .expect("Failed to serialize epoch number");
+
+            let mut committee_election = CE::new(
+                self.quorum_info.clone(),
+                required_committee,
+                self.coin_keys.clone(),
+                nonce,
+            );
+
+            let network_ref = SendNodeWrapperRef::new(self.epoch_num, self.own_id, network)
+                .with_wal(self.wal.as_ref());
+
+            committee_election.start(&network_ref)?;
+
+            self.committee_election = CommitteeState::RunningCE(committee_election);
+            self.ce_started_at = Some(Instant::now());
+
+            debug!("Committee election started");
+        }
+
+        if !self.voted_remaining && self.any_included() {
+            self.voted_remaining = true;
+
+            debug!("An agreement included its index vector, voting 0 in the remaining ones");
+
+            let pending = self
+                .node_states
+                .iter()
+                .filter(|(_, state)| matches!(state, NodeState::RunningRBC(_)))
+                .map(|(node, _)| *node)
+                .collect::<Vec<_>>();
+
+            for node in pending {
+                self.log_agreement_input(node, false)?;
+
+                if let Some(NodeState::RunningRBC(rbc)) = self.node_states.remove(&node) {
+                    self.node_states.insert(
+                        node,
+                        NodeState::RunningBoth {
+                            rbc,
+                            aba: A::new(false, self.quorum_info.clone(), self.coin_keys.clone()),
+                        },
+                    );
+                }
+
+                self.open_agreement_round(node, network)?;
+            }
+        }
+
+        if !matches!(self.phase, EpochPhase::Agreement)
+            || self.node_states.is_empty()
+            || self.completed_node_count() < self.node_states.len()
+        {
+            return Ok(EpochResult::MessageProcessed);
+        }
+
+        let index_vectors = self
+            .node_states
+            .iter()
+            .filter_map(|(node, state)| match state {
+                NodeState::Completed {
+                    completed_rbc: Some(vector),
+                    value: true,
+                } => Some((*node, vector.clone())),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        let included = index_vectors
+            .iter()
+            .flat_map(|(_, vector)| vector.indices().iter().cloned())
+            .collect::<BTreeSet<_>>();
+
+        if included.iter().any(|node| {
+            !matches!(self.batch_broadcasts.get(node), Some(BatchState::Delivered(_)))
+        }) {
+            // Every batch an included index vector refers to is delivered by every correct node,
+            // as the correct nodes which echoed the vector had delivered it
+            return Ok(EpochResult::MessageProcessed);
+        }
+
+        let decisions = self
+            .node_states
+            .iter()
+            .filter_map(|(node, state)| match state {
+                NodeState::Completed { value, .. } => Some((*node, *value)),
+                _ => None,
+            })
+            .collect();
+
+        let batches = included
+            .into_iter()
+            .filter_map(|node| match self.batch_broadcasts.remove(&node) {
+                Some(BatchState::Delivered(batch)) => Some((node, batch)),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        let digests = batches
+            .iter()
+            .map(|(node, batch)| (*node, batch.digest()))
+            .collect();
+
+        self.agreement_outcome = Some(AgreementOutcome::new(decisions, index_vectors, digests));
+
+        self.begin_decryption(batches, network)
+    }
+
+    /// Whether the agreement of any committee member has decided to include its index vector
+    fn any_included(&self) -> bool {
+        self.node_states.values().any(|state| {
+            matches!(
+                state,
+                NodeState::AwaitingRBC { .. } | NodeState::Completed { value: true, .. }
+            )
+        })
+    }
 
     fn completed_node_count(&self) -> usize {
         self.node_states
@@ -171,36 +1198,63 @@ where
             .count()
     }
 
-    fn completed_rbc_count(&self) -> usize {
-        self.node_states
-            .iter()
-            .filter(|(_, state)| {
-                matches!(
-                    state,
-                    NodeState::RunningABA { .. } | NodeState::Completed { .. }
-                )
-            })
-            .count()
+    /// The weight of the nodes whose batch we have delivered
+    fn delivered_weight(&self) -> usize {
+        self.quorum_info
+            .accumulated_weight(self.delivered_batches.delivered())
     }
 }
 
 impl<CE, RQ, R, A> Debug for DumboRound<CE, RQ, R, A>
 where
-    CE: Debug,
-    R: Debug,
-    A: Debug,
+    CE: CommitteeElectionProtocol + Debug,
+    R: ReliableBroadcast<ProposedBatch<RQ>> + Debug,
+    A: ABAProtocol + Debug,
 {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("DumboRound")
             .field("epoch_num", &self.epoch_num)
             .field("node_states", &self.node_states)
             .field("committee_election", &self.committee_election)
+            .field("batch_broadcasts", &self.batch_broadcasts)
+            .field("phase", &self.phase)
+            .field("queued_aba_messages", &self.aba_inboxes.len())
+            .field("queued_ce_messages", &self.ce_inbox.len())
+            .field("queued_index_messages", &self.index_inboxes.len())
             .finish()
     }
 }
 
+/// A message polled from one of the sub protocol instances of the epoch
+enum PolledMessage<AM> {
+    Index(StoredMessage<IndexBroadcastMessage>),
+    Aba(StoredMessage<AM>),
+}
+
+/// The phase of a Dumbo epoch.
+enum EpochPhase<RQ> {
+    /// The reliable broadcasts and agreements of the epoch are still running
+    Agreement,
+    /// The agreement has decided, and we are revealing the encrypted batches
+    Decryption(DecryptionPhase<RQ>),
+    /// The batches of the epoch are known, ordered by their proposer
+    Decided(Vec<(NodeId, Vec<StoredMessage<RQ>>)>),
+}
+
+impl<RQ> Debug for EpochPhase<RQ> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EpochPhase::Agreement => write!(f, "Agreement"),
+            EpochPhase::Decryption(decryption) => write!(f, "Decryption({:?})", decryption),
+            EpochPhase::Decided(batches) => write!(f, "Decided({})", batches.len()),
+        }
+    }
+}
+
 /// The current state of the committee election protocol.
 enum CommitteeState<CE> {
+    /// Not enough batches have been delivered to start the election
+    Pending,
     RunningCE(CE),
     Completed { committee: Vec<NodeId> },
 }
@@ -211,38 +1265,64 @@ where
 {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
+            CommitteeState::Pending => write!(f, "Pending"),
             CommitteeState::RunningCE(ce) => write!(f, "RunningCE({:?})", ce),
             CommitteeState::Completed { committee } => write!(f, "Completed({:?})", committee),
         }
     }
 }
 
-/// The state of a node in the Dumbo protocol.
-enum NodeState<RQ, R, A> {
-    RunningRBC(R),
-    RunningABA { completed_rbc: RQ, aba: A },
-    Completed { completed_rbc: RQ, value: bool },
+/// The state of the batch broadcast of a node.
+enum BatchState<RQ, R> {
+    /// The batch of the node is being broadcast
+    Running(R),
+    /// The batch of the node was delivered
+    Delivered(ProposedBatch<RQ>),
 }
 
-impl<RQ, R, A> NodeState<RQ, R, A>
+impl<RQ, R> Debug for BatchState<RQ, R>
 where
-    R: ReliableBroadcast<RQ>,
-    A: ABAProtocol,
+    R: Debug,
 {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BatchState::Running(rbc) => write!(f, "Running({:?})", rbc),
+            BatchState::Delivered(_) => write!(f, "Delivered"),
+        }
+    }
+}
+
+type IndexBroadcast = ReliableBroadcastInstance<IndexVector>;
+
+/// The state of a committee member in the Dumbo protocol.
+enum NodeState<A> {
+    /// The index vector of the member is being broadcast, and we have not voted in its agreement
+    RunningRBC(IndexBroadcast),
+    /// The index vector of the member was delivered, and we voted 1 in its agreement
+    RunningABA { completed_rbc: IndexVector, aba: A },
+    /// We voted 0 in the agreement of the member before its index vector was delivered
+    RunningBoth { rbc: IndexBroadcast, aba: A },
+    /// The agreement decided 1, but we are still waiting for the index vector to be delivered
+    AwaitingRBC { rbc: IndexBroadcast },
+    /// The agreement of the member has decided
+    Completed {
+        completed_rbc: Option<IndexVector>,
+        value: bool,
+    },
 }
 
-impl<RQ, R, A> Debug for NodeState<RQ, R, A>
+impl<A> Debug for NodeState<A>
 where
-    R: Debug,
     A: Debug,
 {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             NodeState::RunningRBC(rbc) => write!(f, "RunningRBC({:?})", rbc),
-            NodeState::RunningABA {
-                completed_rbc: _,
-                aba,
-            } => write!(f, "RunningABA({:?})", aba),
+            NodeState::RunningABA { completed_rbc, aba } => {
+                write!(f, "RunningABA({:?}, {:?})", completed_rbc.indices(), aba)
+            }
+            NodeState::RunningBoth { rbc, aba } => write!(f, "RunningBoth({:?}, {:?})", rbc, aba),
+            NodeState::AwaitingRBC { rbc } => write!(f, "AwaitingRBC({:?})", rbc),
             NodeState::Completed {
                 completed_rbc: _,
                 value,
@@ -257,3 +1337,29 @@ pub(super) enum EpochResult {
     MessageProcessed,
     Finalized,
 }
+
+impl EpochResult {
+    /// The result of a message a sub protocol dropped as its sender is not part of the quorum,
+    /// which is accounted for in the metrics
+    fn non_member() -> EpochResult {
+        debug!("Dropped a message sent by a node outside the quorum");
+
+        metric_increment(NON_MEMBER_MESSAGES_ID, Some(1));
+
+        EpochResult::MessageIgnored
+    }
+
+    /// Merge the results of processing several messages, keeping the most significant one
+    fn merge(self, other: EpochResult) -> EpochResult {
+        match (self, other) {
+            (EpochResult::Finalized, _) | (_, EpochResult::Finalized) => EpochResult::Finalized,
+            (EpochResult::MessageProcessed, _) | (_, EpochResult::MessageProcessed) => {
+                EpochResult::MessageProcessed
+            }
+            (EpochResult::MessageQueued, _) | (_, EpochResult::MessageQueued) => {
+                EpochResult::MessageQueued
+            }
+            _ => EpochResult::MessageIgnored,
+        }
+    }
+}
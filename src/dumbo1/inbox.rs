@@ -0,0 +1,97 @@
+use atlas_common::collections::HashMap;
+use atlas_common::node_id::NodeId;
+use std::collections::VecDeque;
+
+/// Messages received for a sub protocol of an epoch which is not yet able to process them.
+///
+/// Nodes progress through an epoch at different speeds, so we may receive the messages of
+/// an agreement before we have provided our input to it, or the messages of the committee
+/// election before we have started it.
+/// These are held here, in order of arrival, and drained once the sub protocol is running.
+pub(super) struct Inbox<M> {
+    capacity: usize,
+    messages: VecDeque<M>,
+}
+
+impl<M> Inbox<M> {
+    pub(super) fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            messages: VecDeque::new(),
+        }
+    }
+
+    /// Store a message, returning false if the inbox is full and the message was dropped
+    pub(super) fn push(&mut self, message: M) -> bool {
+        if self.messages.len() >= self.capacity {
+            return false;
+        }
+
+        self.messages.push_back(message);
+
+        true
+    }
+
+    pub(super) fn pop(&mut self) -> Option<M> {
+        self.messages.pop_front()
+    }
+
+    pub(super) fn len(&self) -> usize {
+        self.messages.len()
+    }
+
+    pub(super) fn is_empty(&self) -> bool {
+        self.messages.is_empty()
+    }
+}
+
+/// An [`Inbox`] for each instance of a sub protocol which runs once per node in each epoch.
+pub(super) struct InstanceInboxes<M> {
+    capacity: usize,
+    inboxes: HashMap<NodeId, Inbox<M>>,
+}
+
+impl<M> InstanceInboxes<M> {
+    pub(super) fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            inboxes: HashMap::default(),
+        }
+    }
+
+    /// Store a message for the given instance, returning false if its inbox is full
+    pub(super) fn push(&mut self, instance: NodeId, message: M) -> bool {
+        let capacity = self.capacity;
+
+        self.inboxes
+            .entry(instance)
+            .or_insert_with(|| Inbox::new(capacity))
+            .push(message)
+    }
+
+    /// Take the oldest message held for the given instance
+    pub(super) fn pop(&mut self, instance: NodeId) -> Option<M> {
+        let inbox = self.inboxes.get_mut(&instance)?;
+
+        let message = inbox.pop();
+
+        if inbox.is_empty() {
+            self.inboxes.remove(&instance);
+        }
+
+        message
+    }
+
+    /// Discard every message held for the given instance, returning how many were discarded
+    pub(super) fn discard(&mut self, instance: NodeId) -> usize {
+        self.inboxes
+            .remove(&instance)
+            .map(|inbox| inbox.len())
+            .unwrap_or(0)
+    }
+
+    /// The amount of messages held across all instances
+    pub(super) fn len(&self) -> usize {
+        self.inboxes.values().map(Inbox::len).sum()
+    }
+}
@@ -1,3 +1,11 @@
+use crate::aba::VerifiableABAMessage;
+use crate::dumbo1::batch::ProposedBatch;
+use crate::dumbo1::catch_up::CatchUpMessage;
+use crate::dumbo1::certificate::DecisionCertificate;
+use crate::dumbo1::checkpoint::CheckpointMessage;
+use crate::rbc::VerifiableRBCMessage;
+use crate::reliable_broadcast::index_rbc::IndexBroadcastMessage;
+use atlas_common::node_id::NodeId;
 use atlas_common::ordering::{Orderable, SeqNo};
 use atlas_common::serialization_helper::SerMsg;
 use atlas_communication::message::Header;
@@ -5,33 +13,38 @@ use atlas_communication::reconfiguration::NetworkInformationProvider;
 use atlas_core::ordering_protocol::networking::serialize::{
     OrderProtocolVerificationHelper, OrderingProtocolMessage,
 };
+use getset::Getters;
 use serde::{Deserialize, Serialize};
 use std::marker::PhantomData;
 use std::sync::Arc;
-use getset::Getters;
+use thiserror::Error;
 
 /// A message used in the Dumbo1 protocol.
 /// This struct encapsulates a message round and the message type.
 /// See [`DumboMessageType`]
 #[derive(Clone, Serialize, Deserialize, Getters)]
-pub struct DumboMessage<RBM, AM, CEM>
+pub struct DumboMessage<RQ, RBM, AM, CEM>
 {
     message_round: SeqNo,
     #[get = "pub"]
-    message_type: DumboMessageType<RBM, AM, CEM>,
+    message_type: DumboMessageType<RQ, RBM, AM, CEM>,
 }
 
-impl<RBM, AM, CEM> DumboMessage<RBM, AM, CEM>
+impl<RQ, RBM, AM, CEM> DumboMessage<RQ, RBM, AM, CEM>
 {
-    pub fn new(message_round: SeqNo, message_type: DumboMessageType<RBM, AM, CEM>) -> Self {
+    pub fn new(message_round: SeqNo, message_type: DumboMessageType<RQ, RBM, AM, CEM>) -> Self {
         Self {
             message_round,
             message_type,
         }
     }
+
+    pub fn into_inner(self) -> (SeqNo, DumboMessageType<RQ, RBM, AM, CEM>) {
+        (self.message_round, self.message_type)
+    }
 }
 
-impl<RBM, AM, CEM> Orderable for DumboMessage<RBM, AM, CEM>
+impl<RQ, RBM, AM, CEM> Orderable for DumboMessage<RQ, RBM, AM, CEM>
 {
     fn sequence_number(&self) -> SeqNo {
         self.message_round
@@ -44,27 +57,67 @@ impl<RBM, AM, CEM> Orderable for DumboMessage<RBM, AM, CEM>
 /// Asynchronous Binary Agreement, and Committee Election protocols.
 ///
 /// Each variant holds the corresponding message type.
+/// Reliable broadcast and binary agreement messages are tagged with the
+/// node whose proposal the instance refers to, as there is one instance per node in each epoch.
+/// Index broadcast messages are tagged with the committee member whose index vector they carry.
+/// Catch up messages are not bound to the epoch they are tagged with, and checkpoint
+/// messages are tagged with the epoch of their checkpoint.
 #[derive(Clone, Serialize, Deserialize)]
-pub enum DumboMessageType<RBM, AM, CEM>
+pub enum DumboMessageType<RQ, RBM, AM, CEM>
 {
-    ReliableBroadcast(RBM),
-    AsyncBinaryAgreement(AM),
+    ReliableBroadcast(NodeId, RBM),
+    IndexBroadcast(NodeId, IndexBroadcastMessage),
+    AsyncBinaryAgreement(NodeId, AM),
     CommitteeElectionMessage(CEM),
+    DecryptionShare(DecryptionShareMessage),
+    CatchUp(CatchUpMessage<RQ>),
+    Checkpoint(CheckpointMessage),
+}
+
+/// A decryption share for the encrypted batch proposed by `proposer`,
+/// exchanged after the agreement of the epoch has decided.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Getters)]
+pub struct DecryptionShareMessage {
+    #[get = "pub"]
+    proposer: NodeId,
+    #[get = "pub"]
+    share: Vec<u8>,
+}
+
+impl DecryptionShareMessage {
+    pub fn new(proposer: NodeId, share: Vec<u8>) -> Self {
+        Self { proposer, share }
+    }
+
+    pub fn into_inner(self) -> (NodeId, Vec<u8>) {
+        (self.proposer, self.share)
+    }
 }
 
 pub struct DumboSerialization<RQ, RBM, AM, CEM>(PhantomData<fn(RQ, RBM, AM, CEM)>);
 
 impl<RQ, RBM, AM, CEM> OrderingProtocolMessage<RQ> for DumboSerialization<RQ, RBM, AM, CEM>
 where
-    RQ: 'static,
-    RBM: SerMsg,
-    AM: SerMsg,
+    RQ: SerMsg,
+    RBM: SerMsg + VerifiableRBCMessage<ProposedBatch<RQ>>,
+    AM: SerMsg + VerifiableABAMessage,
     CEM: SerMsg,
 {
-    type ProtocolMessage = DumboMessage<RBM, AM, CEM>;
-    type DecisionMetadata = ();
+    type ProtocolMessage = DumboMessage<RQ, RBM, AM, CEM>;
+    type DecisionMetadata = DecisionCertificate;
     type DecisionAdditionalInfo = ();
 
+    /// Stateless verification of a Dumbo message.
+    ///
+    /// Checks that every node referenced by the message is known, that reliable broadcast
+    /// proposals are only sent by the broadcaster of their instance (and carry correctly
+    /// signed requests, or the indices of known nodes) and that agreement messages are well formed.
+    ///
+    /// The checks which depend on the epoch of the message are performed by the protocol when
+    /// it receives the message, as the epoch may not have started yet: the nodes must be members
+    /// of the quorum of the epoch, the epoch must be within the window of epochs we buffer,
+    /// index vectors must be valid for the quorum, and the coin shares of the agreement must
+    /// verify against the key set of the quorum.
     fn internally_verify_message<NI, OPVH>(
         network_info: &Arc<NI>,
         header: &Header,
@@ -75,6 +128,164 @@ where
         OPVH: OrderProtocolVerificationHelper<RQ, Self, NI>,
         Self: Sized,
     {
-        todo!()
+        let sender = header.from();
+
+        if !is_known_node(network_info, sender) {
+            return Err(DumboVerificationError::UnknownSender(sender).into());
+        }
+
+        if message.sequence_number() < SeqNo::ONE {
+            return Err(DumboVerificationError::InvalidEpoch(message.sequence_number()).into());
+        }
+
+        match message.message_type() {
+            DumboMessageType::ReliableBroadcast(instance, rbc_message) => {
+                if !is_known_node(network_info, *instance) {
+                    return Err(DumboVerificationError::UnknownInstance(*instance).into());
+                }
+
+                let Some(proposal) = rbc_message.proposal() else {
+                    return Ok(());
+                };
+
+                if sender != *instance {
+                    return Err(DumboVerificationError::ProposalFromWrongSender {
+                        instance: *instance,
+                        sender,
+                    }
+                    .into());
+                }
+
+                if let Some(reconfiguration) = proposal.reconfiguration() {
+                    reconfiguration.validate().map_err(|err| {
+                        DumboVerificationError::InvalidReconfiguration {
+                            instance: *instance,
+                            reason: err.to_string(),
+                        }
+                    })?;
+                }
+
+                // Encrypted batches are checked by the request verifier of the epoch once they are revealed
+                if let Some(requests) = proposal.plain_requests() {
+                    for request in requests {
+                        OPVH::verify_request_message(
+                            network_info,
+                            request.header(),
+                            request.message().clone(),
+                        )
+                        .map_err(|err| DumboVerificationError::InvalidRequest {
+                            instance: *instance,
+                            reason: err.to_string(),
+                        })?;
+                    }
+                }
+
+                Ok(())
+            }
+            DumboMessageType::IndexBroadcast(instance, index_message) => {
+                if !is_known_node(network_info, *instance) {
+                    return Err(DumboVerificationError::UnknownInstance(*instance).into());
+                }
+
+                let Some(vector) = index_message.proposal() else {
+                    return Ok(());
+                };
+
+                if sender != *instance {
+                    return Err(DumboVerificationError::ProposalFromWrongSender {
+                        instance: *instance,
+                        sender,
+                    }
+                    .into());
+                }
+
+                if let Some(index) = vector
+                    .indices()
+                    .iter()
+                    .find(|index| !is_known_node(network_info, **index))
+                {
+                    return Err(DumboVerificationError::UnknownInstance(*index).into());
+                }
+
+                Ok(())
+            }
+            DumboMessageType::AsyncBinaryAgreement(instance, aba_message) => {
+                if !is_known_node(network_info, *instance) {
+                    return Err(DumboVerificationError::UnknownInstance(*instance).into());
+                }
+
+                if !aba_message.is_well_formed() {
+                    return Err(DumboVerificationError::MalformedAgreementMessage(*instance).into());
+                }
+
+                Ok(())
+            }
+            DumboMessageType::CommitteeElectionMessage(_) => Ok(()),
+            DumboMessageType::CatchUp(CatchUpMessage::Request { from, to }) => {
+                if from >= to {
+                    return Err(DumboVerificationError::InvalidCatchUpRange(*from, *to).into());
+                }
+
+                Ok(())
+            }
+            // The decided epochs are checked against their certificates when processed
+            DumboMessageType::CatchUp(CatchUpMessage::Response(_)) => Ok(()),
+            DumboMessageType::Checkpoint(checkpoint) => {
+                if checkpoint.epoch() != message.sequence_number() {
+                    return Err(DumboVerificationError::InvalidEpoch(checkpoint.epoch()).into());
+                }
+
+                Ok(())
+            }
+            DumboMessageType::DecryptionShare(share) => {
+                if !is_known_node(network_info, *share.proposer()) {
+                    return Err(DumboVerificationError::UnknownInstance(*share.proposer()).into());
+                }
+
+                if share.share().is_empty() {
+                    return Err(DumboVerificationError::EmptyDecryptionShare.into());
+                }
+
+                Ok(())
+            }
+        }
     }
 }
+
+/// Whether the node is known to the network, and can therefore take part in the protocol
+fn is_known_node<NI>(network_info: &Arc<NI>, node: NodeId) -> bool
+where
+    NI: NetworkInformationProvider,
+{
+    network_info.get_public_key(&node).is_some()
+}
+
+#[derive(Error, Debug)]
+pub enum DumboVerificationError {
+    #[error("Message sent by unknown node {0:?}")]
+    UnknownSender(NodeId),
+    #[error("Message refers to the instance of unknown node {0:?}")]
+    UnknownInstance(NodeId),
+    #[error("Message for invalid epoch {0:?}")]
+    InvalidEpoch(SeqNo),
+    #[error("Proposal of the broadcast of {instance:?} was sent by {sender:?}")]
+    ProposalFromWrongSender { instance: NodeId, sender: NodeId },
+    #[error("Proposal of the broadcast of {instance:?} contains an invalid request: {reason}")]
+    InvalidRequest { instance: NodeId, reason: String },
+    #[error("Malformed agreement message in the instance of {0:?}")]
+    MalformedAgreementMessage(NodeId),
+    #[error("Empty decryption share")]
+    EmptyDecryptionShare,
+    #[error("Invalid catch up range [{0:?}, {1:?})")]
+    InvalidCatchUpRange(SeqNo, SeqNo),
+    #[error("Proposal of the broadcast of {instance:?} contains an invalid reconfiguration: {reason}")]
+    InvalidReconfiguration { instance: NodeId, reason: String },
+    #[error("Message for epoch {epoch:?} refers to {node:?}, which is not a member of its quorum")]
+    NotAMember { node: NodeId, epoch: SeqNo },
+    #[error("Message for epoch {0:?}, which is beyond the window of epochs we buffer")]
+    EpochOutOfWindow(SeqNo),
+    #[error("Agreement message in the instance of {0:?} carries an invalid coin share")]
+    InvalidCoinShare(NodeId),
+    #[error("Index vector of {instance:?} is invalid: {reason}")]
+    InvalidIndexVector { instance: NodeId, reason: String },
+}
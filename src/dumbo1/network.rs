@@ -1,24 +1,31 @@
-use crate::aba::AsyncBinaryAgreementSendNode;
+use crate::aba::{AsyncBinaryAgreementSendNode, VerifiableABAMessage};
 use crate::committee_election::CommitteeElectionSendNode;
+use crate::dumbo1::batch::ProposedBatch;
 use crate::dumbo1::message::{DumboMessage, DumboMessageType, DumboSerialization};
 use crate::dumbo1::protocol::{DumboPMessage, DumboPSerialization};
-use crate::rbc::ReliableBroadcastSendNode;
+use crate::dumbo1::wal::{SharedWal, WalEntry, log_entry, log_vote};
+use crate::rbc::{ReliableBroadcastSendNode, VerifiableRBCMessage};
+use crate::reliable_broadcast::index_rbc::IndexBroadcastMessage;
 use atlas_common::node_id::NodeId;
 use atlas_common::ordering::SeqNo;
 use atlas_common::serialization_helper::SerMsg;
 use atlas_core::ordering_protocol::networking::OrderProtocolSendNode;
+use anyhow::anyhow;
 use std::marker::PhantomData;
 use std::sync::Arc;
+use tracing::warn;
 
 struct SendNode<RQ, ABA, BCM, CE> {
     current_round: SeqNo,
+    // The node whose proposal the sub protocol instance refers to
+    instance: NodeId,
     _phantom: PhantomData<fn(RQ, ABA, BCM, CE) -> ()>,
 }
 
 impl<RQ, ABA, BCM, CE> SendNode<RQ, ABA, BCM, CE>
 where
     RQ: SerMsg,
-    ABA: SerMsg,
+    ABA: SerMsg + VerifiableABAMessage,
     CE: SerMsg,
 {
     fn send_rbc<NT>(
@@ -30,11 +37,11 @@ where
     ) -> atlas_common::error::Result<()>
     where
         NT: OrderProtocolSendNode<RQ, DumboSerialization<RQ, BCM, ABA, CE>>,
-        BCM: SerMsg,
+        BCM: SerMsg + VerifiableRBCMessage<ProposedBatch<RQ>>,
     {
         let message = DumboMessage::new(
             self.current_round,
-            DumboMessageType::ReliableBroadcast(message),
+            DumboMessageType::ReliableBroadcast(self.instance, message),
         );
 
         node.send(message, target, flush)
@@ -49,11 +56,11 @@ where
     ) -> atlas_common::error::Result<()>
     where
         NT: OrderProtocolSendNode<RQ, DumboSerialization<RQ, BCM, ABA, CE>>,
-        BCM: SerMsg,
+        BCM: SerMsg + VerifiableRBCMessage<ProposedBatch<RQ>>,
     {
         let message = DumboMessage::new(
             self.current_round,
-            DumboMessageType::ReliableBroadcast(message),
+            DumboMessageType::ReliableBroadcast(self.instance, message),
         );
 
         node.send_signed(message, target, flush)
@@ -63,11 +70,11 @@ where
     where
         I: Iterator<Item = NodeId>,
         NT: OrderProtocolSendNode<RQ, DumboSerialization<RQ, BCM, ABA, CE>>,
-        BCM: SerMsg,
+        BCM: SerMsg + VerifiableRBCMessage<ProposedBatch<RQ>>,
     {
         let message = DumboMessage::new(
             self.current_round,
-            DumboMessageType::ReliableBroadcast(message),
+            DumboMessageType::ReliableBroadcast(self.instance, message),
         );
 
         node.broadcast(message, targets)
@@ -82,53 +89,190 @@ where
     where
         I: Iterator<Item = NodeId>,
         NT: OrderProtocolSendNode<RQ, DumboSerialization<RQ, BCM, ABA, CE>>,
-        BCM: SerMsg,
+        BCM: SerMsg + VerifiableRBCMessage<ProposedBatch<RQ>>,
     {
         let message = DumboMessage::new(
             self.current_round,
-            DumboMessageType::ReliableBroadcast(message),
+            DumboMessageType::ReliableBroadcast(self.instance, message),
+        );
+
+        node.broadcast_signed(message, targets)
+    }
+
+    fn send_index<NT>(
+        &self,
+        node: &NT,
+        message: IndexBroadcastMessage,
+        target: NodeId,
+        flush: bool,
+        signed: bool,
+    ) -> atlas_common::error::Result<()>
+    where
+        NT: OrderProtocolSendNode<RQ, DumboSerialization<RQ, BCM, ABA, CE>>,
+        BCM: SerMsg + VerifiableRBCMessage<ProposedBatch<RQ>>,
+    {
+        let message = DumboMessage::new(
+            self.current_round,
+            DumboMessageType::IndexBroadcast(self.instance, message),
+        );
+
+        if signed {
+            node.send_signed(message, target, flush)
+        } else {
+            node.send(message, target, flush)
+        }
+    }
+
+    fn broadcast_index<I, NT>(
+        &self,
+        node: &NT,
+        message: IndexBroadcastMessage,
+        targets: I,
+        signed: bool,
+    ) -> Result<(), Vec<NodeId>>
+    where
+        I: Iterator<Item = NodeId>,
+        NT: OrderProtocolSendNode<RQ, DumboSerialization<RQ, BCM, ABA, CE>>,
+        BCM: SerMsg + VerifiableRBCMessage<ProposedBatch<RQ>>,
+    {
+        let message = DumboMessage::new(
+            self.current_round,
+            DumboMessageType::IndexBroadcast(self.instance, message),
+        );
+
+        if signed {
+            node.broadcast_signed(message, targets)
+        } else {
+            node.broadcast(message, targets)
+        }
+    }
+
+    fn broadcast_aba<I, NT>(&self, node: &NT, message: ABA, targets: I) -> Result<(), Vec<NodeId>>
+    where
+        I: Iterator<Item = NodeId>,
+        NT: OrderProtocolSendNode<RQ, DumboSerialization<RQ, BCM, ABA, CE>>,
+        BCM: SerMsg + VerifiableRBCMessage<ProposedBatch<RQ>>,
+    {
+        let message = DumboMessage::new(
+            self.current_round,
+            DumboMessageType::AsyncBinaryAgreement(self.instance, message),
+        );
+
+        node.broadcast_signed(message, targets)
+    }
+
+    fn send_ce<NT>(
+        &self,
+        node: &NT,
+        message: CE,
+        target: NodeId,
+        flush: bool,
+        signed: bool,
+    ) -> atlas_common::error::Result<()>
+    where
+        NT: OrderProtocolSendNode<RQ, DumboSerialization<RQ, BCM, ABA, CE>>,
+        BCM: SerMsg + VerifiableRBCMessage<ProposedBatch<RQ>>,
+    {
+        let message = DumboMessage::new(
+            self.current_round,
+            DumboMessageType::CommitteeElectionMessage(message),
+        );
+
+        if signed {
+            node.send_signed(message, target, flush)
+        } else {
+            node.send(message, target, flush)
+        }
+    }
+
+    fn broadcast_ce<I, NT>(&self, node: &NT, message: CE, targets: I) -> Result<(), Vec<NodeId>>
+    where
+        I: Iterator<Item = NodeId>,
+        NT: OrderProtocolSendNode<RQ, DumboSerialization<RQ, BCM, ABA, CE>>,
+        BCM: SerMsg + VerifiableRBCMessage<ProposedBatch<RQ>>,
+    {
+        let message = DumboMessage::new(
+            self.current_round,
+            DumboMessageType::CommitteeElectionMessage(message),
         );
 
         node.broadcast_signed(message, targets)
     }
 }
+
+/// The sending end used by the sub protocols of an epoch.
+///
+/// When a write ahead log is attached, every message is logged before it is sent,
+/// and is not sent at all if it could not be logged. Agreement messages which contradict
+/// one that was logged before are never sent.
 pub(super) struct SendNodeWrapperRef<'a, RQ, ABA, BCM, CE, NT> {
     inner: &'a Arc<NT>,
     inner_node: SendNode<RQ, ABA, BCM, CE>,
+    wal: Option<&'a SharedWal<RQ, BCM, ABA, CE>>,
 }
 
 impl<'a, RQ, ABA, BCM, CE, NT> SendNodeWrapperRef<'a, RQ, ABA, BCM, CE, NT>
 where
     RQ: SerMsg,
-    ABA: SerMsg,
+    ABA: SerMsg + VerifiableABAMessage,
     CE: SerMsg,
 {
-    pub(super) fn new(current_round: SeqNo, inner: &'a Arc<NT>) -> Self {
+    pub(super) fn new(current_round: SeqNo, instance: NodeId, inner: &'a Arc<NT>) -> Self {
         Self {
             inner,
             inner_node: SendNode {
                 current_round,
+                instance,
                 _phantom: PhantomData,
             },
+            wal: None,
         }
     }
+
+    /// Log every message sent through this node in the given write ahead log
+    pub(super) fn with_wal(mut self, wal: Option<&'a SharedWal<RQ, BCM, ABA, CE>>) -> Self {
+        self.wal = wal;
+        self
+    }
+
+    /// Send the messages of the index broadcast of the instance through this node,
+    /// instead of the messages of its batch broadcast
+    pub(super) fn index_broadcast(self) -> IndexSendNodeRef<'a, RQ, ABA, BCM, CE, NT> {
+        IndexSendNodeRef(self)
+    }
 }
 
+/// The sending end used by the index broadcasts of an epoch, see [`SendNodeWrapperRef::index_broadcast`].
+pub(super) struct IndexSendNodeRef<'a, RQ, ABA, BCM, CE, NT>(SendNodeWrapperRef<'a, RQ, ABA, BCM, CE, NT>);
+
 impl<'a, RQ, BCM, ABA, CE, NT> AsyncBinaryAgreementSendNode<ABA>
     for SendNodeWrapperRef<'a, RQ, ABA, BCM, CE, NT>
 where
     RQ: SerMsg,
-    ABA: SerMsg,
-    BCM: SerMsg,
+    ABA: SerMsg + VerifiableABAMessage,
+    BCM: SerMsg + VerifiableRBCMessage<ProposedBatch<RQ>>,
     CE: SerMsg,
     NT: OrderProtocolSendNode<RQ, DumboSerialization<RQ, BCM, ABA, CE>>,
 {
     fn broadcast_message<I>(&self, message: ABA, target: I) -> atlas_common::error::Result<()>
     where
         I: Iterator<Item = NodeId>,
-        ABA: SerMsg,
+        ABA: SerMsg + VerifiableABAMessage,
     {
-        todo!()
+        if !log_vote(self.wal, self.current_round(), self.instance(), &message)? {
+            // The agreement was restarted after the replica stopped, and would now vote differently
+            warn!(
+                "Refusing to send an agreement message of instance {:?} in epoch {:?} which contradicts one we sent before",
+                self.instance(),
+                self.current_round()
+            );
+
+            return Ok(());
+        }
+
+        self.inner_node
+            .broadcast_aba::<I, NT>(&*self.inner, message, target)
+            .map_err(|failed| anyhow!("Failed to broadcast ABA message to {failed:?}"))
     }
 }
 
@@ -136,29 +280,37 @@ impl<'a, RQ, BCM, ABA, CE, NT> CommitteeElectionSendNode<CE>
     for SendNodeWrapperRef<'a, RQ, ABA, BCM, CE, NT>
 where
     RQ: SerMsg,
-    ABA: SerMsg,
-    BCM: SerMsg,
+    ABA: SerMsg + VerifiableABAMessage,
+    BCM: SerMsg + VerifiableRBCMessage<ProposedBatch<RQ>>,
     CE: SerMsg,
     NT: OrderProtocolSendNode<RQ, DumboSerialization<RQ, BCM, ABA, CE>>,
 {
-    fn send(&self, message: CE, target: NodeId, flush: bool) -> atlas_common::error::Result<()> {
-        todo!()
+    fn send(&self, message: CE, target: NodeId, flush: bool) -> Result<(), ()> {
+        self.log_ce(&message).map_err(|_| ())?;
+
+        self.inner_node
+            .send_ce::<NT>(&*self.inner, message, target, flush, false)
+            .map_err(|_| ())
     }
 
-    fn send_signed(
-        &self,
-        message: CE,
-        target: NodeId,
-        flush: bool,
-    ) -> atlas_common::error::Result<()> {
-        todo!()
+    fn send_signed(&self, message: CE, target: NodeId, flush: bool) -> Result<(), ()> {
+        self.log_ce(&message).map_err(|_| ())?;
+
+        self.inner_node
+            .send_ce::<NT>(&*self.inner, message, target, flush, true)
+            .map_err(|_| ())
     }
 
     fn broadcast<I>(&self, message: CE, targets: I) -> Result<(), Vec<NodeId>>
     where
         I: IntoIterator<Item = NodeId>,
     {
-        todo!()
+        if self.log_ce(&message).is_err() {
+            return Err(targets.into_iter().collect());
+        }
+
+        self.inner_node
+            .broadcast_ce::<I::IntoIter, NT>(&*self.inner, message, targets.into_iter())
     }
 }
 
@@ -166,12 +318,14 @@ impl<'a, RQ, ABA, CE, BCM, NT> ReliableBroadcastSendNode<BCM>
     for SendNodeWrapperRef<'a, RQ, ABA, BCM, CE, NT>
 where
     RQ: SerMsg,
-    BCM: SerMsg,
-    ABA: SerMsg,
+    BCM: SerMsg + VerifiableRBCMessage<ProposedBatch<RQ>>,
+    ABA: SerMsg + VerifiableABAMessage,
     CE: SerMsg,
     NT: OrderProtocolSendNode<RQ, DumboSerialization<RQ, BCM, ABA, CE>>,
 {
     fn send(&self, message: BCM, target: NodeId, flush: bool) -> atlas_common::error::Result<()> {
+        self.log_rbc(&message)?;
+
         self.inner_node
             .send_rbc::<NT>(&*self.inner, message, target, flush)
     }
@@ -182,6 +336,8 @@ where
         target: NodeId,
         flush: bool,
     ) -> atlas_common::error::Result<()> {
+        self.log_rbc(&message)?;
+
         self.inner_node
             .send_rbc_signed::<NT>(&*self.inner, message, target, flush)
     }
@@ -190,6 +346,10 @@ where
     where
         I: Iterator<Item = NodeId>,
     {
+        if self.log_rbc(&message).is_err() {
+            return Err(targets.collect());
+        }
+
         self.inner_node
             .broadcast_rbc::<I, NT>(&*self.inner, message, targets)
     }
@@ -198,11 +358,81 @@ where
     where
         I: Iterator<Item = NodeId>,
     {
+        if self.log_rbc(&message).is_err() {
+            return Err(targets.collect());
+        }
+
         self.inner_node
             .broadcast_rbc_signed::<I, NT>(&*self.inner, message, targets)
     }
 }
 
+impl<'a, RQ, ABA, CE, BCM, NT> ReliableBroadcastSendNode<IndexBroadcastMessage>
+    for IndexSendNodeRef<'a, RQ, ABA, BCM, CE, NT>
+where
+    RQ: SerMsg,
+    BCM: SerMsg + VerifiableRBCMessage<ProposedBatch<RQ>>,
+    ABA: SerMsg + VerifiableABAMessage,
+    CE: SerMsg,
+    NT: OrderProtocolSendNode<RQ, DumboSerialization<RQ, BCM, ABA, CE>>,
+{
+    fn send(
+        &self,
+        message: IndexBroadcastMessage,
+        target: NodeId,
+        flush: bool,
+    ) -> atlas_common::error::Result<()> {
+        self.0.log_index(&message)?;
+
+        self.0
+            .inner_node
+            .send_index::<NT>(&*self.0.inner, message, target, flush, false)
+    }
+
+    fn send_signed(
+        &self,
+        message: IndexBroadcastMessage,
+        target: NodeId,
+        flush: bool,
+    ) -> atlas_common::error::Result<()> {
+        self.0.log_index(&message)?;
+
+        self.0
+            .inner_node
+            .send_index::<NT>(&*self.0.inner, message, target, flush, true)
+    }
+
+    fn broadcast<I>(&self, message: IndexBroadcastMessage, targets: I) -> Result<(), Vec<NodeId>>
+    where
+        I: Iterator<Item = NodeId>,
+    {
+        if self.0.log_index(&message).is_err() {
+            return Err(targets.collect());
+        }
+
+        self.0
+            .inner_node
+            .broadcast_index::<I, NT>(&*self.0.inner, message, targets, false)
+    }
+
+    fn broadcast_signed<I>(
+        &self,
+        message: IndexBroadcastMessage,
+        targets: I,
+    ) -> Result<(), Vec<NodeId>>
+    where
+        I: Iterator<Item = NodeId>,
+    {
+        if self.0.log_index(&message).is_err() {
+            return Err(targets.collect());
+        }
+
+        self.0
+            .inner_node
+            .broadcast_index::<I, NT>(&*self.0.inner, message, targets, true)
+    }
+}
+
 impl<RA, ABA, BCM, CE, NT> SendNodeWrapperRef<'_, RA, ABA, BCM, CE, NT>
 where
     RA: SerMsg,
@@ -212,6 +442,47 @@ where
     pub(super) fn current_round(&self) -> SeqNo {
         self.inner_node.current_round
     }
+
+    pub(super) fn instance(&self) -> NodeId {
+        self.inner_node.instance
+    }
+}
+
+impl<RQ, ABA, BCM, CE, NT> SendNodeWrapperRef<'_, RQ, ABA, BCM, CE, NT>
+where
+    RQ: SerMsg,
+    ABA: SerMsg + VerifiableABAMessage,
+    BCM: SerMsg,
+    CE: SerMsg,
+{
+    fn log_rbc(&self, message: &BCM) -> atlas_common::error::Result<()> {
+        log_entry(self.wal, || WalEntry::ReliableBroadcast {
+            epoch: self.current_round(),
+            instance: self.instance(),
+            message: message.clone(),
+        })?;
+
+        Ok(())
+    }
+
+    fn log_index(&self, message: &IndexBroadcastMessage) -> atlas_common::error::Result<()> {
+        log_entry(self.wal, || WalEntry::IndexBroadcast {
+            epoch: self.current_round(),
+            instance: self.instance(),
+            message: message.clone(),
+        })?;
+
+        Ok(())
+    }
+
+    fn log_ce(&self, message: &CE) -> atlas_common::error::Result<()> {
+        log_entry(self.wal, || WalEntry::CommitteeElection {
+            epoch: self.current_round(),
+            message: message.clone(),
+        })?;
+
+        Ok(())
+    }
 }
 
 pub(super) struct SendNodeWrapper<RQ, ABA, BCM, CE, NT> {
@@ -222,8 +493,8 @@ pub(super) struct SendNodeWrapper<RQ, ABA, BCM, CE, NT> {
 impl<RQ, ABA, CE, BCM, NT> ReliableBroadcastSendNode<BCM> for SendNodeWrapper<RQ, ABA, BCM, CE, NT>
 where
     RQ: SerMsg,
-    BCM: SerMsg,
-    ABA: SerMsg,
+    BCM: SerMsg + VerifiableRBCMessage<ProposedBatch<RQ>>,
+    ABA: SerMsg + VerifiableABAMessage,
     CE: SerMsg,
     NT: OrderProtocolSendNode<RQ, DumboSerialization<RQ, BCM, ABA, CE>>,
 {
@@ -0,0 +1,195 @@
+use crate::rq_aggregator::rq_aggregator::{DecidedRequests, batch_digest};
+use atlas_common::collections::{HashMap, HashSet};
+use atlas_common::crypto::hash::{Context, Digest};
+use atlas_common::node_id::NodeId;
+use atlas_common::ordering::{Orderable, SeqNo};
+use atlas_communication::message::StoredMessage;
+use atlas_core::ordering_protocol::{BatchedDecision, ProtocolConsensusDecision};
+use getset::{CopyGetters, Getters};
+use std::collections::BTreeMap;
+
+/// How the requests of the batches decided in an epoch are ordered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OrderingStrategy {
+    /// Order by proposer index, and then by the order of the requests within each batch
+    #[default]
+    ProposerOrder,
+    /// Order by the hash of each request, seeded with the epoch number and the digests
+    /// of every decided batch, so that no proposer can predict the position of its requests.
+    ///
+    /// Requires threshold encryption. With plain batches, the last proposer can see the
+    /// other batches before proposing its own and grind it until its requests land where it
+    /// wants. With encryption, the other batches are only revealed after the agreement, so
+    /// a proposer can still choose its own batch but cannot predict the seed it produces.
+    HashShuffled,
+}
+
+/// The ordered output of a decided epoch.
+#[derive(Debug, Getters, CopyGetters)]
+pub struct EpochOutput<RQ> {
+    #[get_copy = "pub"]
+    epoch: SeqNo,
+    #[get = "pub"]
+    requests: Vec<StoredMessage<RQ>>,
+    /// A digest over the ordered requests, identical for every correct replica
+    #[get = "pub"]
+    digest: Digest,
+}
+
+impl<RQ> EpochOutput<RQ> {
+    /// Map this output into the decision handed to the Atlas replica
+    pub fn into_protocol_decision(self) -> ProtocolConsensusDecision<RQ> {
+        let batch = BatchedDecision::new(self.epoch, self.requests, None);
+
+        ProtocolConsensusDecision::new(self.epoch, batch, Some(self.digest))
+    }
+
+    pub fn into_inner(self) -> (SeqNo, Vec<StoredMessage<RQ>>, Digest) {
+        (self.epoch, self.requests, self.digest)
+    }
+}
+
+/// Turns the batches selected by the agreement of each epoch into a single request sequence.
+///
+/// Every correct replica feeds the same batches in the same epoch order, so the state kept here
+/// (the sequence numbers decided for each client) evolves identically across replicas.
+#[derive(Debug)]
+pub(super) struct EpochOrdering {
+    strategy: OrderingStrategy,
+    // The sequence numbers decided for each client, in any previous epoch
+    decided: HashMap<NodeId, DecidedRequests>,
+}
+
+impl EpochOrdering {
+    pub(super) fn new(strategy: OrderingStrategy) -> Self {
+        Self {
+            strategy,
+            decided: HashMap::default(),
+        }
+    }
+
+    /// Order the batches decided in the given epoch.
+    ///
+    /// Requests that appear in several batches are only kept the first time they appear
+    /// in the final order, and requests which were already decided in earlier epochs are dropped.
+    pub(super) fn order_epoch<RQ>(
+        &mut self,
+        epoch: SeqNo,
+        mut batches: Vec<(NodeId, Vec<StoredMessage<RQ>>)>,
+    ) -> EpochOutput<RQ>
+    where
+        RQ: Orderable,
+    {
+        batches.sort_by_key(|(proposer, _)| *proposer);
+
+        let mut requests = match self.strategy {
+            OrderingStrategy::ProposerOrder => batches
+                .into_iter()
+                .flat_map(|(_, requests)| requests)
+                .collect::<Vec<_>>(),
+            OrderingStrategy::HashShuffled => {
+                let seed = shuffle_seed(epoch, &batches);
+
+                let mut keyed = batches
+                    .into_iter()
+                    .flat_map(|(_, requests)| requests)
+                    .enumerate()
+                    .map(|(position, request)| {
+                        (shuffle_key(&seed, request.header().digest()), position, request)
+                    })
+                    .collect::<Vec<_>>();
+
+                // Ties on the key are broken by the proposer order, which is itself deterministic
+                keyed.sort_by(|(key_a, pos_a, _), (key_b, pos_b, _)| {
+                    key_a.as_ref().cmp(key_b.as_ref()).then(pos_a.cmp(pos_b))
+                });
+
+                keyed.into_iter().map(|(_, _, request)| request).collect()
+            }
+        };
+
+        let mut seen = HashSet::default();
+
+        requests.retain(|request| {
+            let key = (request.header().from(), request.message().sequence_number());
+
+            let already_decided = self
+                .decided
+                .get(&key.0)
+                .is_some_and(|decided| decided.contains(key.1));
+
+            !already_decided && seen.insert(key)
+        });
+
+        self.register_output(&requests);
+
+        let digest = batch_digest(&requests);
+
+        EpochOutput {
+            epoch,
+            requests,
+            digest,
+        }
+    }
+
+    /// Register the output of an epoch which was ordered elsewhere, such as an epoch
+    /// installed through catch up, so that later epochs are deduplicated against it
+    pub(super) fn register_output<RQ>(&mut self, requests: &[StoredMessage<RQ>])
+    where
+        RQ: Orderable,
+    {
+        requests.iter().for_each(|request| {
+            let client = request.header().from();
+            let seq_no = request.message().sequence_number();
+
+            self.decided.entry(client).or_default().insert(seq_no);
+        });
+    }
+
+    /// The sequence numbers decided for each client, ordered by client
+    pub(super) fn decided(&self) -> BTreeMap<NodeId, DecidedRequests> {
+        self.decided
+            .iter()
+            .map(|(client, decided)| (*client, decided.clone()))
+            .collect()
+    }
+
+    /// Replace the sequence numbers decided for each client with the ones recorded in a checkpoint,
+    /// as the epochs which decided them are no longer available
+    pub(super) fn restore(&mut self, decided: &BTreeMap<NodeId, DecidedRequests>) {
+        self.decided = decided
+            .iter()
+            .map(|(client, decided)| (*client, decided.clone()))
+            .collect();
+    }
+}
+
+/// The seed of the shuffle of an epoch, which depends on every decided batch
+/// and is therefore only known once the agreement has decided (and the batches are revealed)
+fn shuffle_seed<RQ>(epoch: SeqNo, batches: &[(NodeId, Vec<StoredMessage<RQ>>)]) -> Digest {
+    let mut context = Context::new();
+
+    let serialized_epoch = bincode::serde::encode_to_vec(epoch, bincode::config::standard())
+        .expect("Failed to serialize epoch number");
+
+    context.update(&serialized_epoch);
+
+    batches.iter().for_each(|(proposer, requests)| {
+        context.update(&proposer.0.to_le_bytes()[..]);
+
+        requests
+            .iter()
+            .for_each(|request| context.update(request.header().digest().as_ref()));
+    });
+
+    context.finish()
+}
+
+fn shuffle_key(seed: &Digest, request_digest: &Digest) -> Digest {
+    let mut context = Context::new();
+
+    context.update(seed.as_ref());
+    context.update(request_digest.as_ref());
+
+    context.finish()
+}
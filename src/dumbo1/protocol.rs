@@ -1,20 +1,51 @@
-use crate::aba::ABAProtocol;
+use crate::aba::{ABAProtocol, VerifiableABAMessage};
 use crate::committee_election::CommitteeElectionProtocol;
-use crate::dumbo1::epoch::DumboRound;
-use crate::dumbo1::message::DumboSerialization;
+use crate::dumbo1::batch::{ProposedBatch, RequestVerifier};
+use crate::dumbo1::buffer::{BufferResult, BufferStatistics, FutureEpochBuffer};
+use crate::dumbo1::catch_up::{CatchUp, CatchUpMessage, DecidedEpoch};
+use crate::dumbo1::certificate::DecisionCertificate;
+use crate::dumbo1::checkpoint::{
+    CheckpointMessage, CheckpointResult, CheckpointState, Checkpoints, StableCheckpoint,
+};
+use crate::dumbo1::config::DumboConfig;
+use crate::dumbo1::epoch::{DumboRound, EpochResult};
+use crate::dumbo1::message::{
+    DumboMessage, DumboMessageType, DumboSerialization, DumboVerificationError,
+};
+use crate::dumbo1::ordering::{EpochOrdering, OrderingStrategy};
+use crate::dumbo1::reconfiguration::{Membership, MembershipKeys, Reconfiguration, Reconfigurations};
+use crate::dumbo1::wal::{DumboPWal, DumboWal, RecoveredState, WalEntry, WalError, log_entry};
+use crate::metrics::{
+    BATCH_SIZE_ID, EPOCH_LATENCY_ID, EPOCHS_DECIDED_ID, FUTURE_MESSAGES_DEPTH_ID,
+    NON_MEMBER_MESSAGES_ID, PENDING_DECISIONS_DEPTH_ID, REQUEST_QUEUE_DEPTH_ID,
+    REQUESTS_DECIDED_ID,
+};
 use crate::quorum_info::quorum_info::QuorumInfo;
-use crate::rbc::ReliableBroadcast;
+use crate::rbc::{ReliableBroadcast, VerifiableRBCMessage};
+use crate::rq_aggregator::rq_aggregator::{
+    DuplicateStatistics, RequestAggregator, RequestAggregatorError,
+};
+use anyhow::anyhow;
+use atlas_common::crypto::threshold_crypto::PublicKeySet;
 use atlas_common::error::Result;
+use atlas_common::maybe_vec::MaybeVec;
+use atlas_common::node_id::NodeId;
 use atlas_common::ordering::{Orderable, SeqNo};
 use atlas_common::serialization_helper::SerMsg;
+use atlas_communication::message::StoredMessage;
+use atlas_core::ordering_protocol::networking::OrderProtocolSendNode;
 use atlas_core::ordering_protocol::networking::serialize::OrderingProtocolMessage;
 use atlas_core::ordering_protocol::{
-    OPExResult, OPResult, OrderProtocolTolerance, OrderingProtocol, ShareableConsensusMessage,
+    Decision, DecisionsAhead, OPExResult, OPResult, OrderProtocolTolerance, OrderingProtocol,
+    ShareableConsensusMessage,
 };
 use atlas_core::timeouts::timeout::{ModTimeout, TimeoutableMod};
+use atlas_metrics::metrics::{metric_duration, metric_increment, metric_store_count};
+use either::Either;
 use getset::{Getters, Setters};
 use std::collections::VecDeque;
-use std::sync::{Arc, LazyLock};
+use std::sync::{Arc, LazyLock, Mutex};
+use tracing::{debug, debug_span, info, warn};
 
 /// The name of the Dumbo1 module.
 /// Used for logging and metrics.
@@ -22,48 +53,934 @@ const DUMBO1_MOD_NAME: LazyLock<Arc<str>> = LazyLock::new(|| Arc::from("Dumbo1")
 
 pub type DumboPSerialization<
     RQ,
-    R: ReliableBroadcast<RQ>,
+    R: ReliableBroadcast<ProposedBatch<RQ>>,
     A: ABAProtocol,
     CE: CommitteeElectionProtocol,
 > = DumboSerialization<RQ, R::ReliableBroadcastMessage, A::AsyncBinaryMessage, CE::Message>;
 
 pub(super) type DumboPMessage<
     RQ: 'static,
-    R: ReliableBroadcast<RQ>,
+    R: ReliableBroadcast<ProposedBatch<RQ>>,
     A: ABAProtocol,
     CE: CommitteeElectionProtocol,
 > = <DumboPSerialization<RQ, R, A, CE> as OrderingProtocolMessage<RQ>>::ProtocolMessage;
 
+/// A decision of the Dumbo protocol, as delivered to the Atlas replica.
+pub(super) type DumboDecision<
+    RQ: 'static,
+    R: ReliableBroadcast<ProposedBatch<RQ>>,
+    A: ABAProtocol,
+    CE: CommitteeElectionProtocol,
+> = Decision<
+    <DumboPSerialization<RQ, R, A, CE> as OrderingProtocolMessage<RQ>>::DecisionMetadata,
+    <DumboPSerialization<RQ, R, A, CE> as OrderingProtocolMessage<RQ>>::DecisionAdditionalInfo,
+    DumboPMessage<RQ, R, A, CE>,
+    RQ,
+>;
+
 /// An instance of the Dumbo protocol.
 /// Holds the state of the protocol for a specific epoch.
 /// Tracks the state of each node in the protocol.
 #[derive(Debug, Getters, Setters)]
-pub struct Dumbo<CE, RQ, R, A> {
-    // The current epoch number.
+pub struct Dumbo<CE, RQ, R, A, NT>
+where
+    RQ: SerMsg,
+    R: ReliableBroadcast<ProposedBatch<RQ>>,
+    A: ABAProtocol,
+    CE: CommitteeElectionProtocol,
+{
+    // Our own id
+    node_id: NodeId,
+
+    // The current epoch number, which is the lowest epoch that has not yet been decided.
     epoch_num: SeqNo,
 
     // The current quorum information
     quorum_info: QuorumInfo,
 
-    // The rounds of the dumbo protocol.
+    // The configuration of the protocol
+    config: DumboConfig,
+
+    // The rounds of the dumbo protocol which are currently running, in epoch order.
+    // The front round is always the one for `epoch_num`.
     rounds: VecDeque<DumboRound<CE, RQ, R, A>>,
+
+    // The ordering of the batches decided in each epoch
+    ordering: EpochOrdering,
+
+    // The requests waiting to be proposed by this node
+    request_aggregator: RequestAggregator<RQ>,
+
+    // Messages received for epochs which we have not yet started
+    future_messages:
+        FutureEpochBuffer<ShareableConsensusMessage<RQ, DumboPSerialization<RQ, R, A, CE>>>,
+
+    // The catch up state, to serve lagging replicas and to catch up when we are lagging
+    catch_up: CatchUp<RQ>,
+
+    // The checkpoints, which bound the decided epochs we keep
+    checkpoints: Checkpoints,
+
+    // The membership of each epoch, along with the reconfigurations which have been decided
+    reconfigurations: Reconfigurations,
+
+    // Decisions which have been reached, but not yet delivered to the replica
+    pending_decisions: VecDeque<DumboDecision<RQ, R, A, CE>>,
+
+    // Whether the replica is currently executing the decisions we deliver
+    is_executing: bool,
+
+    // The write ahead log, when the protocol state is persisted
+    wal: Option<DumboPWal<RQ, R, A, CE>>,
+
+    // Verifies the requests of encrypted batches once they are revealed
+    request_verifier: Arc<dyn RequestVerifier<RQ>>,
+
+    // The network node used to send messages
+    node: Arc<NT>,
 }
 
-impl<CE, RQ, R, A> Dumbo<CE, RQ, R, A> {
-    pub fn new(quorum_info: QuorumInfo) -> Self {
-        Self {
+impl<CE, RQ, R, A, NT> Dumbo<CE, RQ, R, A, NT>
+where
+    RQ: SerMsg + Orderable,
+    R: ReliableBroadcast<ProposedBatch<RQ>>,
+    A: ABAProtocol,
+    CE: CommitteeElectionProtocol,
+    NT: OrderProtocolSendNode<RQ, DumboPSerialization<RQ, R, A, CE>>,
+{
+    /// Create a new instance of the protocol.
+    ///
+    /// When a write ahead log is configured, the state it holds is recovered first, so a replica
+    /// which crashed resumes the epochs it was running where it left them.
+    ///
+    /// The request verifier checks the requests of encrypted batches once they are revealed,
+    /// usually a [`crate::dumbo1::batch::HelperRequestVerifier`].
+    pub fn new(
+        node_id: NodeId,
+        quorum_info: QuorumInfo,
+        config: DumboConfig,
+        request_verifier: Arc<dyn RequestVerifier<RQ>>,
+        node: Arc<NT>,
+    ) -> Result<Self> {
+        if config.reconfiguration_delay() < config.pipeline_depth() {
+            return Err(anyhow!(
+                "The reconfiguration delay ({}) must be at least the pipeline depth ({})",
+                config.reconfiguration_delay(),
+                config.pipeline_depth()
+            ));
+        }
+
+        if config.ordering_strategy() == OrderingStrategy::HashShuffled
+            && config.threshold_encryption().is_none()
+        {
+            return Err(anyhow!(
+                "The hash shuffled ordering requires threshold encryption, or proposers can grind its seed"
+            ));
+        }
+
+        if config.certificate_keys().is_none() || config.coin_key_shares().is_empty() {
+            return Err(anyhow!(
+                "The agreements require the key set of the quorum and the key shares dealt to us to flip their coin"
+            ));
+        }
+
+        let (wal, recovered) = match config.wal_path() {
+            Some(path) => {
+                let (wal, recovered) = DumboWal::open(path)?;
+
+                (Some(Arc::new(Mutex::new(wal))), Some(recovered))
+            }
+            None => (None, None),
+        };
+
+        let selection_strategy = config
+            .request_aggregator()
+            .selection_strategy()
+            .for_members(node_id, quorum_info.quorum_members());
+
+        let mut dumbo = Self {
+            node_id,
             epoch_num: SeqNo::ONE,
+            ordering: EpochOrdering::new(config.ordering_strategy()),
+            request_aggregator: RequestAggregator::new(
+                config
+                    .request_aggregator()
+                    .clone()
+                    .with_selection_strategy(selection_strategy),
+            ),
+            future_messages: FutureEpochBuffer::new(
+                config.future_epoch_window(),
+                config.max_buffered_per_epoch(),
+            ),
+            catch_up: CatchUp::new(
+                quorum_info.clone(),
+                config.certificate_keys().clone(),
+                config.catch_up_history(),
+            ),
+            checkpoints: Checkpoints::new(quorum_info.clone(), config.checkpoint_period()),
+            reconfigurations: Reconfigurations::new(
+                Membership::new(
+                    quorum_info.clone(),
+                    config.threshold_encryption().is_some(),
+                    MembershipKeys::new(
+                        config.threshold_encryption().clone(),
+                        config.certificate_keys().clone(),
+                        config.coin_key_shares().clone(),
+                    ),
+                ),
+                config.reconfiguration_delay(),
+            ),
             quorum_info,
+            config,
             rounds: VecDeque::new(),
+            pending_decisions: VecDeque::new(),
+            is_executing: true,
+            wal,
+            request_verifier,
+            node,
+        };
+
+        if let Some(recovered) = recovered {
+            dumbo.recover(recovered)?;
+        }
+
+        dumbo.fill_pipeline()?;
+
+        Ok(dumbo)
+    }
+
+    /// Restore the state recovered from the write ahead log.
+    ///
+    /// The decided epochs are not delivered again, as the replica keeps its own log of
+    /// the decisions it has executed, but they are needed to resume from the right epoch, to keep
+    /// deduplicating their requests, to schedule the reconfigurations decided in them and to serve
+    /// them to lagging peers.
+    /// The epochs which were running are then rebuilt, in epoch order.
+    fn recover(
+        &mut self,
+        recovered: RecoveredState<
+            RQ,
+            R::ReliableBroadcastMessage,
+            A::AsyncBinaryMessage,
+            CE::Message,
+        >,
+    ) -> Result<()> {
+        let RecoveredState {
+            checkpoint,
+            decided,
+            mut running,
+        } = recovered;
+
+        if let Some(checkpoint) = checkpoint {
+            // The decided epochs the checkpoint covers were discarded from the log along with
+            // their requests and reconfigurations, so they are taken from the checkpoint instead
+            self.epoch_num = checkpoint.epoch().next();
+            self.restore_state(checkpoint.state());
+            self.checkpoints.restore(checkpoint);
+        }
+
+        for decided in decided {
+            self.ordering.register_output(decided.requests());
+            self.request_aggregator.register_decided(decided.requests());
+
+            let epoch = decided.sequence_number();
+
+            self.reconfigurations
+                .observe_decided(epoch, decided.certificate().reconfigurations());
+
+            // We may have stopped before the checkpoint of this epoch became stable
+            if let Some(vote) = self.checkpoints.record_decided(
+                epoch,
+                decided.certificate().output_digest(),
+                || checkpoint_state(&self.ordering, &self.reconfigurations),
+            ) {
+                self.broadcast_checkpoint(vote);
+            }
+
+            self.epoch_num = epoch.next();
+            self.catch_up.record_decided(decided);
+        }
+
+        self.apply_reconfiguration();
+
+        let mut resumed = 0;
+
+        loop {
+            let epoch = self.next_epoch();
+
+            let Some(recovered) = running.remove(&epoch) else {
+                break;
+            };
+
+            let proposed = recovered.proposal.is_some();
+
+            let mut round = self.new_round(epoch);
+
+            round.recover(recovered, &self.node);
+
+            if !proposed {
+                round.propose(self.take_proposal(epoch)?, &self.node)?;
+            }
+
+            self.rounds.push_back(round);
+            resumed += 1;
+        }
+
+        info!("Recovered from the write ahead log at epoch {:?}, resuming {resumed} epochs", self.epoch_num);
+
+        Ok(())
+    }
+
+    /// Queue client requests to be proposed by this node in the following epochs.
+    ///
+    /// Returns the requests that could not be queued because the aggregator is full,
+    /// so that the caller can retry them later.
+    pub fn queue_requests<I>(&mut self, requests: I) -> Vec<StoredMessage<RQ>>
+    where
+        I: IntoIterator<Item = StoredMessage<RQ>>,
+    {
+        requests
+            .into_iter()
+            .filter_map(|request| match self.request_aggregator.push_request(request) {
+                Ok(_) => None,
+                Err(RequestAggregatorError::QueueFull(request)) => Some(request),
+            })
+            .collect()
+    }
+
+    /// The latest checkpoint agreed on by the quorum.
+    ///
+    /// The decided epochs it covers have been discarded, so a replica lagging behind
+    /// it can only recover through state transfer, which hands it over to
+    /// [`Self::install_checkpoint`] before resuming with [`OrderingProtocol::install_seq_no`].
+    pub fn stable_checkpoint(&self) -> Option<&StableCheckpoint> {
+        self.checkpoints.last_stable()
+    }
+
+    /// Install a stable checkpoint obtained through state transfer.
+    ///
+    /// The state it carries takes effect once [`OrderingProtocol::install_seq_no`] resumes us
+    /// at the epoch following it, so the requests decided in the epochs we skipped keep being
+    /// deduplicated, and the reconfigurations decided in them keep being scheduled.
+    ///
+    /// Fails when the checkpoint is not proven by the membership which ran its epoch.
+    pub fn install_checkpoint(&mut self, checkpoint: StableCheckpoint) -> Result<()> {
+        if checkpoint.epoch() < self.epoch_num
+            || self
+                .checkpoints
+                .last_stable()
+                .is_some_and(|stable| stable.epoch() >= checkpoint.epoch())
+        {
+            // We already hold the state of its epoch
+            return Ok(());
+        }
+
+        checkpoint.verify(
+            self.reconfigurations
+                .membership_for(checkpoint.epoch())
+                .quorum_info(),
+        )?;
+
+        self.checkpoints.restore(checkpoint);
+
+        Ok(())
+    }
+
+    /// Request a reconfiguration of the membership of the quorum, along with the keys
+    /// dealt to the new membership.
+    ///
+    /// The reconfiguration is proposed along with our batches until it is decided, which
+    /// requires `f + 1` members to propose it in batches included in the same epoch.
+    /// It then takes effect [`DumboConfig::reconfiguration_delay`] epochs later.
+    /// Reconfigurations are only observed in the epochs we agree on ourselves, not in the
+    /// epochs installed through catch up.
+    ///
+    /// Fails when the new membership drops the threshold encryption required by the hash shuffled
+    /// ordering, or when the keys lack its threshold encryption scheme or the keys the agreements
+    /// of the new membership flip their coin with.
+    pub fn request_reconfiguration(
+        &mut self,
+        reconfiguration: Reconfiguration,
+        keys: MembershipKeys,
+    ) -> Result<()> {
+        if keys.coin_keys().is_none() {
+            return Err(anyhow!(
+                "The new membership requires its key set and the key shares dealt to us to flip the coin of its agreements"
+            ));
+        }
+
+        if self.config.ordering_strategy() == OrderingStrategy::HashShuffled
+            && !reconfiguration.encrypted()
+        {
+            return Err(anyhow!(
+                "The hash shuffled ordering requires the new membership to use threshold encryption"
+            ));
+        }
+
+        if reconfiguration.encrypted() && keys.threshold_encryption().is_none() {
+            return Err(anyhow!(
+                "The new membership encrypts its batches, which requires its threshold encryption scheme"
+            ));
+        }
+
+        self.reconfigurations.request(reconfiguration, keys);
+
+        Ok(())
+    }
+
+    /// The epoch the decided reconfiguration takes effect in, if one is pending
+    pub fn scheduled_reconfiguration(&self) -> Option<SeqNo> {
+        self.reconfigurations.scheduled_epoch()
+    }
+
+    /// The counters of the messages received for epochs we had not yet started
+    pub fn future_epoch_statistics(&self) -> BufferStatistics {
+        self.future_messages.statistics()
+    }
+
+    /// How many of the requests decided in the last epoch were included more than once
+    pub fn duplicate_statistics(&self) -> Option<&DuplicateStatistics> {
+        self.request_aggregator.last_epoch_stats()
+    }
+
+    /// Create the round for the given epoch, propose our batch in it and replay
+    /// the messages we had buffered for it.
+    ///
+    /// A round which gets decided by the replayed messages is collected in the next poll.
+    fn start_round(&mut self, epoch: SeqNo) -> Result<()> {
+        let mut round = self.new_round(epoch);
+
+        round.propose(self.take_proposal(epoch)?, &self.node)?;
+
+        for message in self.future_messages.take(epoch) {
+            if let Err(err) = round.process_message(message, &self.node) {
+                warn!("Failed to replay buffered message in epoch {epoch:?}: {err:?}");
+            }
+        }
+
+        self.rounds.push_back(round);
+
+        Ok(())
+    }
+
+    fn new_round(&self, epoch: SeqNo) -> DumboRound<CE, RQ, R, A> {
+        DumboRound::new(
+            epoch,
+            self.node_id,
+            self.reconfigurations.membership_for(epoch),
+            self.config.max_inbox_messages(),
+            self.request_verifier.clone(),
+            self.wal.clone(),
+        )
+    }
+
+    /// Cut the batch we propose in the given epoch, encrypting it if the membership running
+    /// the epoch encrypts its batches, and attaching the reconfiguration we have requested, if any
+    fn take_proposal(&mut self, epoch: SeqNo) -> Result<ProposedBatch<RQ>> {
+        let (requests, _) = self.request_aggregator.take_batch().into_inner();
+
+        metric_store_count(BATCH_SIZE_ID, requests.len());
+
+        let membership = self.reconfigurations.membership_for(epoch);
+
+        let batch = if membership.encrypted() {
+            let scheme = membership.keys().threshold_encryption().as_ref().ok_or_else(|| {
+                anyhow!(
+                    "The membership of epoch {epoch:?} encrypts its batches, but we were not handed its threshold encryption scheme"
+                )
+            })?;
+
+            ProposedBatch::encrypt(requests, scheme.as_ref())?
+        } else {
+            ProposedBatch::Plain(requests)
+        };
+
+        Ok(match self.reconfigurations.to_propose() {
+            Some(reconfiguration) => batch.with_reconfiguration(reconfiguration.clone()),
+            None => batch,
+        })
+    }
+
+    /// Apply the scheduled reconfiguration once the current epoch has reached it.
+    /// Every round from then on is started by the new membership.
+    fn apply_reconfiguration(&mut self) {
+        let Some(membership) = self.reconfigurations.advance_to(self.epoch_num) else {
+            return;
+        };
+
+        let quorum_info = membership.quorum_info().clone();
+        let certificate_keys = membership.keys().certificate_keys().clone();
+
+        self.enter_membership(quorum_info, certificate_keys);
+    }
+
+    /// Restore the state recorded in a checkpoint, as the decided epochs it covers
+    /// are not available to rebuild it from.
+    /// A scheduled reconfiguration takes effect once we move to its epoch.
+    fn restore_state(&mut self, state: &CheckpointState) {
+        self.ordering.restore(state.decided());
+        self.request_aggregator.restore_decided(state.decided());
+
+        if let Some(membership) = self.reconfigurations.restore(
+            state.quorum_info().clone(),
+            state.encrypted(),
+            state.scheduled().clone(),
+        ) {
+            let quorum_info = membership.quorum_info().clone();
+            let certificate_keys = membership.keys().certificate_keys().clone();
+
+            self.enter_membership(quorum_info, certificate_keys);
+        }
+    }
+
+    /// Run the epochs from now on with the given membership
+    fn enter_membership(&mut self, quorum_info: QuorumInfo, certificate_keys: Option<PublicKeySet>) {
+        info!(
+            "Reconfigured into {:?} at epoch {:?}",
+            quorum_info.quorum_members(),
+            self.epoch_num
+        );
+
+        // The partitions of the requests follow the new membership
+        let selection_strategy = self
+            .request_aggregator
+            .config()
+            .selection_strategy()
+            .for_members(self.node_id, quorum_info.quorum_members());
+
+        self.request_aggregator.set_selection_strategy(selection_strategy);
+
+        self.catch_up.set_membership(quorum_info.clone(), certificate_keys);
+        self.checkpoints.set_quorum_info(quorum_info.clone());
+        self.quorum_info = quorum_info;
+    }
+
+    /// The epoch of the next round to be started
+    fn next_epoch(&self) -> SeqNo {
+        self.rounds
+            .back()
+            .map(|round| round.epoch_num().next())
+            .unwrap_or(self.epoch_num)
+    }
+
+    /// Whether we can start rounds ahead of the current epoch.
+    ///
+    /// Look-ahead is halted while the replica is not executing, while we are catching up, or while the decisions
+    /// which have not yet been delivered already fill the pipeline, so that a slow replica
+    /// does not keep accumulating decided epochs in memory.
+    fn can_look_ahead(&self) -> bool {
+        self.is_executing
+            && !self.catch_up.is_catching_up()
+            && self.pending_decisions.len() < self.config.pipeline_depth()
+    }
+
+    /// Start rounds until the pipeline is full, or look-ahead is no longer allowed.
+    /// The round of the current epoch is always running.
+    fn fill_pipeline(&mut self) -> Result<()> {
+        while self.rounds.is_empty()
+            || (self.rounds.len() < self.config.pipeline_depth() && self.can_look_ahead())
+        {
+            self.start_round(self.next_epoch())?;
         }
+
+        Ok(())
+    }
+
+    /// Get the round corresponding to the given epoch, if we are currently running it
+    fn round_for(&mut self, epoch: SeqNo) -> Option<&mut DumboRound<CE, RQ, R, A>> {
+        match epoch.index(self.epoch_num) {
+            Either::Right(index) => self.rounds.get_mut(index),
+            Either::Left(_) => None,
+        }
+    }
+
+    /// Collect the decisions of all of the rounds at the front of the queue which have been decided,
+    /// advancing the current epoch and starting the following rounds.
+    ///
+    /// Later rounds may decide before the front one, but they are only collected
+    /// once every round before them has been, so decisions are delivered in epoch order.
+    fn collect_decisions(&mut self) -> Result<()> {
+        while self.rounds.front().is_some_and(|round| round.is_decided()) {
+            let mut round = self.rounds.pop_front().unwrap();
+
+            let batches = round.take_decided().unwrap();
+
+            // Registered before ordering, so the duplicate statistics also count
+            // the requests the ordering drops for being included more than once
+            self.request_aggregator
+                .register_decided_epoch(batches.iter().map(|(_, batch)| batch.as_slice()));
+
+            let output = self.ordering.order_epoch(round.epoch_num(), batches);
+
+            let (epoch, requests, digest) = output.into_inner();
+
+            let certificate = DecisionCertificate::new(
+                epoch,
+                round
+                    .committee()
+                    .cloned()
+                    .expect("A decided round must have elected its committee"),
+                round
+                    .agreement_outcome()
+                    .cloned()
+                    .expect("A decided round must have an agreement outcome"),
+                digest,
+            )
+            .with_reconfigurations(round.reconfigurations().to_vec());
+
+            metric_duration(EPOCH_LATENCY_ID, round.started_at().elapsed());
+
+            self.deliver_epoch(DecidedEpoch::new(certificate, requests))?;
+        }
+
+        self.catch_up.discard_before(self.epoch_num);
+        self.future_messages.discard_finalized(self.epoch_num);
+
+        self.fill_pipeline()
+    }
+
+    /// Hand a decided epoch to the replica, and advance to the following epoch.
+    ///
+    /// The reconfigurations are taken from the certificate, so epochs we did not decide
+    /// ourselves schedule them as well.
+    fn deliver_epoch(&mut self, decided: DecidedEpoch<RQ>) -> Result<()> {
+        log_entry(self.wal.as_ref(), || WalEntry::Decided(decided.clone()))?;
+
+        self.reconfigurations.observe_decided(
+            decided.sequence_number(),
+            decided.certificate().reconfigurations(),
+        );
+
+        self.catch_up.record_decided(decided.clone());
+
+        let epoch = decided.sequence_number();
+
+        if let Some(vote) = self.checkpoints.record_decided(
+            epoch,
+            decided.certificate().output_digest(),
+            || checkpoint_state(&self.ordering, &self.reconfigurations),
+        ) {
+            self.broadcast_checkpoint(vote);
+
+            // The votes of the rest of the quorum may have arrived before we decided
+            if let Some(stable) = self.checkpoints.check_stable(epoch) {
+                self.discard_up_to(&stable)?;
+            }
+        }
+
+        metric_increment(EPOCHS_DECIDED_ID, Some(1));
+        metric_increment(REQUESTS_DECIDED_ID, Some(decided.requests().len() as u64));
+
+        let (certificate, decision) = decided.into_protocol_decision();
+
+        self.pending_decisions.push_back(Decision::full_decision_info(
+            epoch,
+            certificate,
+            Vec::new(),
+            decision,
+        ));
+
+        self.epoch_num = epoch.next();
+
+        debug!(epoch = ?epoch, "Epoch delivered");
+
+        self.apply_reconfiguration();
+
+        Ok(())
+    }
+
+    fn broadcast_checkpoint(&self, vote: CheckpointMessage) {
+        let message = DumboMessage::new(vote.epoch(), DumboMessageType::Checkpoint(vote));
+
+        if let Err(failed) = self
+            .node
+            .broadcast_signed(message, self.quorum_info.quorum_members().iter().cloned())
+        {
+            warn!("Failed to broadcast checkpoint to {failed:?}");
+        }
+    }
+
+    fn process_checkpoint(
+        &mut self,
+        message: ShareableConsensusMessage<RQ, DumboPSerialization<RQ, R, A, CE>>,
+    ) -> Result<Option<EpochResult>> {
+        let DumboMessageType::Checkpoint(checkpoint) = message.message().message_type() else {
+            unreachable!()
+        };
+
+        let _span = debug_span!("checkpoint", epoch = ?checkpoint.epoch()).entered();
+
+        let checkpoint = StoredMessage::new(message.header().clone(), checkpoint.clone());
+
+        match self.checkpoints.process_message(checkpoint, self.epoch_num) {
+            CheckpointResult::MessageIgnored => Ok(Some(EpochResult::MessageIgnored)),
+            CheckpointResult::MessageProcessed => Ok(Some(EpochResult::MessageProcessed)),
+            CheckpointResult::Lagging(epoch) => {
+                if let Some((from, to)) = self.catch_up.begin(self.epoch_num, epoch.next()) {
+                    self.request_catch_up(from, to);
+                }
+
+                Ok(Some(EpochResult::MessageProcessed))
+            }
+            CheckpointResult::Stable(stable) => {
+                self.discard_up_to(&stable)?;
+
+                Ok(Some(EpochResult::MessageProcessed))
+            }
+        }
+    }
+
+    /// Discard the decided epochs covered by a stable checkpoint, and compact the write ahead log
+    fn discard_up_to(&mut self, checkpoint: &StableCheckpoint) -> Result<()> {
+        self.catch_up.discard_history_up_to(checkpoint.epoch());
+
+        if let Some(wal) = &self.wal {
+            wal.lock()
+                .map_err(|_| WalError::Poisoned)?
+                .compact(checkpoint)?;
+        }
+
+        debug!("Discarded the state up to the checkpoint at {:?}", checkpoint.epoch());
+
+        Ok(())
+    }
+
+    /// Install epochs obtained through catch up, which we did not decide ourselves.
+    ///
+    /// The rounds we were running for those epochs are abandoned, and we resume
+    /// participating from the epoch following the last installed one.
+    fn install_caught_up(&mut self, epochs: Vec<DecidedEpoch<RQ>>) -> Result<()> {
+        for decided in epochs {
+            self.ordering.register_output(decided.requests());
+            self.request_aggregator.register_decided(decided.requests());
+
+            self.deliver_epoch(decided)?;
+        }
+
+        self.install_seq_no(self.epoch_num)
+    }
+
+    fn process_catch_up(
+        &mut self,
+        message: ShareableConsensusMessage<RQ, DumboPSerialization<RQ, R, A, CE>>,
+    ) -> Result<Option<EpochResult>> {
+        let sender = message.header().from();
+
+        let _span = debug_span!("catch_up", ?sender).entered();
+
+        let DumboMessageType::CatchUp(catch_up) = message.message().message_type() else {
+            unreachable!()
+        };
+
+        match catch_up {
+            CatchUpMessage::Request { from, to } => {
+                let epochs = self.catch_up.serve(*from, *to);
+
+                let response = DumboMessage::new(
+                    self.epoch_num,
+                    DumboMessageType::CatchUp(CatchUpMessage::Response(epochs)),
+                );
+
+                // The request was sent to the whole quorum, so the requester can still gather
+                // its answers from the other members
+                if let Err(err) = self.node.send(response, sender, true) {
+                    warn!("Failed to answer the catch up request of {sender:?}: {err:?}");
+                }
+
+                Ok(Some(EpochResult::MessageProcessed))
+            }
+            CatchUpMessage::Response(epochs) => {
+                let ready = self
+                    .catch_up
+                    .process_response(sender, epochs.clone(), self.epoch_num);
+
+                if ready.is_empty() {
+                    return Ok(Some(EpochResult::MessageProcessed));
+                }
+
+                self.install_caught_up(ready)?;
+
+                Ok(Some(EpochResult::Finalized))
+            }
+        }
+    }
+
+    /// Request the decided epochs in `[from, to)` from the rest of the quorum
+    fn request_catch_up(&self, from: SeqNo, to: SeqNo) {
+        let request = DumboMessage::new(
+            self.epoch_num,
+            DumboMessageType::CatchUp(CatchUpMessage::Request { from, to }),
+        );
+
+        let targets = self
+            .quorum_info
+            .quorum_members()
+            .iter()
+            .filter(|member| **member != self.node_id)
+            .cloned();
+
+        if let Err(failed) = self.node.broadcast(request, targets) {
+            warn!("Failed to send catch up request to {failed:?}");
+        }
+    }
+
+    /// Record the depths of the queues the protocol holds
+    fn record_queue_depths(&self) {
+        metric_store_count(REQUEST_QUEUE_DEPTH_ID, self.request_aggregator.queue_len());
+        metric_store_count(PENDING_DECISIONS_DEPTH_ID, self.pending_decisions.len());
+        metric_store_count(FUTURE_MESSAGES_DEPTH_ID, self.future_messages.len());
+    }
+
+    /// Take all of the decisions which have not yet been delivered to the replica
+    fn take_pending_decisions(&mut self) -> Option<MaybeVec<DumboDecision<RQ, R, A, CE>>> {
+        if self.pending_decisions.is_empty() {
+            return None;
+        }
+
+        Some(MaybeVec::from_many(self.pending_decisions.drain(..).collect()))
+    }
+
+    /// Route a message to the round of its epoch, buffering it if the epoch has not yet started.
+    /// Returns None if the message was dropped.
+    fn route_message(
+        &mut self,
+        message: ShareableConsensusMessage<RQ, DumboPSerialization<RQ, R, A, CE>>,
+    ) -> Result<Option<EpochResult>> {
+        match message.message().message_type() {
+            DumboMessageType::CatchUp(_) => return self.process_catch_up(message),
+            DumboMessageType::Checkpoint(_) => return self.process_checkpoint(message),
+            _ => {}
+        }
+
+        let epoch = message.message().sequence_number();
+        let sender = message.header().from();
+
+        if let Err(err) = self.verify_epoch_message(&message) {
+            match err {
+                DumboVerificationError::NotAMember { .. } => {
+                    metric_increment(NON_MEMBER_MESSAGES_ID, Some(1));
+                }
+                DumboVerificationError::EpochOutOfWindow(_) => {
+                    // Members far ahead of us are a sign that we are lagging behind
+                    if let Some((from, to)) = self.catch_up.observe_ahead(sender, epoch, self.epoch_num) {
+                        self.request_catch_up(from, to);
+                    }
+                }
+                _ => {}
+            }
+
+            debug!("Dropped message from {sender:?} for epoch {epoch:?}: {err}");
+
+            return Ok(None);
+        }
+
+        let node = self.node.clone();
+
+        let Some(round) = self.round_for(epoch) else {
+            return match self.future_messages.buffer(self.epoch_num, epoch, message) {
+                BufferResult::Buffered => Ok(Some(EpochResult::MessageQueued)),
+                result => {
+                    debug!("Dropped message for epoch {epoch:?} at epoch {:?}: {result:?}", self.epoch_num);
+
+                    Ok(None)
+                }
+            };
+        };
+
+        let result = round.process_message(message, &node)?;
+
+        if let EpochResult::Finalized = result {
+            self.collect_decisions()?;
+        }
+
+        Ok(Some(result))
+    }
+
+    /// Verify the parts of an epoch message which depend on the state of the protocol,
+    /// and which the stateless verification of the message therefore can not check.
+    ///
+    /// The sender, along with the instance the message refers to, must be a member of the
+    /// quorum of the epoch, the epoch must be running or within the window of epochs we buffer,
+    /// and the coin shares of the agreement must verify against the key set of the quorum.
+    fn verify_epoch_message(
+        &self,
+        message: &ShareableConsensusMessage<RQ, DumboPSerialization<RQ, R, A, CE>>,
+    ) -> std::result::Result<(), DumboVerificationError> {
+        let epoch = message.message().sequence_number();
+        let sender = message.header().from();
+
+        let membership = self.reconfigurations.membership_for(epoch);
+
+        let instance = match message.message().message_type() {
+            DumboMessageType::ReliableBroadcast(instance, _)
+            | DumboMessageType::IndexBroadcast(instance, _)
+            | DumboMessageType::AsyncBinaryAgreement(instance, _) => Some(*instance),
+            DumboMessageType::DecryptionShare(share) => Some(*share.proposer()),
+            _ => None,
+        };
+
+        if let Some(node) = std::iter::once(sender)
+            .chain(instance)
+            .find(|node| !membership.quorum_info().is_member(*node))
+        {
+            return Err(DumboVerificationError::NotAMember { node, epoch });
+        }
+
+        let window = self.rounds.len().max(self.config.future_epoch_window());
+
+        if matches!(epoch.index(self.epoch_num), Either::Right(distance) if distance >= window) {
+            return Err(DumboVerificationError::EpochOutOfWindow(epoch));
+        }
+
+        if let DumboMessageType::IndexBroadcast(instance, index_message) =
+            message.message().message_type()
+        {
+            if let Some(vector) = index_message.proposal() {
+                vector
+                    .validate(membership.quorum_info())
+                    .map_err(|err| DumboVerificationError::InvalidIndexVector {
+                        instance: *instance,
+                        reason: err.to_string(),
+                    })?;
+            }
+        }
+
+        if let (DumboMessageType::AsyncBinaryAgreement(instance, aba_message), Some(key_set)) = (
+            message.message().message_type(),
+            membership.keys().certificate_keys(),
+        ) {
+            if !aba_message.has_valid_coin_share(sender, membership.quorum_info(), key_set) {
+                return Err(DumboVerificationError::InvalidCoinShare(*instance));
+            }
+        }
+
+        Ok(())
     }
 }
 
-impl<CE, RQ, R, A> OrderProtocolTolerance for Dumbo<CE, RQ, R, A>
+/// The state of the protocol recorded in a checkpoint
+fn checkpoint_state(ordering: &EpochOrdering, reconfigurations: &Reconfigurations) -> CheckpointState {
+    let current = reconfigurations.current();
+
+    CheckpointState::new(
+        ordering.decided(),
+        current.quorum_info().clone(),
+        current.encrypted(),
+        reconfigurations.scheduled().map(|(activation, membership)| {
+            (
+                activation,
+                membership.quorum_info().clone(),
+                membership.encrypted(),
+            )
+        }),
+    )
+}
+
+impl<CE, RQ, R, A, NT> OrderProtocolTolerance for Dumbo<CE, RQ, R, A, NT>
 where
     A: ABAProtocol,
     CE: CommitteeElectionProtocol,
-    R: ReliableBroadcast<RQ>,
+    R: ReliableBroadcast<ProposedBatch<RQ>>,
     RQ: SerMsg,
 {
     fn get_n_for_f(f: usize) -> usize {
@@ -80,24 +997,24 @@ where
     }
 }
 
-impl<CE, RQ, R, A> Orderable for Dumbo<CE, RQ, R, A>
+impl<CE, RQ, R, A, NT> Orderable for Dumbo<CE, RQ, R, A, NT>
 where
     A: ABAProtocol,
     CE: CommitteeElectionProtocol,
-    R: ReliableBroadcast<RQ>,
+    R: ReliableBroadcast<ProposedBatch<RQ>>,
     RQ: SerMsg,
 {
     fn sequence_number(&self) -> SeqNo {
-        self.sequence_number()
+        self.epoch_num
     }
 }
 
-impl<CE, RQ, R, A> TimeoutableMod<OPExResult<RQ, DumboPSerialization<RQ, R, A, CE>>>
-    for Dumbo<CE, RQ, R, A>
+impl<CE, RQ, R, A, NT> TimeoutableMod<OPExResult<RQ, DumboPSerialization<RQ, R, A, CE>>>
+    for Dumbo<CE, RQ, R, A, NT>
 where
     A: ABAProtocol,
     CE: CommitteeElectionProtocol,
-    R: ReliableBroadcast<RQ>,
+    R: ReliableBroadcast<ProposedBatch<RQ>>,
     RQ: SerMsg,
 {
     fn mod_name() -> Arc<str> {
@@ -106,45 +1023,109 @@ where
 
     fn handle_timeout(
         &mut self,
-        timeout: Vec<ModTimeout>,
+        _timeout: Vec<ModTimeout>,
     ) -> Result<OPExResult<RQ, DumboPSerialization<RQ, R, A, CE>>> {
-        todo!()
+        // Dumbo is fully asynchronous, so it never registers any timeouts
+        Ok(OPExResult::MessageProcessedNoUpdate)
     }
 }
 
-impl<CE, RQ, R, A> OrderingProtocol<RQ> for Dumbo<CE, RQ, R, A>
+impl<CE, RQ, R, A, NT> OrderingProtocol<RQ> for Dumbo<CE, RQ, R, A, NT>
 where
-    RQ: SerMsg,
-    R: ReliableBroadcast<RQ>,
+    RQ: SerMsg + Orderable,
+    R: ReliableBroadcast<ProposedBatch<RQ>>,
     A: ABAProtocol,
     CE: CommitteeElectionProtocol,
+    NT: OrderProtocolSendNode<RQ, DumboPSerialization<RQ, R, A, CE>>,
 {
-    type Config = ();
+    type Config = DumboConfig;
     type Serialization = DumboPSerialization<RQ, R, A, CE>;
 
     fn handle_off_ctx_message(
         &mut self,
         message: ShareableConsensusMessage<RQ, Self::Serialization>,
     ) {
-        todo!()
+        // Any decision reached here is delivered in the next poll
+        if let Err(err) = self.route_message(message) {
+            warn!("Failed to process out of context message: {err:?}");
+        }
     }
 
     fn handle_execution_changed(&mut self, is_executing: bool) -> Result<()> {
-        todo!()
+        self.is_executing = is_executing;
+
+        self.fill_pipeline()
     }
 
     fn poll(&mut self) -> Result<OPResult<RQ, Self::Serialization>> {
-        todo!()
+        self.record_queue_depths();
+
+        if let Some(decisions) = self.take_pending_decisions() {
+            return Ok(OPResult::ProgressedDecision(DecisionsAhead::Ignore, decisions));
+        }
+
+        // Delivering the pending decisions may have made room for rounds ahead of the current one
+        self.fill_pipeline()?;
+
+        let node = self.node.clone();
+
+        for round in self.rounds.iter_mut() {
+            round.poll(&node)?;
+        }
+
+        // Rounds may also have been decided by replayed messages when they were started
+        self.collect_decisions()?;
+
+        match self.take_pending_decisions() {
+            Some(decisions) => Ok(OPResult::ProgressedDecision(DecisionsAhead::Ignore, decisions)),
+            None => Ok(OPResult::ReceiveMsg),
+        }
     }
 
     fn process_message(
         &mut self,
         message: ShareableConsensusMessage<RQ, Self::Serialization>,
     ) -> Result<OPExResult<RQ, Self::Serialization>> {
-        todo!()
+        let Some(result) = self.route_message(message)? else {
+            return Ok(OPExResult::MessageDropped);
+        };
+
+        Ok(match result {
+            EpochResult::MessageIgnored => OPExResult::MessageDropped,
+            EpochResult::MessageQueued => OPExResult::MessageQueued,
+            EpochResult::MessageProcessed => OPExResult::MessageProcessedNoUpdate,
+            EpochResult::Finalized => match self.take_pending_decisions() {
+                Some(decisions) => OPExResult::ProgressedDecision(DecisionsAhead::Ignore, decisions),
+                None => OPExResult::MessageProcessedNoUpdate,
+            },
+        })
     }
 
     fn install_seq_no(&mut self, seq_no: SeqNo) -> Result<()> {
-        todo!()
+        if seq_no != self.epoch_num {
+            // The epochs in between were installed through state transfer, along with
+            // the checkpoint they end in when it was handed to us
+            let installed = self
+                .checkpoints
+                .last_stable()
+                .filter(|stable| stable.epoch() >= self.epoch_num && stable.epoch().next() == seq_no)
+                .cloned();
+
+            match installed {
+                Some(checkpoint) => {
+                    self.restore_state(checkpoint.state());
+                    self.discard_up_to(&checkpoint)?;
+                }
+                None => self.checkpoints.skip_to(seq_no),
+            }
+        }
+
+        self.epoch_num = seq_no;
+        self.apply_reconfiguration();
+        self.rounds.clear();
+        self.future_messages.discard_finalized(seq_no);
+        self.catch_up.discard_before(seq_no);
+
+        self.fill_pipeline()
     }
 }
@@ -0,0 +1,360 @@
+use crate::aba::CoinKeys;
+use crate::quorum_info::quorum_info::QuorumInfo;
+use crate::tpke::ThresholdEncryptionScheme;
+use atlas_common::crypto::hash::{Context, Digest};
+use atlas_common::crypto::threshold_crypto::{PrivateKeyPart, PublicKeySet};
+use atlas_common::node_id::NodeId;
+use atlas_common::ordering::SeqNo;
+use getset::{CopyGetters, Getters};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use thiserror::Error;
+use tracing::{info, warn};
+
+/// A change of the membership of the quorum, proposed along with a batch.
+///
+/// A reconfiguration decided in epoch `e` takes effect in epoch `e + k`, where `k` is the
+/// configured reconfiguration delay, so that every epoch which may already be running
+/// when it is decided keeps the membership it was started with.
+#[derive(Debug, Clone, PartialEq, Eq, Getters, CopyGetters, Serialize, Deserialize)]
+pub struct Reconfiguration {
+    /// The members of the new quorum, ordered by id
+    #[get = "pub"]
+    members: Vec<NodeId>,
+    /// The amount of faults tolerated by the new quorum
+    #[get_copy = "pub"]
+    f: usize,
+    /// Whether the batches proposed by the new quorum are threshold encrypted.
+    /// It is decided along with the members, so every member handles the batches of an epoch alike.
+    #[get_copy = "pub"]
+    encrypted: bool,
+}
+
+impl Reconfiguration {
+    /// A reconfiguration into the given members, tolerating as many faults as they allow
+    pub fn new(members: Vec<NodeId>) -> Result<Self, ReconfigurationError> {
+        let n = members.len();
+
+        Self::with_f(members, n.saturating_sub(1) / 3)
+    }
+
+    /// A reconfiguration into the given members, tolerating `f` faults
+    pub fn with_f(mut members: Vec<NodeId>, f: usize) -> Result<Self, ReconfigurationError> {
+        members.sort();
+        members.dedup();
+
+        let reconfiguration = Self {
+            members,
+            f,
+            encrypted: false,
+        };
+
+        reconfiguration.validate()?;
+
+        Ok(reconfiguration)
+    }
+
+    /// Have the new quorum threshold encrypt the batches it proposes
+    pub fn with_threshold_encryption(mut self) -> Self {
+        self.encrypted = true;
+        self
+    }
+
+    /// Check that the new membership is a valid quorum.
+    /// Reconfigurations are received from other nodes, so this must hold before they are used.
+    pub fn validate(&self) -> Result<(), ReconfigurationError> {
+        let n = self.members.len();
+
+        if n == 0 {
+            return Err(ReconfigurationError::EmptyMembership);
+        }
+
+        if self.members.windows(2).any(|pair| pair[0] >= pair[1]) {
+            return Err(ReconfigurationError::UnorderedMembers);
+        }
+
+        if self.f > (n - 1) / 3 {
+            return Err(ReconfigurationError::TooManyFaults { n, f: self.f });
+        }
+
+        Ok(())
+    }
+
+    /// The quorum formed by the new membership
+    pub fn quorum_info(&self) -> QuorumInfo {
+        QuorumInfo::new(self.members.len(), self.f, self.members.clone())
+    }
+
+    pub fn digest(&self) -> Digest {
+        let mut context = Context::new();
+
+        self.members
+            .iter()
+            .for_each(|member| context.update(&member.0.to_le_bytes()[..]));
+
+        context.update(&(self.f as u64).to_le_bytes()[..]);
+        context.update(&[self.encrypted as u8]);
+
+        context.finish()
+    }
+}
+
+/// The keys of a membership of the quorum.
+///
+/// Threshold keys are bound to the members they were dealt to, so every reconfiguration needs
+/// a fresh set, which is generated outside of the protocol and handed over to it.
+#[derive(Debug, Clone, Default, Getters)]
+pub struct MembershipKeys {
+    #[get = "pub"]
+    threshold_encryption: Option<Arc<dyn ThresholdEncryptionScheme>>,
+    #[get = "pub"]
+    certificate_keys: Option<PublicKeySet>,
+    /// The key shares dealt to us under the certificate keys, which flip the common coin
+    /// of the agreements
+    #[get = "pub"]
+    coin_key_shares: Vec<PrivateKeyPart>,
+}
+
+impl MembershipKeys {
+    pub fn new(
+        threshold_encryption: Option<Arc<dyn ThresholdEncryptionScheme>>,
+        certificate_keys: Option<PublicKeySet>,
+        coin_key_shares: Vec<PrivateKeyPart>,
+    ) -> Self {
+        Self {
+            threshold_encryption,
+            certificate_keys,
+            coin_key_shares,
+        }
+    }
+
+    /// The keys the agreements of this membership flip their common coin with,
+    /// if both the key set of the quorum and our key shares are known
+    pub fn coin_keys(&self) -> Option<CoinKeys> {
+        match &self.certificate_keys {
+            Some(key_set) if !self.coin_key_shares.is_empty() => Some(CoinKeys::new(
+                key_set.clone(),
+                self.coin_key_shares.clone(),
+            )),
+            _ => None,
+        }
+    }
+}
+
+/// A membership of the quorum, along with its keys.
+#[derive(Debug, Clone, Getters, CopyGetters)]
+pub(super) struct Membership {
+    #[get = "pub(super)"]
+    quorum_info: QuorumInfo,
+    /// Whether the batches of the membership are threshold encrypted, which is agreed on by
+    /// every member rather than taken from the keys each of them was handed
+    #[get_copy = "pub(super)"]
+    encrypted: bool,
+    #[get = "pub(super)"]
+    keys: MembershipKeys,
+}
+
+impl Membership {
+    pub(super) fn new(quorum_info: QuorumInfo, encrypted: bool, keys: MembershipKeys) -> Self {
+        Self {
+            quorum_info,
+            encrypted,
+            keys,
+        }
+    }
+}
+
+/// The reconfigurations of a Dumbo replica.
+///
+/// A reconfiguration is only scheduled once `f + 1` members have proposed it in batches
+/// included in the same epoch, so that at least one correct member has requested it.
+/// Only one reconfiguration can be scheduled at a time.
+pub(super) struct Reconfigurations {
+    delay: usize,
+    // The membership of the current epoch
+    current: Membership,
+    // The reconfiguration we propose, until it is scheduled
+    requested: Option<(Reconfiguration, MembershipKeys)>,
+    // The reconfiguration which was decided, and the epoch it takes effect in
+    scheduled: Option<(SeqNo, Membership)>,
+}
+
+impl Reconfigurations {
+    pub(super) fn new(current: Membership, delay: usize) -> Self {
+        Self {
+            delay,
+            current,
+            requested: None,
+            scheduled: None,
+        }
+    }
+
+    pub(super) fn current(&self) -> &Membership {
+        &self.current
+    }
+
+    /// The epoch the scheduled reconfiguration takes effect in, if there is one
+    pub(super) fn scheduled_epoch(&self) -> Option<SeqNo> {
+        self.scheduled.as_ref().map(|(epoch, _)| *epoch)
+    }
+
+    /// The membership which was decided but has not yet taken effect,
+    /// along with the epoch it takes effect in
+    pub(super) fn scheduled(&self) -> Option<(SeqNo, &Membership)> {
+        self.scheduled
+            .as_ref()
+            .map(|(activation, membership)| (*activation, membership))
+    }
+
+    /// The membership which runs the given epoch
+    pub(super) fn membership_for(&self, epoch: SeqNo) -> &Membership {
+        match &self.scheduled {
+            Some((activation, membership)) if epoch >= *activation => membership,
+            _ => &self.current,
+        }
+    }
+
+    /// Request a reconfiguration, with the keys of the new membership
+    pub(super) fn request(&mut self, reconfiguration: Reconfiguration, keys: MembershipKeys) {
+        self.requested = Some((reconfiguration, keys));
+    }
+
+    /// The reconfiguration to propose along with our next batch
+    pub(super) fn to_propose(&self) -> Option<&Reconfiguration> {
+        self.requested
+            .as_ref()
+            .filter(|_| self.scheduled.is_none())
+            .map(|(reconfiguration, _)| reconfiguration)
+    }
+
+    /// Register the reconfigurations proposed in the batches included in a decided epoch.
+    ///
+    /// Returns the epoch the reconfiguration takes effect in, if one was scheduled.
+    pub(super) fn observe_decided(
+        &mut self,
+        epoch: SeqNo,
+        proposed: &[(NodeId, Reconfiguration)],
+    ) -> Option<SeqNo> {
+        if self.scheduled.is_some() {
+            return None;
+        }
+
+        let quorum_info = &self.membership_for(epoch).quorum_info;
+
+        // The reconfiguration of the lowest proposer is preferred when several gather enough support
+        let decided = proposed.iter().find_map(|(_, candidate)| {
+            let supporters = proposed
+                .iter()
+                .filter(|(_, other)| other == candidate)
+                .map(|(proposer, _)| proposer);
+
+            (quorum_info.accumulated_weight(supporters) > quorum_info.f()).then_some(candidate)
+        })?;
+
+        if decided.validate().is_err() {
+            return None;
+        }
+
+        let keys = match self.requested.take() {
+            Some((requested, keys)) if requested == *decided => keys,
+            requested => {
+                warn!("Reconfiguration decided in epoch {epoch:?} was not requested by us, keeping our current keys");
+
+                self.requested = requested;
+                self.current.keys.clone()
+            }
+        };
+
+        let activation = (0..self.delay).fold(epoch, |epoch, _| epoch.next());
+
+        info!(
+            "Reconfiguration into {:?} decided in epoch {epoch:?}, taking effect in epoch {activation:?}",
+            decided.members()
+        );
+
+        self.scheduled = Some((
+            activation,
+            Membership::new(decided.quorum_info(), decided.encrypted(), keys),
+        ));
+
+        Some(activation)
+    }
+
+    /// Restore the memberships recorded in a checkpoint, in place of the ones derived from
+    /// the epochs decided before it, which are no longer available.
+    ///
+    /// Returns the current membership when it differs from ours.
+    pub(super) fn restore(
+        &mut self,
+        current: QuorumInfo,
+        encrypted: bool,
+        scheduled: Option<(SeqNo, QuorumInfo, bool)>,
+    ) -> Option<&Membership> {
+        self.scheduled = scheduled.map(|(activation, quorum_info, encrypted)| {
+            let keys = self.keys_for(&quorum_info);
+
+            (activation, Membership::new(quorum_info, encrypted, keys))
+        });
+
+        if current == self.current.quorum_info && encrypted == self.current.encrypted {
+            return None;
+        }
+
+        let keys = self.keys_for(&current);
+
+        self.current = Membership::new(current, encrypted, keys);
+
+        Some(&self.current)
+    }
+
+    /// The keys of the given quorum: the ones we requested the reconfiguration into it with,
+    /// or our current keys when we did not request it
+    fn keys_for(&mut self, quorum_info: &QuorumInfo) -> MembershipKeys {
+        match self.requested.take() {
+            Some((requested, keys)) if requested.quorum_info() == *quorum_info => keys,
+            requested => {
+                self.requested = requested;
+                self.current.keys.clone()
+            }
+        }
+    }
+
+    /// Move to the given epoch, applying the scheduled reconfiguration if it has taken effect.
+    ///
+    /// Returns the new membership when it was applied.
+    pub(super) fn advance_to(&mut self, epoch: SeqNo) -> Option<&Membership> {
+        if !self
+            .scheduled
+            .as_ref()
+            .is_some_and(|(activation, _)| epoch >= *activation)
+        {
+            return None;
+        }
+
+        let (_, membership) = self.scheduled.take()?;
+
+        self.current = membership;
+
+        Some(&self.current)
+    }
+}
+
+impl std::fmt::Debug for Reconfigurations {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Reconfigurations")
+            .field("members", self.current.quorum_info.quorum_members())
+            .field("requested", &self.requested.as_ref().map(|(requested, _)| requested))
+            .field("scheduled_epoch", &self.scheduled_epoch())
+            .finish()
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum ReconfigurationError {
+    #[error("The new membership has no members")]
+    EmptyMembership,
+    #[error("The members of the new membership are not ordered, or are repeated")]
+    UnorderedMembers,
+    #[error("A membership of {n} members can not tolerate {f} faults")]
+    TooManyFaults { n: usize, f: usize },
+}
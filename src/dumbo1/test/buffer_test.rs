@@ -0,0 +1,112 @@
+use crate::dumbo1::buffer::{BufferResult, FutureEpochBuffer};
+use crate::dumbo1::message::{DumboMessage, DumboMessageType};
+use crate::simulator::byzantine::strategy::{ByzantineStrategy, StaleEpochReplay};
+use atlas_common::node_id::NodeId;
+use atlas_common::ordering::{Orderable, SeqNo};
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+
+fn epoch(n: u32) -> SeqNo {
+    SeqNo::from(n)
+}
+
+#[test]
+fn messages_replayed_in_order_of_arrival() {
+    let mut buffer = FutureEpochBuffer::new(4, 16);
+
+    assert_eq!(buffer.buffer(epoch(1), epoch(2), 1), BufferResult::Buffered);
+    assert_eq!(buffer.buffer(epoch(1), epoch(3), 2), BufferResult::Buffered);
+    assert_eq!(buffer.buffer(epoch(1), epoch(2), 3), BufferResult::Buffered);
+
+    assert_eq!(buffer.take(epoch(2)), vec![1, 3]);
+    assert_eq!(buffer.take(epoch(2)), Vec::<u32>::new());
+    assert_eq!(buffer.len(), 1);
+
+    let statistics = buffer.statistics();
+
+    assert_eq!(statistics.buffered(), 3);
+    assert_eq!(statistics.replayed(), 2);
+    assert_eq!(statistics.dropped(), 0);
+}
+
+#[test]
+fn messages_outside_window_dropped() {
+    let mut buffer = FutureEpochBuffer::new(2, 16);
+
+    assert_eq!(buffer.buffer(epoch(5), epoch(6), 0), BufferResult::Buffered);
+    assert_eq!(
+        buffer.buffer(epoch(5), epoch(7), 0),
+        BufferResult::DroppedOutOfWindow
+    );
+    assert_eq!(
+        buffer.buffer(epoch(5), epoch(4), 0),
+        BufferResult::DroppedFinalized
+    );
+
+    let statistics = buffer.statistics();
+
+    assert_eq!(statistics.dropped_out_of_window(), 1);
+    assert_eq!(statistics.dropped_finalized(), 1);
+    assert_eq!(buffer.len(), 1);
+}
+
+#[test]
+fn epoch_capacity_enforced() {
+    let mut buffer = FutureEpochBuffer::new(4, 2);
+
+    assert_eq!(buffer.buffer(epoch(0), epoch(1), 0), BufferResult::Buffered);
+    assert_eq!(buffer.buffer(epoch(0), epoch(1), 1), BufferResult::Buffered);
+    assert_eq!(buffer.buffer(epoch(0), epoch(1), 2), BufferResult::DroppedFull);
+
+    // The capacity is per epoch, so other epochs are unaffected
+    assert_eq!(buffer.buffer(epoch(0), epoch(2), 3), BufferResult::Buffered);
+
+    assert_eq!(buffer.statistics().dropped_full(), 1);
+}
+
+#[test]
+fn finalized_epochs_discarded() {
+    let mut buffer = FutureEpochBuffer::new(8, 16);
+
+    buffer.buffer(epoch(0), epoch(1), 0);
+    buffer.buffer(epoch(0), epoch(2), 1);
+    buffer.buffer(epoch(0), epoch(3), 2);
+
+    buffer.discard_finalized(epoch(3));
+
+    assert_eq!(buffer.len(), 1);
+    assert_eq!(buffer.statistics().dropped_finalized(), 2);
+    assert_eq!(buffer.take(epoch(3)), vec![2]);
+}
+
+#[test]
+fn stale_epoch_replays_dropped_as_finalized() {
+    let mut replay = StaleEpochReplay::default();
+    let mut rng = StdRng::seed_from_u64(0);
+
+    // A Byzantine node sending a single message in each of the first epochs
+    let sent = (0..3)
+        .flat_map(|n| {
+            let message: DumboMessage<(), (), (), ()> =
+                DumboMessage::new(epoch(n), DumboMessageType::ReliableBroadcast(NodeId(0), ()));
+
+            replay.tamper(NodeId(1), message, &mut rng)
+        })
+        .map(|(_, message)| message.sequence_number())
+        .collect::<Vec<_>>();
+
+    assert_eq!(sent, vec![epoch(0), epoch(0), epoch(1), epoch(1), epoch(2)]);
+
+    let mut buffer = FutureEpochBuffer::new(4, 16);
+
+    // Once we are at the third epoch, the messages of the earlier ones are all finalized
+    for stale in sent.iter().filter(|sent| **sent < epoch(2)) {
+        assert_eq!(
+            buffer.buffer(epoch(2), *stale, ()),
+            BufferResult::DroppedFinalized
+        );
+    }
+
+    assert_eq!(buffer.statistics().dropped_finalized(), 4);
+    assert_eq!(buffer.len(), 0);
+}
@@ -0,0 +1,214 @@
+use crate::dumbo1::catch_up::{CatchUp, DecidedEpoch};
+use crate::dumbo1::certificate::{AgreementOutcome, DecisionCertificate};
+use crate::dumbo1::reconfiguration::Reconfiguration;
+use crate::quorum_info::quorum_info::QuorumInfo;
+use crate::reliable_broadcast::index_rbc::IndexVector;
+use crate::rq_aggregator::rq_aggregator::batch_digest;
+use atlas_common::crypto::hash::{Context, Digest};
+use atlas_common::node_id::NodeId;
+use atlas_common::ordering::{Orderable, SeqNo};
+use atlas_communication::lookup_table::MessageModule;
+use atlas_communication::message::{Buf, StoredMessage};
+
+const N: usize = 4;
+const F: usize = 1;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct TestRequest(SeqNo);
+
+impl Orderable for TestRequest {
+    fn sequence_number(&self) -> SeqNo {
+        self.0
+    }
+}
+
+fn request(client: u32, seq_no: u32) -> StoredMessage<TestRequest> {
+    let mut context = Context::new();
+    context.update(&client.to_le_bytes()[..]);
+    context.update(&seq_no.to_le_bytes()[..]);
+
+    let wire_msg = atlas_communication::message::WireMessage::new(
+        NodeId(client),
+        NodeId(0),
+        MessageModule::Application,
+        Buf::new(),
+        0,
+        Some(context.finish()),
+        None,
+    );
+
+    StoredMessage::new(
+        wire_msg.header().clone(),
+        TestRequest(SeqNo::from(seq_no)),
+    )
+}
+
+fn quorum_info() -> QuorumInfo {
+    QuorumInfo::new(N, F, (0..N as u32).map(NodeId).collect())
+}
+
+fn epoch(n: u32) -> SeqNo {
+    SeqNo::from(n)
+}
+
+fn digest(value: u32) -> Digest {
+    let mut context = Context::new();
+    context.update(&value.to_le_bytes()[..]);
+    context.finish()
+}
+
+fn decided_epoch(n: u32, client: u32) -> DecidedEpoch<TestRequest> {
+    let requests = vec![request(client, n)];
+
+    let committee = vec![NodeId(0), NodeId(1)];
+
+    let outcome = AgreementOutcome::new(
+        committee.iter().map(|member| (*member, true)).collect(),
+        vec![
+            (NodeId(0), IndexVector::new((0..3).map(NodeId).collect())),
+            (NodeId(1), IndexVector::new((1..4).map(NodeId).collect())),
+        ],
+        (0..N as u32).map(|node| (NodeId(node), digest(node))).collect(),
+    );
+
+    let certificate =
+        DecisionCertificate::new(epoch(n), committee, outcome, batch_digest(&requests));
+
+    DecidedEpoch::new(certificate, requests)
+}
+
+/// Bring the catch up state to the point where it is catching up on `[1, 4)`
+fn lagging_catch_up() -> CatchUp<TestRequest> {
+    let mut catch_up = CatchUp::new(quorum_info(), None, 16);
+
+    assert_eq!(catch_up.observe_ahead(NodeId(1), epoch(4), epoch(1)), None);
+    assert_eq!(
+        catch_up.observe_ahead(NodeId(2), epoch(5), epoch(1)),
+        Some((epoch(1), epoch(4)))
+    );
+    assert!(catch_up.is_catching_up());
+
+    catch_up
+}
+
+#[test]
+fn catch_up_requires_f_plus_one_members_ahead() {
+    let mut catch_up = CatchUp::<TestRequest>::new(quorum_info(), None, 16);
+
+    // A single node may be faulty, and a non member is never trusted
+    assert_eq!(catch_up.observe_ahead(NodeId(1), epoch(10), epoch(1)), None);
+    assert_eq!(catch_up.observe_ahead(NodeId(1), epoch(12), epoch(1)), None);
+    assert_eq!(catch_up.observe_ahead(NodeId(7), epoch(12), epoch(1)), None);
+    assert!(!catch_up.is_catching_up());
+
+    // The f + 1 highest epoch is the furthest one we know a correct replica has reached
+    assert_eq!(
+        catch_up.observe_ahead(NodeId(2), epoch(9), epoch(1)),
+        Some((epoch(1), epoch(9)))
+    );
+}
+
+#[test]
+fn matching_answers_installed_in_order() {
+    let mut catch_up = lagging_catch_up();
+
+    let answer = vec![decided_epoch(1, 100), decided_epoch(2, 101), decided_epoch(3, 102)];
+
+    assert!(
+        catch_up
+            .process_response(NodeId(1), answer.clone(), epoch(1))
+            .is_empty()
+    );
+
+    let installed = catch_up.process_response(NodeId(2), answer, epoch(1));
+
+    assert_eq!(
+        installed
+            .iter()
+            .map(|decided| decided.sequence_number())
+            .collect::<Vec<_>>(),
+        vec![epoch(1), epoch(2), epoch(3)]
+    );
+    assert!(!catch_up.is_catching_up());
+}
+
+#[test]
+fn conflicting_answers_not_installed() {
+    let mut catch_up = lagging_catch_up();
+
+    catch_up.process_response(NodeId(1), vec![decided_epoch(1, 100)], epoch(1));
+
+    let installed = catch_up.process_response(NodeId(2), vec![decided_epoch(1, 200)], epoch(1));
+
+    assert!(installed.is_empty());
+    assert!(catch_up.is_catching_up());
+
+    // The same node answering twice does not count as two answers
+    let installed = catch_up.process_response(NodeId(2), vec![decided_epoch(1, 200)], epoch(1));
+
+    assert!(installed.is_empty());
+}
+
+#[test]
+fn answer_not_matching_certificate_ignored() {
+    let mut catch_up = lagging_catch_up();
+
+    let (certificate, _) = decided_epoch(1, 100).into_inner();
+    let tampered = DecidedEpoch::new(certificate, vec![request(666, 1)]);
+
+    assert!(!tampered.matches_certificate());
+
+    catch_up.process_response(NodeId(1), vec![tampered.clone()], epoch(1));
+    let installed = catch_up.process_response(NodeId(2), vec![tampered], epoch(1));
+
+    assert!(installed.is_empty());
+}
+
+#[test]
+fn serves_recorded_epochs_in_range() {
+    let mut catch_up = CatchUp::new(quorum_info(), None, 2);
+
+    catch_up.record_decided(decided_epoch(1, 100));
+    catch_up.record_decided(decided_epoch(2, 100));
+    catch_up.record_decided(decided_epoch(3, 100));
+
+    // Only the latest epochs are kept
+    let served = catch_up.serve(epoch(1), epoch(3));
+
+    assert_eq!(served.len(), 1);
+    assert_eq!(served[0].sequence_number(), epoch(2));
+}
+
+#[test]
+fn answers_with_different_reconfigurations_not_matched() {
+    let mut catch_up = lagging_catch_up();
+
+    let reconfigured = |members: Vec<u32>| {
+        let (certificate, requests) = decided_epoch(1, 100).into_inner();
+
+        let reconfiguration =
+            Reconfiguration::new(members.into_iter().map(NodeId).collect()).unwrap();
+
+        DecidedEpoch::new(
+            certificate.with_reconfigurations(vec![(NodeId(0), reconfiguration)]),
+            requests,
+        )
+    };
+
+    // The same output, but a faulty member swapped the reconfiguration decided along with it
+    let first = reconfigured(vec![0, 1, 2, 3, 4]);
+    let swapped = reconfigured(vec![0, 1, 2, 5]);
+
+    catch_up.process_response(NodeId(1), vec![first.clone()], epoch(1));
+    let installed = catch_up.process_response(NodeId(2), vec![swapped], epoch(1));
+
+    assert!(installed.is_empty());
+
+    let installed = catch_up.process_response(NodeId(3), vec![first], epoch(1));
+
+    assert_eq!(installed.len(), 1);
+    assert_eq!(
+        installed[0].certificate().reconfigurations()[0].1.members().len(),
+        5
+    );
+}
@@ -0,0 +1,154 @@
+use crate::dumbo1::certificate::{AgreementOutcome, CertificateError, DecisionCertificate};
+use crate::dumbo1::reconfiguration::Reconfiguration;
+use crate::quorum_info::quorum_info::QuorumInfo;
+use crate::reliable_broadcast::index_rbc::IndexVector;
+use atlas_common::crypto::hash::{Context, Digest};
+use atlas_common::node_id::NodeId;
+use atlas_common::ordering::SeqNo;
+
+const N: usize = 4;
+const F: usize = 1;
+
+fn quorum_info() -> QuorumInfo {
+    QuorumInfo::new(N, F, (0..N as u32).map(NodeId).collect())
+}
+
+fn digest(value: u32) -> Digest {
+    let mut context = Context::new();
+    context.update(&value.to_le_bytes()[..]);
+    context.finish()
+}
+
+fn certificate(
+    decisions: &[(u32, bool)],
+    vectors: &[(u32, &[u32])],
+    digests: &[u32],
+) -> DecisionCertificate {
+    let outcome = AgreementOutcome::new(
+        decisions
+            .iter()
+            .map(|(node, decision)| (NodeId(*node), *decision))
+            .collect(),
+        vectors
+            .iter()
+            .map(|(node, indices)| {
+                (
+                    NodeId(*node),
+                    IndexVector::new(indices.iter().cloned().map(NodeId).collect()),
+                )
+            })
+            .collect(),
+        digests
+            .iter()
+            .map(|node| (NodeId(*node), digest(*node)))
+            .collect(),
+    );
+
+    DecisionCertificate::new(SeqNo::ONE, vec![NodeId(0), NodeId(1)], outcome, digest(100))
+}
+
+#[test]
+fn consistent_certificate_verifies() {
+    let certificate = certificate(
+        &[(1, true), (0, true)],
+        &[(1, &[1, 2, 3]), (0, &[0, 1, 2])],
+        &[2, 0, 3, 1],
+    );
+
+    assert!(certificate.verify(&quorum_info()).is_ok());
+    assert_eq!(
+        certificate.outcome().included().collect::<Vec<_>>(),
+        vec![NodeId(0), NodeId(1), NodeId(2), NodeId(3)]
+    );
+}
+
+#[test]
+fn missing_decision_rejected() {
+    let certificate = certificate(&[(0, true)], &[(0, &[0, 1, 2])], &[0, 1, 2]);
+
+    assert!(matches!(
+        certificate.verify(&quorum_info()),
+        Err(CertificateError::MissingDecisions(1, 2))
+    ));
+}
+
+#[test]
+fn decision_of_non_committee_member_rejected() {
+    let certificate = certificate(
+        &[(0, true), (1, false), (2, true)],
+        &[(0, &[0, 1, 2]), (2, &[0, 1, 2])],
+        &[0, 1, 2],
+    );
+
+    assert!(matches!(
+        certificate.verify(&quorum_info()),
+        Err(CertificateError::UnknownMember(NodeId(2)))
+    ));
+}
+
+#[test]
+fn not_enough_included_rejected() {
+    let certificate = certificate(&[(0, true), (1, false)], &[(0, &[0, 1])], &[0, 1]);
+
+    assert!(matches!(
+        certificate.verify(&quorum_info()),
+        Err(CertificateError::InvalidInclusion(_))
+    ));
+}
+
+#[test]
+fn vector_of_rejected_member_rejected() {
+    let certificate = certificate(
+        &[(0, true), (1, false)],
+        &[(0, &[0, 1, 2]), (1, &[1, 2, 3])],
+        &[0, 1, 2, 3],
+    );
+
+    assert!(matches!(
+        certificate.verify(&quorum_info()),
+        Err(CertificateError::InconsistentInclusion)
+    ));
+}
+
+#[test]
+fn digest_of_excluded_batch_rejected() {
+    let certificate = certificate(&[(0, true), (1, false)], &[(0, &[0, 1, 2])], &[0, 1, 2, 3]);
+
+    assert!(matches!(
+        certificate.verify(&quorum_info()),
+        Err(CertificateError::InconsistentInclusion)
+    ));
+}
+
+#[test]
+fn unsigned_certificate_has_no_signature() {
+    let certificate = certificate(&[(0, true), (1, false)], &[(0, &[0, 1, 2])], &[0, 1, 2]);
+
+    assert!(certificate.output_signature().is_none());
+}
+
+fn reconfiguration() -> Reconfiguration {
+    Reconfiguration::new((0..5).map(NodeId).collect()).unwrap()
+}
+
+#[test]
+fn reconfiguration_of_excluded_batch_rejected() {
+    let certificate = certificate(&[(0, true), (1, false)], &[(0, &[0, 1, 2])], &[0, 1, 2])
+        .with_reconfigurations(vec![(NodeId(3), reconfiguration())]);
+
+    assert!(matches!(
+        certificate.verify(&quorum_info()),
+        Err(CertificateError::InvalidReconfiguration(NodeId(3)))
+    ));
+}
+
+#[test]
+fn reconfigurations_are_certified() {
+    let plain = certificate(&[(0, true), (1, false)], &[(0, &[0, 1, 2])], &[0, 1, 2]);
+    let reconfigured = plain
+        .clone()
+        .with_reconfigurations(vec![(NodeId(1), reconfiguration())]);
+
+    assert!(reconfigured.verify(&quorum_info()).is_ok());
+    assert_ne!(plain.signing_payload(), reconfigured.signing_payload());
+}
@@ -0,0 +1,262 @@
+use crate::dumbo1::checkpoint::{
+    CheckpointError, CheckpointMessage, CheckpointResult, CheckpointState, Checkpoints,
+};
+use crate::quorum_info::quorum_info::QuorumInfo;
+use crate::rq_aggregator::rq_aggregator::DecidedRequests;
+use atlas_common::crypto::hash::{Context, Digest};
+use atlas_common::node_id::NodeId;
+use atlas_common::ordering::SeqNo;
+use atlas_communication::lookup_table::MessageModule;
+use atlas_communication::message::{Buf, StoredMessage};
+use std::collections::BTreeMap;
+
+const N: usize = 4;
+const F: usize = 1;
+const PERIOD: usize = 4;
+
+fn quorum_info() -> QuorumInfo {
+    QuorumInfo::new(N, F, (0..N as u32).map(NodeId).collect())
+}
+
+fn epoch(n: u32) -> SeqNo {
+    SeqNo::from(n)
+}
+
+fn digest(value: u32) -> Digest {
+    let mut context = Context::new();
+    context.update(&value.to_le_bytes()[..]);
+    context.finish()
+}
+
+fn state() -> CheckpointState {
+    CheckpointState::new(BTreeMap::new(), quorum_info(), false, None)
+}
+
+/// A state in which the given client has decided its first `decided` requests
+fn state_with_decided(client: u32, decided: u32) -> CheckpointState {
+    let mut requests = DecidedRequests::default();
+
+    (0..decided).for_each(|seq_no| requests.insert(SeqNo::from(seq_no)));
+
+    CheckpointState::new(
+        BTreeMap::from([(NodeId(client), requests)]),
+        quorum_info(),
+        false,
+        None,
+    )
+}
+
+fn vote(sender: u32, message: &CheckpointMessage) -> StoredMessage<CheckpointMessage> {
+    let wire_msg = atlas_communication::message::WireMessage::new(
+        NodeId(sender),
+        NodeId(0),
+        MessageModule::Application,
+        Buf::new(),
+        0,
+        None,
+        None,
+    );
+
+    StoredMessage::new(wire_msg.header().clone(), message.clone())
+}
+
+/// Decide the epochs up to and including `last`, returning our own checkpoint votes
+fn decide_up_to(checkpoints: &mut Checkpoints, last: u32) -> Vec<CheckpointMessage> {
+    (1..=last)
+        .filter_map(|n| checkpoints.record_decided(epoch(n), &digest(n), state))
+        .collect()
+}
+
+#[test]
+fn test_votes_every_period() {
+    let mut checkpoints = Checkpoints::new(quorum_info(), PERIOD);
+
+    let votes = decide_up_to(&mut checkpoints, 9);
+
+    assert_eq!(
+        votes.iter().map(CheckpointMessage::epoch).collect::<Vec<_>>(),
+        vec![epoch(4), epoch(8)]
+    );
+}
+
+#[test]
+fn test_replicas_agree_on_checkpoint_digest() {
+    let mut first = Checkpoints::new(quorum_info(), PERIOD);
+    let mut second = Checkpoints::new(quorum_info(), PERIOD);
+
+    assert_eq!(decide_up_to(&mut first, 4), decide_up_to(&mut second, 4));
+
+    let mut diverging = Checkpoints::new(quorum_info(), PERIOD);
+
+    decide_up_to(&mut diverging, 3);
+    let diverged = diverging.record_decided(epoch(4), &digest(100), state).unwrap();
+
+    assert_ne!(decide_up_to(&mut Checkpoints::new(quorum_info(), PERIOD), 4)[0], diverged);
+}
+
+#[test]
+fn test_checkpoint_becomes_stable_with_quorum() {
+    let mut checkpoints = Checkpoints::new(quorum_info(), PERIOD);
+
+    let own = decide_up_to(&mut checkpoints, 4).remove(0);
+
+    assert!(matches!(
+        checkpoints.process_message(vote(0, &own), epoch(5)),
+        CheckpointResult::MessageProcessed
+    ));
+    assert!(matches!(
+        checkpoints.process_message(vote(1, &own), epoch(5)),
+        CheckpointResult::MessageProcessed
+    ));
+
+    let CheckpointResult::Stable(stable) = checkpoints.process_message(vote(2, &own), epoch(5))
+    else {
+        panic!("The checkpoint should be stable with n - f matching votes");
+    };
+
+    assert_eq!(stable.epoch(), epoch(4));
+    assert_eq!(stable.proof().len(), N - F);
+    assert_eq!(checkpoints.last_stable().map(|stable| stable.epoch()), Some(epoch(4)));
+
+    // Late votes for the stable checkpoint are no longer needed
+    assert!(matches!(
+        checkpoints.process_message(vote(3, &own), epoch(5)),
+        CheckpointResult::MessageIgnored
+    ));
+}
+
+#[test]
+fn test_duplicate_and_foreign_votes_are_ignored() {
+    let mut checkpoints = Checkpoints::new(quorum_info(), PERIOD);
+
+    let own = decide_up_to(&mut checkpoints, 4).remove(0);
+
+    checkpoints.process_message(vote(1, &own), epoch(5));
+
+    assert!(matches!(
+        checkpoints.process_message(vote(1, &own), epoch(5)),
+        CheckpointResult::MessageIgnored
+    ));
+    assert!(matches!(
+        checkpoints.process_message(vote(1, &CheckpointMessage::new(epoch(4), digest(7))), epoch(5)),
+        CheckpointResult::MessageIgnored
+    ));
+    assert!(matches!(
+        checkpoints.process_message(vote(10, &own), epoch(5)),
+        CheckpointResult::MessageIgnored
+    ));
+}
+
+#[test]
+fn test_quorum_is_not_stable_before_we_decide() {
+    let mut checkpoints = Checkpoints::new(quorum_info(), PERIOD);
+
+    let mut reference = Checkpoints::new(quorum_info(), PERIOD);
+    let agreed = decide_up_to(&mut reference, 4).remove(0);
+
+    decide_up_to(&mut checkpoints, 2);
+
+    assert!(matches!(
+        checkpoints.process_message(vote(1, &agreed), epoch(3)),
+        CheckpointResult::MessageProcessed
+    ));
+    assert!(matches!(
+        checkpoints.process_message(vote(2, &agreed), epoch(3)),
+        CheckpointResult::Lagging(lagging) if lagging == epoch(4)
+    ));
+    assert!(matches!(
+        checkpoints.process_message(vote(3, &agreed), epoch(3)),
+        CheckpointResult::MessageProcessed
+    ));
+
+    assert!(checkpoints.last_stable().is_none());
+
+    checkpoints.record_decided(epoch(3), &digest(3), state);
+    checkpoints.record_decided(epoch(4), &digest(4), state);
+
+    let stable = checkpoints.check_stable(epoch(4)).unwrap();
+
+    assert_eq!(stable.digest(), agreed.digest());
+}
+
+#[test]
+fn test_diverging_checkpoint_is_not_stable() {
+    let mut checkpoints = Checkpoints::new(quorum_info(), PERIOD);
+
+    decide_up_to(&mut checkpoints, 3);
+    checkpoints.record_decided(epoch(4), &digest(100), state);
+
+    let mut reference = Checkpoints::new(quorum_info(), PERIOD);
+    let agreed = decide_up_to(&mut reference, 4).remove(0);
+
+    for sender in 1..N as u32 {
+        assert!(!matches!(
+            checkpoints.process_message(vote(sender, &agreed), epoch(5)),
+            CheckpointResult::Stable(_)
+        ));
+    }
+
+    assert!(checkpoints.last_stable().is_none());
+}
+
+#[test]
+fn test_no_vote_after_skipping_part_of_a_period() {
+    let mut checkpoints = Checkpoints::new(quorum_info(), PERIOD);
+
+    checkpoints.skip_to(epoch(3));
+
+    assert!(checkpoints.record_decided(epoch(3), &digest(3), state).is_none());
+    assert!(checkpoints.record_decided(epoch(4), &digest(4), state).is_none());
+
+    // The following period was fully decided by us
+    let votes = (5..=8)
+        .filter_map(|n| checkpoints.record_decided(epoch(n), &digest(n), state))
+        .collect::<Vec<_>>();
+
+    assert_eq!(
+        votes.iter().map(CheckpointMessage::epoch).collect::<Vec<_>>(),
+        vec![epoch(8)]
+    );
+}
+
+#[test]
+fn test_replicas_with_diverging_state_disagree() {
+    let mut first = Checkpoints::new(quorum_info(), PERIOD);
+    let mut second = Checkpoints::new(quorum_info(), PERIOD);
+
+    decide_up_to(&mut first, 3);
+    decide_up_to(&mut second, 3);
+
+    let first = first.record_decided(epoch(4), &digest(4), || state_with_decided(100, 2));
+    let second = second.record_decided(epoch(4), &digest(4), || state_with_decided(100, 3));
+
+    assert_ne!(first.unwrap(), second.unwrap());
+}
+
+#[test]
+fn test_stable_checkpoint_carries_verifiable_state() {
+    let mut checkpoints = Checkpoints::new(quorum_info(), PERIOD);
+
+    decide_up_to(&mut checkpoints, 3);
+
+    let own = checkpoints
+        .record_decided(epoch(4), &digest(4), || state_with_decided(100, 2))
+        .unwrap();
+
+    for sender in 0..(N - F) as u32 {
+        checkpoints.process_message(vote(sender, &own), epoch(5));
+    }
+
+    let stable = checkpoints.last_stable().cloned().unwrap();
+
+    assert_eq!(stable.state(), &state_with_decided(100, 2));
+    assert!(stable.verify(&quorum_info()).is_ok());
+
+    // Members of another quorum did not vote for it
+    let other = QuorumInfo::new(N, F, (10..10 + N as u32).map(NodeId).collect());
+
+    assert!(matches!(
+        stable.verify(&other),
+        Err(CheckpointError::NonMemberVote(_))
+    ));
+}
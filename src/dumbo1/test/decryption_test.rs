@@ -0,0 +1,369 @@
+use crate::dumbo1::batch::{EncryptedBatch, ProposedBatch, RequestVerifier};
+use crate::dumbo1::decryption::{DecryptionPhase, DecryptionResult};
+use crate::dumbo1::message::DecryptionShareMessage;
+use crate::quorum_info::quorum_info::QuorumInfo;
+use crate::tpke::{BlsThresholdEncryption, TPKEError, ThresholdEncryptionScheme};
+use anyhow::anyhow;
+use atlas_common::crypto::hash::Digest;
+use atlas_common::node_id::NodeId;
+use atlas_communication::lookup_table::MessageModule;
+use atlas_communication::message::{Buf, StoredMessage};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use threshold_crypto::SecretKeySet;
+
+const N: usize = 4;
+const F: usize = 1;
+const KEY: u8 = 0x5A;
+
+/// A toy threshold encryption scheme, where the ciphertext is the plaintext xor'ed with a key
+/// and each decryption share is the key tagged with the id of the node that produced it.
+/// The share of each node covers as many key shares as its weight in the quorum.
+#[derive(Debug)]
+struct MockScheme {
+    node: NodeId,
+    quorum_info: QuorumInfo,
+    threshold: usize,
+}
+
+impl ThresholdEncryptionScheme for MockScheme {
+    fn threshold(&self) -> usize {
+        self.threshold
+    }
+
+    fn encrypt(&self, plaintext: &[u8]) -> Result<Vec<u8>, TPKEError> {
+        Ok(b"ct"
+            .iter()
+            .cloned()
+            .chain(plaintext.iter().map(|byte| byte ^ KEY))
+            .collect())
+    }
+
+    fn verify_ciphertext(&self, ciphertext: &[u8]) -> bool {
+        ciphertext.starts_with(b"ct")
+    }
+
+    fn decryption_share(&self, _ciphertext: &[u8]) -> Result<Vec<u8>, TPKEError> {
+        Ok(vec![self.node.0 as u8, KEY])
+    }
+
+    fn verify_decryption_share(&self, from: NodeId, _ciphertext: &[u8], share: &[u8]) -> bool {
+        share == [from.0 as u8, KEY]
+    }
+
+    fn combine_shares(
+        &self,
+        ciphertext: &[u8],
+        shares: &[(NodeId, &[u8])],
+    ) -> Result<Vec<u8>, TPKEError> {
+        let key_shares = self
+            .quorum_info
+            .accumulated_weight(shares.iter().map(|(node, _)| *node));
+
+        if key_shares < self.threshold {
+            return Err(TPKEError::NotEnoughShares(key_shares, self.threshold));
+        }
+
+        Ok(ciphertext[2..].iter().map(|byte| byte ^ KEY).collect())
+    }
+}
+
+fn scheme(node: NodeId) -> Arc<dyn ThresholdEncryptionScheme> {
+    weighted_scheme(node, quorum_info())
+}
+
+fn weighted_scheme(node: NodeId, quorum_info: QuorumInfo) -> Arc<dyn ThresholdEncryptionScheme> {
+    Arc::new(MockScheme {
+        node,
+        threshold: quorum_info.f() + 1,
+        quorum_info,
+    })
+}
+
+/// Rejects the requests carrying the given value
+#[derive(Debug)]
+struct RejectValue(u8);
+
+impl RequestVerifier<u8> for RejectValue {
+    fn verify_request(&self, request: &StoredMessage<u8>) -> atlas_common::error::Result<()> {
+        if *request.message() == self.0 {
+            return Err(anyhow!("Invalid request"));
+        }
+
+        Ok(())
+    }
+}
+
+fn accept_all() -> Arc<dyn RequestVerifier<u8>> {
+    Arc::new(RejectValue(u8::MAX))
+}
+
+fn quorum_info() -> QuorumInfo {
+    QuorumInfo::new(N, F, (0..N).map(NodeId::from).collect())
+}
+
+fn request(client: u32, value: u8) -> StoredMessage<u8> {
+    let wire_msg = atlas_communication::message::WireMessage::new(
+        NodeId(client),
+        NodeId(0),
+        MessageModule::Application,
+        Buf::new(),
+        0,
+        Some(Digest::blank()),
+        None,
+    );
+
+    StoredMessage::new(wire_msg.header().clone(), value)
+}
+
+fn share_from(node: NodeId, proposer: NodeId) -> DecryptionShareMessage {
+    let share = scheme(node).decryption_share(&[]).unwrap();
+
+    DecryptionShareMessage::new(proposer, share)
+}
+
+#[test]
+fn test_plain_batches_need_no_shares() {
+    let decided = vec![(NodeId(0), ProposedBatch::Plain(vec![request(100, 1)]))];
+
+    let phase = DecryptionPhase::new(scheme(NodeId(0)), accept_all(), quorum_info(), decided);
+
+    assert!(phase.is_complete());
+    assert!(phase.own_shares().unwrap().is_empty());
+}
+
+#[test]
+fn test_batch_revealed_after_threshold_shares() {
+    let own_scheme = scheme(NodeId(0));
+
+    let encrypted = ProposedBatch::encrypt(vec![request(100, 7)], own_scheme.as_ref()).unwrap();
+
+    let decided = vec![
+        (NodeId(1), encrypted),
+        (NodeId(2), ProposedBatch::Plain(vec![request(101, 3)])),
+    ];
+
+    let mut phase = DecryptionPhase::new(own_scheme, accept_all(), quorum_info(), decided);
+
+    assert_eq!(1, phase.own_shares().unwrap().len());
+
+    // F shares are not enough to reveal the batch
+    for node in 0..F {
+        let result = phase.process_share(NodeId::from(node), share_from(NodeId::from(node), NodeId(1)));
+
+        assert!(matches!(result, DecryptionResult::ShareAccepted));
+    }
+
+    assert!(!phase.is_complete());
+
+    let result = phase.process_share(NodeId::from(F), share_from(NodeId::from(F), NodeId(1)));
+
+    assert!(matches!(result, DecryptionResult::Completed));
+
+    let batches = phase.finalize().unwrap();
+
+    assert_eq!(2, batches.len());
+    assert_eq!(NodeId(1), batches[0].0);
+    assert_eq!(7, *batches[0].1[0].message());
+}
+
+/// Test that the share of a member covers all of its key shares, so a member whose weight
+/// reaches the threshold reveals a batch on its own
+#[test]
+fn test_weighted_shares_reveal_batch() {
+    let quorum_info = QuorumInfo::weighted(
+        F,
+        vec![
+            (NodeId(0), 1),
+            (NodeId(1), 2),
+            (NodeId(2), 1),
+            (NodeId(3), 1),
+        ],
+    );
+
+    let own_scheme = weighted_scheme(NodeId(0), quorum_info.clone());
+
+    let encrypted = ProposedBatch::encrypt(vec![request(100, 7)], own_scheme.as_ref()).unwrap();
+
+    let mut phase = DecryptionPhase::new(
+        own_scheme,
+        accept_all(),
+        quorum_info,
+        vec![(NodeId(2), encrypted)],
+    );
+
+    let result = phase.process_share(NodeId(1), share_from(NodeId(1), NodeId(2)));
+
+    assert!(matches!(result, DecryptionResult::Completed));
+
+    let batches = phase.finalize().unwrap();
+
+    assert_eq!(7, *batches[0].1[0].message());
+}
+
+#[test]
+fn test_invalid_and_duplicate_shares_ignored() {
+    let own_scheme = scheme(NodeId(0));
+
+    let encrypted = ProposedBatch::encrypt(vec![request(100, 7)], own_scheme.as_ref()).unwrap();
+
+    let mut phase = DecryptionPhase::new(
+        own_scheme,
+        accept_all(),
+        quorum_info(),
+        vec![(NodeId(1), encrypted)],
+    );
+
+    // A share claiming to be from another node
+    let result = phase.process_share(NodeId(1), share_from(NodeId(2), NodeId(1)));
+    assert!(matches!(result, DecryptionResult::ShareIgnored));
+
+    // A share for a batch that was not decided
+    let result = phase.process_share(NodeId(1), share_from(NodeId(1), NodeId(3)));
+    assert!(matches!(result, DecryptionResult::ShareIgnored));
+
+    let result = phase.process_share(NodeId(1), share_from(NodeId(1), NodeId(1)));
+    assert!(matches!(result, DecryptionResult::ShareAccepted));
+
+    // The same share twice
+    let result = phase.process_share(NodeId(1), share_from(NodeId(1), NodeId(1)));
+    assert!(matches!(result, DecryptionResult::ShareIgnored));
+
+    // A share from a non member
+    let result = phase.process_share(NodeId(9), share_from(NodeId(9), NodeId(1)));
+    assert!(matches!(result, DecryptionResult::ShareIgnored));
+
+    assert!(!phase.is_complete());
+}
+
+#[test]
+fn test_invalid_revealed_requests_dropped() {
+    let own_scheme = scheme(NodeId(0));
+
+    let encrypted = ProposedBatch::encrypt(
+        vec![request(100, 7), request(101, 8), request(102, 9)],
+        own_scheme.as_ref(),
+    )
+    .unwrap();
+
+    let mut phase = DecryptionPhase::new(
+        own_scheme,
+        Arc::new(RejectValue(8)),
+        quorum_info(),
+        vec![(NodeId(1), encrypted)],
+    );
+
+    for node in 0..=F {
+        phase.process_share(NodeId::from(node), share_from(NodeId::from(node), NodeId(1)));
+    }
+
+    let batches = phase.finalize().unwrap();
+
+    let values = batches[0].1.iter().map(|request| *request.message()).collect::<Vec<_>>();
+
+    assert_eq!(vec![7, 9], values);
+}
+
+/// Test that an epoch whose included batches all carry an invalid ciphertext is decided
+/// without waiting for shares, as no member produces shares for them
+#[test]
+fn test_invalid_ciphertexts_need_no_shares() {
+    let decided = vec![(
+        NodeId(1),
+        ProposedBatch::Encrypted(EncryptedBatch::new(b"not a ciphertext".to_vec())),
+    )];
+
+    let phase = DecryptionPhase::new(scheme(NodeId(0)), accept_all(), quorum_info(), decided);
+
+    assert!(phase.is_complete());
+    assert!(phase.own_shares().unwrap().is_empty());
+
+    let batches = phase.finalize().unwrap();
+
+    assert!(batches[0].1.is_empty());
+}
+
+/// The BLS threshold encryption schemes of every member of the given quorum,
+/// dealt from a fresh key set
+fn bls_schemes(quorum_info: &QuorumInfo) -> BTreeMap<NodeId, Arc<dyn ThresholdEncryptionScheme>> {
+    let key_set = SecretKeySet::random(quorum_info.f(), &mut rand_07::thread_rng());
+
+    quorum_info
+        .quorum_members()
+        .iter()
+        .map(|member| {
+            let key_shares = quorum_info
+                .key_shares(*member)
+                .map(|index| key_set.secret_key_share(index))
+                .collect();
+
+            let scheme: Arc<dyn ThresholdEncryptionScheme> = Arc::new(BlsThresholdEncryption::new(
+                quorum_info.clone(),
+                key_set.public_keys(),
+                key_shares,
+            ));
+
+            (*member, scheme)
+        })
+        .collect()
+}
+
+#[test]
+fn test_batch_revealed_with_bls_threshold_encryption() {
+    let quorum_info = QuorumInfo::weighted(
+        F,
+        vec![
+            (NodeId(0), 1),
+            (NodeId(1), 2),
+            (NodeId(2), 1),
+            (NodeId(3), 1),
+        ],
+    );
+
+    let schemes = bls_schemes(&quorum_info);
+
+    let encrypted = ProposedBatch::encrypt(
+        vec![request(100, 7), request(101, 8)],
+        schemes[&NodeId(3)].as_ref(),
+    )
+    .unwrap();
+
+    let decided = vec![(NodeId(3), encrypted)];
+
+    let phase = |node: u32| {
+        DecryptionPhase::new(
+            schemes[&NodeId(node)].clone(),
+            accept_all(),
+            quorum_info.clone(),
+            decided.clone(),
+        )
+    };
+
+    let own_share = |node: u32| phase(node).own_shares().unwrap().remove(0);
+
+    let mut own = phase(0);
+
+    assert!(!own.is_complete());
+
+    // A share is only valid for the key shares of the member which produced it
+    assert!(matches!(
+        own.process_share(NodeId(0), own_share(2)),
+        DecryptionResult::ShareIgnored
+    ));
+    assert!(matches!(
+        own.process_share(NodeId(2), own_share(2)),
+        DecryptionResult::ShareAccepted
+    ));
+
+    // The share of a member covers every key share dealt to it
+    assert!(matches!(
+        own.process_share(NodeId(1), own_share(1)),
+        DecryptionResult::Completed
+    ));
+
+    let batches = own.finalize().unwrap();
+
+    let values = batches[0].1.iter().map(|request| *request.message()).collect::<Vec<_>>();
+
+    assert_eq!(vec![7, 8], values);
+}
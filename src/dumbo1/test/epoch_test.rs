@@ -0,0 +1,267 @@
+use crate::aba::{ABAProtocol, AsyncBinaryAgreementResult, AsyncBinaryAgreementSendNode, CoinKeys};
+use crate::async_bin_agreement::async_bin_agreement::ABAError;
+use crate::async_bin_agreement::messages::{
+    AsyncBinaryAgreementMessage, AsyncBinaryAgreementMessageType,
+};
+use crate::async_bin_agreement::test::async_bin_agreement_test::key_shares;
+use crate::committee_election::{
+    CommitteeElectionProtocol, CommitteeElectionResult, CommitteeElectionSendNode,
+};
+use crate::dumbo1::batch::{ProposedBatch, RequestVerifier};
+use crate::dumbo1::epoch::DumboRound;
+use crate::dumbo1::reconfiguration::{Membership, MembershipKeys};
+use crate::quorum_info::quorum_info::QuorumInfo;
+use crate::rbc::{
+    ReliableBroadcast, ReliableBroadcastResult, ReliableBroadcastSendNode, VerifiableRBCMessage,
+};
+use anyhow::anyhow;
+use atlas_common::crypto::hash::Digest;
+use atlas_common::crypto::threshold_crypto::PrivateKeySet;
+use atlas_common::node_id::NodeId;
+use atlas_common::ordering::SeqNo;
+use atlas_communication::lookup_table::MessageModule;
+use atlas_communication::message::{Buf, StoredMessage};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use thiserror::Error;
+
+const N: usize = 4;
+const F: usize = 1;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct UnusedMessage;
+
+impl VerifiableRBCMessage<ProposedBatch<u8>> for UnusedMessage {
+    fn proposal(&self) -> Option<&ProposedBatch<u8>> {
+        None
+    }
+}
+
+/// A reliable broadcast which never delivers, as these tests only drive the agreements
+struct SilentBroadcast;
+
+impl ReliableBroadcast<ProposedBatch<u8>> for SilentBroadcast {
+    type ReliableBroadcastMessage = UnusedMessage;
+
+    fn new(_sender: NodeId, _quorum_info: QuorumInfo) -> Self {
+        Self
+    }
+
+    fn new_with_propose<NT>(
+        _sender: NodeId,
+        _quorum_info: QuorumInfo,
+        _request: ProposedBatch<u8>,
+        _network: &NT,
+    ) -> Self
+    where
+        NT: ReliableBroadcastSendNode<Self::ReliableBroadcastMessage>,
+    {
+        Self
+    }
+
+    fn poll(&mut self) -> Option<StoredMessage<Self::ReliableBroadcastMessage>> {
+        None
+    }
+
+    fn process_message<NT>(
+        &mut self,
+        _message: StoredMessage<Self::ReliableBroadcastMessage>,
+        _network: &NT,
+    ) -> ReliableBroadcastResult
+    where
+        NT: ReliableBroadcastSendNode<Self::ReliableBroadcastMessage>,
+    {
+        ReliableBroadcastResult::MessageIgnored
+    }
+
+    fn finalize(self) -> ProposedBatch<u8> {
+        unreachable!("The broadcast never delivers")
+    }
+}
+
+#[derive(Error, Debug)]
+#[error("No committee is elected in the epoch")]
+struct NoCommittee;
+
+struct NoElection;
+
+impl CommitteeElectionProtocol for NoElection {
+    type Message = String;
+    type CEError = NoCommittee;
+
+    fn new(
+        _quorum_info: QuorumInfo,
+        _committee_size: usize,
+        _coin_keys: CoinKeys,
+        _nonce: Vec<u8>,
+    ) -> Self {
+        Self
+    }
+
+    fn start<NT>(&mut self, _network: &NT) -> Result<(), Self::CEError>
+    where
+        NT: CommitteeElectionSendNode<Self::Message>,
+    {
+        Ok(())
+    }
+
+    fn poll(&mut self) -> Option<StoredMessage<Self::Message>> {
+        None
+    }
+
+    fn process_message<NT>(
+        &mut self,
+        _message: StoredMessage<Self::Message>,
+        _network: &NT,
+    ) -> Result<CommitteeElectionResult, Self::CEError>
+    where
+        NT: CommitteeElectionSendNode<Self::Message>,
+    {
+        Err(NoCommittee)
+    }
+
+    fn finalize(self) -> Result<Vec<NodeId>, Self::CEError> {
+        Err(NoCommittee)
+    }
+}
+
+/// An agreement which broadcasts a vote for every message it processes
+struct VotingAgreement;
+
+impl ABAProtocol for VotingAgreement {
+    type AsyncBinaryMessage = AsyncBinaryAgreementMessage;
+    type ABAError = ABAError;
+
+    fn new(_input_bit: bool, _quorum_info: QuorumInfo, _coin_keys: CoinKeys) -> Self {
+        Self
+    }
+
+    fn round(&self) -> usize {
+        0
+    }
+
+    fn broadcast_estimate<NT>(&self, _network: &NT) -> Result<(), Self::ABAError>
+    where
+        NT: AsyncBinaryAgreementSendNode<Self::AsyncBinaryMessage>,
+    {
+        Ok(())
+    }
+
+    fn poll(&mut self) -> Option<StoredMessage<Self::AsyncBinaryMessage>> {
+        None
+    }
+
+    fn process_message<NT>(
+        &mut self,
+        message: StoredMessage<Self::AsyncBinaryMessage>,
+        network: &NT,
+    ) -> Result<AsyncBinaryAgreementResult, Self::ABAError>
+    where
+        NT: AsyncBinaryAgreementSendNode<Self::AsyncBinaryMessage>,
+    {
+        let round = message.message().round();
+
+        network
+            .broadcast_message(message.message().clone(), (0..N).map(NodeId::from))
+            .map_err(|source| ABAError::BroadcastFailed {
+                round,
+                vote: "estimate",
+                source,
+            })?;
+
+        Ok(AsyncBinaryAgreementResult::Processed)
+    }
+
+    fn finalize(self) -> Result<bool, Self::ABAError> {
+        Err(ABAError::FailedToFinalizeNotReady)
+    }
+}
+
+/// A sender disconnected from the quorum
+struct FailingNetwork;
+
+impl AsyncBinaryAgreementSendNode<AsyncBinaryAgreementMessage> for FailingNetwork {
+    fn broadcast_message<I>(
+        &self,
+        _message: AsyncBinaryAgreementMessage,
+        _target: I,
+    ) -> atlas_common::error::Result<()>
+    where
+        I: Iterator<Item = NodeId>,
+    {
+        Err(anyhow!("Not connected to the quorum"))
+    }
+}
+
+#[derive(Debug)]
+struct AcceptAll;
+
+impl RequestVerifier<u8> for AcceptAll {
+    fn verify_request(&self, _request: &StoredMessage<u8>) -> atlas_common::error::Result<()> {
+        Ok(())
+    }
+}
+
+type TestRound = DumboRound<NoElection, u8, SilentBroadcast, VotingAgreement>;
+
+fn quorum_info() -> QuorumInfo {
+    QuorumInfo::new(N, F, (0..N).map(NodeId::from).collect())
+}
+
+fn round() -> TestRound {
+    let key_set = PrivateKeySet::gen_random(F);
+
+    let keys = MembershipKeys::new(
+        None,
+        Some(key_set.public_key_set()),
+        key_shares(&key_set, &quorum_info(), NodeId(0)),
+    );
+
+    let membership = Membership::new(quorum_info(), false, keys);
+
+    DumboRound::new(SeqNo::ONE, NodeId(0), &membership, 16, Arc::new(AcceptAll), None)
+}
+
+fn val_message(from: NodeId) -> StoredMessage<AsyncBinaryAgreementMessage> {
+    let wire_msg = atlas_communication::message::WireMessage::new(
+        from,
+        NodeId(0),
+        MessageModule::Application,
+        Buf::new(),
+        0,
+        Some(Digest::blank()),
+        None,
+    );
+
+    StoredMessage::new(
+        wire_msg.header().clone(),
+        AsyncBinaryAgreementMessage::new(
+            AsyncBinaryAgreementMessageType::Val { estimate: true },
+            0,
+        ),
+    )
+}
+
+#[test]
+fn test_failed_vote_broadcast_carries_on() {
+    let round = round();
+
+    let result = VotingAgreement.process_message(val_message(NodeId(1)), &FailingNetwork);
+
+    assert!(matches!(result, Err(ABAError::BroadcastFailed { round: 0, .. })));
+
+    // The message was processed, so the epoch carries on with the agreement
+    assert!(matches!(
+        round.agreement_result(NodeId(1), result),
+        Ok(AsyncBinaryAgreementResult::Processed)
+    ));
+}
+
+#[test]
+fn test_other_agreement_failures_are_reported() {
+    let round = round();
+
+    let result = round.agreement_result(NodeId(1), Err(ABAError::FailedToFinalizeNotReady));
+
+    assert!(result.is_err());
+}
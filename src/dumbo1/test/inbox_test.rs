@@ -0,0 +1,43 @@
+use crate::dumbo1::inbox::{Inbox, InstanceInboxes};
+use atlas_common::node_id::NodeId;
+
+#[test]
+fn inbox_drained_in_order_of_arrival() {
+    let mut inbox = Inbox::new(4);
+
+    assert!(inbox.push(1));
+    assert!(inbox.push(2));
+    assert!(inbox.push(3));
+
+    assert_eq!(inbox.pop(), Some(1));
+    assert_eq!(inbox.pop(), Some(2));
+    assert_eq!(inbox.pop(), Some(3));
+    assert_eq!(inbox.pop(), None);
+}
+
+#[test]
+fn full_inbox_drops_messages() {
+    let mut inbox = Inbox::new(2);
+
+    assert!(inbox.push(1));
+    assert!(inbox.push(2));
+    assert!(!inbox.push(3));
+
+    assert_eq!(inbox.len(), 2);
+}
+
+#[test]
+fn instance_inboxes_are_independent() {
+    let mut inboxes = InstanceInboxes::new(1);
+
+    assert!(inboxes.push(NodeId(0), 1));
+    assert!(!inboxes.push(NodeId(0), 2));
+    assert!(inboxes.push(NodeId(1), 3));
+
+    assert_eq!(inboxes.len(), 2);
+    assert_eq!(inboxes.pop(NodeId(1)), Some(3));
+    assert_eq!(inboxes.pop(NodeId(1)), None);
+
+    assert_eq!(inboxes.discard(NodeId(0)), 1);
+    assert_eq!(inboxes.len(), 0);
+}
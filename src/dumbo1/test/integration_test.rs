@@ -0,0 +1,601 @@
+use crate::aba::{ABAProtocol, AsyncBinaryAgreementResult, CoinKeys};
+use crate::async_bin_agreement::async_bin_agreement::AsyncBinaryAgreement;
+use crate::async_bin_agreement::messages::AsyncBinaryAgreementMessage;
+use crate::async_bin_agreement::test::async_bin_agreement_test::{key_shares, quorum_info};
+use crate::coin_election::coin_election::{CoinCommitteeElection, CoinElectionMessage};
+use crate::committee_election::{CommitteeElectionProtocol, CommitteeElectionResult};
+use crate::dumbo1::batch::ProposedBatch;
+use crate::quorum_info::quorum_info::QuorumInfo;
+use crate::rbc::{ReliableBroadcast, ReliableBroadcastResult};
+use crate::reliable_broadcast::index_rbc::{DeliveredBatches, IndexBroadcastMessage, IndexVector};
+use crate::reliable_broadcast::messages::ReliableBroadcastMessage;
+use crate::reliable_broadcast::reliable_broadcast::{
+    ReliableBroadcastInstance, ReliableBroadcastResult as IndexBroadcastResult,
+};
+use crate::simulator::byzantine::strategy::Silent;
+use crate::simulator::network::{NetworkConfig, Outbox};
+use crate::simulator::simulation::{SimProtocol, Simulation, stored_message};
+use atlas_common::crypto::threshold_crypto::PrivateKeySet;
+use atlas_common::node_id::NodeId;
+use atlas_communication::message::StoredMessage;
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::Arc;
+
+const N: usize = 4;
+const F: usize = 1;
+const SEEDS: u64 = 8;
+
+type MsgType = u8;
+
+type BatchBroadcast = ReliableBroadcastInstance<ProposedBatch<MsgType>>;
+type IndexBroadcast = ReliableBroadcastInstance<IndexVector>;
+
+/// The messages of an epoch, along with the node whose broadcast or agreement they are about
+#[derive(Debug, Clone)]
+enum EpochMessage {
+    Batch(NodeId, ReliableBroadcastMessage<ProposedBatch<MsgType>>),
+    Index(NodeId, IndexBroadcastMessage),
+    Election(CoinElectionMessage),
+    Agreement(NodeId, AsyncBinaryAgreementMessage),
+}
+
+/// The binary agreement on accepting the index vector of a committee member
+enum Agreement {
+    /// We have not voted yet, so the messages received for it are held back
+    Waiting(Vec<StoredMessage<AsyncBinaryAgreementMessage>>),
+    Running {
+        aba: AsyncBinaryAgreement,
+        // The round we have sent our estimate for
+        round: usize,
+    },
+    Decided(bool),
+}
+
+/// A node running a Dumbo1 epoch with the concrete reliable broadcast, binary agreement
+/// and committee election, driven through the interfaces the epoch drives them with.
+///
+/// Every node reliably broadcasts its batch, and once it has delivered the batches of `n - f`
+/// of the quorum it starts the committee election. Each member of the elected committee then
+/// reliably broadcasts the indices of the batches it has delivered, and a binary agreement per
+/// committee member decides whether its index vector is accepted. We vote for every index vector
+/// we deliver, and against the rest once any of them has been accepted.
+struct DumboNode {
+    id: NodeId,
+    quorum_info: QuorumInfo,
+    coin_keys: CoinKeys,
+    // Our batch, until we have proposed it
+    proposal: Option<ProposedBatch<MsgType>>,
+    batch_broadcasts: BTreeMap<NodeId, BatchBroadcast>,
+    delivered: DeliveredBatches,
+    batches: BTreeMap<NodeId, ProposedBatch<MsgType>>,
+    election: Option<CoinCommitteeElection>,
+    election_started: bool,
+    committee: Option<Vec<NodeId>>,
+    index_proposed: bool,
+    index_broadcasts: BTreeMap<NodeId, IndexBroadcast>,
+    index_vectors: BTreeMap<NodeId, IndexVector>,
+    agreements: BTreeMap<NodeId, Agreement>,
+}
+
+/// Hand the messages sent by a sub protocol to the network, tagged with what they are about
+fn forward<M, F>(sent: Vec<(NodeId, M)>, outbox: &Outbox<EpochMessage>, tag: F)
+where
+    F: Fn(M) -> EpochMessage,
+{
+    sent.into_iter()
+        .for_each(|(target, message)| outbox.push(target, tag(message)));
+}
+
+impl DumboNode {
+    fn new(id: NodeId, quorum_info: &QuorumInfo, key_set: &PrivateKeySet) -> Self {
+        let coin_keys = CoinKeys::new(
+            key_set.public_key_set(),
+            key_shares(key_set, quorum_info, id),
+        );
+
+        let batch_broadcasts = quorum_info
+            .quorum_members()
+            .iter()
+            .filter(|member| **member != id)
+            .map(|member| {
+                (
+                    *member,
+                    <BatchBroadcast as ReliableBroadcast<_>>::new(*member, quorum_info.clone()),
+                )
+            })
+            .collect();
+
+        let election = CoinCommitteeElection::new(
+            quorum_info.clone(),
+            F + 1,
+            coin_keys.clone(),
+            b"epoch".to_vec(),
+        );
+
+        Self {
+            id,
+            quorum_info: quorum_info.clone(),
+            coin_keys,
+            proposal: Some(ProposedBatch::Plain(vec![stored_message(
+                id,
+                id,
+                id.0 as MsgType,
+            )])),
+            batch_broadcasts,
+            delivered: DeliveredBatches::new(quorum_info.clone()),
+            batches: BTreeMap::new(),
+            election: Some(election),
+            election_started: false,
+            committee: None,
+            index_proposed: false,
+            index_broadcasts: BTreeMap::new(),
+            index_vectors: BTreeMap::new(),
+            agreements: BTreeMap::new(),
+        }
+    }
+
+    fn delivered_weight(&self) -> usize {
+        self.quorum_info.accumulated_weight(self.batches.keys())
+    }
+
+    fn process_batch_message(
+        &mut self,
+        sender: NodeId,
+        message: StoredMessage<ReliableBroadcastMessage<ProposedBatch<MsgType>>>,
+        outbox: &Outbox<EpochMessage>,
+    ) {
+        let Some(rbc) = self.batch_broadcasts.get_mut(&sender) else {
+            return;
+        };
+
+        let sent = Outbox::default();
+        let mut pending = vec![message];
+        let mut finalized = false;
+
+        while !pending.is_empty() && !finalized {
+            let mut progressed = false;
+
+            for message in pending {
+                match ReliableBroadcast::process_message(rbc, message, &sent) {
+                    ReliableBroadcastResult::Processed => progressed = true,
+                    ReliableBroadcastResult::Finalized => {
+                        finalized = true;
+
+                        break;
+                    }
+                    ReliableBroadcastResult::MessageQueued
+                    | ReliableBroadcastResult::MessageIgnored
+                    | ReliableBroadcastResult::NonMember => {}
+                }
+            }
+
+            pending = if progressed && !finalized {
+                std::iter::from_fn(|| ReliableBroadcast::poll(rbc)).collect()
+            } else {
+                Vec::new()
+            };
+        }
+
+        forward(sent.take(), outbox, |message| {
+            EpochMessage::Batch(sender, message)
+        });
+
+        if finalized {
+            let rbc = self.batch_broadcasts.remove(&sender).unwrap();
+
+            self.batches
+                .insert(sender, ReliableBroadcast::finalize(rbc));
+            self.delivered.register_delivered(sender);
+
+            self.release_index_echoes(outbox);
+            self.check_progress(outbox);
+        }
+    }
+
+    /// Echo the index vectors whose batches we have all delivered by now
+    fn release_index_echoes(&mut self, outbox: &Outbox<EpochMessage>) {
+        let withheld = self.index_broadcasts.keys().cloned().collect::<Vec<_>>();
+
+        for sender in withheld {
+            let Some(rbc) = self.index_broadcasts.get_mut(&sender) else {
+                continue;
+            };
+
+            let sent = Outbox::default();
+
+            let released = rbc.try_release_echo(&sent, &self.delivered);
+
+            forward(sent.take(), outbox, |message| {
+                EpochMessage::Index(sender, message)
+            });
+
+            if released {
+                let polled = std::iter::from_fn(|| rbc.poll()).collect::<Vec<_>>();
+
+                for message in polled {
+                    self.process_index_message(sender, message, outbox);
+                }
+            }
+        }
+    }
+
+    fn process_index_message(
+        &mut self,
+        sender: NodeId,
+        message: StoredMessage<IndexBroadcastMessage>,
+        outbox: &Outbox<EpochMessage>,
+    ) {
+        if self.index_vectors.contains_key(&sender) {
+            return;
+        }
+
+        let rbc = self
+            .index_broadcasts
+            .entry(sender)
+            .or_insert_with(|| IndexBroadcast::new(sender, self.quorum_info.clone()));
+
+        let sent = Outbox::default();
+        let mut pending = vec![message];
+        let mut finalized = false;
+
+        while !pending.is_empty() && !finalized {
+            let mut progressed = false;
+
+            for message in pending {
+                match rbc.process_message_with_predicate(message, &sent, &self.delivered) {
+                    IndexBroadcastResult::Progressed(_) => progressed = true,
+                    IndexBroadcastResult::Finalized => {
+                        finalized = true;
+
+                        break;
+                    }
+                    IndexBroadcastResult::MessageQueued
+                    | IndexBroadcastResult::MessageIgnored
+                    | IndexBroadcastResult::NonMember => {}
+                }
+            }
+
+            pending = if progressed && !finalized {
+                std::iter::from_fn(|| rbc.poll()).collect()
+            } else {
+                Vec::new()
+            };
+        }
+
+        forward(sent.take(), outbox, |message| {
+            EpochMessage::Index(sender, message)
+        });
+
+        if finalized {
+            let (vector, _) = self
+                .index_broadcasts
+                .remove(&sender)
+                .unwrap()
+                .finalize()
+                .unwrap();
+
+            self.index_vectors.insert(sender, vector);
+
+            self.check_progress(outbox);
+        }
+    }
+
+    fn process_election_message(
+        &mut self,
+        message: StoredMessage<CoinElectionMessage>,
+        outbox: &Outbox<EpochMessage>,
+    ) {
+        let Some(election) = self.election.as_mut() else {
+            return;
+        };
+
+        let sent = Outbox::default();
+
+        let result = election.process_message(message, &sent).unwrap();
+
+        forward(sent.take(), outbox, EpochMessage::Election);
+
+        if let CommitteeElectionResult::Decided = result {
+            let committee = self.election.take().unwrap().finalize().unwrap();
+
+            for member in &committee {
+                self.agreements
+                    .entry(*member)
+                    .or_insert_with(|| Agreement::Waiting(Vec::new()));
+            }
+
+            self.committee = Some(committee);
+
+            self.check_progress(outbox);
+        }
+    }
+
+    fn process_agreement_message(
+        &mut self,
+        member: NodeId,
+        message: StoredMessage<AsyncBinaryAgreementMessage>,
+        outbox: &Outbox<EpochMessage>,
+    ) {
+        let agreement = self
+            .agreements
+            .entry(member)
+            .or_insert_with(|| Agreement::Waiting(Vec::new()));
+
+        match agreement {
+            Agreement::Waiting(held) => held.push(message),
+            Agreement::Running { .. } => self.run_agreement(member, vec![message], outbox),
+            Agreement::Decided(_) => {}
+        }
+    }
+
+    /// Feed the given messages to the running agreement of `member`, broadcasting our estimate
+    /// whenever it moves to a new round
+    fn run_agreement(
+        &mut self,
+        member: NodeId,
+        mut pending: Vec<StoredMessage<AsyncBinaryAgreementMessage>>,
+        outbox: &Outbox<EpochMessage>,
+    ) {
+        let Some(Agreement::Running { aba, round }) = self.agreements.get_mut(&member) else {
+            return;
+        };
+
+        let sent = Outbox::default();
+        let mut decided = false;
+
+        while !pending.is_empty() && !decided {
+            let mut progressed = false;
+
+            for message in pending {
+                match ABAProtocol::process_message(aba, message, &sent).unwrap() {
+                    AsyncBinaryAgreementResult::Processed => progressed = true,
+                    AsyncBinaryAgreementResult::Decided => {
+                        decided = true;
+
+                        break;
+                    }
+                    AsyncBinaryAgreementResult::MessageIgnored
+                    | AsyncBinaryAgreementResult::NonMember
+                    | AsyncBinaryAgreementResult::MessageQueued => {}
+                }
+
+                if ABAProtocol::round(aba) > *round {
+                    *round = ABAProtocol::round(aba);
+
+                    aba.broadcast_estimate(&sent).unwrap();
+                }
+            }
+
+            pending = if progressed && !decided {
+                std::iter::from_fn(|| ABAProtocol::poll(aba)).collect()
+            } else {
+                Vec::new()
+            };
+        }
+
+        forward(sent.take(), outbox, |message| {
+            EpochMessage::Agreement(member, message)
+        });
+
+        if decided {
+            let Some(Agreement::Running { aba, .. }) = self.agreements.remove(&member) else {
+                unreachable!()
+            };
+
+            let accepted = ABAProtocol::finalize(aba).unwrap();
+
+            self.agreements.insert(member, Agreement::Decided(accepted));
+
+            self.check_progress(outbox);
+        }
+    }
+
+    /// Give our input to the agreement of `member`, unless we already have
+    fn vote(&mut self, member: NodeId, input: bool, outbox: &Outbox<EpochMessage>) {
+        let Some(Agreement::Waiting(held)) = self.agreements.get_mut(&member) else {
+            return;
+        };
+
+        let held = std::mem::take(held);
+
+        let aba = <AsyncBinaryAgreement as ABAProtocol>::new(
+            input,
+            self.quorum_info.clone(),
+            self.coin_keys.clone(),
+        );
+
+        let sent = Outbox::default();
+
+        aba.broadcast_estimate(&sent).unwrap();
+
+        forward(sent.take(), outbox, |message| {
+            EpochMessage::Agreement(member, message)
+        });
+
+        let round = ABAProtocol::round(&aba);
+
+        self.agreements
+            .insert(member, Agreement::Running { aba, round });
+
+        self.run_agreement(member, held, outbox);
+    }
+
+    /// Move on to the next step of the epoch our current state allows
+    fn check_progress(&mut self, outbox: &Outbox<EpochMessage>) {
+        if !self.election_started && self.delivered_weight() >= self.quorum_info.quorum_size() {
+            self.election_started = true;
+
+            let sent = Outbox::default();
+
+            if let Some(election) = self.election.as_mut() {
+                election.start(&sent).unwrap();
+            }
+
+            forward(sent.take(), outbox, EpochMessage::Election);
+        }
+
+        let Some(committee) = self.committee.clone() else {
+            return;
+        };
+
+        if !self.index_proposed
+            && committee.contains(&self.id)
+            && self.delivered_weight() >= self.quorum_info.quorum_size()
+        {
+            self.index_proposed = true;
+
+            let vector = IndexVector::new(self.batches.keys().cloned().collect());
+            let digest = vector.digest();
+
+            outbox.push_all(
+                self.quorum_info.quorum_members().iter().cloned(),
+                EpochMessage::Index(self.id, ReliableBroadcastMessage::Send(vector, digest)),
+            );
+        }
+
+        for member in &committee {
+            if self.index_vectors.contains_key(member) {
+                self.vote(*member, true, outbox);
+            }
+        }
+
+        let any_accepted = self
+            .agreements
+            .values()
+            .any(|agreement| matches!(agreement, Agreement::Decided(true)));
+
+        if any_accepted {
+            for member in &committee {
+                self.vote(*member, false, outbox);
+            }
+        }
+    }
+}
+
+impl SimProtocol for DumboNode {
+    type Message = EpochMessage;
+    /// The batches included in the epoch, by their proposer
+    type Output = BTreeMap<NodeId, Vec<MsgType>>;
+
+    fn start(&mut self, outbox: &Arc<Outbox<Self::Message>>) {
+        let Some(batch) = self.proposal.take() else {
+            return;
+        };
+
+        let sent = Outbox::default();
+
+        let rbc = <BatchBroadcast as ReliableBroadcast<_>>::new_with_propose(
+            self.id,
+            self.quorum_info.clone(),
+            batch,
+            &sent,
+        );
+
+        self.batch_broadcasts.insert(self.id, rbc);
+
+        forward(sent.take(), outbox, |message| {
+            EpochMessage::Batch(self.id, message)
+        });
+    }
+
+    fn deliver(
+        &mut self,
+        message: StoredMessage<Self::Message>,
+        outbox: &Arc<Outbox<Self::Message>>,
+    ) {
+        let (header, message) = message.into_inner();
+        let from = header.from();
+
+        match message {
+            EpochMessage::Batch(sender, message) => {
+                self.process_batch_message(sender, stored_message(from, self.id, message), outbox)
+            }
+            EpochMessage::Index(sender, message) => {
+                self.process_index_message(sender, stored_message(from, self.id, message), outbox)
+            }
+            EpochMessage::Election(message) => {
+                self.process_election_message(stored_message(from, self.id, message), outbox)
+            }
+            EpochMessage::Agreement(member, message) => self.process_agreement_message(
+                member,
+                stored_message(from, self.id, message),
+                outbox,
+            ),
+        }
+    }
+
+    fn output(&self) -> Option<Self::Output> {
+        let committee = self.committee.as_ref()?;
+
+        let mut included = BTreeSet::new();
+
+        for member in committee {
+            match self.agreements.get(member)? {
+                Agreement::Decided(true) => {
+                    included.extend(self.index_vectors.get(member)?.indices().iter().cloned())
+                }
+                Agreement::Decided(false) => {}
+                Agreement::Waiting(_) | Agreement::Running { .. } => return None,
+            }
+        }
+
+        included
+            .into_iter()
+            .map(|proposer| {
+                let requests = self.batches.get(&proposer)?.plain_requests()?;
+
+                Some((
+                    proposer,
+                    requests.iter().map(|request| *request.message()).collect(),
+                ))
+            })
+            .collect()
+    }
+}
+
+fn simulation(seed: u64, key_set: &PrivateKeySet) -> Simulation<DumboNode> {
+    let qi = quorum_info(N, F);
+
+    let nodes = (0..N).map(|id| {
+        let id = NodeId::from(id);
+
+        (id, DumboNode::new(id, &qi, key_set))
+    });
+
+    Simulation::new(seed, NetworkConfig::default(), nodes)
+}
+
+/// The epoch includes the batches of nodes holding at least `n - f` of the weight,
+/// each of them being the batch its proposer broadcast
+fn is_valid(included: &BTreeMap<NodeId, Vec<MsgType>>) -> bool {
+    let qi = quorum_info(N, F);
+
+    qi.accumulated_weight(included.keys()) >= qi.quorum_size()
+        && included
+            .iter()
+            .all(|(proposer, requests)| *requests == vec![proposer.0 as MsgType])
+}
+
+#[test]
+fn test_epoch_decides_the_same_batches() {
+    for seed in 0..SEEDS {
+        let report = simulation(seed, &PrivateKeySet::gen_random(F)).run();
+
+        report.check_termination().unwrap();
+        report.check_agreement().unwrap();
+        report.check_validity(is_valid).unwrap();
+    }
+}
+
+#[test]
+fn test_epoch_decides_despite_a_silent_node() {
+    for seed in 0..SEEDS {
+        let report = simulation(seed, &PrivateKeySet::gen_random(F))
+            .with_byzantine(NodeId(3), Silent)
+            .run();
+
+        report.check_termination().unwrap();
+        report.check_agreement().unwrap();
+        report.check_validity(is_valid).unwrap();
+    }
+}
@@ -0,0 +1,158 @@
+use crate::dumbo1::ordering::{EpochOrdering, OrderingStrategy};
+use atlas_common::crypto::hash::{Context, Digest};
+use atlas_common::node_id::NodeId;
+use atlas_common::ordering::{Orderable, SeqNo};
+use atlas_communication::lookup_table::MessageModule;
+use atlas_communication::message::{Buf, StoredMessage};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct TestRequest(SeqNo);
+
+impl Orderable for TestRequest {
+    fn sequence_number(&self) -> SeqNo {
+        self.0
+    }
+}
+
+fn request(client: u32, seq_no: u32) -> StoredMessage<TestRequest> {
+    let mut context = Context::new();
+    context.update(&client.to_le_bytes()[..]);
+    context.update(&seq_no.to_le_bytes()[..]);
+
+    let wire_msg = atlas_communication::message::WireMessage::new(
+        NodeId(client),
+        NodeId(0),
+        MessageModule::Application,
+        Buf::new(),
+        0,
+        Some(context.finish()),
+        None,
+    );
+
+    StoredMessage::new(
+        wire_msg.header().clone(),
+        TestRequest(SeqNo::from(seq_no)),
+    )
+}
+
+fn keys(requests: &[StoredMessage<TestRequest>]) -> Vec<(NodeId, SeqNo)> {
+    requests
+        .iter()
+        .map(|rq| (rq.header().from(), rq.message().sequence_number()))
+        .collect()
+}
+
+fn epoch(n: u32) -> SeqNo {
+    SeqNo::from(n)
+}
+
+#[test]
+fn test_proposer_order() {
+    let mut ordering = EpochOrdering::new(OrderingStrategy::ProposerOrder);
+
+    let batches = vec![
+        (NodeId(2), vec![request(102, 0)]),
+        (NodeId(0), vec![request(100, 0), request(100, 1)]),
+        (NodeId(1), vec![request(101, 0)]),
+    ];
+
+    let output = ordering.order_epoch(epoch(1), batches);
+
+    assert_eq!(
+        vec![
+            (NodeId(100), SeqNo::from(0u32)),
+            (NodeId(100), SeqNo::from(1u32)),
+            (NodeId(101), SeqNo::from(0u32)),
+            (NodeId(102), SeqNo::from(0u32)),
+        ],
+        keys(output.requests())
+    );
+}
+
+#[test]
+fn test_duplicates_across_batches_removed() {
+    let mut ordering = EpochOrdering::new(OrderingStrategy::ProposerOrder);
+
+    let batches = vec![
+        (NodeId(0), vec![request(100, 0), request(101, 0)]),
+        (NodeId(1), vec![request(101, 0), request(102, 0)]),
+    ];
+
+    let output = ordering.order_epoch(epoch(1), batches);
+
+    assert_eq!(3, output.requests().len());
+}
+
+#[test]
+fn test_requests_decided_in_earlier_epochs_removed() {
+    let mut ordering = EpochOrdering::new(OrderingStrategy::ProposerOrder);
+
+    ordering.order_epoch(epoch(1), vec![(NodeId(0), vec![request(100, 1)])]);
+
+    let output = ordering.order_epoch(
+        epoch(2),
+        vec![(NodeId(1), vec![request(100, 0), request(100, 1), request(100, 2)])],
+    );
+
+    // Request 0 was never decided, so it is kept even though a later one was
+    assert_eq!(
+        vec![(NodeId(100), SeqNo::from(0u32)), (NodeId(100), SeqNo::from(2u32))],
+        keys(output.requests())
+    );
+}
+
+#[test]
+fn test_restored_requests_are_still_removed() {
+    let mut decided_before = EpochOrdering::new(OrderingStrategy::ProposerOrder);
+
+    decided_before.order_epoch(epoch(1), vec![(NodeId(0), vec![request(100, 0), request(100, 2)])]);
+
+    // A replica resuming from a checkpoint taken after that epoch
+    let mut ordering = EpochOrdering::new(OrderingStrategy::ProposerOrder);
+    ordering.restore(&decided_before.decided());
+
+    let output = ordering.order_epoch(
+        epoch(2),
+        vec![(NodeId(1), vec![request(100, 0), request(100, 1), request(100, 2)])],
+    );
+
+    assert_eq!(vec![(NodeId(100), SeqNo::from(1u32))], keys(output.requests()));
+}
+
+#[test]
+fn test_hash_shuffle_is_deterministic() {
+    let batches = || {
+        vec![
+            (NodeId(0), (0..8).map(|seq| request(100, seq)).collect::<Vec<_>>()),
+            (NodeId(1), (0..8).map(|seq| request(101, seq)).collect::<Vec<_>>()),
+        ]
+    };
+
+    let mut first = EpochOrdering::new(OrderingStrategy::HashShuffled);
+    let mut second = EpochOrdering::new(OrderingStrategy::HashShuffled);
+
+    let mut reversed = batches();
+    reversed.reverse();
+
+    let first_output = first.order_epoch(epoch(1), batches());
+    let second_output = second.order_epoch(epoch(1), reversed);
+
+    assert_eq!(keys(first_output.requests()), keys(second_output.requests()));
+    assert_eq!(first_output.digest(), second_output.digest());
+    assert_eq!(16, first_output.requests().len());
+}
+
+#[test]
+fn test_hash_shuffle_depends_on_epoch() {
+    let batches = || {
+        vec![(
+            NodeId(0),
+            (0..16).map(|client| request(100 + client, 0)).collect::<Vec<_>>(),
+        )]
+    };
+
+    let first = EpochOrdering::new(OrderingStrategy::HashShuffled).order_epoch(epoch(1), batches());
+    let second = EpochOrdering::new(OrderingStrategy::HashShuffled).order_epoch(epoch(2), batches());
+
+    assert_ne!(keys(first.requests()), keys(second.requests()));
+}
@@ -0,0 +1,186 @@
+use crate::dumbo1::batch::ProposedBatch;
+use crate::dumbo1::reconfiguration::{
+    Membership, MembershipKeys, Reconfiguration, ReconfigurationError, Reconfigurations,
+};
+use crate::quorum_info::quorum_info::QuorumInfo;
+use atlas_common::node_id::NodeId;
+use atlas_common::ordering::SeqNo;
+use serde::{Deserialize, Serialize};
+
+const N: u32 = 4;
+const DELAY: usize = 4;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct TestRequest(u32);
+
+fn epoch(n: u32) -> SeqNo {
+    SeqNo::from(n)
+}
+
+fn reconfigurations() -> Reconfigurations {
+    let quorum_info = QuorumInfo::new(N as usize, 1, (0..N).map(NodeId).collect());
+
+    Reconfigurations::new(Membership::new(quorum_info, false, MembershipKeys::default()), DELAY)
+}
+
+/// The reconfiguration growing the quorum from 4 to 7 members
+fn grow_to_seven() -> Reconfiguration {
+    Reconfiguration::new((0..7).map(NodeId).collect()).unwrap()
+}
+
+fn proposed_by(proposers: &[u32], reconfiguration: &Reconfiguration) -> Vec<(NodeId, Reconfiguration)> {
+    proposers
+        .iter()
+        .map(|proposer| (NodeId(*proposer), reconfiguration.clone()))
+        .collect()
+}
+
+#[test]
+fn test_growth_takes_effect_after_delay() {
+    let mut reconfigurations = reconfigurations();
+
+    reconfigurations.request(grow_to_seven(), MembershipKeys::default());
+
+    assert_eq!(reconfigurations.to_propose(), Some(&grow_to_seven()));
+
+    let activation = reconfigurations
+        .observe_decided(epoch(3), &proposed_by(&[0, 2], &grow_to_seven()))
+        .unwrap();
+
+    assert_eq!(activation, epoch(3 + DELAY as u32));
+    assert_eq!(reconfigurations.scheduled_epoch(), Some(activation));
+    assert!(reconfigurations.to_propose().is_none());
+
+    // The epochs before the activation keep the old membership
+    let before = reconfigurations.membership_for(epoch(6)).quorum_info();
+
+    assert_eq!(before.quorum_members().len(), 4);
+    assert_eq!(before.f(), 1);
+
+    let after = reconfigurations.membership_for(activation).quorum_info();
+
+    assert_eq!(after.quorum_members().len(), 7);
+    assert_eq!(after.f(), 2);
+    assert_eq!(after.quorum_size(), 5);
+
+    assert!(reconfigurations.advance_to(epoch(6)).is_none());
+    assert_eq!(reconfigurations.current().quorum_info().quorum_members().len(), 4);
+
+    assert!(reconfigurations.advance_to(activation).is_some());
+    assert_eq!(reconfigurations.current().quorum_info().quorum_members().len(), 7);
+    assert!(reconfigurations.scheduled_epoch().is_none());
+}
+
+#[test]
+fn test_single_proposal_is_not_scheduled() {
+    let mut reconfigurations = reconfigurations();
+
+    // A single member can not reconfigure the quorum on its own
+    assert!(reconfigurations
+        .observe_decided(epoch(1), &proposed_by(&[3], &grow_to_seven()))
+        .is_none());
+
+    // Nor can nodes which are not members of the quorum
+    assert!(reconfigurations
+        .observe_decided(epoch(1), &proposed_by(&[3, 5, 6], &grow_to_seven()))
+        .is_none());
+
+    assert!(reconfigurations.scheduled_epoch().is_none());
+    assert_eq!(reconfigurations.membership_for(epoch(10)).quorum_info().quorum_members().len(), 4);
+}
+
+#[test]
+fn test_differing_proposals_are_not_combined() {
+    let mut reconfigurations = reconfigurations();
+
+    let shrink = Reconfiguration::new((0..3).map(NodeId).collect()).unwrap();
+
+    let mut proposed = proposed_by(&[0], &grow_to_seven());
+    proposed.extend(proposed_by(&[1], &shrink));
+
+    assert!(reconfigurations.observe_decided(epoch(1), &proposed).is_none());
+}
+
+#[test]
+fn test_invalid_memberships_are_rejected() {
+    assert!(matches!(
+        Reconfiguration::new(Vec::new()),
+        Err(ReconfigurationError::EmptyMembership)
+    ));
+    assert!(matches!(
+        Reconfiguration::with_f((0..4).map(NodeId).collect(), 2),
+        Err(ReconfigurationError::TooManyFaults { n: 4, f: 2 })
+    ));
+
+    // Repeated members are removed rather than counted twice
+    let deduplicated = Reconfiguration::new(vec![NodeId(2), NodeId(0), NodeId(2), NodeId(1)]).unwrap();
+
+    assert_eq!(deduplicated.members(), &vec![NodeId(0), NodeId(1), NodeId(2)]);
+    assert_eq!(deduplicated.f(), 0);
+}
+
+#[test]
+fn test_reconfiguration_is_split_from_batch() {
+    let plain = ProposedBatch::<TestRequest>::Plain(Vec::new());
+
+    let proposed = plain.clone().with_reconfiguration(grow_to_seven());
+
+    assert_eq!(proposed.reconfiguration(), Some(&grow_to_seven()));
+    assert!(proposed.plain_requests().is_some());
+    assert_ne!(proposed.digest(), plain.digest());
+
+    let (batch, reconfiguration) = proposed.into_parts();
+
+    assert!(matches!(batch, ProposedBatch::Plain(_)));
+    assert_eq!(batch.digest(), plain.digest());
+    assert_eq!(reconfiguration, Some(grow_to_seven()));
+}
+
+#[test]
+fn test_scheduled_reconfiguration_is_restored() {
+    let mut reconfigurations = reconfigurations();
+    let current = reconfigurations.current().quorum_info().clone();
+
+    // The membership the checkpoint was taken in is ours, so there is nothing to move to yet
+    assert!(reconfigurations
+        .restore(current, false, Some((epoch(7), grow_to_seven().quorum_info(), false)))
+        .is_none());
+    assert_eq!(reconfigurations.scheduled_epoch(), Some(epoch(7)));
+
+    assert!(reconfigurations.advance_to(epoch(7)).is_some());
+    assert_eq!(reconfigurations.current().quorum_info().quorum_members().len(), 7);
+}
+
+#[test]
+fn test_reconfigured_membership_is_restored() {
+    let mut reconfigurations = reconfigurations();
+
+    let restored = reconfigurations
+        .restore(grow_to_seven().quorum_info(), false, None)
+        .unwrap();
+
+    assert_eq!(restored.quorum_info().quorum_members().len(), 7);
+    assert!(reconfigurations.scheduled_epoch().is_none());
+}
+
+#[test]
+fn test_encryption_is_decided_with_the_membership() {
+    let mut reconfigurations = reconfigurations();
+
+    let encrypted = grow_to_seven().with_threshold_encryption();
+
+    assert_ne!(encrypted.digest(), grow_to_seven().digest());
+
+    // Members proposing the same quorum with a different encryption do not support each other
+    let mut proposed = proposed_by(&[0], &encrypted);
+    proposed.extend(proposed_by(&[2], &grow_to_seven()));
+
+    assert!(reconfigurations.observe_decided(epoch(1), &proposed).is_none());
+
+    let activation = reconfigurations
+        .observe_decided(epoch(2), &proposed_by(&[0, 2], &encrypted))
+        .unwrap();
+
+    assert!(!reconfigurations.membership_for(epoch(3)).encrypted());
+    assert!(reconfigurations.membership_for(activation).encrypted());
+}
@@ -0,0 +1,430 @@
+use crate::async_bin_agreement::messages::{
+    AsyncBinaryAgreementMessage, AsyncBinaryAgreementMessageType,
+};
+use crate::dumbo1::batch::ProposedBatch;
+use crate::dumbo1::catch_up::DecidedEpoch;
+use crate::dumbo1::certificate::{AgreementOutcome, DecisionCertificate};
+use crate::dumbo1::checkpoint::{CheckpointState, Checkpoints, StableCheckpoint};
+use crate::dumbo1::wal::{DumboWal, FileLog, WalEntry};
+use crate::quorum_info::quorum_info::QuorumInfo;
+use crate::reliable_broadcast::index_rbc::IndexVector;
+use crate::reliable_broadcast::messages::ReliableBroadcastMessage;
+use crate::rq_aggregator::rq_aggregator::batch_digest;
+use atlas_common::crypto::hash::Context;
+use atlas_common::node_id::NodeId;
+use atlas_common::ordering::SeqNo;
+use atlas_communication::lookup_table::MessageModule;
+use atlas_communication::message::{Buf, StoredMessage};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+const N: u32 = 4;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct TestRequest(u32);
+
+type TestWal = DumboWal<TestRequest, String, String, String>;
+
+type AgreementWal = DumboWal<TestRequest, String, AsyncBinaryAgreementMessage, String>;
+
+/// A path for a log which does not exist yet, unique to the calling test
+fn wal_path(name: &str) -> PathBuf {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+    let path = std::env::temp_dir().join(format!(
+        "dumbo-wal-{}-{}-{}",
+        std::process::id(),
+        name,
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+
+    let _ = std::fs::remove_file(&path);
+
+    path
+}
+
+fn epoch(n: u32) -> SeqNo {
+    SeqNo::from(n)
+}
+
+fn request(client: u32, value: u32) -> StoredMessage<TestRequest> {
+    let mut context = Context::new();
+    context.update(&client.to_le_bytes()[..]);
+    context.update(&value.to_le_bytes()[..]);
+
+    let wire_msg = atlas_communication::message::WireMessage::new(
+        NodeId(client),
+        NodeId(0),
+        MessageModule::Application,
+        Buf::new(),
+        0,
+        Some(context.finish()),
+        None,
+    );
+
+    StoredMessage::new(wire_msg.header().clone(), TestRequest(value))
+}
+
+/// A checkpoint at the given epoch, made stable by the votes of `n - f` members
+fn stable_checkpoint(n: u32) -> StableCheckpoint {
+    let quorum_info = QuorumInfo::new(N as usize, 1, (0..N).map(NodeId).collect());
+
+    let mut checkpoints = Checkpoints::new(quorum_info.clone(), n as usize);
+
+    let vote = (1..=n)
+        .filter_map(|decided| {
+            checkpoints.record_decided(epoch(decided), &batch_digest(&[request(1000, decided)]), || {
+                CheckpointState::new(BTreeMap::new(), quorum_info.clone(), false, None)
+            })
+        })
+        .next()
+        .unwrap();
+
+    for sender in 0..N - 1 {
+        let wire_msg = atlas_communication::message::WireMessage::new(
+            NodeId(sender),
+            NodeId(0),
+            MessageModule::Application,
+            Buf::new(),
+            0,
+            None,
+            None,
+        );
+
+        let vote = StoredMessage::new(wire_msg.header().clone(), vote.clone());
+
+        checkpoints.process_message(vote, epoch(n + 1));
+    }
+
+    checkpoints.last_stable().cloned().unwrap()
+}
+
+fn decided_epoch(n: u32) -> DecidedEpoch<TestRequest> {
+    let requests = vec![request(1000, n)];
+
+    let committee = vec![NodeId(0), NodeId(1)];
+
+    let outcome = AgreementOutcome::new(
+        committee.iter().map(|member| (*member, *member == NodeId(0))).collect(),
+        vec![(NodeId(0), IndexVector::new(vec![NodeId(0)]))],
+        vec![(NodeId(0), batch_digest(&requests))],
+    );
+
+    let certificate =
+        DecisionCertificate::new(epoch(n), committee, outcome, batch_digest(&requests));
+
+    DecidedEpoch::new(certificate, requests)
+}
+
+#[test]
+fn test_file_log_roundtrip() {
+    let path = wal_path("roundtrip");
+
+    {
+        let (mut log, records) = FileLog::open(&path).unwrap();
+
+        assert!(records.is_empty());
+
+        log.append(b"first").unwrap();
+        log.append(b"").unwrap();
+        log.append(b"third").unwrap();
+    }
+
+    let (_, records) = FileLog::open(&path).unwrap();
+
+    assert_eq!(records, vec![b"first".to_vec(), Vec::new(), b"third".to_vec()]);
+
+    let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn test_file_log_discards_torn_record() {
+    let path = wal_path("torn");
+
+    {
+        let (mut log, _) = FileLog::open(&path).unwrap();
+
+        log.append(b"complete").unwrap();
+    }
+
+    // Simulate a crash in the middle of writing a record
+    {
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+
+        file.write_all(&100u32.to_le_bytes()).unwrap();
+        file.write_all(b"partial").unwrap();
+    }
+
+    {
+        let (mut log, records) = FileLog::open(&path).unwrap();
+
+        assert_eq!(records, vec![b"complete".to_vec()]);
+
+        log.append(b"after").unwrap();
+    }
+
+    let (_, records) = FileLog::open(&path).unwrap();
+
+    assert_eq!(records, vec![b"complete".to_vec(), b"after".to_vec()]);
+
+    let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn test_file_log_rewrite() {
+    let path = wal_path("rewrite");
+
+    {
+        let (mut log, _) = FileLog::open(&path).unwrap();
+
+        log.append(b"old").unwrap();
+        log.rewrite([b"kept".as_slice()]).unwrap();
+        log.append(b"new").unwrap();
+    }
+
+    let (_, records) = FileLog::open(&path).unwrap();
+
+    assert_eq!(records, vec![b"kept".to_vec(), b"new".to_vec()]);
+
+    let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn test_recovers_running_epochs() {
+    let path = wal_path("running");
+
+    {
+        let (mut wal, recovered) = TestWal::open(&path).unwrap();
+
+        assert!(recovered.decided.is_empty());
+        assert!(recovered.running.is_empty());
+
+        let index_vector = IndexVector::new((0..3).map(NodeId).collect());
+
+        let entries = [
+            WalEntry::Proposal {
+                epoch: epoch(1),
+                batch: ProposedBatch::Plain(vec![request(1000, 1)]),
+            },
+            WalEntry::ReliableBroadcast {
+                epoch: epoch(1),
+                instance: NodeId(0),
+                message: "echo".to_string(),
+            },
+            WalEntry::IndexBroadcast {
+                epoch: epoch(1),
+                instance: NodeId(1),
+                message: ReliableBroadcastMessage::Echo(index_vector.digest()),
+            },
+            WalEntry::AgreementInput {
+                epoch: epoch(1),
+                instance: NodeId(2),
+                value: true,
+            },
+            WalEntry::AsyncBinaryAgreement {
+                epoch: epoch(1),
+                instance: NodeId(2),
+                message: "bval".to_string(),
+            },
+            WalEntry::Proposal {
+                epoch: epoch(2),
+                batch: ProposedBatch::Plain(Vec::new()),
+            },
+            WalEntry::AgreementInput {
+                epoch: epoch(1),
+                instance: NodeId(3),
+                value: false,
+            },
+        ];
+
+        for entry in &entries {
+            wal.append(entry).unwrap();
+        }
+    }
+
+    let (_, recovered) = TestWal::open(&path).unwrap();
+
+    assert!(recovered.decided.is_empty());
+    assert_eq!(
+        recovered.running.keys().cloned().collect::<Vec<_>>(),
+        vec![epoch(1), epoch(2)]
+    );
+
+    let first = &recovered.running[&epoch(1)];
+
+    assert!(first.proposal.is_some());
+    assert_eq!(
+        first.agreement_inputs,
+        vec![(NodeId(2), true), (NodeId(3), false)]
+    );
+    assert!(matches!(
+        first.sent.as_slice(),
+        [
+            WalEntry::ReliableBroadcast { .. },
+            WalEntry::IndexBroadcast { .. },
+            WalEntry::AsyncBinaryAgreement { .. }
+        ]
+    ));
+
+    let second = &recovered.running[&epoch(2)];
+
+    assert!(second.proposal.is_some());
+    assert!(second.agreement_inputs.is_empty());
+    assert!(second.sent.is_empty());
+
+    let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn test_decided_epochs_are_not_resumed() {
+    let path = wal_path("decided");
+
+    {
+        let (mut wal, _) = TestWal::open(&path).unwrap();
+
+        for n in 1..=3 {
+            wal.append(&WalEntry::Proposal {
+                epoch: epoch(n),
+                batch: ProposedBatch::Plain(Vec::new()),
+            })
+            .unwrap();
+        }
+
+        wal.append(&WalEntry::CommitteeElection {
+            epoch: epoch(1),
+            message: "share".to_string(),
+        })
+        .unwrap();
+
+        wal.append(&WalEntry::Decided(decided_epoch(1))).unwrap();
+        wal.append(&WalEntry::Decided(decided_epoch(2))).unwrap();
+    }
+
+    let (_, recovered) = TestWal::open(&path).unwrap();
+
+    assert_eq!(
+        recovered
+            .decided
+            .iter()
+            .map(|decided| decided.certificate().epoch())
+            .collect::<Vec<_>>(),
+        vec![epoch(1), epoch(2)]
+    );
+    assert!(recovered.decided.iter().all(DecidedEpoch::matches_certificate));
+
+    assert_eq!(
+        recovered.running.keys().cloned().collect::<Vec<_>>(),
+        vec![epoch(3)]
+    );
+
+    let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn test_compaction_keeps_entries_after_checkpoint() {
+    let path = wal_path("compact");
+
+    let (mut wal, _) = TestWal::open(&path).unwrap();
+
+    for n in 1..=3 {
+        wal.append(&WalEntry::Proposal {
+            epoch: epoch(n),
+            batch: ProposedBatch::Plain(Vec::new()),
+        })
+        .unwrap();
+    }
+
+    wal.append(&WalEntry::Decided(decided_epoch(1))).unwrap();
+    wal.append(&WalEntry::Decided(decided_epoch(2))).unwrap();
+
+    wal.compact(&stable_checkpoint(2)).unwrap();
+
+    wal.append(&WalEntry::AgreementInput {
+        epoch: epoch(3),
+        instance: NodeId(1),
+        value: true,
+    })
+    .unwrap();
+
+    drop(wal);
+
+    let (_, recovered) = TestWal::open(&path).unwrap();
+
+    assert_eq!(
+        recovered.checkpoint.as_ref().map(|checkpoint| checkpoint.epoch()),
+        Some(epoch(2))
+    );
+    assert!(recovered.decided.is_empty());
+    assert_eq!(
+        recovered.running.keys().cloned().collect::<Vec<_>>(),
+        vec![epoch(3)]
+    );
+    assert_eq!(
+        recovered.running[&epoch(3)].agreement_inputs,
+        vec![(NodeId(1), true)]
+    );
+
+    let _ = std::fs::remove_file(&path);
+}
+
+fn aux(accepted_estimates: Vec<bool>, round: usize) -> AsyncBinaryAgreementMessage {
+    AsyncBinaryAgreementMessage::new(
+        AsyncBinaryAgreementMessageType::Aux { accepted_estimates },
+        round,
+    )
+}
+
+fn val(estimate: bool, round: usize) -> AsyncBinaryAgreementMessage {
+    AsyncBinaryAgreementMessage::new(AsyncBinaryAgreementMessageType::Val { estimate }, round)
+}
+
+#[test]
+fn test_no_conflicting_vote_after_crash() {
+    let path = wal_path("votes");
+
+    {
+        let (mut wal, _) = AgreementWal::open(&path).unwrap();
+
+        assert!(wal.log_vote(epoch(1), NodeId(2), &val(true, 0)).unwrap());
+        assert!(wal.log_vote(epoch(1), NodeId(2), &aux(vec![true], 0)).unwrap());
+
+        // The replica crashes right after sending its Aux
+    }
+
+    let (mut wal, recovered) = AgreementWal::open(&path).unwrap();
+
+    assert_eq!(recovered.running[&epoch(1)].sent.len(), 2);
+
+    // The restarted agreement accepted the other value first, which must not be sent
+    assert!(!wal.log_vote(epoch(1), NodeId(2), &aux(vec![false], 0)).unwrap());
+    assert!(!wal.log_vote(epoch(1), NodeId(2), &aux(vec![true, false], 0)).unwrap());
+
+    // Sending the same vote again, relaying the other value or moving on is not a contradiction
+    assert!(wal.log_vote(epoch(1), NodeId(2), &aux(vec![true], 0)).unwrap());
+    assert!(wal.log_vote(epoch(1), NodeId(2), &val(false, 0)).unwrap());
+    assert!(wal.log_vote(epoch(1), NodeId(2), &aux(vec![false], 1)).unwrap());
+
+    // Nor is voting in another instance
+    assert!(wal.log_vote(epoch(1), NodeId(3), &aux(vec![false], 0)).unwrap());
+
+    drop(wal);
+
+    // The refused votes were never logged
+    let (_, recovered) = AgreementWal::open(&path).unwrap();
+
+    let refused = recovered.running[&epoch(1)].sent.iter().any(|entry| {
+        matches!(
+            entry,
+            WalEntry::AsyncBinaryAgreement { instance, message, .. }
+                if *instance == NodeId(2) && *message == aux(vec![false], 0)
+        )
+    });
+
+    assert!(!refused);
+
+    let _ = std::fs::remove_file(&path);
+}
@@ -0,0 +1,507 @@
+use crate::aba::{ABAProtocol, VerifiableABAMessage};
+use crate::committee_election::CommitteeElectionProtocol;
+use crate::dumbo1::batch::ProposedBatch;
+use crate::dumbo1::catch_up::DecidedEpoch;
+use crate::dumbo1::checkpoint::StableCheckpoint;
+use crate::rbc::ReliableBroadcast;
+use crate::reliable_broadcast::index_rbc::IndexBroadcastMessage;
+use atlas_common::node_id::NodeId;
+use atlas_common::ordering::{Orderable, SeqNo};
+use atlas_common::serialization_helper::SerMsg;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::{File, OpenOptions};
+use std::io::{BufReader, ErrorKind, Read, Write};
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use thiserror::Error;
+use tracing::warn;
+
+/// A write ahead log shared between the protocol and the epochs it runs.
+pub(super) type SharedWal<RQ, RBM, AM, CEM> = Arc<Mutex<DumboWal<RQ, RBM, AM, CEM>>>;
+
+pub(super) type DumboPWal<
+    RQ,
+    R: ReliableBroadcast<ProposedBatch<RQ>>,
+    A: ABAProtocol,
+    CE: CommitteeElectionProtocol,
+> = SharedWal<RQ, R::ReliableBroadcastMessage, A::AsyncBinaryMessage, CE::Message>;
+
+/// An entry of the write ahead log of a Dumbo replica.
+///
+/// Everything we send is logged before it leaves the replica, so that after a crash
+/// we never send a message which contradicts one we sent before it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(super) enum WalEntry<RQ, RBM, AM, CEM> {
+    /// The batch we proposed in an epoch
+    Proposal {
+        epoch: SeqNo,
+        batch: ProposedBatch<RQ>,
+    },
+    /// The input we provided to the agreement of an instance
+    AgreementInput {
+        epoch: SeqNo,
+        instance: NodeId,
+        value: bool,
+    },
+    /// A reliable broadcast message we sent
+    ReliableBroadcast {
+        epoch: SeqNo,
+        instance: NodeId,
+        message: RBM,
+    },
+    /// A message we sent in the index broadcast of a committee member
+    IndexBroadcast {
+        epoch: SeqNo,
+        instance: NodeId,
+        message: IndexBroadcastMessage,
+    },
+    /// A binary agreement message we sent
+    AsyncBinaryAgreement {
+        epoch: SeqNo,
+        instance: NodeId,
+        message: AM,
+    },
+    /// A committee election message we sent
+    CommitteeElection { epoch: SeqNo, message: CEM },
+    /// An epoch which was decided
+    Decided(DecidedEpoch<RQ>),
+    /// The latest stable checkpoint, which replaces every entry up to its epoch
+    Checkpoint(StableCheckpoint),
+}
+
+impl<RQ, RBM, AM, CEM> WalEntry<RQ, RBM, AM, CEM> {
+    /// The epoch this entry belongs to
+    pub(super) fn epoch(&self) -> SeqNo {
+        match self {
+            WalEntry::Proposal { epoch, .. }
+            | WalEntry::AgreementInput { epoch, .. }
+            | WalEntry::ReliableBroadcast { epoch, .. }
+            | WalEntry::IndexBroadcast { epoch, .. }
+            | WalEntry::AsyncBinaryAgreement { epoch, .. }
+            | WalEntry::CommitteeElection { epoch, .. } => *epoch,
+            WalEntry::Decided(decided) => decided.sequence_number(),
+            WalEntry::Checkpoint(checkpoint) => checkpoint.sequence_number(),
+        }
+    }
+}
+
+/// The state of an epoch which was running when the replica stopped.
+#[derive(Debug)]
+pub(super) struct RecoveredEpoch<RQ, RBM, AM, CEM> {
+    pub(super) proposal: Option<ProposedBatch<RQ>>,
+    pub(super) agreement_inputs: Vec<(NodeId, bool)>,
+    /// The messages we sent in the epoch, in the order they were sent
+    pub(super) sent: Vec<WalEntry<RQ, RBM, AM, CEM>>,
+}
+
+impl<RQ, RBM, AM, CEM> Default for RecoveredEpoch<RQ, RBM, AM, CEM> {
+    fn default() -> Self {
+        Self {
+            proposal: None,
+            agreement_inputs: Vec::new(),
+            sent: Vec::new(),
+        }
+    }
+}
+
+/// The state recovered from the write ahead log.
+#[derive(Debug)]
+pub(super) struct RecoveredState<RQ, RBM, AM, CEM> {
+    /// The latest stable checkpoint
+    pub(super) checkpoint: Option<StableCheckpoint>,
+    /// The decided epochs, in epoch order
+    pub(super) decided: Vec<DecidedEpoch<RQ>>,
+    /// The epochs which had not yet been decided, in epoch order
+    pub(super) running: BTreeMap<SeqNo, RecoveredEpoch<RQ, RBM, AM, CEM>>,
+}
+
+/// The typed write ahead log of a Dumbo replica, on top of a [`FileLog`].
+pub(super) struct DumboWal<RQ, RBM, AM, CEM> {
+    log: FileLog,
+    // The agreement messages logged for the epochs which are still running,
+    // by epoch and instance, so we never send a vote contradicting one of them
+    sent_votes: BTreeMap<(SeqNo, NodeId), Vec<AM>>,
+    _phantom: PhantomData<fn(RQ, RBM, AM, CEM)>,
+}
+
+impl<RQ, RBM, AM, CEM> DumboWal<RQ, RBM, AM, CEM>
+where
+    RQ: SerMsg,
+    RBM: SerMsg,
+    AM: SerMsg,
+    CEM: SerMsg,
+{
+    /// Open the log at the given path, recovering the state it holds
+    pub(super) fn open(path: impl AsRef<Path>) -> Result<(Self, RecoveredState<RQ, RBM, AM, CEM>), WalError> {
+        let (log, records) = FileLog::open(path)?;
+
+        let entries = records
+            .iter()
+            .map(|record| decode(record))
+            .collect::<Result<Vec<WalEntry<RQ, RBM, AM, CEM>>, _>>()?;
+
+        let state = Self::recover(entries);
+
+        let mut sent_votes: BTreeMap<_, Vec<_>> = BTreeMap::new();
+
+        for (epoch, running) in &state.running {
+            for entry in &running.sent {
+                if let WalEntry::AsyncBinaryAgreement {
+                    instance, message, ..
+                } = entry
+                {
+                    sent_votes
+                        .entry((*epoch, *instance))
+                        .or_default()
+                        .push(message.clone());
+                }
+            }
+        }
+
+        let wal = Self {
+            log,
+            sent_votes,
+            _phantom: PhantomData,
+        };
+
+        Ok((wal, state))
+    }
+
+    /// Durably append an entry to the log
+    pub(super) fn append(&mut self, entry: &WalEntry<RQ, RBM, AM, CEM>) -> Result<(), WalError> {
+        self.log.append(&encode(entry)?)?;
+
+        match entry {
+            WalEntry::AsyncBinaryAgreement {
+                epoch,
+                instance,
+                message,
+            } => {
+                self.sent_votes
+                    .entry((*epoch, *instance))
+                    .or_default()
+                    .push(message.clone());
+            }
+            WalEntry::Decided(decided) => {
+                let decided = decided.sequence_number();
+
+                self.sent_votes.retain(|(epoch, _), _| *epoch != decided);
+            }
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    /// Discard every entry covered by the given stable checkpoint, which takes their place
+    pub(super) fn compact(&mut self, checkpoint: &StableCheckpoint) -> Result<(), WalError> {
+        let mut records = vec![encode(&WalEntry::<RQ, RBM, AM, CEM>::Checkpoint(
+            checkpoint.clone(),
+        ))?];
+
+        for record in self.log.read()? {
+            let entry: WalEntry<RQ, RBM, AM, CEM> = decode(&record)?;
+
+            if entry.epoch() > checkpoint.epoch() {
+                records.push(record);
+            }
+        }
+
+        self.log.rewrite(records.iter().map(Vec::as_slice))?;
+
+        self.sent_votes
+            .retain(|(epoch, _), _| *epoch > checkpoint.epoch());
+
+        Ok(())
+    }
+
+    fn recover(entries: Vec<WalEntry<RQ, RBM, AM, CEM>>) -> RecoveredState<RQ, RBM, AM, CEM> {
+        let mut checkpoint = None;
+        let mut decided = BTreeMap::new();
+        let mut running: BTreeMap<SeqNo, RecoveredEpoch<RQ, RBM, AM, CEM>> = BTreeMap::new();
+
+        for entry in entries {
+            match entry {
+                WalEntry::Checkpoint(stable) => {
+                    decided.retain(|epoch, _| *epoch > stable.epoch());
+                    running.retain(|epoch, _| *epoch > stable.epoch());
+
+                    checkpoint = Some(stable);
+                }
+                WalEntry::Decided(epoch) => {
+                    let seq = epoch.certificate().epoch();
+
+                    running.remove(&seq);
+                    decided.insert(seq, epoch);
+                }
+                WalEntry::Proposal { epoch, batch } => {
+                    running.entry(epoch).or_default().proposal = Some(batch);
+                }
+                WalEntry::AgreementInput {
+                    epoch,
+                    instance,
+                    value,
+                } => {
+                    running
+                        .entry(epoch)
+                        .or_default()
+                        .agreement_inputs
+                        .push((instance, value));
+                }
+                WalEntry::ReliableBroadcast { epoch, .. }
+                | WalEntry::IndexBroadcast { epoch, .. }
+                | WalEntry::AsyncBinaryAgreement { epoch, .. }
+                | WalEntry::CommitteeElection { epoch, .. } => {
+                    running.entry(epoch).or_default().sent.push(entry);
+                }
+            }
+        }
+
+        // Anything logged for an epoch which was decided afterwards is no longer needed
+        if let Some(last_decided) = decided.keys().next_back().cloned() {
+            running.retain(|epoch, _| *epoch > last_decided);
+        }
+
+        RecoveredState {
+            checkpoint,
+            decided: decided.into_values().collect(),
+            running,
+        }
+    }
+}
+
+fn encode<RQ, RBM, AM, CEM>(entry: &WalEntry<RQ, RBM, AM, CEM>) -> Result<Vec<u8>, WalError>
+where
+    RQ: SerMsg,
+    RBM: SerMsg,
+    AM: SerMsg,
+    CEM: SerMsg,
+{
+    bincode::serde::encode_to_vec(entry, bincode::config::standard())
+        .map_err(|err| WalError::Serialization(err.to_string()))
+}
+
+fn decode<RQ, RBM, AM, CEM>(record: &[u8]) -> Result<WalEntry<RQ, RBM, AM, CEM>, WalError>
+where
+    RQ: SerMsg,
+    RBM: SerMsg,
+    AM: SerMsg,
+    CEM: SerMsg,
+{
+    bincode::serde::decode_from_slice(record, bincode::config::standard())
+        .map(|(entry, _)| entry)
+        .map_err(|err| WalError::Corrupted(err.to_string()))
+}
+
+impl<RQ, RBM, AM, CEM> DumboWal<RQ, RBM, AM, CEM>
+where
+    RQ: SerMsg,
+    RBM: SerMsg,
+    AM: SerMsg + VerifiableABAMessage,
+    CEM: SerMsg,
+{
+    /// Durably log an agreement message we are about to send in the given instance,
+    /// unless it contradicts one we logged before, even before the replica stopped.
+    ///
+    /// Returns whether the message may be sent.
+    pub(super) fn log_vote(
+        &mut self,
+        epoch: SeqNo,
+        instance: NodeId,
+        message: &AM,
+    ) -> Result<bool, WalError> {
+        if let Some(sent) = self.sent_votes.get(&(epoch, instance)) {
+            if sent.iter().any(|logged| logged.conflicts_with(message)) {
+                return Ok(false);
+            }
+        }
+
+        self.append(&WalEntry::AsyncBinaryAgreement {
+            epoch,
+            instance,
+            message: message.clone(),
+        })?;
+
+        Ok(true)
+    }
+}
+
+/// Durably append an entry to the log, when there is one.
+///
+/// The entry is only built when it is going to be logged.
+pub(super) fn log_entry<RQ, RBM, AM, CEM, F>(
+    wal: Option<&SharedWal<RQ, RBM, AM, CEM>>,
+    entry: F,
+) -> Result<(), WalError>
+where
+    RQ: SerMsg,
+    RBM: SerMsg,
+    AM: SerMsg,
+    CEM: SerMsg,
+    F: FnOnce() -> WalEntry<RQ, RBM, AM, CEM>,
+{
+    let Some(wal) = wal else {
+        return Ok(());
+    };
+
+    wal.lock()
+        .map_err(|_| WalError::Poisoned)?
+        .append(&entry())
+}
+
+/// Durably log an agreement message we are about to send, when there is a log.
+///
+/// Returns whether the message may be sent, see [`DumboWal::log_vote`].
+pub(super) fn log_vote<RQ, RBM, AM, CEM>(
+    wal: Option<&SharedWal<RQ, RBM, AM, CEM>>,
+    epoch: SeqNo,
+    instance: NodeId,
+    message: &AM,
+) -> Result<bool, WalError>
+where
+    RQ: SerMsg,
+    RBM: SerMsg,
+    AM: SerMsg + VerifiableABAMessage,
+    CEM: SerMsg,
+{
+    let Some(wal) = wal else {
+        return Ok(true);
+    };
+
+    wal.lock()
+        .map_err(|_| WalError::Poisoned)?
+        .log_vote(epoch, instance, message)
+}
+
+impl<RQ, RBM, AM, CEM> std::fmt::Debug for DumboWal<RQ, RBM, AM, CEM> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DumboWal").field("log", &self.log).finish()
+    }
+}
+
+/// An append only file of length prefixed records, synced to disk on every append.
+#[derive(Debug)]
+pub(super) struct FileLog {
+    path: PathBuf,
+    file: File,
+}
+
+impl FileLog {
+    /// Open the log at the given path, creating it if it does not exist,
+    /// and read all of the records it holds.
+    ///
+    /// A record which was only partially written when the replica stopped is discarded.
+    pub(super) fn open(path: impl AsRef<Path>) -> Result<(Self, Vec<Vec<u8>>), WalError> {
+        let path = path.as_ref().to_path_buf();
+
+        let (records, valid_length) = match File::open(&path) {
+            Ok(file) => read_records(file)?,
+            Err(err) if err.kind() == ErrorKind::NotFound => (Vec::new(), 0),
+            Err(err) => return Err(err.into()),
+        };
+
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)?;
+
+        if file.metadata()?.len() > valid_length {
+            warn!("Discarding a partially written record at the end of {path:?}");
+
+            file.set_len(valid_length)?;
+        }
+
+        Ok((Self { path, file }, records))
+    }
+
+    pub(super) fn append(&mut self, record: &[u8]) -> Result<(), WalError> {
+        let length = u32::try_from(record.len()).map_err(|_| WalError::RecordTooLarge(record.len()))?;
+
+        self.file.write_all(&length.to_le_bytes())?;
+        self.file.write_all(record)?;
+        self.file.sync_data()?;
+
+        Ok(())
+    }
+
+    /// Read all of the records currently in the log
+    pub(super) fn read(&self) -> Result<Vec<Vec<u8>>, WalError> {
+        let (records, _) = read_records(File::open(&self.path)?)?;
+
+        Ok(records)
+    }
+
+    /// Replace the contents of the log with the given records
+    pub(super) fn rewrite<'a, I>(&mut self, records: I) -> Result<(), WalError>
+    where
+        I: IntoIterator<Item = &'a [u8]>,
+    {
+        let temp_path = self.path.with_extension("compact");
+
+        {
+            let mut temp = File::create(&temp_path)?;
+
+            for record in records {
+                let length =
+                    u32::try_from(record.len()).map_err(|_| WalError::RecordTooLarge(record.len()))?;
+
+                temp.write_all(&length.to_le_bytes())?;
+                temp.write_all(record)?;
+            }
+
+            temp.sync_all()?;
+        }
+
+        std::fs::rename(&temp_path, &self.path)?;
+
+        self.file = OpenOptions::new().append(true).open(&self.path)?;
+
+        Ok(())
+    }
+}
+
+/// Read all of the complete records in the file, and the length of the file they occupy
+fn read_records(file: File) -> Result<(Vec<Vec<u8>>, u64), WalError> {
+    let mut reader = BufReader::new(file);
+    let mut records = Vec::new();
+    let mut valid_length = 0u64;
+
+    loop {
+        let mut length = [0u8; 4];
+
+        match reader.read_exact(&mut length) {
+            Ok(()) => {}
+            Err(err) if err.kind() == ErrorKind::UnexpectedEof => break,
+            Err(err) => return Err(err.into()),
+        }
+
+        let mut record = vec![0u8; u32::from_le_bytes(length) as usize];
+
+        match reader.read_exact(&mut record) {
+            Ok(()) => {}
+            Err(err) if err.kind() == ErrorKind::UnexpectedEof => break,
+            Err(err) => return Err(err.into()),
+        }
+
+        valid_length += (length.len() + record.len()) as u64;
+        records.push(record);
+    }
+
+    Ok((records, valid_length))
+}
+
+#[derive(Error, Debug)]
+pub enum WalError {
+    #[error("IO error in the write ahead log: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Failed to serialize a write ahead log entry: {0}")]
+    Serialization(String),
+    #[error("The write ahead log is corrupted: {0}")]
+    Corrupted(String),
+    #[error("Write ahead log record of {0} bytes is too large")]
+    RecordTooLarge(usize),
+    #[error("The write ahead log lock is poisoned")]
+    Poisoned,
+}
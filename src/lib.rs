@@ -1,12 +1,21 @@
 #![feature(lazy_type_alias)]
+#![cfg_attr(test, feature(test))]
+
+#[cfg(test)]
+extern crate test;
 
 mod reliable_broadcast {
+    pub mod index_rbc;
     pub mod messages;
     pub mod reliable_broadcast;
 
     #[cfg(test)]
     pub mod test {
+        pub mod index_rbc_test;
+        pub mod model_test;
+        pub mod reliable_broadcast_bench;
         pub mod reliable_broadcast_test;
+        pub mod simulation_test;
     }
 }
 
@@ -21,19 +30,83 @@ mod async_bin_agreement {
     pub mod pending_messages;
     #[cfg(test)]
     pub mod test {
+        pub mod async_bin_agreement_bench;
         pub mod async_bin_agreement_test;
         pub mod message_handling_test;
+        pub mod model_test;
+        pub mod property_test;
+        pub mod simulation_test;
+    }
+}
+
+#[cfg(test)]
+mod simulator {
+    pub mod byzantine {
+        pub mod aba;
+        pub mod rbc;
+        pub mod strategy;
+    }
+    pub mod model_checker;
+    pub mod network;
+    pub mod properties;
+    pub mod simulation;
+
+    mod test {
+        pub mod epoch_bench;
+        pub mod network_test;
     }
 }
 
 pub mod aba;
+pub mod metrics;
 pub mod rbc;
-mod rq_aggregator;
+pub mod tpke;
+pub mod rq_aggregator {
+    pub mod rq_aggregator;
+
+    #[cfg(test)]
+    pub mod test {
+        pub mod rq_aggregator_test;
+    }
+}
 mod committee_election;
 
+mod coin_election {
+    pub mod coin_election;
+    #[cfg(test)]
+    pub mod test {
+        pub mod coin_election_test;
+    }
+}
+
 pub mod dumbo1 {
+    pub mod batch;
+    pub mod buffer;
+    mod catch_up;
+    pub mod certificate;
+    pub mod checkpoint;
+    pub mod config;
     pub mod protocol;
+    mod decryption;
     mod epoch;
+    mod inbox;
     mod message;
     mod network;
+    pub mod ordering;
+    pub mod reconfiguration;
+    mod wal;
+    #[cfg(test)]
+    mod test {
+        pub mod buffer_test;
+        pub mod catch_up_test;
+        pub mod certificate_test;
+        pub mod checkpoint_test;
+        pub mod decryption_test;
+        pub mod epoch_test;
+        pub mod inbox_test;
+        pub mod integration_test;
+        pub mod ordering_test;
+        pub mod reconfiguration_test;
+        pub mod wal_test;
+    }
 }
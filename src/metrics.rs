@@ -0,0 +1,139 @@
+//! The metrics of the Dumbo stack, which take the 15XX range.
+//! Their names and ids are stable, so they can be relied on by dashboards.
+
+use atlas_metrics::metrics::MetricKind;
+use atlas_metrics::{MetricLevel, MetricRegistry};
+
+/// The time between receiving (or sending) the proposal of a reliable broadcast and delivering it
+pub const RBC_LATENCY: &str = "RBC_LATENCY";
+pub const RBC_LATENCY_ID: usize = 1500;
+
+/// The amount of rounds a binary agreement took to decide
+pub const ABA_ROUNDS_TO_DECIDE: &str = "ABA_ROUNDS_TO_DECIDE";
+pub const ABA_ROUNDS_TO_DECIDE_ID: usize = 1501;
+
+/// The duration of each round of a binary agreement
+pub const ABA_ROUND_LATENCY: &str = "ABA_ROUND_LATENCY";
+pub const ABA_ROUND_LATENCY_ID: usize = 1502;
+
+/// The amount of common coins which could not be combined from the shares received
+pub const ABA_COIN_FAILURES: &str = "ABA_COIN_FAILURES";
+pub const ABA_COIN_FAILURES_ID: usize = 1503;
+
+/// The time between starting a committee election and its decision
+pub const CE_LATENCY: &str = "CE_LATENCY";
+pub const CE_LATENCY_ID: usize = 1504;
+
+/// The time between starting an epoch and delivering its decision
+pub const EPOCH_LATENCY: &str = "EPOCH_LATENCY";
+pub const EPOCH_LATENCY_ID: usize = 1505;
+
+/// The amount of requests in the batch we propose in each epoch
+pub const BATCH_SIZE: &str = "BATCH_SIZE";
+pub const BATCH_SIZE_ID: usize = 1506;
+
+/// The amount of requests decided, from which the throughput is derived
+pub const REQUESTS_DECIDED: &str = "REQUESTS_DECIDED";
+pub const REQUESTS_DECIDED_ID: usize = 1507;
+
+/// The amount of epochs decided
+pub const EPOCHS_DECIDED: &str = "EPOCHS_DECIDED";
+pub const EPOCHS_DECIDED_ID: usize = 1508;
+
+/// The amount of requests waiting to be proposed by this node
+pub const REQUEST_QUEUE_DEPTH: &str = "REQUEST_QUEUE_DEPTH";
+pub const REQUEST_QUEUE_DEPTH_ID: usize = 1509;
+
+/// The amount of decisions waiting to be delivered to the replica
+pub const PENDING_DECISIONS_DEPTH: &str = "PENDING_DECISIONS_DEPTH";
+pub const PENDING_DECISIONS_DEPTH_ID: usize = 1510;
+
+/// The amount of messages buffered for epochs which have not yet started
+pub const FUTURE_MESSAGES_DEPTH: &str = "FUTURE_MESSAGES_DEPTH";
+pub const FUTURE_MESSAGES_DEPTH_ID: usize = 1511;
+
+/// The amount of messages dropped by the sub protocols as their sender is not part of the quorum
+pub const NON_MEMBER_MESSAGES: &str = "NON_MEMBER_MESSAGES";
+pub const NON_MEMBER_MESSAGES_ID: usize = 1512;
+
+/// The amount of agreement votes which failed to be broadcast to the quorum
+pub const ABA_BROADCAST_FAILURES: &str = "ABA_BROADCAST_FAILURES";
+pub const ABA_BROADCAST_FAILURES_ID: usize = 1513;
+
+/// The metrics of the Dumbo stack, to be registered when the metrics are initialized
+pub fn metrics() -> Vec<MetricRegistry> {
+    vec![
+        (RBC_LATENCY_ID, RBC_LATENCY.to_string(), MetricKind::Duration).into(),
+        (
+            ABA_ROUNDS_TO_DECIDE_ID,
+            ABA_ROUNDS_TO_DECIDE.to_string(),
+            MetricKind::Count,
+        )
+            .into(),
+        (
+            ABA_ROUND_LATENCY_ID,
+            ABA_ROUND_LATENCY.to_string(),
+            MetricKind::Duration,
+        )
+            .into(),
+        (
+            ABA_COIN_FAILURES_ID,
+            ABA_COIN_FAILURES.to_string(),
+            MetricKind::Counter,
+        )
+            .into(),
+        (CE_LATENCY_ID, CE_LATENCY.to_string(), MetricKind::Duration).into(),
+        (
+            EPOCH_LATENCY_ID,
+            EPOCH_LATENCY.to_string(),
+            MetricKind::Duration,
+            MetricLevel::Info,
+        )
+            .into(),
+        (BATCH_SIZE_ID, BATCH_SIZE.to_string(), MetricKind::Count).into(),
+        (
+            REQUESTS_DECIDED_ID,
+            REQUESTS_DECIDED.to_string(),
+            MetricKind::Counter,
+            MetricLevel::Info,
+        )
+            .into(),
+        (
+            EPOCHS_DECIDED_ID,
+            EPOCHS_DECIDED.to_string(),
+            MetricKind::Counter,
+            MetricLevel::Info,
+        )
+            .into(),
+        (
+            REQUEST_QUEUE_DEPTH_ID,
+            REQUEST_QUEUE_DEPTH.to_string(),
+            MetricKind::Count,
+        )
+            .into(),
+        (
+            PENDING_DECISIONS_DEPTH_ID,
+            PENDING_DECISIONS_DEPTH.to_string(),
+            MetricKind::Count,
+        )
+            .into(),
+        (
+            FUTURE_MESSAGES_DEPTH_ID,
+            FUTURE_MESSAGES_DEPTH.to_string(),
+            MetricKind::Count,
+        )
+            .into(),
+        (
+            NON_MEMBER_MESSAGES_ID,
+            NON_MEMBER_MESSAGES.to_string(),
+            MetricKind::Counter,
+        )
+            .into(),
+        (
+            ABA_BROADCAST_FAILURES_ID,
+            ABA_BROADCAST_FAILURES.to_string(),
+            MetricKind::Counter,
+        )
+            .into(),
+    ]
+}
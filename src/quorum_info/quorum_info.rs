@@ -1,28 +1,109 @@
 use atlas_common::node_id::NodeId;
 use getset::{CopyGetters, Getters};
+use serde::{Deserialize, Serialize};
+use std::borrow::Borrow;
+use std::ops::Range;
 
-#[derive(Debug, Clone, PartialEq, Eq, Getters, CopyGetters)]
+/// The members of a quorum, along with the voting power of each of them.
+///
+/// Every threshold of the protocols is expressed over accumulated weight:
+/// `f` is the largest weight the faulty members may hold and `quorum_size` is `n - f`,
+/// where `n` is the total weight of the quorum.
+/// When every member has a weight of 1 these are the usual node counts.
+#[derive(Debug, Clone, PartialEq, Eq, Getters, CopyGetters, Serialize, Deserialize)]
 pub struct QuorumInfo {
     #[get_copy = "pub"]
     f: usize,
     #[get_copy = "pub"]
     quorum_size: usize,
+    #[get_copy = "pub"]
+    total_weight: usize,
     #[get = "pub"]
     quorum_members: Vec<NodeId>,
+    // The weight of each member, in the order of `quorum_members`
+    #[get = "pub"]
+    weights: Vec<usize>,
 }
 
 impl QuorumInfo {
+    /// A quorum of `n` members which each have a weight of 1
     pub fn new(n: usize, f: usize, quorum_members: Vec<NodeId>) -> Self {
         assert!(n > 0 && f <= (n - 1) / 3, "Invalid quorum parameters");
+        assert_eq!(n, quorum_members.len(), "The quorum must have n members");
         let quorum_size = n - f;
+        let weights = vec![1; quorum_members.len()];
         Self {
             f,
             quorum_size,
+            total_weight: n,
             quorum_members,
+            weights,
+        }
+    }
+
+    /// A quorum where each member votes with the given weight, and the faulty members
+    /// hold at most `f` of the total weight
+    pub fn weighted(f: usize, members: Vec<(NodeId, usize)>) -> Self {
+        let total_weight = members.iter().map(|(_, weight)| weight).sum::<usize>();
+
+        assert!(
+            total_weight > 0
+                && f <= (total_weight - 1) / 3
+                && members.iter().all(|(_, weight)| *weight > 0),
+            "Invalid quorum parameters"
+        );
+
+        let (quorum_members, weights) = members.into_iter().unzip();
+
+        Self {
+            f,
+            quorum_size: total_weight - f,
+            total_weight,
+            quorum_members,
+            weights,
         }
     }
 
     pub fn is_member(&self, node_id: NodeId) -> bool {
         self.quorum_members.contains(&node_id)
     }
+
+    /// The weight of the given node, which is 0 for nodes outside the quorum
+    pub fn weight_of(&self, node_id: NodeId) -> usize {
+        self.quorum_members
+            .iter()
+            .position(|member| *member == node_id)
+            .map_or(0, |index| self.weights[index])
+    }
+
+    /// The indices of the threshold key shares dealt to the given node, which is empty for nodes
+    /// outside the quorum.
+    ///
+    /// Threshold keys are dealt over the total weight of the quorum, so each member holds
+    /// one key share per unit of its weight, following the order of the members.
+    pub fn key_shares(&self, node_id: NodeId) -> Range<usize> {
+        let Some(index) = self
+            .quorum_members
+            .iter()
+            .position(|member| *member == node_id)
+        else {
+            return 0..0;
+        };
+
+        let first = self.weights[..index].iter().sum::<usize>();
+
+        first..first + self.weights[index]
+    }
+
+    /// The weight accumulated by the given nodes, which must be distinct
+    pub fn accumulated_weight<I>(&self, nodes: I) -> usize
+    where
+        I: IntoIterator,
+        I::Item: Borrow<NodeId>,
+    {
+        nodes
+            .into_iter()
+            .map(|node| self.weight_of(*node.borrow()))
+            .sum()
+    }
 }
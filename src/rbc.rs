@@ -1,3 +1,4 @@
+use crate::quorum_info::quorum_info::QuorumInfo;
 use crate::reliable_broadcast::messages::ReliableBroadcastMessage;
 use atlas_common::node_id::NodeId;
 use atlas_common::serialization_helper::SerMsg;
@@ -11,14 +12,18 @@ use std::sync::Arc;
 /// The protocol ensures that messages broadcasted by a node are reliably delivered to all correct nodes in the network.
 ///
 pub trait ReliableBroadcast<RQ> {
-    type ReliableBroadcastMessage: SerMsg;
-    fn new() -> Self;
+    type ReliableBroadcastMessage: SerMsg + VerifiableRBCMessage<RQ>;
 
-    fn new_with_propose<NT>(request: RQ, network: &NT) -> Self
+    /// Create the broadcast of `sender`, run by the members of the given quorum
+    fn new(sender: NodeId, quorum_info: QuorumInfo) -> Self;
+
+    /// Create our own broadcast, run by the members of the given quorum, and propose the request in it
+    fn new_with_propose<NT>(sender: NodeId, quorum_info: QuorumInfo, request: RQ, network: &NT) -> Self
     where
         NT: ReliableBroadcastSendNode<Self::ReliableBroadcastMessage>;
 
-    fn poll(&mut self) -> Option<Self::ReliableBroadcastMessage>;
+    /// Polls the protocol for pending messages which can now be processed
+    fn poll(&mut self) -> Option<StoredMessage<Self::ReliableBroadcastMessage>>;
 
     fn process_message<NT>(
         &mut self,
@@ -31,9 +36,19 @@ pub trait ReliableBroadcast<RQ> {
     fn finalize(self) -> RQ;
 }
 
+/// Stateless inspection of reliable broadcast messages, so that they can be verified
+/// before they reach the protocol instance.
+pub trait VerifiableRBCMessage<RQ> {
+    /// The value proposed in this message, if it is the initial proposal of the broadcast.
+    /// Only the broadcaster of the instance may send the proposal.
+    fn proposal(&self) -> Option<&RQ>;
+}
+
 pub enum ReliableBroadcastResult {
     MessageQueued,
     MessageIgnored,
+    /// The message was sent by a node outside the quorum, so it was dropped
+    NonMember,
     Processed,
     Finalized,
 }
@@ -75,3 +90,37 @@ where
     where
         I: Iterator<Item = NodeId>;
 }
+
+/// A shared sending end, so the reliable broadcast can be driven with the [`Arc`] a node is held in
+impl<BCM, NT> ReliableBroadcastSendNode<BCM> for Arc<NT>
+where
+    BCM: SerMsg,
+    NT: ReliableBroadcastSendNode<BCM>,
+{
+    fn send(&self, message: BCM, target: NodeId, flush: bool) -> atlas_common::error::Result<()> {
+        (**self).send(message, target, flush)
+    }
+
+    fn send_signed(
+        &self,
+        message: BCM,
+        target: NodeId,
+        flush: bool,
+    ) -> atlas_common::error::Result<()> {
+        (**self).send_signed(message, target, flush)
+    }
+
+    fn broadcast<I>(&self, message: BCM, targets: I) -> std::result::Result<(), Vec<NodeId>>
+    where
+        I: Iterator<Item = NodeId>,
+    {
+        (**self).broadcast(message, targets)
+    }
+
+    fn broadcast_signed<I>(&self, message: BCM, targets: I) -> std::result::Result<(), Vec<NodeId>>
+    where
+        I: Iterator<Item = NodeId>,
+    {
+        (**self).broadcast_signed(message, targets)
+    }
+}
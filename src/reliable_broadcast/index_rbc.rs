@@ -0,0 +1,195 @@
+use crate::quorum_info::quorum_info::QuorumInfo;
+use crate::reliable_broadcast::messages::ReliableBroadcastMessage;
+use crate::reliable_broadcast::reliable_broadcast::BroadcastPayload;
+use atlas_common::collections::HashSet;
+use atlas_common::crypto::hash::{Context, Digest};
+use atlas_common::node_id::NodeId;
+use getset::Getters;
+use serde::{Deserialize, Deserializer, Serialize};
+use thiserror::Error;
+
+/// The messages of the index reliable broadcast of a committee member
+pub(crate) type IndexBroadcastMessage = ReliableBroadcastMessage<IndexVector>;
+
+/// The payload of an index reliable broadcast.
+///
+/// In Dumbo1, each committee member reliably broadcasts the indices (the senders)
+/// of `n - f` reliable broadcast instances it has already delivered.
+/// Honest nodes only echo such a vector once they have delivered all of the
+/// referenced broadcasts themselves, which guarantees that any index vector
+/// that is delivered can be resolved into batches by every correct node.
+///
+/// The indices are always sorted, and vectors received from other nodes whose
+/// indices are not strictly increasing fail to deserialize.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Getters, Serialize)]
+pub struct IndexVector {
+    #[get = "pub(crate)"]
+    indices: Vec<NodeId>,
+}
+
+impl IndexVector {
+    pub(crate) fn new(mut indices: Vec<NodeId>) -> Self {
+        // Keep the indices sorted so the digest does not depend on delivery order
+        indices.sort();
+
+        Self { indices }
+    }
+
+    /// Calculate the digest of this index vector, which is the digest
+    /// used to identify the index reliable broadcast
+    pub(crate) fn digest(&self) -> Digest {
+        let mut context = Context::new();
+
+        self.indices
+            .iter()
+            .for_each(|index| context.update(&index.0.to_le_bytes()[..]));
+
+        context.finish()
+    }
+
+    /// Validate the structure of this index vector against the current quorum.
+    ///
+    /// The vector must reference distinct members of the quorum, holding at least `n - f` of its weight.
+    pub(crate) fn validate(&self, quorum_info: &QuorumInfo) -> Result<(), IndexRBCError> {
+        let mut seen = HashSet::default();
+
+        for index in &self.indices {
+            if !quorum_info.is_member(*index) {
+                return Err(IndexRBCError::UnknownIndex(*index));
+            }
+
+            if !seen.insert(*index) {
+                return Err(IndexRBCError::DuplicateIndex(*index));
+            }
+        }
+
+        let weight = quorum_info.accumulated_weight(&seen);
+
+        if weight < quorum_info.quorum_size() {
+            return Err(IndexRBCError::NotEnoughIndices(
+                weight,
+                quorum_info.quorum_size(),
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+impl<'de> Deserialize<'de> for IndexVector {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        struct UncheckedIndexVector {
+            indices: Vec<NodeId>,
+        }
+
+        let UncheckedIndexVector { indices } = UncheckedIndexVector::deserialize(deserializer)?;
+
+        // Any other order would give the same vector a different digest
+        if indices.windows(2).any(|pair| pair[0] >= pair[1]) {
+            return Err(serde::de::Error::custom(
+                "The indices of an index vector must be strictly increasing",
+            ));
+        }
+
+        Ok(Self { indices })
+    }
+}
+
+impl BroadcastPayload for IndexVector {
+    fn payload_digest(&self) -> Digest {
+        self.digest()
+    }
+}
+
+/// The outcome of checking whether a proposal can be echoed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Deliverability {
+    /// The proposal can be echoed right away
+    Deliverable,
+    /// The proposal is valid, but we are still missing some of the data
+    /// it depends on, so we must withhold our echo for now
+    Withheld,
+    /// The proposal is invalid and must never be echoed
+    Invalid,
+}
+
+/// A predicate deciding whether the proposal of a reliable broadcast instance
+/// can be echoed by this node.
+///
+/// The plain reliable broadcast uses [`AlwaysDeliverable`], while the index
+/// reliable broadcast of Dumbo1 uses [`DeliveredBatches`].
+pub(crate) trait DeliverabilityPredicate<P> {
+    fn deliverability(&self, proposal: &P) -> Deliverability;
+}
+
+/// The predicate used by the regular reliable broadcast, which echoes
+/// every proposal it receives.
+#[derive(Debug, Default, Clone, Copy)]
+pub(crate) struct AlwaysDeliverable;
+
+impl<P> DeliverabilityPredicate<P> for AlwaysDeliverable {
+    fn deliverability(&self, _: &P) -> Deliverability {
+        Deliverability::Deliverable
+    }
+}
+
+/// Tracks the batch reliable broadcasts that have been delivered locally in an epoch,
+/// and decides whether an index vector can be echoed.
+#[derive(Debug, Clone, Getters)]
+pub(crate) struct DeliveredBatches {
+    #[get = "pub(crate)"]
+    quorum_info: QuorumInfo,
+    #[get = "pub(crate)"]
+    delivered: HashSet<NodeId>,
+}
+
+impl DeliveredBatches {
+    pub(crate) fn new(quorum_info: QuorumInfo) -> Self {
+        Self {
+            quorum_info,
+            delivered: HashSet::default(),
+        }
+    }
+
+    /// Register that the batch broadcast by the given sender has been delivered.
+    /// Returns true if this is the first time we see this delivery
+    pub(crate) fn register_delivered(&mut self, sender: NodeId) -> bool {
+        self.delivered.insert(sender)
+    }
+
+    pub(crate) fn is_delivered(&self, sender: NodeId) -> bool {
+        self.delivered.contains(&sender)
+    }
+}
+
+impl DeliverabilityPredicate<IndexVector> for DeliveredBatches {
+    fn deliverability(&self, index_vector: &IndexVector) -> Deliverability {
+        if index_vector.validate(&self.quorum_info).is_err() {
+            return Deliverability::Invalid;
+        }
+
+        if index_vector
+            .indices()
+            .iter()
+            .all(|index| self.is_delivered(*index))
+        {
+            Deliverability::Deliverable
+        } else {
+            Deliverability::Withheld
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum IndexRBCError {
+    #[error("The index vector references {0:?}, which is not a member of the quorum")]
+    UnknownIndex(NodeId),
+    #[error("The index vector references {0:?} more than once")]
+    DuplicateIndex(NodeId),
+    #[error("The index vector only references a weight of {0}, {1} is required")]
+    NotEnoughIndices(usize, usize),
+}
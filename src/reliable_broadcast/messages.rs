@@ -1,15 +1,17 @@
+use crate::rbc::VerifiableRBCMessage;
 use atlas_common::crypto::hash::Digest;
-use atlas_communication::message::StoredMessage;
 use serde::{Deserialize, Serialize};
 
+/// The messages of a reliable broadcast of a value of type `P`.
+/// Only the proposal carries the value, the echoes and readies refer to it by its digest.
 #[derive(Debug, Serialize, Deserialize)]
-pub(crate) enum ReliableBroadcastMessage<RQ> {
-    Send(Vec<StoredMessage<RQ>>, Digest),
+pub enum ReliableBroadcastMessage<P> {
+    Send(P, Digest),
     Echo(Digest),
     Ready(Digest),
 }
 
-impl<RQ> PartialEq for ReliableBroadcastMessage<RQ> {
+impl<P> PartialEq for ReliableBroadcastMessage<P> {
     fn eq(&self, other: &Self) -> bool {
         match (self, other) {
             (
@@ -23,19 +25,28 @@ impl<RQ> PartialEq for ReliableBroadcastMessage<RQ> {
     }
 }
 
-impl<RQ> Eq for ReliableBroadcastMessage<RQ> where RQ: PartialEq {}
+impl<P> Eq for ReliableBroadcastMessage<P> where P: PartialEq {}
 
-impl<RQ> Clone for ReliableBroadcastMessage<RQ>
+impl<P> Clone for ReliableBroadcastMessage<P>
 where
-    RQ: Clone,
+    P: Clone,
 {
     fn clone(&self) -> Self {
         match self {
-            ReliableBroadcastMessage::Send(messages, digest) => {
-                ReliableBroadcastMessage::Send(messages.clone(), *digest)
+            ReliableBroadcastMessage::Send(proposal, digest) => {
+                ReliableBroadcastMessage::Send(proposal.clone(), *digest)
             }
             ReliableBroadcastMessage::Echo(digest) => ReliableBroadcastMessage::Echo(*digest),
             ReliableBroadcastMessage::Ready(digest) => ReliableBroadcastMessage::Ready(*digest),
         }
     }
 }
+
+impl<P> VerifiableRBCMessage<P> for ReliableBroadcastMessage<P> {
+    fn proposal(&self) -> Option<&P> {
+        match self {
+            ReliableBroadcastMessage::Send(proposal, _) => Some(proposal),
+            ReliableBroadcastMessage::Echo(_) | ReliableBroadcastMessage::Ready(_) => None,
+        }
+    }
+}
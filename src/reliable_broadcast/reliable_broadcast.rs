@@ -1,21 +1,32 @@
+use crate::metrics::RBC_LATENCY_ID;
 use crate::quorum_info::quorum_info::QuorumInfo;
-use crate::rbc::ReliableBroadcastSendNode;
+use crate::rbc::{
+    ReliableBroadcast, ReliableBroadcastResult as RBCResult, ReliableBroadcastSendNode,
+};
+use crate::reliable_broadcast::index_rbc::{
+    AlwaysDeliverable, Deliverability, DeliverabilityPredicate,
+};
 use crate::reliable_broadcast::messages::ReliableBroadcastMessage;
+use crate::rq_aggregator::rq_aggregator::batch_digest;
 use atlas_common::collections::HashSet;
 use atlas_common::crypto::hash::Digest;
 use atlas_common::node_id::NodeId;
 use atlas_common::serialization_helper::SerMsg;
 use atlas_communication::message::StoredMessage;
+use atlas_metrics::metrics::metric_duration;
 use getset::{Getters, MutGetters};
 use std::collections::VecDeque;
 use std::fmt::Debug;
-use std::sync::Arc;
+use std::time::Instant;
 use thiserror::Error;
-use tracing::warn;
+use tracing::{debug, debug_span, warn};
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 enum ReliableBroadcastState {
     Init,
+    /// We have received a SEND message, but its deliverability predicate
+    /// does not hold yet, so our echo is withheld.
+    AwaitingDelivery,
     /// We have received a SEND message and are waiting for echoes.
     Proposed,
     /// We have received enough echoes and are waiting for readies.
@@ -24,30 +35,50 @@ enum ReliableBroadcastState {
     Ready,
 }
 
+/// A value which can be reliably broadcast, identified by its digest.
+///
+/// The digest carried along with a proposal must match the digest of the proposed value,
+/// so the echoes and readies of an instance can refer to the value by it.
+pub(crate) trait BroadcastPayload: Clone {
+    fn payload_digest(&self) -> Digest;
+}
+
+/// A batch of requests, identified by the digests of their headers
+impl<RQ> BroadcastPayload for Vec<StoredMessage<RQ>>
+where
+    RQ: Clone,
+{
+    fn payload_digest(&self) -> Digest {
+        batch_digest(self)
+    }
+}
+
 /// An instance of the reliable broadcast protocol.
 ///
 /// It holds the state of the protocol for a specific sender and quorum.
 /// It tracks the proposed messages, message tracking information, and pending messages.
 ///
-#[derive(Debug, Getters)]
-pub(super) struct ReliableBroadcastInstance<RQ> {
-    #[get = "pub(super)"]
+#[derive(Debug, Clone, Getters)]
+pub(crate) struct ReliableBroadcastInstance<P> {
+    #[get = "pub(crate)"]
     sender: NodeId,
     #[get = ""]
     quorum_info: QuorumInfo,
     #[get = ""]
-    proposed_messages: Option<(Vec<StoredMessage<RQ>>, Digest)>,
+    proposed_messages: Option<(P, Digest)>,
     #[get = ""]
     message_tracking: MessageTracking,
     #[get = ""]
     reliable_broadcast_state: ReliableBroadcastState,
     #[get = ""]
-    pending_messages: PendingMessages<RQ>,
+    pending_messages: PendingMessages<P>,
+    // When the proposal of this broadcast was received, to measure the latency of its delivery
+    proposal_received_at: Option<Instant>,
 }
 
-impl<RQ> ReliableBroadcastInstance<RQ>
+impl<P> ReliableBroadcastInstance<P>
 where
-    RQ: SerMsg,
+    P: BroadcastPayload,
 {
     pub fn new(sender: NodeId, quorum_info: QuorumInfo) -> Self {
         Self {
@@ -56,47 +87,108 @@ where
             proposed_messages: None,
             message_tracking: MessageTracking::default(),
             reliable_broadcast_state: ReliableBroadcastState::Init,
-            pending_messages: PendingMessages::<RQ>::default(),
+            pending_messages: PendingMessages::<P>::default(),
+            proposal_received_at: None,
         }
     }
 
-    pub(super) fn has_pending(&self) -> bool {
+    pub(crate) fn has_pending(&self) -> bool {
         match self.reliable_broadcast_state {
             ReliableBroadcastState::Proposed => !self.pending_messages.echoes.is_empty(),
             ReliableBroadcastState::Echoed => !self.pending_messages.readies.is_empty(),
-            ReliableBroadcastState::Init | ReliableBroadcastState::Ready => false,
+            ReliableBroadcastState::Init
+            | ReliableBroadcastState::AwaitingDelivery
+            | ReliableBroadcastState::Ready => false,
         }
     }
 
-    pub(super) fn poll(&mut self) -> Option<StoredMessage<ReliableBroadcastMessage<RQ>>> {
+    pub(crate) fn poll(&mut self) -> Option<StoredMessage<ReliableBroadcastMessage<P>>> {
         match self.reliable_broadcast_state {
             ReliableBroadcastState::Proposed => self.pending_messages.pop_echo(),
             ReliableBroadcastState::Echoed => self.pending_messages.pop_ready(),
-            ReliableBroadcastState::Ready | ReliableBroadcastState::Init => None,
+            ReliableBroadcastState::Ready
+            | ReliableBroadcastState::Init
+            | ReliableBroadcastState::AwaitingDelivery => None,
         }
     }
 
     /// Processes a message received from the network or queued in the pending messages.
-    pub(super) fn process_message<NT>(
+    pub(crate) fn process_message<NT>(
+        &mut self,
+        sys_msg: StoredMessage<ReliableBroadcastMessage<P>>,
+        network: &NT,
+    ) -> ReliableBroadcastResult<P>
+    where
+        NT: ReliableBroadcastSendNode<ReliableBroadcastMessage<P>>,
+    {
+        self.process_message_with_predicate(sys_msg, network, &AlwaysDeliverable)
+    }
+
+    /// Processes a message, only echoing the proposal once the given
+    /// deliverability predicate holds for it.
+    ///
+    /// When the predicate does not hold yet, the proposal is stored and
+    /// [`Self::try_release_echo`] must be called once more data is available locally.
+    pub(crate) fn process_message_with_predicate<NT, D>(
         &mut self,
-        sys_msg: StoredMessage<ReliableBroadcastMessage<RQ>>,
-        network: &Arc<NT>,
-    ) -> ReliableBroadcastResult<RQ>
+        sys_msg: StoredMessage<ReliableBroadcastMessage<P>>,
+        network: &NT,
+        predicate: &D,
+    ) -> ReliableBroadcastResult<P>
     where
-        NT: ReliableBroadcastSendNode<ReliableBroadcastMessage<RQ>>,
+        NT: ReliableBroadcastSendNode<ReliableBroadcastMessage<P>>,
+        D: DeliverabilityPredicate<P>,
     {
+        let _span = debug_span!("broadcast", sender = ?self.sender).entered();
+
         let (header, message) = sys_msg.clone().into_inner();
 
+        if !self.quorum_info.is_member(header.from()) {
+            // Checked before queueing, so nodes outside the quorum can not fill our pending messages
+            return ReliableBroadcastResult::NonMember;
+        }
+
         match message {
-            ReliableBroadcastMessage::Send(messages, digest)
+            ReliableBroadcastMessage::Send(_, _) if header.from() != self.sender => {
+                warn!(
+                    from = ?header.from(),
+                    "Received a send message from a node other than the sender, ignoring."
+                );
+
+                ReliableBroadcastResult::MessageIgnored
+            }
+            ReliableBroadcastMessage::Send(proposal, digest)
+                if proposal.payload_digest() != digest =>
+            {
+                warn!("Received a send message whose proposal does not match its digest, ignoring.");
+
+                ReliableBroadcastResult::MessageIgnored
+            }
+            ReliableBroadcastMessage::Send(proposal, digest)
                 if self.proposed_messages.is_none()
                     && matches!(self.reliable_broadcast_state, ReliableBroadcastState::Init) =>
             {
-                self.proposed_messages = Some((messages, digest));
-
-                self.broadcast_echo_message(digest, network);
-
-                self.reliable_broadcast_state = ReliableBroadcastState::Proposed;
+                match predicate.deliverability(&proposal) {
+                    Deliverability::Invalid => {
+                        warn!("Received a send message which is not valid for this instance, ignoring.");
+
+                        return ReliableBroadcastResult::MessageIgnored;
+                    }
+                    Deliverability::Withheld => {
+                        self.proposed_messages = Some((proposal, digest));
+                        self.proposal_received_at = Some(Instant::now());
+
+                        self.transition(ReliableBroadcastState::AwaitingDelivery);
+                    }
+                    Deliverability::Deliverable => {
+                        self.proposed_messages = Some((proposal, digest));
+                        self.proposal_received_at = Some(Instant::now());
+
+                        self.broadcast_echo_message(digest, network);
+
+                        self.transition(ReliableBroadcastState::Proposed);
+                    }
+                }
 
                 ReliableBroadcastResult::Progressed(sys_msg)
             }
@@ -114,11 +206,13 @@ where
             {
                 self.message_tracking.handle_received_echo(header.from());
 
-                if self.message_tracking.received_echoes().len()
+                if self
+                    .quorum_info
+                    .accumulated_weight(self.message_tracking.received_echoes())
                     >= self.quorum_info().quorum_size() - self.quorum_info.f()
                     && !self.message_tracking.sent_echo()
                 {
-                    self.reliable_broadcast_state = ReliableBroadcastState::Echoed;
+                    self.transition(ReliableBroadcastState::Echoed);
                     self.broadcast_ready_message(digest, network);
 
                     self.message_tracking.set_sent_echo();
@@ -135,12 +229,19 @@ where
             {
                 self.message_tracking.handle_received_ready(header.from());
 
-                if self.message_tracking.received_readies().len() > 2 * self.quorum_info.f()
+                if self
+                    .quorum_info
+                    .accumulated_weight(self.message_tracking.received_readies())
+                    > 2 * self.quorum_info.f()
                     && !self.message_tracking.sent_ready()
                 {
-                    self.reliable_broadcast_state = ReliableBroadcastState::Ready;
+                    self.transition(ReliableBroadcastState::Ready);
                     self.message_tracking.set_sent_ready();
 
+                    if let Some(received_at) = self.proposal_received_at {
+                        metric_duration(RBC_LATENCY_ID, received_at.elapsed());
+                    }
+
                     return ReliableBroadcastResult::Finalized;
                 }
 
@@ -154,13 +255,142 @@ where
         }
     }
 
+    /// Re-evaluates the deliverability predicate of a withheld proposal,
+    /// broadcasting our echo if it now holds.
+    ///
+    /// Returns true if the echo was released, after which the pending messages
+    /// of this instance should be polled.
+    pub(crate) fn try_release_echo<NT, D>(&mut self, network: &NT, predicate: &D) -> bool
+    where
+        NT: ReliableBroadcastSendNode<ReliableBroadcastMessage<P>>,
+        D: DeliverabilityPredicate<P>,
+    {
+        if !matches!(
+            self.reliable_broadcast_state,
+            ReliableBroadcastState::AwaitingDelivery
+        ) {
+            return false;
+        }
+
+        let Some((proposal, digest)) = self.proposed_messages.as_ref() else {
+            return false;
+        };
+
+        if !matches!(
+            predicate.deliverability(proposal),
+            Deliverability::Deliverable
+        ) {
+            return false;
+        }
+
+        let digest = *digest;
+
+        self.broadcast_echo_message(digest, network);
+
+        self.transition(ReliableBroadcastState::Proposed);
+
+        true
+    }
+
+    fn transition(&mut self, next: ReliableBroadcastState) {
+        debug!(
+            sender = ?self.sender,
+            from = ?self.reliable_broadcast_state,
+            to = ?next,
+            "Reliable broadcast state transition"
+        );
+
+        self.reliable_broadcast_state = next;
+    }
+
     fn get_current_digest(&self) -> Option<Digest> {
         self.proposed_messages.as_ref().map(|(_, digest)| *digest)
     }
 
-    fn broadcast_echo_message<NT>(&self, digest: Digest, network: &Arc<NT>)
+    /// Whether the message can no longer have any effect on this instance
+    #[cfg(test)]
+    pub(super) fn ignores(&self, message: &ReliableBroadcastMessage<P>) -> bool {
+        let digest = match message {
+            ReliableBroadcastMessage::Send(_, _) => {
+                return !matches!(self.reliable_broadcast_state, ReliableBroadcastState::Init);
+            }
+            ReliableBroadcastMessage::Echo(digest) => {
+                if matches!(
+                    self.reliable_broadcast_state,
+                    ReliableBroadcastState::Echoed | ReliableBroadcastState::Ready
+                ) {
+                    return true;
+                }
+
+                digest
+            }
+            ReliableBroadcastMessage::Ready(digest) => {
+                if matches!(self.reliable_broadcast_state, ReliableBroadcastState::Ready) {
+                    return true;
+                }
+
+                digest
+            }
+        };
+
+        // Once we have a proposal, votes for any other digest are queued forever
+        self.get_current_digest()
+            .is_some_and(|current| current != *digest)
+    }
+
+    /// Feed into `hasher` the part of the state which can still influence this instance,
+    /// so the model checker can merge the schedules which lead to the same state
+    #[cfg(test)]
+    pub(super) fn fingerprint<H: std::hash::Hasher>(&self, hasher: &mut H) {
+        use std::hash::Hash;
+
+        self.reliable_broadcast_state.hash(hasher);
+        self.get_current_digest().hash(hasher);
+
+        let sorted = |nodes: &HashSet<NodeId>| {
+            let mut nodes = nodes.iter().cloned().collect::<Vec<_>>();
+            nodes.sort();
+            nodes
+        };
+
+        match self.reliable_broadcast_state {
+            ReliableBroadcastState::Proposed => {
+                sorted(self.message_tracking.received_echoes()).hash(hasher)
+            }
+            ReliableBroadcastState::Echoed => {
+                sorted(self.message_tracking.received_readies()).hash(hasher)
+            }
+            ReliableBroadcastState::Init
+            | ReliableBroadcastState::AwaitingDelivery
+            | ReliableBroadcastState::Ready => {}
+        }
+
+        let mut pending = self
+            .pending_messages
+            .echoes
+            .iter()
+            .chain(self.pending_messages.readies.iter())
+            .filter(|message| !self.ignores(message.message()))
+            .map(|message| {
+                let (is_echo, digest) = match message.message() {
+                    ReliableBroadcastMessage::Echo(digest) => (true, digest),
+                    ReliableBroadcastMessage::Ready(digest) => (false, digest),
+                    ReliableBroadcastMessage::Send(_, _) => {
+                        unreachable!("Only Echo and Ready messages are queued")
+                    }
+                };
+
+                (message.header().from(), is_echo, digest.as_ref().to_vec())
+            })
+            .collect::<Vec<_>>();
+
+        pending.sort();
+        pending.hash(hasher);
+    }
+
+    fn broadcast_echo_message<NT>(&self, digest: Digest, network: &NT)
     where
-        NT: ReliableBroadcastSendNode<ReliableBroadcastMessage<RQ>>,
+        NT: ReliableBroadcastSendNode<ReliableBroadcastMessage<P>>,
     {
         let message = ReliableBroadcastMessage::Echo(digest);
 
@@ -171,9 +401,9 @@ where
         }
     }
 
-    fn broadcast_ready_message<NT>(&self, digest: Digest, network: &Arc<NT>)
+    fn broadcast_ready_message<NT>(&self, digest: Digest, network: &NT)
     where
-        NT: ReliableBroadcastSendNode<ReliableBroadcastMessage<RQ>>,
+        NT: ReliableBroadcastSendNode<ReliableBroadcastMessage<P>>,
     {
         let message = ReliableBroadcastMessage::Ready(digest);
 
@@ -184,13 +414,11 @@ where
         }
     }
 
-    pub(super) fn finalize(
-        self,
-    ) -> Result<(Vec<StoredMessage<RQ>>, Digest), ReliableBroadcastError> {
+    pub(crate) fn finalize(self) -> Result<(P, Digest), ReliableBroadcastError> {
         if matches!(self.reliable_broadcast_state, ReliableBroadcastState::Ready) {
             // We can finalize the broadcast
-            if let Some((messages, digest)) = self.proposed_messages {
-                Ok((messages, digest))
+            if let Some((proposal, digest)) = self.proposed_messages {
+                Ok((proposal, digest))
             } else {
                 Err(ReliableBroadcastError::NoProposedMessages)
             }
@@ -204,14 +432,81 @@ where
     }
 }
 
-pub(super) enum ReliableBroadcastResult<RQ> {
+/// The reliable broadcast run for the batches of a Dumbo epoch.
+///
+/// Every proposal is echoed as soon as it is received, as a batch does not depend on any other data.
+impl<P> ReliableBroadcast<P> for ReliableBroadcastInstance<P>
+where
+    P: BroadcastPayload,
+    ReliableBroadcastMessage<P>: SerMsg,
+{
+    type ReliableBroadcastMessage = ReliableBroadcastMessage<P>;
+
+    fn new(sender: NodeId, quorum_info: QuorumInfo) -> Self {
+        ReliableBroadcastInstance::new(sender, quorum_info)
+    }
+
+    fn new_with_propose<NT>(
+        sender: NodeId,
+        quorum_info: QuorumInfo,
+        request: P,
+        network: &NT,
+    ) -> Self
+    where
+        NT: ReliableBroadcastSendNode<Self::ReliableBroadcastMessage>,
+    {
+        let digest = request.payload_digest();
+
+        // Our own proposal is delivered to us along with everyone else, so it is processed like any other
+        if let Err(failed) = network.broadcast_signed(
+            ReliableBroadcastMessage::Send(request, digest),
+            quorum_info.quorum_members().iter().cloned(),
+        ) {
+            warn!("Failed to broadcast our proposal to {failed:?}");
+        }
+
+        ReliableBroadcastInstance::new(sender, quorum_info)
+    }
+
+    fn poll(&mut self) -> Option<StoredMessage<Self::ReliableBroadcastMessage>> {
+        ReliableBroadcastInstance::poll(self)
+    }
+
+    fn process_message<NT>(
+        &mut self,
+        message: StoredMessage<Self::ReliableBroadcastMessage>,
+        network: &NT,
+    ) -> RBCResult
+    where
+        NT: ReliableBroadcastSendNode<Self::ReliableBroadcastMessage>,
+    {
+        match ReliableBroadcastInstance::process_message(self, message, network) {
+            ReliableBroadcastResult::MessageIgnored => RBCResult::MessageIgnored,
+            ReliableBroadcastResult::NonMember => RBCResult::NonMember,
+            ReliableBroadcastResult::MessageQueued => RBCResult::MessageQueued,
+            ReliableBroadcastResult::Progressed(_) => RBCResult::Processed,
+            ReliableBroadcastResult::Finalized => RBCResult::Finalized,
+        }
+    }
+
+    fn finalize(self) -> P {
+        let (proposal, _) = ReliableBroadcastInstance::finalize(self)
+            .expect("The reliable broadcast is only finalized once it has delivered its proposal");
+
+        proposal
+    }
+}
+
+pub(crate) enum ReliableBroadcastResult<P> {
     MessageIgnored,
+    /// The message was sent by a node outside the quorum, so it was dropped
+    NonMember,
     MessageQueued,
-    Progressed(StoredMessage<ReliableBroadcastMessage<RQ>>),
+    Progressed(StoredMessage<ReliableBroadcastMessage<P>>),
     Finalized,
 }
 
-#[derive(MutGetters)]
+#[derive(Clone, MutGetters)]
 struct PendingMessages<M> {
     #[get_mut]
     echoes: VecDeque<StoredMessage<ReliableBroadcastMessage<M>>>,
@@ -261,7 +556,7 @@ impl<M> Debug for PendingMessages<M> {
     }
 }
 
-#[derive(Default, Debug, Getters)]
+#[derive(Default, Debug, Clone, Getters)]
 struct MessageTracking {
     #[get = "pub(super)"]
     received_echoes: HashSet<NodeId>,
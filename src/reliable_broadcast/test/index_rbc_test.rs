@@ -0,0 +1,236 @@
+use crate::quorum_info::quorum_info::QuorumInfo;
+use crate::rbc::ReliableBroadcastSendNode;
+use crate::reliable_broadcast::index_rbc::{
+    DeliveredBatches, IndexRBCError, IndexVector,
+};
+use crate::reliable_broadcast::messages::ReliableBroadcastMessage;
+use crate::reliable_broadcast::reliable_broadcast::{
+    ReliableBroadcastInstance, ReliableBroadcastResult,
+};
+use atlas_common::crypto::hash::Digest;
+use atlas_common::node_id::NodeId;
+use atlas_communication::lookup_table::MessageModule;
+use atlas_communication::message::{Buf, StoredMessage};
+use std::cell::RefCell;
+use std::sync::Arc;
+
+// Mock network to capture broadcasts
+#[derive(Default)]
+struct MockNetwork {
+    sent: RefCell<Vec<(ReliableBroadcastMessage<IndexVector>, Vec<NodeId>)>>,
+}
+
+impl ReliableBroadcastSendNode<ReliableBroadcastMessage<IndexVector>> for MockNetwork {
+    fn send(
+        &self,
+        message: ReliableBroadcastMessage<IndexVector>,
+        target: NodeId,
+        flush: bool,
+    ) -> atlas_common::error::Result<()> {
+        self.send_signed(message, target, flush)
+    }
+    fn send_signed(
+        &self,
+        message: ReliableBroadcastMessage<IndexVector>,
+        target: NodeId,
+        _flush: bool,
+    ) -> atlas_common::error::Result<()> {
+        self.sent.borrow_mut().push((message, vec![target]));
+        Ok(())
+    }
+    fn broadcast<I>(
+        &self,
+        message: ReliableBroadcastMessage<IndexVector>,
+        targets: I,
+    ) -> Result<(), Vec<NodeId>>
+    where
+        I: Iterator<Item = NodeId>,
+    {
+        self.sent.borrow_mut().push((message, targets.collect()));
+        Ok(())
+    }
+    fn broadcast_signed<I>(
+        &self,
+        message: ReliableBroadcastMessage<IndexVector>,
+        targets: I,
+    ) -> Result<(), Vec<NodeId>>
+    where
+        I: Iterator<Item = NodeId>,
+    {
+        self.broadcast(message, targets)
+    }
+}
+
+const N: usize = 4;
+const F: usize = 1;
+
+fn quorum_info(n: usize, f: usize) -> QuorumInfo {
+    QuorumInfo::new(n, f, (0..n).map(NodeId::from).collect())
+}
+
+fn stored_msg<T>(from: NodeId, to: NodeId, msg: T) -> StoredMessage<T> {
+    let wire_msg = atlas_communication::message::WireMessage::new(
+        from,
+        to,
+        MessageModule::Application,
+        Buf::new(),
+        0,
+        Some(Digest::blank()),
+        None,
+    );
+
+    StoredMessage::new(wire_msg.header().clone(), msg)
+}
+
+fn index_send(
+    sender: NodeId,
+    indices: Vec<NodeId>,
+) -> StoredMessage<ReliableBroadcastMessage<IndexVector>> {
+    let vector = IndexVector::new(indices);
+    let digest = vector.digest();
+
+    stored_msg(sender, sender, ReliableBroadcastMessage::Send(vector, digest))
+}
+
+fn sent_echo(network: &MockNetwork) -> bool {
+    network
+        .sent
+        .borrow()
+        .iter()
+        .any(|(msg, _)| matches!(msg, ReliableBroadcastMessage::Echo(_)))
+}
+
+#[test]
+fn test_index_vector_validation() {
+    let quorum = quorum_info(N, F);
+
+    assert!(
+        IndexVector::new((0..3).map(NodeId::from).collect())
+            .validate(&quorum)
+            .is_ok()
+    );
+
+    assert!(matches!(
+        IndexVector::new(vec![NodeId(0), NodeId(1)]).validate(&quorum),
+        Err(IndexRBCError::NotEnoughIndices(2, 3))
+    ));
+
+    assert!(matches!(
+        IndexVector::new(vec![NodeId(0), NodeId(1), NodeId(1)]).validate(&quorum),
+        Err(IndexRBCError::DuplicateIndex(_))
+    ));
+
+    assert!(matches!(
+        IndexVector::new(vec![NodeId(0), NodeId(1), NodeId(7)]).validate(&quorum),
+        Err(IndexRBCError::UnknownIndex(_))
+    ));
+}
+
+#[test]
+fn test_index_digest_independent_of_order() {
+    let first = IndexVector::new(vec![NodeId(2), NodeId(0), NodeId(1)]);
+    let second = IndexVector::new(vec![NodeId(0), NodeId(1), NodeId(2)]);
+
+    assert_eq!(first.digest(), second.digest());
+}
+
+#[test]
+fn test_echo_withheld_until_batches_delivered() {
+    let quorum = quorum_info(N, F);
+    let sender = NodeId(0);
+    let mut rbc = ReliableBroadcastInstance::<IndexVector>::new(sender, quorum.clone());
+    let network = Arc::new(MockNetwork::default());
+    let mut delivered = DeliveredBatches::new(quorum);
+
+    delivered.register_delivered(NodeId(0));
+    delivered.register_delivered(NodeId(1));
+
+    let send = index_send(sender, (0..3).map(NodeId::from).collect());
+    let result = rbc.process_message_with_predicate(send, &network, &delivered);
+
+    assert!(matches!(result, ReliableBroadcastResult::Progressed(_)));
+    assert!(!sent_echo(&network), "Echo must be withheld until all batches are delivered");
+
+    // Nothing changed, so the echo must remain withheld
+    assert!(!rbc.try_release_echo(&network, &delivered));
+
+    delivered.register_delivered(NodeId(2));
+
+    assert!(rbc.try_release_echo(&network, &delivered));
+    assert!(sent_echo(&network));
+
+    // The echo must only be released once
+    assert!(!rbc.try_release_echo(&network, &delivered));
+}
+
+#[test]
+fn test_echo_immediate_when_batches_delivered() {
+    let quorum = quorum_info(N, F);
+    let sender = NodeId(1);
+    let mut rbc = ReliableBroadcastInstance::<IndexVector>::new(sender, quorum.clone());
+    let network = Arc::new(MockNetwork::default());
+    let mut delivered = DeliveredBatches::new(quorum);
+
+    (0..N).map(NodeId::from).for_each(|node| {
+        delivered.register_delivered(node);
+    });
+
+    let send = index_send(sender, (1..4).map(NodeId::from).collect());
+    rbc.process_message_with_predicate(send, &network, &delivered);
+
+    assert!(sent_echo(&network));
+}
+
+#[test]
+fn test_invalid_index_proposal_ignored() {
+    let quorum = quorum_info(N, F);
+    let sender = NodeId(0);
+    let mut rbc = ReliableBroadcastInstance::<IndexVector>::new(sender, quorum.clone());
+    let network = Arc::new(MockNetwork::default());
+    let delivered = DeliveredBatches::new(quorum);
+
+    // Only f + 1 indices, which is less than the required n - f
+    let send = index_send(sender, vec![NodeId(0), NodeId(1)]);
+    let result = rbc.process_message_with_predicate(send, &network, &delivered);
+
+    assert!(matches!(result, ReliableBroadcastResult::MessageIgnored));
+    assert!(!sent_echo(&network));
+}
+
+#[test]
+fn test_index_from_other_node_is_ignored() {
+    let quorum = quorum_info(N, F);
+    let mut rbc = ReliableBroadcastInstance::<IndexVector>::new(NodeId(0), quorum.clone());
+    let network = Arc::new(MockNetwork::default());
+    let mut delivered = DeliveredBatches::new(quorum);
+
+    (0..N).map(NodeId::from).for_each(|node| {
+        delivered.register_delivered(node);
+    });
+
+    // The vector of node 2 can not be the proposal of the broadcast of node 0
+    let send = index_send(NodeId(2), (0..3).map(NodeId::from).collect());
+    let result = rbc.process_message_with_predicate(send, &network, &delivered);
+
+    assert!(matches!(result, ReliableBroadcastResult::MessageIgnored));
+    assert!(!sent_echo(&network));
+}
+
+#[test]
+fn test_unsorted_index_vector_rejected() {
+    let config = bincode::config::standard();
+
+    let vector = IndexVector::new(vec![NodeId(2), NodeId(0), NodeId(1)]);
+    let encoded = bincode::serde::encode_to_vec(&vector, config).unwrap();
+
+    let (decoded, _): (IndexVector, _) =
+        bincode::serde::decode_from_slice(&encoded, config).unwrap();
+
+    assert_eq!(vector, decoded);
+
+    // A vector encodes the same as its indices, so these are an unsorted vector
+    let unsorted =
+        bincode::serde::encode_to_vec(vec![NodeId(2), NodeId(0), NodeId(1)], config).unwrap();
+
+    assert!(bincode::serde::decode_from_slice::<IndexVector, _>(&unsorted, config).is_err());
+}
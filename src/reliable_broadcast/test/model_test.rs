@@ -0,0 +1,95 @@
+use crate::quorum_info::quorum_info::QuorumInfo;
+use crate::reliable_broadcast::messages::ReliableBroadcastMessage;
+use crate::reliable_broadcast::test::simulation_test::{RbcNode, make_batch, make_digest};
+use crate::simulator::model_checker::{ModelCheckError, ModelChecker, agreement, all_output};
+use atlas_common::node_id::NodeId;
+
+const N: usize = 4;
+const F: usize = 1;
+
+/// The correct nodes of the model, as the last node is faulty
+const CORRECT: [NodeId; 3] = [NodeId(0), NodeId(1), NodeId(2)];
+const FAULTY: NodeId = NodeId(3);
+
+fn quorum_info() -> QuorumInfo {
+    QuorumInfo::new(N, F, (0..N).map(NodeId::from).collect())
+}
+
+/// The correct nodes broadcasting the proposal of the first of them, with the faulty node crashed
+fn honest_broadcast() -> ModelChecker<RbcNode> {
+    let quorum_info = quorum_info();
+    let sender = CORRECT[0];
+
+    ModelChecker::new(CORRECT.map(|id| {
+        let proposal = (id == sender).then(|| make_batch(42));
+
+        (id, RbcNode::new(sender, &quorum_info, proposal))
+    }))
+}
+
+#[test]
+fn test_every_schedule_delivers_the_proposal() {
+    let exploration = honest_broadcast()
+        .with_invariant("agreement", agreement)
+        .with_invariant("validity", |outputs| {
+            outputs
+                .values()
+                .flatten()
+                .all(|digest| *digest == make_digest(42))
+        })
+        .with_quiescent_invariant("no deadlock", all_output)
+        .explore()
+        .unwrap_or_else(|err| panic!("{err}"));
+
+    assert!(exploration.quiescent_states > 0);
+}
+
+#[test]
+fn test_equivocating_sender_never_breaks_agreement() {
+    let quorum_info = quorum_info();
+
+    let model = ModelChecker::new(CORRECT.map(|id| (id, RbcNode::new(FAULTY, &quorum_info, None))));
+
+    // The faulty sender proposes a different digest to the first node, and backs
+    // each side of its equivocation with its own echo and ready
+    let model = CORRECT.iter().fold(model, |model, target| {
+        let value = if *target == CORRECT[0] { 1 } else { 2 };
+        let digest = make_digest(value);
+
+        model
+            .with_message(
+                FAULTY,
+                *target,
+                ReliableBroadcastMessage::Send(make_batch(value), digest),
+            )
+            .with_message(FAULTY, *target, ReliableBroadcastMessage::Echo(digest))
+            .with_message(FAULTY, *target, ReliableBroadcastMessage::Ready(digest))
+    });
+
+    model
+        .with_invariant("agreement", agreement)
+        .explore()
+        .unwrap_or_else(|err| panic!("{err}"));
+}
+
+#[test]
+fn test_violation_reports_shortest_schedule() {
+    let result = honest_broadcast()
+        .with_invariant("nothing delivered", |outputs| {
+            outputs.values().all(Option::is_none)
+        })
+        .explore();
+
+    let Err(ModelCheckError::Violation {
+        invariant, trace, ..
+    }) = result
+    else {
+        panic!("The delivery of the proposal should violate the invariant");
+    };
+
+    assert_eq!(invariant, "nothing delivered");
+
+    // Each correct node needs the proposal and two echoes to send its ready,
+    // and the first node to deliver needs the readies of all three
+    assert_eq!(trace.len(), 3 + 3 * 2 + 3);
+}
@@ -0,0 +1,92 @@
+use crate::quorum_info::quorum_info::QuorumInfo;
+use crate::reliable_broadcast::messages::ReliableBroadcastMessage;
+use crate::reliable_broadcast::reliable_broadcast::{
+    ReliableBroadcastInstance, ReliableBroadcastResult,
+};
+use crate::reliable_broadcast::test::simulation_test::{Batch, MsgType};
+use crate::rq_aggregator::rq_aggregator::batch_digest;
+use crate::simulator::network::Outbox;
+use crate::simulator::simulation::stored_message;
+use atlas_common::node_id::NodeId;
+use atlas_communication::message::StoredMessage;
+use std::hint::black_box;
+use std::sync::Arc;
+use test::Bencher;
+
+/// The messages a node receives in a broadcast of the first node, in the order
+/// which makes each of them progress: the proposal, then every echo, then every ready
+fn broadcast_messages(
+    quorum_info: &QuorumInfo,
+    batch_size: usize,
+) -> Vec<StoredMessage<ReliableBroadcastMessage<Batch>>> {
+    let members = quorum_info.quorum_members();
+    let (sender, receiver) = (members[0], members[1]);
+
+    let batch = (0..batch_size)
+        .map(|request| stored_message(sender, receiver, request as MsgType))
+        .collect::<Vec<_>>();
+
+    let digest = batch_digest(&batch);
+
+    let proposal = ReliableBroadcastMessage::Send(batch, digest);
+
+    let votes = [
+        ReliableBroadcastMessage::Echo(digest),
+        ReliableBroadcastMessage::Ready(digest),
+    ];
+
+    std::iter::once(stored_message(sender, receiver, proposal))
+        .chain(votes.into_iter().flat_map(|vote| {
+            members
+                .iter()
+                .map(move |member| stored_message(*member, receiver, vote.clone()))
+        }))
+        .collect()
+}
+
+/// Process the messages of a single broadcast until it delivers, which takes the proposal,
+/// every echo and `2f + 1` readies, so the cost per message is the time per iteration over those
+fn bench_broadcast(bencher: &mut Bencher, n: usize, batch_size: usize) {
+    let f = (n - 1) / 3;
+    let quorum_info = QuorumInfo::new(n, f, (0..n).map(NodeId::from).collect());
+
+    let messages = broadcast_messages(&quorum_info, batch_size);
+    let outbox = Arc::new(Outbox::default());
+
+    bencher.iter(|| {
+        let mut instance =
+            ReliableBroadcastInstance::<Batch>::new(NodeId(0), quorum_info.clone());
+
+        let finalized = messages.iter().cloned().any(|message| {
+            matches!(
+                instance.process_message(message, &outbox),
+                ReliableBroadcastResult::Finalized
+            )
+        });
+
+        assert!(finalized);
+
+        black_box(outbox.take());
+        black_box(instance)
+    });
+}
+
+#[bench]
+fn bench_broadcast_n4(bencher: &mut Bencher) {
+    bench_broadcast(bencher, 4, 1);
+}
+
+#[bench]
+fn bench_broadcast_n16(bencher: &mut Bencher) {
+    bench_broadcast(bencher, 16, 1);
+}
+
+#[bench]
+fn bench_broadcast_n64(bencher: &mut Bencher) {
+    bench_broadcast(bencher, 64, 1);
+}
+
+#[bench]
+fn bench_broadcast_n16_batch_1000(bencher: &mut Bencher) {
+    bench_broadcast(bencher, 16, 1_000);
+}
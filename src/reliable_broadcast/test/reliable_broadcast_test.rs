@@ -4,7 +4,8 @@ use crate::reliable_broadcast::messages::ReliableBroadcastMessage;
 use crate::reliable_broadcast::reliable_broadcast::{
     ReliableBroadcastInstance, ReliableBroadcastResult,
 };
-use atlas_common::crypto::hash::{Context, Digest};
+use crate::reliable_broadcast::test::simulation_test::{Batch, MsgType, make_batch, make_digest};
+use atlas_common::crypto::hash::Digest;
 use atlas_common::node_id::NodeId;
 use atlas_communication::lookup_table::MessageModule;
 use atlas_communication::message::{Buf, StoredMessage};
@@ -13,7 +14,7 @@ use std::sync::Arc;
 
 // Mock network to capture broadcasts
 struct MockNetwork {
-    sent: RefCell<Vec<(ReliableBroadcastMessage<u8>, Vec<NodeId>)>>,
+    sent: RefCell<Vec<(ReliableBroadcastMessage<Batch>, Vec<NodeId>)>>,
 }
 
 impl MockNetwork {
@@ -24,12 +25,10 @@ impl MockNetwork {
     }
 }
 
-type MsgType = u8;
-
-impl ReliableBroadcastSendNode<ReliableBroadcastMessage<MsgType>> for MockNetwork {
+impl ReliableBroadcastSendNode<ReliableBroadcastMessage<Batch>> for MockNetwork {
     fn send(
         &self,
-        message: ReliableBroadcastMessage<MsgType>,
+        message: ReliableBroadcastMessage<Batch>,
         target: NodeId,
         flush: bool,
     ) -> atlas_common::error::Result<()> {
@@ -37,7 +36,7 @@ impl ReliableBroadcastSendNode<ReliableBroadcastMessage<MsgType>> for MockNetwor
     }
     fn send_signed(
         &self,
-        message: ReliableBroadcastMessage<MsgType>,
+        message: ReliableBroadcastMessage<Batch>,
         target: NodeId,
         _flush: bool,
     ) -> atlas_common::error::Result<()> {
@@ -47,7 +46,7 @@ impl ReliableBroadcastSendNode<ReliableBroadcastMessage<MsgType>> for MockNetwor
     }
     fn broadcast<I>(
         &self,
-        message: ReliableBroadcastMessage<MsgType>,
+        message: ReliableBroadcastMessage<Batch>,
         targets: I,
     ) -> Result<(), Vec<NodeId>>
     where
@@ -59,7 +58,7 @@ impl ReliableBroadcastSendNode<ReliableBroadcastMessage<MsgType>> for MockNetwor
     }
     fn broadcast_signed<I>(
         &self,
-        message: ReliableBroadcastMessage<MsgType>,
+        message: ReliableBroadcastMessage<Batch>,
         targets: I,
     ) -> Result<(), Vec<NodeId>>
     where
@@ -83,18 +82,11 @@ fn sender_from_quorum(quorum: &QuorumInfo) -> NodeId {
         .unwrap_or(NodeId(0))
 }
 
-fn make_digest(val: MsgType) -> Digest {
-    let mut context = Context::new();
-    context.update(&[val]);
-
-    context.finish()
-}
-
 fn stored_msg(
     from: NodeId,
     to: NodeId,
-    msg: ReliableBroadcastMessage<MsgType>,
-) -> StoredMessage<ReliableBroadcastMessage<MsgType>> {
+    msg: ReliableBroadcastMessage<Batch>,
+) -> StoredMessage<ReliableBroadcastMessage<Batch>> {
     let wire_msg = atlas_communication::message::WireMessage::new(
         from,
         to,
@@ -115,13 +107,13 @@ const F: usize = 1;
 fn test_send_phase() {
     let quorum = quorum_info(N, F);
     let sender = sender_from_quorum(&quorum);
-    let mut rbc = ReliableBroadcastInstance::<MsgType>::new(sender, quorum);
+    let mut rbc = ReliableBroadcastInstance::<Batch>::new(sender, quorum);
     let network = Arc::new(MockNetwork::new());
     let digest = make_digest(42);
     let send_msg = stored_msg(
         sender,
         sender,
-        ReliableBroadcastMessage::Send(vec![], digest),
+        ReliableBroadcastMessage::Send(make_batch(42), digest),
     );
 
     // Process SEND
@@ -136,7 +128,7 @@ fn test_send_phase() {
 fn test_echo_phase() {
     let quorum = quorum_info(N, F);
     let sender = sender_from_quorum(&quorum);
-    let mut rbc = ReliableBroadcastInstance::<MsgType>::new(sender, quorum);
+    let mut rbc = ReliableBroadcastInstance::<Batch>::new(sender, quorum);
     let network = Arc::new(MockNetwork::new());
     let digest = make_digest(42);
 
@@ -144,7 +136,7 @@ fn test_echo_phase() {
     let send_msg = stored_msg(
         sender,
         sender,
-        ReliableBroadcastMessage::Send(vec![], digest),
+        ReliableBroadcastMessage::Send(make_batch(42), digest),
     );
     rbc.process_message(send_msg, &network);
 
@@ -162,7 +154,7 @@ fn test_echo_phase() {
 }
 
 fn simulate_echo(
-    rbc: &mut ReliableBroadcastInstance<MsgType>,
+    rbc: &mut ReliableBroadcastInstance<Batch>,
     quorum: &QuorumInfo,
     sender: NodeId,
     network: &Arc<MockNetwork>,
@@ -182,7 +174,7 @@ fn simulate_echo(
 fn test_ready_phase_and_deliver() {
     let quorum = quorum_info(N, F);
     let sender = sender_from_quorum(&quorum);
-    let mut rbc = ReliableBroadcastInstance::<MsgType>::new(sender, quorum.clone());
+    let mut rbc = ReliableBroadcastInstance::<Batch>::new(sender, quorum.clone());
     let network = Arc::new(MockNetwork::new());
     let digest = make_digest(42);
 
@@ -190,7 +182,7 @@ fn test_ready_phase_and_deliver() {
     let send_msg = stored_msg(
         sender,
         sender,
-        ReliableBroadcastMessage::Send(vec![], digest),
+        ReliableBroadcastMessage::Send(make_batch(42), digest),
     );
     rbc.process_message(send_msg, &network);
 
@@ -215,7 +207,7 @@ fn test_ready_phase_and_deliver() {
 
     let (requests, digest) = rbc.finalize().unwrap();
 
-    assert_eq!(requests.len(), 0, "No requests should be finalized");
+    assert_eq!(requests.len(), 1, "The proposed request should be finalized");
     assert_eq!(digest, digest, "Digest should match the one sent");
 }
 
@@ -223,7 +215,7 @@ fn test_ready_phase_and_deliver() {
 fn test_not_enough_echoes_no_ready() {
     let quorum = quorum_info(N, F);
     let sender = sender_from_quorum(&quorum);
-    let mut rbc = ReliableBroadcastInstance::<MsgType>::new(sender, quorum.clone());
+    let mut rbc = ReliableBroadcastInstance::<Batch>::new(sender, quorum.clone());
     let network = Arc::new(MockNetwork::new());
     let digest = make_digest(1);
 
@@ -231,7 +223,7 @@ fn test_not_enough_echoes_no_ready() {
     let send_msg = stored_msg(
         sender,
         sender,
-        ReliableBroadcastMessage::Send(vec![], digest),
+        ReliableBroadcastMessage::Send(make_batch(1), digest),
     );
     rbc.process_message(send_msg, &network);
 
@@ -253,7 +245,7 @@ fn test_not_enough_echoes_no_ready() {
 fn test_duplicate_echoes_ignored() {
     let quorum = quorum_info(N, F);
     let sender = sender_from_quorum(&quorum);
-    let mut rbc = ReliableBroadcastInstance::<MsgType>::new(sender, quorum.clone());
+    let mut rbc = ReliableBroadcastInstance::<Batch>::new(sender, quorum.clone());
     let network = Arc::new(MockNetwork::new());
     let digest = make_digest(2);
 
@@ -261,7 +253,7 @@ fn test_duplicate_echoes_ignored() {
     let send_msg = stored_msg(
         sender,
         sender,
-        ReliableBroadcastMessage::Send(vec![], digest),
+        ReliableBroadcastMessage::Send(make_batch(2), digest),
     );
     rbc.process_message(send_msg, &network);
 
@@ -284,7 +276,7 @@ fn test_duplicate_echoes_ignored() {
 fn test_duplicate_readies_ignored() {
     let quorum = quorum_info(N, F);
     let sender = sender_from_quorum(&quorum);
-    let mut rbc = ReliableBroadcastInstance::<MsgType>::new(sender, quorum.clone());
+    let mut rbc = ReliableBroadcastInstance::<Batch>::new(sender, quorum.clone());
     let network = Arc::new(MockNetwork::new());
     let digest = make_digest(3);
 
@@ -292,7 +284,7 @@ fn test_duplicate_readies_ignored() {
     let send_msg = stored_msg(
         sender,
         sender,
-        ReliableBroadcastMessage::Send(vec![], digest),
+        ReliableBroadcastMessage::Send(make_batch(3), digest),
     );
     rbc.process_message(send_msg, &network);
 
@@ -316,7 +308,7 @@ fn test_duplicate_readies_ignored() {
 fn test_mismatched_digest_ignored() {
     let quorum = quorum_info(N, F);
     let sender = sender_from_quorum(&quorum);
-    let mut rbc = ReliableBroadcastInstance::<MsgType>::new(sender, quorum.clone());
+    let mut rbc = ReliableBroadcastInstance::<Batch>::new(sender, quorum.clone());
     let network = Arc::new(MockNetwork::new());
     let digest = make_digest(4);
     let wrong_digest = make_digest(99);
@@ -325,7 +317,7 @@ fn test_mismatched_digest_ignored() {
     let send_msg = stored_msg(
         sender,
         sender,
-        ReliableBroadcastMessage::Send(vec![], digest),
+        ReliableBroadcastMessage::Send(make_batch(4), digest),
     );
     rbc.process_message(send_msg, &network);
 
@@ -348,7 +340,7 @@ fn test_mismatched_digest_ignored() {
 fn test_send_after_proposed_ignored() {
     let quorum = quorum_info(N, F);
     let sender = sender_from_quorum(&quorum);
-    let mut rbc = ReliableBroadcastInstance::<MsgType>::new(sender, quorum.clone());
+    let mut rbc = ReliableBroadcastInstance::<Batch>::new(sender, quorum.clone());
     let network = Arc::new(MockNetwork::new());
     let digest = make_digest(5);
 
@@ -356,7 +348,7 @@ fn test_send_after_proposed_ignored() {
     let send_msg = stored_msg(
         sender,
         sender,
-        ReliableBroadcastMessage::Send(vec![], digest),
+        ReliableBroadcastMessage::Send(make_batch(5), digest),
     );
     rbc.process_message(send_msg.clone(), &network);
 
@@ -372,7 +364,7 @@ fn test_send_after_proposed_ignored() {
 fn test_echo_before_send_queued() {
     let quorum = quorum_info(N, F);
     let sender = sender_from_quorum(&quorum);
-    let mut rbc = ReliableBroadcastInstance::<MsgType>::new(sender, quorum.clone());
+    let mut rbc = ReliableBroadcastInstance::<Batch>::new(sender, quorum.clone());
     let network = Arc::new(MockNetwork::new());
     let digest = make_digest(6);
 
@@ -390,7 +382,7 @@ fn test_echo_before_send_queued() {
 fn test_ready_before_send_queued() {
     let quorum = quorum_info(N, F);
     let sender = sender_from_quorum(&quorum);
-    let mut rbc = ReliableBroadcastInstance::<MsgType>::new(sender, quorum.clone());
+    let mut rbc = ReliableBroadcastInstance::<Batch>::new(sender, quorum.clone());
     let network = Arc::new(MockNetwork::new());
     let digest = make_digest(7);
 
@@ -403,3 +395,145 @@ fn test_ready_before_send_queued() {
         "READY before SEND should be queued"
     );
 }
+
+#[test]
+fn test_non_member_votes_not_counted() {
+    let quorum = quorum_info(N, F);
+    let sender = sender_from_quorum(&quorum);
+    let mut rbc = ReliableBroadcastInstance::<Batch>::new(sender, quorum.clone());
+    let network = Arc::new(MockNetwork::new());
+    let digest = make_digest(8);
+
+    let send_msg = stored_msg(
+        sender,
+        sender,
+        ReliableBroadcastMessage::Send(make_batch(8), digest),
+    );
+    rbc.process_message(send_msg, &network);
+
+    // ECHOs from enough nodes outside the quorum to make up n - f
+    for i in N..(N + quorum.quorum_size()) {
+        let echo_msg = stored_msg(NodeId::from(i), sender, ReliableBroadcastMessage::Echo(digest));
+        let result = rbc.process_message(echo_msg, &network);
+
+        assert!(
+            matches!(result, ReliableBroadcastResult::NonMember),
+            "ECHO from a node outside the quorum should be rejected"
+        );
+    }
+
+    // Should NOT broadcast READY
+    let sent = network.sent.borrow();
+    assert!(
+        !sent
+            .iter()
+            .any(|(msg, _)| matches!(msg, ReliableBroadcastMessage::Ready(_))),
+        "Should not broadcast READY with ECHOs from outside the quorum"
+    );
+}
+
+#[test]
+fn test_echo_quorum_is_weighted() {
+    // A total weight of 6 with f = 1, so the echoes must hold a weight of 4
+    let quorum = QuorumInfo::weighted(
+        F,
+        vec![
+            (NodeId(0), 3),
+            (NodeId(1), 1),
+            (NodeId(2), 1),
+            (NodeId(3), 1),
+        ],
+    );
+    let sender = NodeId(1);
+    let network = Arc::new(MockNetwork::new());
+    let digest = make_digest(42);
+
+    let is_ready = |(msg, _): &(ReliableBroadcastMessage<Batch>, Vec<NodeId>)| {
+        matches!(msg, ReliableBroadcastMessage::Ready(d) if *d == digest)
+    };
+
+    // Three light members only hold a weight of 3
+    let mut rbc = ReliableBroadcastInstance::<Batch>::new(sender, quorum.clone());
+    let send_msg = stored_msg(
+        sender,
+        sender,
+        ReliableBroadcastMessage::Send(make_batch(42), digest),
+    );
+    rbc.process_message(send_msg.clone(), &network);
+
+    for i in 1..=3 {
+        let echo_msg = stored_msg(NodeId(i), sender, ReliableBroadcastMessage::Echo(digest));
+        rbc.process_message(echo_msg, &network);
+    }
+
+    assert!(
+        !network.sent.borrow().iter().any(is_ready),
+        "Should not broadcast READY with ECHOs holding less than n - 2f of the weight"
+    );
+
+    // While the heavy member and a light one are enough
+    let mut rbc = ReliableBroadcastInstance::<Batch>::new(sender, quorum);
+    rbc.process_message(send_msg, &network);
+
+    for i in [0, 1] {
+        let echo_msg = stored_msg(NodeId(i), sender, ReliableBroadcastMessage::Echo(digest));
+        rbc.process_message(echo_msg, &network);
+    }
+
+    assert!(network.sent.borrow().iter().any(is_ready));
+}
+
+#[test]
+fn test_send_from_other_node_ignored() {
+    let quorum = quorum_info(N, F);
+    let sender = sender_from_quorum(&quorum);
+    let mut rbc = ReliableBroadcastInstance::<Batch>::new(sender, quorum);
+    let network = Arc::new(MockNetwork::new());
+    let digest = make_digest(9);
+
+    // Another member tries to start the broadcast of the sender
+    let send_msg = stored_msg(
+        NodeId(2),
+        sender,
+        ReliableBroadcastMessage::Send(make_batch(9), digest),
+    );
+    let result = rbc.process_message(send_msg, &network);
+
+    assert!(
+        matches!(result, ReliableBroadcastResult::MessageIgnored),
+        "SEND from a node other than the sender should be ignored"
+    );
+    assert!(network.sent.borrow().is_empty(), "Should not ECHO a forged SEND");
+}
+
+#[test]
+fn test_send_with_wrong_digest_ignored() {
+    let quorum = quorum_info(N, F);
+    let sender = sender_from_quorum(&quorum);
+    let mut rbc = ReliableBroadcastInstance::<Batch>::new(sender, quorum);
+    let network = Arc::new(MockNetwork::new());
+
+    // The digest does not match the requests it is sent with
+    let send_msg = stored_msg(
+        sender,
+        sender,
+        ReliableBroadcastMessage::Send(make_batch(10), make_digest(11)),
+    );
+    let result = rbc.process_message(send_msg, &network);
+
+    assert!(
+        matches!(result, ReliableBroadcastResult::MessageIgnored),
+        "SEND whose digest does not match its requests should be ignored"
+    );
+    assert!(network.sent.borrow().is_empty(), "Should not ECHO a mismatched SEND");
+
+    // The sender can still propose a valid batch afterwards
+    let send_msg = stored_msg(
+        sender,
+        sender,
+        ReliableBroadcastMessage::Send(make_batch(10), make_digest(10)),
+    );
+    let result = rbc.process_message(send_msg, &network);
+
+    assert!(matches!(result, ReliableBroadcastResult::Progressed(_)));
+}
@@ -0,0 +1,257 @@
+use crate::quorum_info::quorum_info::QuorumInfo;
+use crate::reliable_broadcast::messages::ReliableBroadcastMessage;
+use crate::reliable_broadcast::reliable_broadcast::{
+    ReliableBroadcastInstance, ReliableBroadcastResult,
+};
+use crate::rq_aggregator::rq_aggregator::batch_digest;
+use crate::simulator::byzantine::rbc::{EquivocatingSender, WrongDigestEcho};
+use crate::simulator::byzantine::strategy::Replay;
+use crate::simulator::model_checker::ModelProtocol;
+use crate::simulator::network::{NetworkConfig, Outbox};
+use crate::simulator::simulation::{SimProtocol, SimReport, Simulation};
+use atlas_common::crypto::hash::{Context, Digest};
+use atlas_common::node_id::NodeId;
+use atlas_communication::lookup_table::MessageModule;
+use atlas_communication::message::{Buf, StoredMessage, WireMessage};
+use std::hash::{Hash, Hasher};
+use std::sync::Arc;
+
+pub(crate) type MsgType = u8;
+
+/// The proposal of the reliable broadcasts of these tests
+pub(crate) type Batch = Vec<StoredMessage<MsgType>>;
+
+const N: usize = 4;
+const F: usize = 1;
+const SEEDS: u64 = 32;
+
+/// A node taking part in the reliable broadcast of a single sender
+#[derive(Clone)]
+pub(crate) struct RbcNode {
+    members: Vec<NodeId>,
+    instance: Option<ReliableBroadcastInstance<Batch>>,
+    // The requests to propose, when this node is the sender and has not yet sent them
+    proposal: Option<Batch>,
+    delivered: Option<Digest>,
+}
+
+impl RbcNode {
+    pub(crate) fn new(
+        sender: NodeId,
+        quorum_info: &QuorumInfo,
+        proposal: Option<Batch>,
+    ) -> Self {
+        Self {
+            members: quorum_info.quorum_members().clone(),
+            instance: Some(ReliableBroadcastInstance::new(sender, quorum_info.clone())),
+            proposal,
+            delivered: None,
+        }
+    }
+}
+
+impl SimProtocol for RbcNode {
+    type Message = ReliableBroadcastMessage<Batch>;
+    type Output = Digest;
+
+    fn start(&mut self, outbox: &Arc<Outbox<Self::Message>>) {
+        if let Some(batch) = self.proposal.take() {
+            let digest = batch_digest(&batch);
+
+            outbox.push_all(
+                self.members.iter().cloned(),
+                ReliableBroadcastMessage::Send(batch, digest),
+            );
+        }
+    }
+
+    fn deliver(
+        &mut self,
+        message: StoredMessage<Self::Message>,
+        outbox: &Arc<Outbox<Self::Message>>,
+    ) {
+        let Some(instance) = self.instance.as_mut() else {
+            return;
+        };
+
+        let mut pending = vec![message];
+
+        // Queued messages are requeued when they still can not be processed,
+        // so they are only retried after some other message made progress
+        while !pending.is_empty() {
+            let mut progressed = false;
+
+            for message in pending {
+                match instance.process_message(message, outbox) {
+                    ReliableBroadcastResult::Progressed(_) => progressed = true,
+                    ReliableBroadcastResult::Finalized => {
+                        let (_, digest) = self.instance.take().unwrap().finalize().unwrap();
+
+                        self.delivered = Some(digest);
+
+                        return;
+                    }
+                    ReliableBroadcastResult::MessageIgnored
+                    | ReliableBroadcastResult::NonMember
+                    | ReliableBroadcastResult::MessageQueued => {}
+                }
+            }
+
+            pending = if progressed {
+                std::iter::from_fn(|| instance.poll()).collect()
+            } else {
+                Vec::new()
+            };
+        }
+    }
+
+    fn output(&self) -> Option<Self::Output> {
+        self.delivered
+    }
+}
+
+impl ModelProtocol for RbcNode {
+    fn fingerprint<H: Hasher>(&self, hasher: &mut H) {
+        self.delivered.hash(hasher);
+        self.proposal.as_deref().map(batch_digest).hash(hasher);
+
+        if let Some(instance) = &self.instance {
+            instance.fingerprint(hasher);
+        }
+    }
+
+    fn message_fingerprint<H: Hasher>(message: &Self::Message, hasher: &mut H) {
+        match message {
+            ReliableBroadcastMessage::Send(_, digest) => (0u8, digest).hash(hasher),
+            ReliableBroadcastMessage::Echo(digest) => (1u8, digest).hash(hasher),
+            ReliableBroadcastMessage::Ready(digest) => (2u8, digest).hash(hasher),
+        }
+    }
+
+    fn ignores(&self, message: &Self::Message) -> bool {
+        self.instance
+            .as_ref()
+            .is_none_or(|instance| instance.ignores(message))
+    }
+}
+
+/// A batch with a single request, whose digest is derived from `val`
+pub(crate) fn make_batch(val: MsgType) -> Batch {
+    let mut context = Context::new();
+    context.update(&[val]);
+
+    let wire_msg = WireMessage::new(
+        NodeId(0),
+        NodeId(0),
+        MessageModule::Application,
+        Buf::new(),
+        0,
+        Some(context.finish()),
+        None,
+    );
+
+    vec![StoredMessage::new(wire_msg.header().clone(), val)]
+}
+
+/// The digest of the batch made from `val`
+pub(crate) fn make_digest(val: MsgType) -> Digest {
+    batch_digest(&make_batch(val))
+}
+
+fn simulation(seed: u64, config: NetworkConfig) -> Simulation<RbcNode> {
+    let quorum_info = QuorumInfo::new(N, F, (0..N).map(NodeId::from).collect());
+    let sender = NodeId(0);
+
+    let nodes = (0..N).map(NodeId::from).map(|id| {
+        let proposal = (id == sender).then(|| make_batch(42));
+
+        (id, RbcNode::new(sender, &quorum_info, proposal))
+    });
+
+    Simulation::new(seed, config, nodes)
+}
+
+fn run(seed: u64, config: NetworkConfig) -> SimReport<Digest> {
+    simulation(seed, config).run()
+}
+
+#[test]
+fn test_broadcast_is_delivered_by_every_node() {
+    for seed in 0..SEEDS {
+        let report = run(seed, NetworkConfig::default());
+
+        report.check_termination().unwrap();
+        report.check_agreement().unwrap();
+        report
+            .check_validity(|digest| *digest == make_digest(42))
+            .unwrap();
+
+        assert_eq!(report.undelivered, 0);
+    }
+}
+
+#[test]
+fn test_broadcast_with_heavy_reordering() {
+    let config = NetworkConfig {
+        min_delay: 0,
+        max_delay: 10_000,
+    };
+
+    for seed in 0..SEEDS {
+        let report = run(seed, config);
+
+        report.check_totality().unwrap();
+        report.check_agreement().unwrap();
+    }
+}
+
+#[test]
+fn test_run_is_reproducible_from_seed() {
+    let first = run(7, NetworkConfig::default());
+    let second = run(7, NetworkConfig::default());
+
+    assert_eq!(first.steps, second.steps);
+    assert_eq!(first.elapsed, second.elapsed);
+    assert_eq!(first.outputs, second.outputs);
+}
+
+#[test]
+fn test_equivocating_sender_does_not_break_agreement() {
+    for seed in 0..SEEDS {
+        let report = simulation(seed, NetworkConfig::default())
+            .with_byzantine(NodeId(0), EquivocatingSender::new(make_batch(43)))
+            .run();
+
+        report.check_agreement().unwrap();
+        report
+            .check_validity(|digest| *digest == make_digest(42) || *digest == make_digest(43))
+            .unwrap();
+    }
+}
+
+#[test]
+fn test_wrong_digest_echoes_are_tolerated() {
+    for seed in 0..SEEDS {
+        let report = simulation(seed, NetworkConfig::default())
+            .with_byzantine(NodeId(3), WrongDigestEcho::new(make_digest(43)))
+            .run();
+
+        report.check_termination().unwrap();
+        report.check_agreement().unwrap();
+        report
+            .check_validity(|digest| *digest == make_digest(42))
+            .unwrap();
+    }
+}
+
+#[test]
+fn test_replayed_messages_are_tolerated() {
+    for seed in 0..SEEDS {
+        let report = simulation(seed, NetworkConfig::default())
+            .with_byzantine(NodeId(2), Replay::new(2))
+            .run();
+
+        report.check_termination().unwrap();
+        report.check_agreement().unwrap();
+    }
+}
@@ -0,0 +1,695 @@
+use crate::reliable_broadcast::messages::ReliableBroadcastMessage;
+use atlas_common::collections::{HashMap, HashSet};
+use atlas_common::crypto::hash::{Context, Digest};
+use atlas_common::node_id::NodeId;
+use atlas_common::ordering::{Orderable, SeqNo};
+use atlas_communication::message::StoredMessage;
+use either::Either;
+use getset::{CopyGetters, Getters};
+use itertools::Itertools;
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::fmt::Debug;
+use std::time::{Duration, Instant};
+use thiserror::Error;
+use tracing::debug;
+
+/// The limits used by the [`RequestAggregator`] to decide when a batch is ready.
+///
+/// A batch is cut as soon as any of the size, byte or time limits is reached.
+#[derive(Debug, Clone, CopyGetters)]
+pub struct RequestAggregatorConfig {
+    /// The maximum amount of requests in a single batch
+    #[get_copy = "pub"]
+    max_batch_size: usize,
+    /// The maximum amount of payload bytes in a single batch
+    #[get_copy = "pub"]
+    max_batch_bytes: usize,
+    /// The maximum amount of time the oldest request can wait before a batch is cut
+    #[get_copy = "pub"]
+    batch_timeout: Duration,
+    /// The maximum amount of requests we are willing to hold before applying backpressure
+    #[get_copy = "pub"]
+    max_queue_size: usize,
+    /// How the requests of a batch are selected from the queue
+    #[get_copy = "pub"]
+    selection_strategy: BatchSelectionStrategy,
+    /// The amount of epochs a request assigned to another node under hash partitioning
+    /// may remain undecided before we propose it ourselves
+    #[get_copy = "pub"]
+    partition_fallback_epochs: usize,
+}
+
+impl RequestAggregatorConfig {
+    pub fn new(
+        max_batch_size: usize,
+        max_batch_bytes: usize,
+        batch_timeout: Duration,
+        max_queue_size: usize,
+    ) -> Self {
+        assert!(
+            max_batch_size > 0 && max_queue_size >= max_batch_size,
+            "Invalid request aggregator parameters"
+        );
+
+        Self {
+            max_batch_size,
+            max_batch_bytes,
+            batch_timeout,
+            max_queue_size,
+            selection_strategy: BatchSelectionStrategy::Full,
+            partition_fallback_epochs: 3,
+        }
+    }
+
+    pub fn with_selection_strategy(mut self, selection_strategy: BatchSelectionStrategy) -> Self {
+        match selection_strategy {
+            BatchSelectionStrategy::Full => {}
+            BatchSelectionStrategy::RandomSubset { n, .. } => {
+                assert!(n > 0, "Invalid random subset parameters");
+            }
+            BatchSelectionStrategy::HashPartitioned { n, own_index } => {
+                assert!(n > 0 && own_index < n, "Invalid hash partition parameters");
+            }
+        }
+
+        self.selection_strategy = selection_strategy;
+        self
+    }
+
+    pub fn with_partition_fallback_epochs(mut self, partition_fallback_epochs: usize) -> Self {
+        self.partition_fallback_epochs = partition_fallback_epochs;
+        self
+    }
+}
+
+/// How a node selects the requests it proposes from its queue.
+///
+/// Since every node proposes from the same mempool, proposing the head of the queue
+/// means the same requests end up included `n` times in each epoch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BatchSelectionStrategy {
+    /// Propose the head of the queue, up to the batch limits
+    Full,
+    /// HoneyBadgerBFT style selection, where each node proposes `⌊B/n⌋` requests chosen
+    /// at random from the first `B` requests of its queue, with `B` the maximum batch size
+    RandomSubset { n: usize, seed: u64 },
+    /// Each client is assigned to a single node by hashing its id, and nodes only propose
+    /// the requests of the clients assigned to them.
+    ///
+    /// Requests of other clients are kept in the queue until they are decided, and are
+    /// proposed by us as well if they remain undecided for
+    /// [`RequestAggregatorConfig::partition_fallback_epochs`] epochs, so that a faulty node
+    /// can not censor the clients assigned to it.
+    HashPartitioned { n: usize, own_index: usize },
+}
+
+impl BatchSelectionStrategy {
+    /// The strategy for a node of the given members, which is only meaningful while
+    /// the node is one of them
+    pub fn for_members(self, node_id: NodeId, members: &[NodeId]) -> Self {
+        let Some(own_index) = members.iter().position(|member| *member == node_id) else {
+            return self;
+        };
+
+        let n = members.len();
+
+        match self {
+            BatchSelectionStrategy::Full => BatchSelectionStrategy::Full,
+            BatchSelectionStrategy::RandomSubset { seed, .. } => {
+                BatchSelectionStrategy::RandomSubset { n, seed }
+            }
+            BatchSelectionStrategy::HashPartitioned { .. } => {
+                BatchSelectionStrategy::HashPartitioned { n, own_index }
+            }
+        }
+    }
+}
+
+impl Default for RequestAggregatorConfig {
+    fn default() -> Self {
+        Self::new(1024, 1024 * 1024, Duration::from_millis(50), 64 * 1024)
+    }
+}
+
+/// A batch of client requests, ready to be proposed in a reliable broadcast.
+#[derive(Debug, Getters)]
+pub struct Batch<RQ> {
+    #[get = "pub"]
+    requests: Vec<StoredMessage<RQ>>,
+    #[get = "pub"]
+    digest: Digest,
+}
+
+impl<RQ> Batch<RQ> {
+    fn new(requests: Vec<StoredMessage<RQ>>) -> Self {
+        let digest = batch_digest(&requests);
+
+        Self { requests, digest }
+    }
+
+    pub fn len(&self) -> usize {
+        self.requests.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.requests.is_empty()
+    }
+
+    /// Turn this batch into the proposal of our reliable broadcast instance
+    pub(crate) fn into_proposal(self) -> ReliableBroadcastMessage<Vec<StoredMessage<RQ>>> {
+        ReliableBroadcastMessage::Send(self.requests, self.digest)
+    }
+
+    pub fn into_inner(self) -> (Vec<StoredMessage<RQ>>, Digest) {
+        (self.requests, self.digest)
+    }
+}
+
+/// Calculate the digest of a batch, from the digests of the requests it contains
+pub(crate) fn batch_digest<RQ>(requests: &[StoredMessage<RQ>]) -> Digest {
+    let mut context = Context::new();
+
+    requests
+        .iter()
+        .for_each(|request| context.update(request.header().digest().as_ref()));
+
+    context.finish()
+}
+
+/// The result of submitting a request to the aggregator.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AggregatorPushResult {
+    /// The request has been queued and will be included in a future batch
+    Queued,
+    /// The request was already queued or decided, so it was discarded
+    Duplicate,
+}
+
+/// Collects client requests and forms them into batches to be proposed
+/// by this node in each Dumbo epoch.
+///
+/// Requests are deduplicated by their client id and sequence number,
+/// both against the requests currently queued and the requests
+/// which have already been decided.
+pub struct RequestAggregator<RQ> {
+    config: RequestAggregatorConfig,
+    // The requests waiting to be included in a batch, in arrival order
+    queue: VecDeque<StoredMessage<RQ>>,
+    // The amount of payload bytes currently queued
+    queued_bytes: usize,
+    // The amount of queued requests (and their payload bytes) this node can propose,
+    // which under hash partitioning are only those of the clients assigned to it
+    proposable_requests: usize,
+    proposable_bytes: usize,
+    // The (client, sequence number) pairs currently queued
+    queued_requests: HashSet<(NodeId, SeqNo)>,
+    // The queued requests assigned to other nodes under hash partitioning,
+    // along with the amount of epochs registered when they arrived
+    foreign_requests: HashMap<(NodeId, SeqNo), usize>,
+    // The amount of decided epochs registered so far
+    registered_epochs: usize,
+    // The sequence numbers decided for each client
+    decided: HashMap<NodeId, DecidedRequests>,
+    // The moment the oldest request currently in the queue arrived
+    oldest_request: Option<Instant>,
+    // The random number generator used by the random subset selection
+    rng: StdRng,
+    // The duplicate statistics of the last decided epoch
+    last_epoch_stats: Option<DuplicateStatistics>,
+}
+
+impl<RQ> RequestAggregator<RQ>
+where
+    RQ: Orderable,
+{
+    pub fn new(config: RequestAggregatorConfig) -> Self {
+        let seed = match config.selection_strategy {
+            BatchSelectionStrategy::RandomSubset { seed, .. } => seed,
+            _ => 0,
+        };
+
+        Self {
+            config,
+            queue: VecDeque::new(),
+            queued_bytes: 0,
+            proposable_requests: 0,
+            proposable_bytes: 0,
+            queued_requests: HashSet::default(),
+            foreign_requests: HashMap::default(),
+            registered_epochs: 0,
+            decided: HashMap::default(),
+            oldest_request: None,
+            rng: StdRng::seed_from_u64(seed),
+            last_epoch_stats: None,
+        }
+    }
+
+    /// The duplicate statistics of the last epoch registered with [`Self::register_decided_epoch`]
+    pub fn last_epoch_stats(&self) -> Option<&DuplicateStatistics> {
+        self.last_epoch_stats.as_ref()
+    }
+
+    pub fn config(&self) -> &RequestAggregatorConfig {
+        &self.config
+    }
+
+    /// The amount of requests currently waiting to be batched
+    pub fn queue_len(&self) -> usize {
+        self.queue.len()
+    }
+
+    /// Whether the queue is full, meaning new requests we can propose will be rejected until
+    /// batches are taken from the aggregator.
+    ///
+    /// Requests assigned to other nodes under hash partitioning are bounded separately,
+    /// so they never apply backpressure to the clients assigned to us.
+    pub fn is_full(&self) -> bool {
+        self.proposable_requests >= self.config.max_queue_size
+    }
+
+    /// Change the selection strategy, as the membership of the quorum changed
+    pub fn set_selection_strategy(&mut self, selection_strategy: BatchSelectionStrategy) {
+        self.config = self.config.clone().with_selection_strategy(selection_strategy);
+
+        let previous = std::mem::take(&mut self.foreign_requests);
+
+        self.proposable_requests = 0;
+        self.proposable_bytes = 0;
+
+        for request in &self.queue {
+            let key = request_key(request);
+
+            if self.is_assigned_to_us(request) {
+                self.proposable_requests += 1;
+                self.proposable_bytes += request.header().payload_length();
+            } else {
+                let arrival = previous.get(&key).copied().unwrap_or(self.registered_epochs);
+
+                self.foreign_requests.insert(key, arrival);
+            }
+        }
+    }
+
+    /// Submit a client request to the aggregator.
+    ///
+    /// Returns [`RequestAggregatorError::QueueFull`] when the aggregator is applying backpressure,
+    /// in which case the request is handed back to the caller.
+    pub fn push_request(
+        &mut self,
+        request: StoredMessage<RQ>,
+    ) -> Result<AggregatorPushResult, RequestAggregatorError<RQ>> {
+        let key = request_key(&request);
+
+        if self.is_decided(key) || self.queued_requests.contains(&key) {
+            return Ok(AggregatorPushResult::Duplicate);
+        }
+
+        let assigned_to_us = self.is_assigned_to_us(&request);
+
+        let full = if assigned_to_us {
+            self.is_full()
+        } else {
+            self.foreign_requests.len() >= self.config.max_queue_size
+        };
+
+        if full {
+            return Err(RequestAggregatorError::QueueFull(request));
+        }
+
+        self.queued_requests.insert(key);
+        self.queued_bytes += request.header().payload_length();
+
+        if assigned_to_us {
+            self.proposable_requests += 1;
+            self.proposable_bytes += request.header().payload_length();
+        } else {
+            self.foreign_requests.insert(key, self.registered_epochs);
+        }
+
+        if self.queue.is_empty() {
+            self.oldest_request = Some(Instant::now());
+        }
+
+        self.queue.push_back(request);
+
+        Ok(AggregatorPushResult::Queued)
+    }
+
+    /// Whether a batch can be cut right now, according to the configured limits.
+    ///
+    /// Only the requests this node can propose count towards the limits, so under hash
+    /// partitioning the requests of clients assigned to other nodes never cut a batch.
+    pub fn is_batch_ready(&self) -> bool {
+        if self.proposable_requests == 0 {
+            return false;
+        }
+
+        self.proposable_requests >= self.config.max_batch_size
+            || self.proposable_bytes >= self.config.max_batch_bytes
+            || self
+                .oldest_request
+                .is_some_and(|arrival| arrival.elapsed() >= self.config.batch_timeout)
+    }
+
+    /// Poll the aggregator for a batch, which is only returned
+    /// when one of the configured limits has been reached
+    pub fn poll_batch(&mut self) -> Option<Batch<RQ>> {
+        if self.is_batch_ready() {
+            Some(self.take_batch())
+        } else {
+            None
+        }
+    }
+
+    /// Cut a batch with the requests selected from the queue, regardless
+    /// of whether the limits have been reached.
+    ///
+    /// Used when an epoch starts and we must propose something, even if that is an empty batch.
+    pub fn take_batch(&mut self) -> Batch<RQ> {
+        let candidates = self.select_candidates();
+
+        let mut selected = HashSet::default();
+        let mut batch_bytes = 0;
+
+        for index in candidates {
+            let request_bytes = self.queue[index].header().payload_length();
+
+            if selected.len() >= self.config.max_batch_size
+                || (!selected.is_empty() && batch_bytes + request_bytes > self.config.max_batch_bytes)
+            {
+                break;
+            }
+
+            batch_bytes += request_bytes;
+            selected.insert(index);
+        }
+
+        let (requests, queue): (Vec<_>, VecDeque<_>) = std::mem::take(&mut self.queue)
+            .into_iter()
+            .enumerate()
+            .partition_map(|(index, request)| {
+                if selected.contains(&index) {
+                    Either::Left(request)
+                } else {
+                    Either::Right(request)
+                }
+            });
+
+        self.queue = queue;
+
+        self.remove_from_queued(&requests);
+
+        Batch::new(requests)
+    }
+
+    /// Select the indexes of the queued requests which are candidates for the next batch,
+    /// in queue order, according to the configured selection strategy
+    fn select_candidates(&mut self) -> Vec<usize> {
+        match self.config.selection_strategy {
+            BatchSelectionStrategy::Full => (0..self.queue.len()).collect(),
+            BatchSelectionStrategy::RandomSubset { n, .. } => {
+                let window = self.queue.len().min(self.config.max_batch_size);
+
+                if window == 0 {
+                    return Vec::new();
+                }
+
+                let amount = (self.config.max_batch_size / n).clamp(1, window);
+
+                let mut selected = rand::seq::index::sample(&mut self.rng, window, amount).into_vec();
+
+                selected.sort_unstable();
+
+                selected
+            }
+            BatchSelectionStrategy::HashPartitioned { .. } => self
+                .queue
+                .iter()
+                .enumerate()
+                .filter(|(_, request)| self.is_proposable(request))
+                .map(|(index, _)| index)
+                .collect(),
+        }
+    }
+
+    /// Register the requests that have been decided, so that they are removed from
+    /// the queue and never proposed again by this node
+    pub fn register_decided<'a, I>(&mut self, decided: I)
+    where
+        I: IntoIterator<Item = &'a StoredMessage<RQ>>,
+        RQ: 'a,
+    {
+        decided.into_iter().for_each(|request| {
+            let (client, seq_no) = request_key(request);
+
+            self.decided.entry(client).or_default().insert(seq_no);
+        });
+
+        self.discard_decided();
+    }
+
+    /// Replace the requests registered as decided with the ones recorded in a checkpoint,
+    /// as the epochs which decided them are no longer available
+    pub(crate) fn restore_decided(&mut self, decided: &BTreeMap<NodeId, DecidedRequests>) {
+        self.decided = decided
+            .iter()
+            .map(|(client, decided)| (*client, decided.clone()))
+            .collect();
+
+        self.discard_decided();
+    }
+
+    /// Remove the requests which have been decided from the queue
+    fn discard_decided(&mut self) {
+        let (decided, queue): (Vec<_>, VecDeque<_>) = std::mem::take(&mut self.queue)
+            .into_iter()
+            .partition(|request| self.is_decided(request_key(request)));
+
+        self.queue = queue;
+
+        self.remove_from_queued(&decided);
+    }
+
+    /// Register the batches decided in an epoch, removing their requests from the queue
+    /// and calculating how many of the decided requests were included more than once.
+    pub fn register_decided_epoch<'a, I>(&mut self, batches: I) -> DuplicateStatistics
+    where
+        I: IntoIterator<Item = &'a [StoredMessage<RQ>]>,
+        RQ: 'a,
+    {
+        let decided = batches.into_iter().flatten().collect::<Vec<_>>();
+
+        let unique_requests = decided
+            .iter()
+            .map(|request| request_key(request))
+            .collect::<HashSet<_>>()
+            .len();
+
+        let stats = DuplicateStatistics {
+            total_requests: decided.len(),
+            unique_requests,
+        };
+
+        debug!(
+            "Decided epoch contained {} requests, {} unique (duplicate rate {:.3})",
+            stats.total_requests,
+            stats.unique_requests,
+            stats.duplicate_rate()
+        );
+
+        self.register_decided(decided);
+
+        self.registered_epochs += 1;
+        self.release_stale_foreign_requests();
+
+        self.last_epoch_stats = Some(stats.clone());
+
+        stats
+    }
+
+    fn is_decided(&self, (client, seq_no): (NodeId, SeqNo)) -> bool {
+        self.decided
+            .get(&client)
+            .is_some_and(|decided| decided.contains(seq_no))
+    }
+
+    /// Whether the client of the given request is assigned to us by the selection strategy
+    fn is_assigned_to_us(&self, request: &StoredMessage<RQ>) -> bool {
+        match self.config.selection_strategy {
+            BatchSelectionStrategy::HashPartitioned { n, own_index } => {
+                client_partition(request.header().from(), n) == own_index
+            }
+            _ => true,
+        }
+    }
+
+    /// Whether the given queued request can currently be proposed by this node
+    fn is_proposable(&self, request: &StoredMessage<RQ>) -> bool {
+        !self.foreign_requests.contains_key(&request_key(request))
+    }
+
+    /// Let us propose the requests assigned to other nodes which have remained undecided
+    /// for too many epochs, as the node they are assigned to may be faulty
+    fn release_stale_foreign_requests(&mut self) {
+        let fallback = self.config.partition_fallback_epochs;
+
+        for request in &self.queue {
+            let key = request_key(request);
+
+            let Some(arrival) = self.foreign_requests.get(&key) else {
+                continue;
+            };
+
+            if self.registered_epochs - arrival >= fallback {
+                self.foreign_requests.remove(&key);
+                self.proposable_requests += 1;
+                self.proposable_bytes += request.header().payload_length();
+            }
+        }
+    }
+
+    fn remove_from_queued(&mut self, requests: &[StoredMessage<RQ>]) {
+        requests.iter().for_each(|request| {
+            let key = request_key(request);
+
+            self.queued_requests.remove(&key);
+            self.queued_bytes -= request.header().payload_length();
+
+            if self.foreign_requests.remove(&key).is_none() {
+                self.proposable_requests -= 1;
+                self.proposable_bytes -= request.header().payload_length();
+            }
+        });
+
+        if self.queue.is_empty() {
+            self.oldest_request = None;
+        } else if !requests.is_empty() {
+            // We do not track the arrival of each request, so the remaining
+            // requests start a new batching period
+            self.oldest_request = Some(Instant::now());
+        }
+    }
+}
+
+/// The sequence numbers decided for a single client.
+///
+/// Requests of a client can be decided out of order, so the sequence numbers decided past
+/// the first one that is still undecided are tracked individually. Every sequence number
+/// below that one has been decided, so they are folded into a floor.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct DecidedRequests {
+    // The lowest sequence number of the client which has not been decided
+    floor: SeqNo,
+    // The sequence numbers decided above the floor
+    recent: BTreeSet<SeqNo>,
+}
+
+impl Default for DecidedRequests {
+    fn default() -> Self {
+        Self {
+            floor: SeqNo::ZERO,
+            recent: BTreeSet::new(),
+        }
+    }
+}
+
+impl DecidedRequests {
+    pub(crate) fn insert(&mut self, seq_no: SeqNo) {
+        if self.contains(seq_no) {
+            return;
+        }
+
+        self.recent.insert(seq_no);
+
+        // Only a contiguous prefix of decided sequence numbers is folded into the floor,
+        // so a request which is delayed is never mistaken for a decided one
+        while self.recent.first() == Some(&self.floor) {
+            self.recent.pop_first();
+            self.floor = self.floor.next();
+        }
+    }
+
+    pub(crate) fn contains(&self, seq_no: SeqNo) -> bool {
+        seq_no < self.floor || self.recent.contains(&seq_no)
+    }
+}
+
+/// The key used to deduplicate client requests
+fn request_key<RQ>(request: &StoredMessage<RQ>) -> (NodeId, SeqNo)
+where
+    RQ: Orderable,
+{
+    (request.header().from(), request.message().sequence_number())
+}
+
+/// Assign a client to one of the `n` partitions, in a way that is
+/// consistent across all nodes
+fn client_partition(client: NodeId, n: usize) -> usize {
+    let mut context = Context::new();
+
+    context.update(&client.0.to_le_bytes()[..]);
+
+    let digest = context.finish();
+
+    let mut bytes = [0u8; 8];
+    bytes.copy_from_slice(&digest.as_ref()[..8]);
+
+    (u64::from_le_bytes(bytes) % n as u64) as usize
+}
+
+/// Statistics on the requests included in a decided epoch
+#[derive(Debug, Clone, PartialEq, Eq, CopyGetters)]
+pub struct DuplicateStatistics {
+    /// The amount of requests included in all of the decided batches
+    #[get_copy = "pub"]
+    total_requests: usize,
+    /// The amount of distinct requests included in the decided batches
+    #[get_copy = "pub"]
+    unique_requests: usize,
+}
+
+impl DuplicateStatistics {
+    /// The amount of requests which were included more than once
+    pub fn duplicates(&self) -> usize {
+        self.total_requests - self.unique_requests
+    }
+
+    /// The fraction of the decided requests which were duplicates
+    pub fn duplicate_rate(&self) -> f64 {
+        if self.total_requests == 0 {
+            0.0
+        } else {
+            self.duplicates() as f64 / self.total_requests as f64
+        }
+    }
+}
+
+impl<RQ> Debug for RequestAggregator<RQ> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RequestAggregator")
+            .field("config", &self.config)
+            .field("queue", &self.queue.len())
+            .field("queued_bytes", &self.queued_bytes)
+            .field("tracked_clients", &self.decided.len())
+            .finish()
+    }
+}
+
+#[derive(Error)]
+pub enum RequestAggregatorError<RQ> {
+    #[error("The request queue is full, cannot accept more requests")]
+    QueueFull(StoredMessage<RQ>),
+}
+
+impl<RQ> Debug for RequestAggregatorError<RQ> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RequestAggregatorError::QueueFull(request) => {
+                write!(f, "QueueFull({:?})", request.header().from())
+            }
+        }
+    }
+}
@@ -0,0 +1,452 @@
+use crate::reliable_broadcast::messages::ReliableBroadcastMessage;
+use crate::rq_aggregator::rq_aggregator::{
+    AggregatorPushResult, BatchSelectionStrategy, RequestAggregator, RequestAggregatorConfig,
+    RequestAggregatorError,
+};
+use atlas_common::collections::HashSet;
+use atlas_common::crypto::hash::Digest;
+use atlas_common::node_id::NodeId;
+use atlas_common::ordering::{Orderable, SeqNo};
+use atlas_communication::lookup_table::MessageModule;
+use atlas_communication::message::{Buf, StoredMessage};
+use std::time::Duration;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct TestRequest(SeqNo);
+
+impl Orderable for TestRequest {
+    fn sequence_number(&self) -> SeqNo {
+        self.0
+    }
+}
+
+fn request(client: u32, seq_no: u32) -> StoredMessage<TestRequest> {
+    let wire_msg = atlas_communication::message::WireMessage::new(
+        NodeId(client),
+        NodeId(0),
+        MessageModule::Application,
+        Buf::new(),
+        0,
+        Some(Digest::blank()),
+        None,
+    );
+
+    StoredMessage::new(
+        wire_msg.header().clone(),
+        TestRequest(SeqNo::from(seq_no)),
+    )
+}
+
+fn aggregator(max_batch_size: usize, max_queue_size: usize) -> RequestAggregator<TestRequest> {
+    RequestAggregator::new(RequestAggregatorConfig::new(
+        max_batch_size,
+        usize::MAX,
+        Duration::from_secs(3600),
+        max_queue_size,
+    ))
+}
+
+#[test]
+fn test_batch_cut_on_size() {
+    let mut aggregator = aggregator(3, 10);
+
+    for seq in 0..2 {
+        aggregator.push_request(request(100, seq)).unwrap();
+    }
+
+    assert!(aggregator.poll_batch().is_none(), "Batch is not full yet");
+
+    aggregator.push_request(request(100, 2)).unwrap();
+
+    let batch = aggregator.poll_batch().expect("Batch should be ready");
+
+    assert_eq!(3, batch.len());
+    assert_eq!(0, aggregator.queue_len());
+}
+
+#[test]
+fn test_batch_cut_on_timeout() {
+    let mut aggregator = RequestAggregator::new(RequestAggregatorConfig::new(
+        10,
+        usize::MAX,
+        Duration::ZERO,
+        10,
+    ));
+
+    aggregator.push_request(request(100, 0)).unwrap();
+
+    let batch = aggregator.poll_batch().expect("Batch should be cut on timeout");
+
+    assert_eq!(1, batch.len());
+}
+
+#[test]
+fn test_duplicate_requests_discarded() {
+    let mut aggregator = aggregator(10, 10);
+
+    assert_eq!(
+        AggregatorPushResult::Queued,
+        aggregator.push_request(request(100, 0)).unwrap()
+    );
+    assert_eq!(
+        AggregatorPushResult::Duplicate,
+        aggregator.push_request(request(100, 0)).unwrap()
+    );
+    // Same sequence number from another client is not a duplicate
+    assert_eq!(
+        AggregatorPushResult::Queued,
+        aggregator.push_request(request(101, 0)).unwrap()
+    );
+
+    assert_eq!(2, aggregator.queue_len());
+}
+
+#[test]
+fn test_decided_requests_removed_and_rejected() {
+    let mut aggregator = aggregator(10, 10);
+
+    for seq in 0..3 {
+        aggregator.push_request(request(100, seq)).unwrap();
+    }
+
+    aggregator.register_decided(&[request(100, 1)]);
+
+    // Only sequence number 1 of client 100 was decided
+    assert_eq!(2, aggregator.queue_len());
+    assert_eq!(
+        AggregatorPushResult::Duplicate,
+        aggregator.push_request(request(100, 1)).unwrap()
+    );
+
+    let batch = aggregator.take_batch();
+
+    assert_eq!(
+        vec![SeqNo::from(0u32), SeqNo::from(2u32)],
+        batch
+            .requests()
+            .iter()
+            .map(|request| request.message().sequence_number())
+            .collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn test_requests_decided_out_of_order() {
+    let mut aggregator = aggregator(10, 10);
+
+    aggregator.register_decided(&[request(100, 5)]);
+
+    // A lower sequence number decided later must still be accepted until then
+    assert_eq!(
+        AggregatorPushResult::Queued,
+        aggregator.push_request(request(100, 3)).unwrap()
+    );
+
+    aggregator.register_decided(&[request(100, 3)]);
+
+    assert_eq!(0, aggregator.queue_len());
+    assert_eq!(
+        AggregatorPushResult::Duplicate,
+        aggregator.push_request(request(100, 3)).unwrap()
+    );
+    assert_eq!(
+        AggregatorPushResult::Duplicate,
+        aggregator.push_request(request(100, 5)).unwrap()
+    );
+}
+
+#[test]
+fn test_delayed_request_not_decided_by_later_ones() {
+    let mut aggregator = aggregator(10, 10);
+
+    // Many requests of the client are decided while the first one is still delayed
+    let decided = (1..=2048).map(|seq| request(100, seq)).collect::<Vec<_>>();
+
+    aggregator.register_decided(&decided);
+
+    assert_eq!(
+        AggregatorPushResult::Queued,
+        aggregator.push_request(request(100, 0)).unwrap()
+    );
+
+    aggregator.register_decided(&[request(100, 0)]);
+
+    assert_eq!(0, aggregator.queue_len());
+    assert_eq!(
+        AggregatorPushResult::Duplicate,
+        aggregator.push_request(request(100, 0)).unwrap()
+    );
+    assert_eq!(
+        AggregatorPushResult::Duplicate,
+        aggregator.push_request(request(100, 2048)).unwrap()
+    );
+}
+
+#[test]
+fn test_backpressure_when_full() {
+    let mut aggregator = aggregator(2, 2);
+
+    aggregator.push_request(request(100, 0)).unwrap();
+    aggregator.push_request(request(100, 1)).unwrap();
+
+    assert!(aggregator.is_full());
+    assert!(matches!(
+        aggregator.push_request(request(100, 2)),
+        Err(RequestAggregatorError::QueueFull(_))
+    ));
+
+    aggregator.take_batch();
+
+    assert!(!aggregator.is_full());
+    assert!(aggregator.push_request(request(100, 2)).is_ok());
+}
+
+#[test]
+fn test_batch_into_proposal() {
+    let mut aggregator = aggregator(10, 10);
+
+    aggregator.push_request(request(100, 0)).unwrap();
+
+    let batch = aggregator.take_batch();
+    let digest = *batch.digest();
+
+    assert!(matches!(
+        batch.into_proposal(),
+        ReliableBroadcastMessage::Send(requests, proposal_digest) if requests.len() == 1 && proposal_digest == digest
+    ));
+}
+
+fn aggregator_with_strategy(
+    max_batch_size: usize,
+    strategy: BatchSelectionStrategy,
+) -> RequestAggregator<TestRequest> {
+    RequestAggregator::new(
+        RequestAggregatorConfig::new(
+            max_batch_size,
+            usize::MAX,
+            Duration::from_secs(3600),
+            max_batch_size * 4,
+        )
+        .with_selection_strategy(strategy),
+    )
+}
+
+#[test]
+fn test_random_subset_selection() {
+    const N: usize = 4;
+    const B: usize = 8;
+
+    let mut aggregator =
+        aggregator_with_strategy(B, BatchSelectionStrategy::RandomSubset { n: N, seed: 42 });
+
+    for seq in 0..(2 * B as u32) {
+        aggregator.push_request(request(100, seq)).unwrap();
+    }
+
+    let batch = aggregator.take_batch();
+
+    // ⌊B/n⌋ requests, all of them from the first B requests of the queue
+    assert_eq!(B / N, batch.len());
+    assert!(
+        batch
+            .requests()
+            .iter()
+            .all(|rq| rq.message().sequence_number() < SeqNo::from(B as u32))
+    );
+    assert_eq!(2 * B - B / N, aggregator.queue_len());
+}
+
+#[test]
+fn test_random_subset_reproducible_from_seed() {
+    let take = |seed| {
+        let mut aggregator =
+            aggregator_with_strategy(16, BatchSelectionStrategy::RandomSubset { n: 4, seed });
+
+        for seq in 0..16 {
+            aggregator.push_request(request(100, seq)).unwrap();
+        }
+
+        aggregator
+            .take_batch()
+            .requests()
+            .iter()
+            .map(|rq| rq.message().sequence_number())
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(take(7), take(7));
+}
+
+#[test]
+#[should_panic(expected = "Invalid random subset parameters")]
+fn test_random_subset_rejects_empty_quorum() {
+    aggregator_with_strategy(8, BatchSelectionStrategy::RandomSubset { n: 0, seed: 42 });
+}
+
+#[test]
+fn test_hash_partitioned_selection_is_disjoint() {
+    const N: usize = 4;
+
+    let mut proposed = HashSet::default();
+    let mut total = 0;
+
+    for own_index in 0..N {
+        let mut aggregator =
+            aggregator_with_strategy(64, BatchSelectionStrategy::HashPartitioned { n: N, own_index });
+
+        for client in 0..16 {
+            aggregator.push_request(request(1000 + client, 0)).unwrap();
+        }
+
+        let batch = aggregator.take_batch();
+
+        total += batch.len();
+
+        batch.requests().iter().for_each(|rq| {
+            assert!(proposed.insert(rq.header().from()), "Client proposed by two nodes");
+        });
+    }
+
+    assert_eq!(16, total, "Every client must be assigned to exactly one node");
+}
+
+/// Split the clients 1000 to 1063 between those assigned to the first of `n` nodes
+/// and those assigned to the others
+fn partition_clients(n: usize) -> (Vec<u32>, Vec<u32>) {
+    let strategy = BatchSelectionStrategy::HashPartitioned { n, own_index: 0 };
+
+    let is_own = |client| {
+        let mut probe = aggregator_with_strategy(1, strategy);
+
+        probe.push_request(request(client, 0)).unwrap();
+
+        !probe.take_batch().is_empty()
+    };
+
+    (1000..1064).partition(|client| is_own(*client))
+}
+
+#[test]
+fn test_hash_partitioned_batch_ready_counts_own_partition() {
+    const N: usize = 4;
+
+    let strategy = BatchSelectionStrategy::HashPartitioned { n: N, own_index: 0 };
+
+    let (own, foreign) = partition_clients(N);
+
+    let mut aggregator = aggregator_with_strategy(2, strategy);
+
+    foreign.iter().take(4).for_each(|client| {
+        aggregator.push_request(request(*client, 0)).unwrap();
+    });
+
+    // The queue is over the batch size, but none of it can be proposed by us
+    assert!(!aggregator.is_batch_ready());
+    assert!(aggregator.poll_batch().is_none());
+
+    own.iter().take(2).for_each(|client| {
+        aggregator.push_request(request(*client, 0)).unwrap();
+    });
+
+    assert!(aggregator.is_batch_ready());
+    assert_eq!(2, aggregator.poll_batch().unwrap().len());
+    assert!(!aggregator.is_batch_ready());
+    assert_eq!(4, aggregator.queue_len());
+}
+
+#[test]
+fn test_hash_partitioned_foreign_requests_do_not_fill_queue() {
+    const N: usize = 4;
+
+    let (own, foreign) = partition_clients(N);
+
+    // A queue of 8 requests
+    let mut aggregator =
+        aggregator_with_strategy(2, BatchSelectionStrategy::HashPartitioned { n: N, own_index: 0 });
+
+    foreign.iter().take(8).for_each(|client| {
+        aggregator.push_request(request(*client, 0)).unwrap();
+    });
+
+    // The requests of other nodes are bounded on their own
+    assert!(!aggregator.is_full());
+    assert!(matches!(
+        aggregator.push_request(request(foreign[8], 0)),
+        Err(RequestAggregatorError::QueueFull(_))
+    ));
+
+    // So our own clients are still accepted
+    assert_eq!(
+        AggregatorPushResult::Queued,
+        aggregator.push_request(request(own[0], 0)).unwrap()
+    );
+}
+
+#[test]
+fn test_hash_partitioned_fallback_after_epochs() {
+    const N: usize = 4;
+
+    let (_, foreign) = partition_clients(N);
+
+    let mut aggregator = RequestAggregator::new(
+        RequestAggregatorConfig::new(4, usize::MAX, Duration::from_secs(3600), 16)
+            .with_selection_strategy(BatchSelectionStrategy::HashPartitioned { n: N, own_index: 0 })
+            .with_partition_fallback_epochs(2),
+    );
+
+    let no_batches: [&[StoredMessage<TestRequest>]; 0] = [];
+
+    aggregator.push_request(request(foreign[0], 0)).unwrap();
+
+    aggregator.register_decided_epoch(no_batches);
+
+    assert!(aggregator.take_batch().is_empty());
+
+    // The node it is assigned to did not get it decided, so we propose it ourselves
+    aggregator.register_decided_epoch(no_batches);
+
+    assert_eq!(1, aggregator.take_batch().len());
+    assert_eq!(0, aggregator.queue_len());
+}
+
+#[test]
+fn test_hash_partitioned_follows_membership() {
+    let members = (0..4).map(NodeId).collect::<Vec<_>>();
+
+    let strategy = BatchSelectionStrategy::HashPartitioned { n: 4, own_index: 0 };
+
+    assert_eq!(
+        BatchSelectionStrategy::HashPartitioned { n: 3, own_index: 1 },
+        strategy.for_members(NodeId(2), &members[1..])
+    );
+
+    let (own, foreign) = partition_clients(4);
+
+    let mut aggregator = aggregator_with_strategy(64, strategy);
+
+    own.iter().chain(foreign.iter()).for_each(|client| {
+        aggregator.push_request(request(*client, 0)).unwrap();
+    });
+
+    // With a single member left every client is assigned to us
+    aggregator.set_selection_strategy(strategy.for_members(NodeId(0), &members[..1]));
+
+    assert_eq!(own.len() + foreign.len(), aggregator.take_batch().len());
+}
+
+#[test]
+fn test_duplicate_statistics() {
+    let mut aggregator = aggregator(10, 10);
+
+    let first = vec![request(100, 0), request(100, 1)];
+    let second = vec![request(100, 1), request(101, 0)];
+
+    let stats =
+        aggregator.register_decided_epoch([first.as_slice(), second.as_slice()]);
+
+    assert_eq!(4, stats.total_requests());
+    assert_eq!(3, stats.unique_requests());
+    assert_eq!(1, stats.duplicates());
+    assert!((stats.duplicate_rate() - 0.25).abs() < f64::EPSILON);
+    assert_eq!(Some(&stats), aggregator.last_epoch_stats());
+}
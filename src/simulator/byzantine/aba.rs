@@ -0,0 +1,136 @@
+use crate::async_bin_agreement::messages::{
+    AsyncBinaryAgreementMessage, AsyncBinaryAgreementMessageType,
+};
+use crate::simulator::byzantine::strategy::ByzantineStrategy;
+use atlas_common::crypto::threshold_crypto::PrivateKeyPart;
+use atlas_common::node_id::NodeId;
+use rand::rngs::StdRng;
+
+/// Votes `true` towards the nodes with an even id and `false` towards the others,
+/// in every phase of every round, trying to keep the correct nodes divided
+#[derive(Debug, Default)]
+pub(crate) struct SplitVoter;
+
+impl ByzantineStrategy<AsyncBinaryAgreementMessage> for SplitVoter {
+    fn tamper(
+        &mut self,
+        target: NodeId,
+        message: AsyncBinaryAgreementMessage,
+        _rng: &mut StdRng,
+    ) -> Vec<(NodeId, AsyncBinaryAgreementMessage)> {
+        let value = target.0 % 2 == 0;
+
+        let (round, message_type) = message.into_inner();
+
+        let message_type = match message_type {
+            AsyncBinaryAgreementMessageType::Val { .. } => {
+                AsyncBinaryAgreementMessageType::Val { estimate: value }
+            }
+            AsyncBinaryAgreementMessageType::Aux { .. } => AsyncBinaryAgreementMessageType::Aux {
+                accepted_estimates: vec![value],
+            },
+            AsyncBinaryAgreementMessageType::Conf {
+                partial_signatures, ..
+            } => AsyncBinaryAgreementMessageType::Conf {
+                feasible_values: vec![value],
+                partial_signatures,
+            },
+            AsyncBinaryAgreementMessageType::Finish { .. } => {
+                AsyncBinaryAgreementMessageType::Finish { value }
+            }
+        };
+
+        vec![(target, AsyncBinaryAgreementMessage::new(message_type, round))]
+    }
+}
+
+/// Never sends its confirmations, which carry its share of the common coin
+#[derive(Debug, Default)]
+pub(crate) struct CoinShareWithholder;
+
+impl ByzantineStrategy<AsyncBinaryAgreementMessage> for CoinShareWithholder {
+    fn tamper(
+        &mut self,
+        target: NodeId,
+        message: AsyncBinaryAgreementMessage,
+        _rng: &mut StdRng,
+    ) -> Vec<(NodeId, AsyncBinaryAgreementMessage)> {
+        match message.message_type() {
+            AsyncBinaryAgreementMessageType::Conf { .. } => Vec::new(),
+            _ => vec![(target, message)],
+        }
+    }
+}
+
+/// Replaces its coin shares with shares for the wrong round, which do not verify
+#[derive(Debug)]
+pub(crate) struct InvalidCoinShare {
+    // The key shares dealt to the node
+    keys: Vec<PrivateKeyPart>,
+}
+
+impl InvalidCoinShare {
+    pub(crate) fn new(keys: Vec<PrivateKeyPart>) -> Self {
+        Self { keys }
+    }
+}
+
+impl ByzantineStrategy<AsyncBinaryAgreementMessage> for InvalidCoinShare {
+    fn tamper(
+        &mut self,
+        target: NodeId,
+        message: AsyncBinaryAgreementMessage,
+        _rng: &mut StdRng,
+    ) -> Vec<(NodeId, AsyncBinaryAgreementMessage)> {
+        let (round, message_type) = message.into_inner();
+
+        let message_type = match message_type {
+            AsyncBinaryAgreementMessageType::Conf {
+                feasible_values, ..
+            } => AsyncBinaryAgreementMessageType::Conf {
+                feasible_values,
+                partial_signatures: self
+                    .keys
+                    .iter()
+                    .map(|key| key.partially_sign(&(round + 1).to_le_bytes()[..]))
+                    .collect(),
+            },
+            message_type => message_type,
+        };
+
+        vec![(target, AsyncBinaryAgreementMessage::new(message_type, round))]
+    }
+}
+
+/// Sends, along with each message, a copy of it for each of the next `rounds` rounds
+#[derive(Debug)]
+pub(crate) struct FutureRoundFlooder {
+    rounds: usize,
+}
+
+impl FutureRoundFlooder {
+    pub(crate) fn new(rounds: usize) -> Self {
+        Self { rounds }
+    }
+}
+
+impl ByzantineStrategy<AsyncBinaryAgreementMessage> for FutureRoundFlooder {
+    fn tamper(
+        &mut self,
+        target: NodeId,
+        message: AsyncBinaryAgreementMessage,
+        _rng: &mut StdRng,
+    ) -> Vec<(NodeId, AsyncBinaryAgreementMessage)> {
+        let round = message.round();
+
+        let flood = (1..=self.rounds)
+            .map(|ahead| {
+                let message_type = message.message_type().clone();
+
+                (target, AsyncBinaryAgreementMessage::new(message_type, round + ahead))
+            })
+            .collect::<Vec<_>>();
+
+        std::iter::once((target, message)).chain(flood).collect()
+    }
+}
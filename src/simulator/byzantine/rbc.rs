@@ -0,0 +1,70 @@
+use crate::reliable_broadcast::messages::ReliableBroadcastMessage;
+use crate::reliable_broadcast::reliable_broadcast::BroadcastPayload;
+use crate::simulator::byzantine::strategy::ByzantineStrategy;
+use atlas_common::crypto::hash::Digest;
+use atlas_common::node_id::NodeId;
+use rand::rngs::StdRng;
+
+/// A reliable broadcast sender which proposes the `alternative` value to the nodes
+/// with an odd id, and its honest proposal to every other node
+#[derive(Debug)]
+pub(crate) struct EquivocatingSender<P> {
+    alternative: P,
+}
+
+impl<P> EquivocatingSender<P> {
+    pub(crate) fn new(alternative: P) -> Self {
+        Self { alternative }
+    }
+}
+
+impl<P> ByzantineStrategy<ReliableBroadcastMessage<P>> for EquivocatingSender<P>
+where
+    P: BroadcastPayload,
+{
+    fn tamper(
+        &mut self,
+        target: NodeId,
+        message: ReliableBroadcastMessage<P>,
+        _rng: &mut StdRng,
+    ) -> Vec<(NodeId, ReliableBroadcastMessage<P>)> {
+        let message = match message {
+            ReliableBroadcastMessage::Send(_, _) if target.0 % 2 == 1 => {
+                let digest = self.alternative.payload_digest();
+
+                ReliableBroadcastMessage::Send(self.alternative.clone(), digest)
+            }
+            message => message,
+        };
+
+        vec![(target, message)]
+    }
+}
+
+/// Echoes `digest` instead of the digest of the proposal it received
+#[derive(Debug)]
+pub(crate) struct WrongDigestEcho {
+    digest: Digest,
+}
+
+impl WrongDigestEcho {
+    pub(crate) fn new(digest: Digest) -> Self {
+        Self { digest }
+    }
+}
+
+impl<P> ByzantineStrategy<ReliableBroadcastMessage<P>> for WrongDigestEcho {
+    fn tamper(
+        &mut self,
+        target: NodeId,
+        message: ReliableBroadcastMessage<P>,
+        _rng: &mut StdRng,
+    ) -> Vec<(NodeId, ReliableBroadcastMessage<P>)> {
+        let message = match message {
+            ReliableBroadcastMessage::Echo(_) => ReliableBroadcastMessage::Echo(self.digest),
+            message => message,
+        };
+
+        vec![(target, message)]
+    }
+}
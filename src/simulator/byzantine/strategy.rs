@@ -0,0 +1,133 @@
+use atlas_common::node_id::NodeId;
+use atlas_common::ordering::{Orderable, SeqNo};
+use rand::Rng;
+use rand::rngs::StdRng;
+
+/// The behavior of a Byzantine node in a simulation.
+///
+/// A Byzantine node still runs the honest protocol, but every message it sends goes
+/// through its strategy first, which decides what is actually put on the network.
+/// Strategies can rewrite, withhold or add messages, so most attacks are expressed
+/// as a deviation from what a correct node would have sent at that point.
+pub(crate) trait ByzantineStrategy<M> {
+    /// Turn a message the honest protocol sent to `target` into the messages actually sent
+    fn tamper(&mut self, target: NodeId, message: M, rng: &mut StdRng) -> Vec<(NodeId, M)>;
+}
+
+impl<M> ByzantineStrategy<M> for Box<dyn ByzantineStrategy<M>> {
+    fn tamper(&mut self, target: NodeId, message: M, rng: &mut StdRng) -> Vec<(NodeId, M)> {
+        self.as_mut().tamper(target, message, rng)
+    }
+}
+
+/// Sends nothing at all, as a crashed node would
+#[derive(Debug, Default)]
+pub(crate) struct Silent;
+
+impl<M> ByzantineStrategy<M> for Silent {
+    fn tamper(&mut self, _target: NodeId, _message: M, _rng: &mut StdRng) -> Vec<(NodeId, M)> {
+        Vec::new()
+    }
+}
+
+/// Sends every message as the honest protocol would, and along with each of them,
+/// replays `replays` messages picked at random from the ones sent before
+#[derive(Debug)]
+pub(crate) struct Replay<M> {
+    replays: usize,
+    history: Vec<(NodeId, M)>,
+}
+
+impl<M> Replay<M> {
+    pub(crate) fn new(replays: usize) -> Self {
+        Self {
+            replays,
+            history: Vec::new(),
+        }
+    }
+}
+
+impl<M> ByzantineStrategy<M> for Replay<M>
+where
+    M: Clone,
+{
+    fn tamper(&mut self, target: NodeId, message: M, rng: &mut StdRng) -> Vec<(NodeId, M)> {
+        let mut sent = Vec::with_capacity(self.replays + 1);
+
+        if !self.history.is_empty() {
+            for _ in 0..self.replays {
+                let replayed = rng.random_range(0..self.history.len());
+
+                sent.push(self.history[replayed].clone());
+            }
+        }
+
+        self.history.push((target, message.clone()));
+        sent.push((target, message));
+
+        sent
+    }
+}
+
+/// Replays every message sent for an epoch once the node moves on to a later one,
+/// so its peers keep receiving messages for epochs they have already finalized
+#[derive(Debug)]
+pub(crate) struct StaleEpochReplay<M> {
+    current: Option<SeqNo>,
+    // The messages sent for the current epoch
+    sent: Vec<(NodeId, M)>,
+}
+
+impl<M> Default for StaleEpochReplay<M> {
+    fn default() -> Self {
+        Self {
+            current: None,
+            sent: Vec::new(),
+        }
+    }
+}
+
+impl<M> ByzantineStrategy<M> for StaleEpochReplay<M>
+where
+    M: Orderable + Clone,
+{
+    fn tamper(&mut self, target: NodeId, message: M, _rng: &mut StdRng) -> Vec<(NodeId, M)> {
+        let epoch = message.sequence_number();
+
+        let mut sent = match self.current {
+            Some(current) if epoch > current => std::mem::take(&mut self.sent),
+            Some(current) if epoch < current => return vec![(target, message)],
+            _ => Vec::new(),
+        };
+
+        self.current = Some(epoch);
+        self.sent.push((target, message.clone()));
+
+        sent.push((target, message));
+
+        sent
+    }
+}
+
+/// Applies several strategies in sequence, each to the messages produced by the previous one
+pub(crate) struct Chain<M> {
+    strategies: Vec<Box<dyn ByzantineStrategy<M>>>,
+}
+
+impl<M> Chain<M> {
+    pub(crate) fn new(strategies: Vec<Box<dyn ByzantineStrategy<M>>>) -> Self {
+        Self { strategies }
+    }
+}
+
+impl<M> ByzantineStrategy<M> for Chain<M> {
+    fn tamper(&mut self, target: NodeId, message: M, rng: &mut StdRng) -> Vec<(NodeId, M)> {
+        self.strategies
+            .iter_mut()
+            .fold(vec![(target, message)], |sent, strategy| {
+                sent.into_iter()
+                    .flat_map(|(target, message)| strategy.tamper(target, message, rng))
+                    .collect()
+            })
+    }
+}
@@ -0,0 +1,340 @@
+use crate::simulator::network::{Envelope, Outbox};
+use crate::simulator::simulation::{SimProtocol, stored_message};
+use atlas_common::node_id::NodeId;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, HashSet, VecDeque};
+use std::fmt::{Debug, Write};
+use std::hash::{Hash, Hasher};
+use std::sync::Arc;
+use thiserror::Error;
+
+/// The output of each node in a state of the model
+pub(crate) type Outputs<O> = BTreeMap<NodeId, Option<O>>;
+
+type Invariant<O> = (&'static str, Box<dyn Fn(&Outputs<O>) -> bool>);
+
+/// No two nodes output different values
+pub(crate) fn agreement<O>(outputs: &Outputs<O>) -> bool
+where
+    O: PartialEq,
+{
+    let mut decided = outputs.values().flatten();
+
+    decided
+        .next()
+        .is_none_or(|first| decided.all(|output| output == first))
+}
+
+/// Every node produced an output
+pub(crate) fn all_output<O>(outputs: &Outputs<O>) -> bool {
+    outputs.values().all(Option::is_some)
+}
+
+/// A protocol whose every schedule can be explored by the [`ModelChecker`].
+///
+/// Exploring every interleaving is only tractable because states which differ only in
+/// what can no longer influence the run are merged, so each node reports the part of its
+/// state which still matters, and which of the messages in flight it will never act on.
+pub(crate) trait ModelProtocol: SimProtocol + Clone {
+    /// Feed into `hasher` the part of the state which can still influence this node
+    fn fingerprint<H: Hasher>(&self, hasher: &mut H);
+
+    /// Feed into `hasher` what identifies the message
+    fn message_fingerprint<H: Hasher>(message: &Self::Message, hasher: &mut H);
+
+    /// Whether delivering the message can no longer have any effect on this node
+    fn ignores(&self, message: &Self::Message) -> bool;
+}
+
+/// The outcome of an exhaustive exploration which found no violation
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Exploration {
+    /// The amount of distinct states explored
+    pub(crate) states: usize,
+    /// The amount of those states in which no message was left in flight
+    pub(crate) quiescent_states: usize,
+}
+
+#[derive(Error, Debug)]
+pub(crate) enum ModelCheckError<M, O>
+where
+    M: Debug,
+    O: Debug,
+{
+    #[error("Invariant {invariant} violated with outputs {outputs:?} after the schedule:\n{}", format_trace(.trace))]
+    Violation {
+        invariant: &'static str,
+        outputs: Outputs<O>,
+        /// The shortest schedule which reaches the violation
+        trace: Vec<Envelope<M>>,
+    },
+    #[error("Gave up after exploring {states} states")]
+    StateLimit { states: usize },
+}
+
+/// Explores every order in which the messages of a run can be delivered, breadth first.
+///
+/// Unlike the [`Simulation`](crate::simulator::simulation::Simulation), which follows a single
+/// schedule drawn from its seed, this checks the invariants in every reachable state, so it
+/// finds the rare interleavings random schedules miss. Being breadth first, the schedule
+/// reported for a violation is a shortest one reaching it.
+///
+/// Only the modelled nodes take part: messages sent to any other node are dropped, which
+/// models crashed nodes, and Byzantine nodes are modelled by the messages they initially send.
+pub(crate) struct ModelChecker<P>
+where
+    P: ModelProtocol,
+{
+    nodes: BTreeMap<NodeId, P>,
+    initial: Vec<Envelope<P::Message>>,
+    invariants: Vec<Invariant<P::Output>>,
+    quiescent_invariants: Vec<Invariant<P::Output>>,
+    max_states: usize,
+}
+
+#[derive(Clone)]
+struct ModelState<P>
+where
+    P: ModelProtocol,
+{
+    nodes: BTreeMap<NodeId, P>,
+    in_flight: Vec<Envelope<P::Message>>,
+}
+
+impl<P> ModelChecker<P>
+where
+    P: ModelProtocol,
+{
+    pub(crate) fn new<I>(nodes: I) -> Self
+    where
+        I: IntoIterator<Item = (NodeId, P)>,
+    {
+        Self {
+            nodes: nodes.into_iter().collect(),
+            initial: Vec::new(),
+            invariants: Vec::new(),
+            quiescent_invariants: Vec::new(),
+            max_states: 1_000_000,
+        }
+    }
+
+    /// A message in flight when the run starts, such as those sent by a Byzantine node
+    pub(crate) fn with_message(mut self, from: NodeId, to: NodeId, message: P::Message) -> Self {
+        self.initial.push(Envelope { from, to, message });
+        self
+    }
+
+    /// An invariant which must hold in every reachable state
+    pub(crate) fn with_invariant<F>(mut self, name: &'static str, invariant: F) -> Self
+    where
+        F: Fn(&Outputs<P::Output>) -> bool + 'static,
+    {
+        self.invariants.push((name, Box::new(invariant)));
+        self
+    }
+
+    /// An invariant which must hold once no message is left in flight, which is
+    /// how deadlocks are expressed
+    pub(crate) fn with_quiescent_invariant<F>(mut self, name: &'static str, invariant: F) -> Self
+    where
+        F: Fn(&Outputs<P::Output>) -> bool + 'static,
+    {
+        self.quiescent_invariants.push((name, Box::new(invariant)));
+        self
+    }
+
+    pub(crate) fn explore(self) -> Result<Exploration, ModelCheckError<P::Message, P::Output>> {
+        let mut initial = ModelState {
+            nodes: self.nodes.clone(),
+            in_flight: self.initial.clone(),
+        };
+
+        let ids = initial.nodes.keys().cloned().collect::<Vec<_>>();
+
+        for id in ids {
+            let outbox = Arc::new(Outbox::default());
+
+            initial.nodes.get_mut(&id).unwrap().start(&outbox);
+
+            initial.send(id, outbox.take());
+        }
+
+        initial.prune();
+
+        let mut visited = HashSet::from([initial.fingerprint()]);
+        // How each explored state was reached, by its parent and the message delivered
+        let mut parents: Vec<Option<(usize, Envelope<P::Message>)>> = vec![None];
+        let mut queue = VecDeque::from([(0, initial)]);
+
+        let mut quiescent_states = 0;
+
+        while let Some((index, state)) = queue.pop_front() {
+            let outputs = state.outputs();
+
+            let quiescent = state.in_flight.is_empty();
+
+            let invariants = self.invariants.iter().chain(
+                quiescent
+                    .then_some(self.quiescent_invariants.iter())
+                    .into_iter()
+                    .flatten(),
+            );
+
+            for (invariant, holds) in invariants {
+                if !holds(&outputs) {
+                    return Err(ModelCheckError::Violation {
+                        invariant: *invariant,
+                        outputs,
+                        trace: trace(&parents, index),
+                    });
+                }
+            }
+
+            if quiescent {
+                quiescent_states += 1;
+                continue;
+            }
+
+            let mut expanded = HashSet::new();
+
+            for position in 0..state.in_flight.len() {
+                // Delivering either of two identical messages leads to the same state
+                if !expanded.insert(envelope_fingerprint::<P>(&state.in_flight[position])) {
+                    continue;
+                }
+
+                let mut next = state.clone();
+
+                let envelope = next.in_flight.swap_remove(position);
+
+                next.deliver(envelope.clone());
+
+                if visited.insert(next.fingerprint()) {
+                    if visited.len() > self.max_states {
+                        return Err(ModelCheckError::StateLimit {
+                            states: visited.len(),
+                        });
+                    }
+
+                    parents.push(Some((index, envelope)));
+                    queue.push_back((parents.len() - 1, next));
+                }
+            }
+        }
+
+        Ok(Exploration {
+            states: visited.len(),
+            quiescent_states,
+        })
+    }
+}
+
+impl<P> ModelState<P>
+where
+    P: ModelProtocol,
+{
+    fn deliver(&mut self, envelope: Envelope<P::Message>) {
+        let Envelope { from, to, message } = envelope;
+
+        let outbox = Arc::new(Outbox::default());
+
+        if let Some(node) = self.nodes.get_mut(&to) {
+            node.deliver(stored_message(from, to, message), &outbox);
+        }
+
+        self.send(to, outbox.take());
+        self.prune();
+    }
+
+    fn send(&mut self, from: NodeId, sent: Vec<(NodeId, P::Message)>) {
+        self.in_flight.extend(
+            sent.into_iter()
+                .map(|(to, message)| Envelope { from, to, message }),
+        );
+    }
+
+    /// Drop the messages in flight which can no longer have any effect on their target
+    fn prune(&mut self) {
+        let nodes = &self.nodes;
+
+        self.in_flight.retain(|envelope| {
+            nodes
+                .get(&envelope.to)
+                .is_some_and(|node| !node.ignores(&envelope.message))
+        });
+    }
+
+    fn outputs(&self) -> Outputs<P::Output> {
+        self.nodes
+            .iter()
+            .map(|(id, node)| (*id, node.output()))
+            .collect()
+    }
+
+    /// Identifies the state up to the order of the messages in flight
+    fn fingerprint(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+
+        for (id, node) in &self.nodes {
+            id.hash(&mut hasher);
+            node.fingerprint(&mut hasher);
+        }
+
+        let mut in_flight = self
+            .in_flight
+            .iter()
+            .map(envelope_fingerprint::<P>)
+            .collect::<Vec<_>>();
+
+        in_flight.sort_unstable();
+        in_flight.hash(&mut hasher);
+
+        hasher.finish()
+    }
+}
+
+fn envelope_fingerprint<P>(envelope: &Envelope<P::Message>) -> u64
+where
+    P: ModelProtocol,
+{
+    let mut hasher = DefaultHasher::new();
+
+    envelope.from.hash(&mut hasher);
+    envelope.to.hash(&mut hasher);
+    P::message_fingerprint(&envelope.message, &mut hasher);
+
+    hasher.finish()
+}
+
+fn trace<M>(parents: &[Option<(usize, Envelope<M>)>], mut index: usize) -> Vec<Envelope<M>>
+where
+    M: Clone,
+{
+    let mut trace = Vec::new();
+
+    while let Some((parent, envelope)) = &parents[index] {
+        trace.push(envelope.clone());
+        index = *parent;
+    }
+
+    trace.reverse();
+    trace
+}
+
+fn format_trace<M>(trace: &[Envelope<M>]) -> String
+where
+    M: Debug,
+{
+    trace
+        .iter()
+        .enumerate()
+        .fold(String::new(), |mut formatted, (step, envelope)| {
+            let _ = writeln!(
+                formatted,
+                "{step:>4}: {:?} -> {:?}: {:?}",
+                envelope.from, envelope.to, envelope.message
+            );
+
+            formatted
+        })
+}
@@ -0,0 +1,245 @@
+use crate::aba::AsyncBinaryAgreementSendNode;
+use crate::committee_election::CommitteeElectionSendNode;
+use crate::rbc::ReliableBroadcastSendNode;
+use atlas_common::node_id::NodeId;
+use atlas_common::serialization_helper::SerMsg;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::cell::RefCell;
+use std::cmp::{Ordering, Reverse};
+use std::collections::BinaryHeap;
+
+/// The delays the simulated network applies to every message, in simulated time units.
+///
+/// Each message is delayed by a random amount within the bounds, so messages
+/// sent by the same node can be delivered in a different order than they were sent in.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct NetworkConfig {
+    pub(crate) min_delay: u64,
+    pub(crate) max_delay: u64,
+}
+
+impl Default for NetworkConfig {
+    fn default() -> Self {
+        Self {
+            min_delay: 1,
+            max_delay: 100,
+        }
+    }
+}
+
+/// A message travelling through the simulated network
+#[derive(Debug, Clone)]
+pub(crate) struct Envelope<M> {
+    pub(crate) from: NodeId,
+    pub(crate) to: NodeId,
+    pub(crate) message: M,
+}
+
+struct InFlight<M> {
+    deliver_at: u64,
+    // The order in which the message was sent, which breaks ties between equal delivery times
+    sequence: u64,
+    envelope: Envelope<M>,
+}
+
+impl<M> PartialEq for InFlight<M> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl<M> Eq for InFlight<M> {}
+
+impl<M> PartialOrd for InFlight<M> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<M> Ord for InFlight<M> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.deliver_at, self.sequence).cmp(&(other.deliver_at, other.sequence))
+    }
+}
+
+/// An in memory network, whose scheduling is fully determined by its seed.
+///
+/// Every message is delivered exactly once, after a random delay drawn from the seeded
+/// generator, so two runs with the same seed deliver the same messages in the same order.
+pub(crate) struct SimNetwork<M> {
+    config: NetworkConfig,
+    rng: StdRng,
+    // The current simulated time, which is the delivery time of the last delivered message
+    now: u64,
+    sent: u64,
+    in_flight: BinaryHeap<Reverse<InFlight<M>>>,
+}
+
+impl<M> SimNetwork<M> {
+    pub(crate) fn new(seed: u64, config: NetworkConfig) -> Self {
+        assert!(
+            config.min_delay <= config.max_delay,
+            "The minimum delay can not exceed the maximum delay"
+        );
+
+        Self {
+            config,
+            rng: StdRng::seed_from_u64(seed),
+            now: 0,
+            sent: 0,
+            in_flight: BinaryHeap::new(),
+        }
+    }
+
+    pub(crate) fn now(&self) -> u64 {
+        self.now
+    }
+
+    pub(crate) fn in_flight(&self) -> usize {
+        self.in_flight.len()
+    }
+
+    pub(crate) fn send(&mut self, from: NodeId, to: NodeId, message: M) {
+        let delay = self
+            .rng
+            .random_range(self.config.min_delay..=self.config.max_delay);
+
+        self.in_flight.push(Reverse(InFlight {
+            deliver_at: self.now + delay,
+            sequence: self.sent,
+            envelope: Envelope { from, to, message },
+        }));
+
+        self.sent += 1;
+    }
+
+    /// Deliver the next message, advancing the simulated time to its delivery
+    pub(crate) fn next(&mut self) -> Option<Envelope<M>> {
+        let Reverse(in_flight) = self.in_flight.pop()?;
+
+        self.now = in_flight.deliver_at;
+
+        Some(in_flight.envelope)
+    }
+}
+
+/// The messages sent by a simulated node while it processes a message.
+///
+/// Implements the send interfaces of the sub protocols, so the state machines
+/// can be driven by the simulator without any change.
+pub(crate) struct Outbox<M> {
+    sent: RefCell<Vec<(NodeId, M)>>,
+}
+
+impl<M> Default for Outbox<M> {
+    fn default() -> Self {
+        Self {
+            sent: RefCell::new(Vec::new()),
+        }
+    }
+}
+
+impl<M> Outbox<M> {
+    pub(crate) fn push(&self, target: NodeId, message: M) {
+        self.sent.borrow_mut().push((target, message));
+    }
+
+    pub(crate) fn push_all<I>(&self, targets: I, message: M)
+    where
+        I: IntoIterator<Item = NodeId>,
+        M: Clone,
+    {
+        let mut sent = self.sent.borrow_mut();
+
+        targets
+            .into_iter()
+            .for_each(|target| sent.push((target, message.clone())));
+    }
+
+    /// Take the messages sent since the last time this was called
+    pub(crate) fn take(&self) -> Vec<(NodeId, M)> {
+        std::mem::take(&mut *self.sent.borrow_mut())
+    }
+}
+
+impl<M> ReliableBroadcastSendNode<M> for Outbox<M>
+where
+    M: SerMsg + Clone,
+{
+    fn send(&self, message: M, target: NodeId, _flush: bool) -> atlas_common::error::Result<()> {
+        self.push(target, message);
+
+        Ok(())
+    }
+
+    fn send_signed(
+        &self,
+        message: M,
+        target: NodeId,
+        _flush: bool,
+    ) -> atlas_common::error::Result<()> {
+        self.push(target, message);
+
+        Ok(())
+    }
+
+    fn broadcast<I>(&self, message: M, targets: I) -> Result<(), Vec<NodeId>>
+    where
+        I: Iterator<Item = NodeId>,
+    {
+        self.push_all(targets, message);
+
+        Ok(())
+    }
+
+    fn broadcast_signed<I>(&self, message: M, targets: I) -> Result<(), Vec<NodeId>>
+    where
+        I: Iterator<Item = NodeId>,
+    {
+        self.push_all(targets, message);
+
+        Ok(())
+    }
+}
+
+impl<M> AsyncBinaryAgreementSendNode<M> for Outbox<M>
+where
+    M: Clone,
+{
+    fn broadcast_message<I>(&self, message: M, target: I) -> atlas_common::error::Result<()>
+    where
+        I: Iterator<Item = NodeId>,
+        M: SerMsg,
+    {
+        self.push_all(target, message);
+
+        Ok(())
+    }
+}
+
+impl<M> CommitteeElectionSendNode<M> for Outbox<M>
+where
+    M: SerMsg + Clone,
+{
+    fn send(&self, message: M, target: NodeId, _flush: bool) -> Result<(), ()> {
+        self.push(target, message);
+
+        Ok(())
+    }
+
+    fn send_signed(&self, message: M, target: NodeId, _flush: bool) -> Result<(), ()> {
+        self.push(target, message);
+
+        Ok(())
+    }
+
+    fn broadcast<I>(&self, message: M, targets: I) -> Result<(), Vec<NodeId>>
+    where
+        I: IntoIterator<Item = NodeId>,
+    {
+        self.push_all(targets, message);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,111 @@
+use crate::simulator::simulation::SimReport;
+use atlas_common::node_id::NodeId;
+use std::fmt::Debug;
+use thiserror::Error;
+
+/// A global property which did not hold at the end of a simulation run.
+///
+/// Carries the seed of the run, so it can be reproduced.
+#[derive(Error, Debug)]
+pub(crate) enum PropertyViolation<O>
+where
+    O: Debug,
+{
+    #[error("Run with seed {seed} violated agreement: {first:?} and {second:?} output different values")]
+    Agreement {
+        seed: u64,
+        first: (NodeId, O),
+        second: (NodeId, O),
+    },
+    #[error("Run with seed {seed} violated validity: {node:?} output {output:?}")]
+    Validity { seed: u64, node: NodeId, output: O },
+    #[error("Run with seed {seed} violated totality: {decided:?} produced an output, but {undecided:?} did not")]
+    Totality {
+        seed: u64,
+        decided: Vec<NodeId>,
+        undecided: Vec<NodeId>,
+    },
+    #[error("Run with seed {seed} did not terminate: {undecided:?} did not produce an output")]
+    Termination { seed: u64, undecided: Vec<NodeId> },
+}
+
+impl<O> SimReport<O>
+where
+    O: Clone + Debug + PartialEq,
+{
+    fn decided(&self) -> impl Iterator<Item = (NodeId, &O)> {
+        self.outputs
+            .iter()
+            .filter_map(|(node, output)| output.as_ref().map(|output| (*node, output)))
+    }
+
+    fn undecided(&self) -> Vec<NodeId> {
+        self.outputs
+            .iter()
+            .filter(|(_, output)| output.is_none())
+            .map(|(node, _)| *node)
+            .collect()
+    }
+
+    /// No two nodes output different values
+    pub(crate) fn check_agreement(&self) -> Result<(), PropertyViolation<O>> {
+        let mut decided = self.decided();
+
+        let Some((first, reference)) = decided.next() else {
+            return Ok(());
+        };
+
+        match decided.find(|(_, output)| *output != reference) {
+            Some((second, output)) => Err(PropertyViolation::Agreement {
+                seed: self.seed,
+                first: (first, reference.clone()),
+                second: (second, output.clone()),
+            }),
+            None => Ok(()),
+        }
+    }
+
+    /// Every output is one the protocol allows, as judged by `is_valid`
+    pub(crate) fn check_validity<F>(&self, is_valid: F) -> Result<(), PropertyViolation<O>>
+    where
+        F: Fn(&O) -> bool,
+    {
+        match self.decided().find(|(_, output)| !is_valid(output)) {
+            Some((node, output)) => Err(PropertyViolation::Validity {
+                seed: self.seed,
+                node,
+                output: output.clone(),
+            }),
+            None => Ok(()),
+        }
+    }
+
+    /// If any node produced an output, every node did
+    pub(crate) fn check_totality(&self) -> Result<(), PropertyViolation<O>> {
+        let undecided = self.undecided();
+
+        if undecided.is_empty() || undecided.len() == self.outputs.len() {
+            return Ok(());
+        }
+
+        Err(PropertyViolation::Totality {
+            seed: self.seed,
+            decided: self.decided().map(|(node, _)| node).collect(),
+            undecided,
+        })
+    }
+
+    /// Every node produced an output
+    pub(crate) fn check_termination(&self) -> Result<(), PropertyViolation<O>> {
+        let undecided = self.undecided();
+
+        if undecided.is_empty() {
+            return Ok(());
+        }
+
+        Err(PropertyViolation::Termination {
+            seed: self.seed,
+            undecided,
+        })
+    }
+}
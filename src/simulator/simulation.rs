@@ -0,0 +1,202 @@
+use crate::simulator::byzantine::strategy::ByzantineStrategy;
+use crate::simulator::network::{Envelope, NetworkConfig, Outbox, SimNetwork};
+use atlas_common::crypto::hash::Digest;
+use atlas_common::node_id::NodeId;
+use atlas_communication::lookup_table::MessageModule;
+use atlas_communication::message::{Buf, StoredMessage};
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+use std::collections::BTreeMap;
+use std::fmt::Debug;
+use std::sync::Arc;
+use tracing::debug;
+
+/// A state machine which can be run by the simulator, on a single node.
+///
+/// Adapters implement this for the sub protocols, driving their queued messages
+/// and starting them in the way the protocol orchestrating them would.
+/// The adapters of each sub protocol live along with its tests.
+pub(crate) trait SimProtocol {
+    type Message: Clone + Debug;
+    type Output: Clone + Debug + PartialEq;
+
+    /// Send the first messages of the protocol, if this node starts it
+    fn start(&mut self, outbox: &Arc<Outbox<Self::Message>>);
+
+    /// Process a message delivered by the network, along with every queued message it unblocks
+    fn deliver(
+        &mut self,
+        message: StoredMessage<Self::Message>,
+        outbox: &Arc<Outbox<Self::Message>>,
+    );
+
+    /// The output of the protocol on this node, once it has produced one
+    fn output(&self) -> Option<Self::Output>;
+}
+
+struct SimNode<P>
+where
+    P: SimProtocol,
+{
+    protocol: P,
+    outbox: Arc<Outbox<P::Message>>,
+    // The strategy every message sent by the node goes through, when it is Byzantine
+    byzantine: Option<(Box<dyn ByzantineStrategy<P::Message>>, StdRng)>,
+}
+
+/// A run of `n` nodes of a protocol, connected by a seeded in memory network.
+///
+/// The run is fully determined by its seed, so any failure it exposes
+/// can be reproduced by running it again with the same seed.
+pub(crate) struct Simulation<P>
+where
+    P: SimProtocol,
+{
+    seed: u64,
+    nodes: BTreeMap<NodeId, SimNode<P>>,
+    network: SimNetwork<P::Message>,
+    max_steps: usize,
+}
+
+impl<P> Simulation<P>
+where
+    P: SimProtocol,
+{
+    pub(crate) fn new<I>(seed: u64, config: NetworkConfig, nodes: I) -> Self
+    where
+        I: IntoIterator<Item = (NodeId, P)>,
+    {
+        let nodes = nodes
+            .into_iter()
+            .map(|(id, protocol)| {
+                let node = SimNode {
+                    protocol,
+                    outbox: Arc::new(Outbox::default()),
+                    byzantine: None,
+                };
+
+                (id, node)
+            })
+            .collect();
+
+        Self {
+            seed,
+            nodes,
+            network: SimNetwork::new(seed, config),
+            max_steps: 1_000_000,
+        }
+    }
+
+    /// Make the given node Byzantine, sending every message through `strategy`.
+    ///
+    /// Byzantine nodes are left out of the report, so the properties are only checked on correct nodes.
+    pub(crate) fn with_byzantine<S>(mut self, id: NodeId, strategy: S) -> Self
+    where
+        S: ByzantineStrategy<P::Message> + 'static,
+    {
+        let rng = StdRng::seed_from_u64(self.seed ^ u64::from(id.0));
+
+        if let Some(node) = self.nodes.get_mut(&id) {
+            node.byzantine = Some((Box::new(strategy), rng));
+        }
+
+        self
+    }
+
+    /// Run until no message is left in flight, or the step bound is reached
+    pub(crate) fn run(mut self) -> SimReport<P::Output> {
+        let ids = self.nodes.keys().cloned().collect::<Vec<_>>();
+
+        for id in &ids {
+            let node = self.nodes.get_mut(id).unwrap();
+
+            node.protocol.start(&node.outbox);
+
+            self.flush(*id);
+        }
+
+        let mut steps = 0;
+
+        while steps < self.max_steps {
+            let Some(Envelope { from, to, message }) = self.network.next() else {
+                break;
+            };
+
+            steps += 1;
+
+            let Some(node) = self.nodes.get_mut(&to) else {
+                continue;
+            };
+
+            node.protocol
+                .deliver(stored_message(from, to, message), &node.outbox);
+
+            self.flush(to);
+        }
+
+        debug!(
+            seed = self.seed,
+            steps,
+            time = self.network.now(),
+            "Simulation finished"
+        );
+
+        SimReport {
+            seed: self.seed,
+            steps,
+            elapsed: self.network.now(),
+            undelivered: self.network.in_flight(),
+            outputs: self
+                .nodes
+                .iter()
+                .filter(|(_, node)| node.byzantine.is_none())
+                .map(|(id, node)| (*id, node.protocol.output()))
+                .collect(),
+        }
+    }
+
+    /// Hand the messages the node has sent to the network
+    fn flush(&mut self, id: NodeId) {
+        let node = self.nodes.get_mut(&id).unwrap();
+
+        let mut sent = node.outbox.take();
+
+        if let Some((strategy, rng)) = &mut node.byzantine {
+            sent = sent
+                .into_iter()
+                .flat_map(|(target, message)| strategy.tamper(target, message, rng))
+                .collect();
+        }
+
+        sent.into_iter()
+            .for_each(|(target, message)| self.network.send(id, target, message));
+    }
+}
+
+/// The outcome of a simulation run
+#[derive(Debug)]
+pub(crate) struct SimReport<O> {
+    pub(crate) seed: u64,
+    /// The amount of messages delivered
+    pub(crate) steps: usize,
+    /// The simulated time the run took
+    pub(crate) elapsed: u64,
+    /// The messages still in flight when the run ended, which is zero unless the step bound was hit
+    pub(crate) undelivered: usize,
+    /// The output of each correct node
+    pub(crate) outputs: BTreeMap<NodeId, Option<O>>,
+}
+
+pub(crate) fn stored_message<M>(from: NodeId, to: NodeId, message: M) -> StoredMessage<M> {
+    let wire_msg = atlas_communication::message::WireMessage::new(
+        from,
+        to,
+        MessageModule::Application,
+        Buf::new(),
+        0,
+        Some(Digest::blank()),
+        None,
+    );
+
+    StoredMessage::new(wire_msg.header().clone(), message)
+}
@@ -0,0 +1,257 @@
+use crate::async_bin_agreement::messages::AsyncBinaryAgreementMessage;
+use crate::async_bin_agreement::test::simulation_test::AbaNode;
+use crate::quorum_info::quorum_info::QuorumInfo;
+use crate::reliable_broadcast::messages::ReliableBroadcastMessage;
+use crate::reliable_broadcast::test::simulation_test::{Batch, MsgType, RbcNode};
+use crate::simulator::network::{NetworkConfig, Outbox};
+use crate::simulator::simulation::{SimProtocol, Simulation, stored_message};
+use atlas_common::crypto::threshold_crypto::PrivateKeySet;
+use atlas_common::node_id::NodeId;
+use atlas_communication::message::StoredMessage;
+use std::sync::Arc;
+use test::Bencher;
+
+/// The messages of an epoch, along with the node whose batch they are about
+#[derive(Debug, Clone)]
+enum EpochMessage {
+    Broadcast(NodeId, ReliableBroadcastMessage<Batch>),
+    Agreement(NodeId, AsyncBinaryAgreementMessage),
+}
+
+/// The binary agreement on including the batch of a node
+enum Agreement {
+    /// We have not voted yet, so the messages received for it are held back
+    Waiting(Vec<StoredMessage<AsyncBinaryAgreementMessage>>),
+    Running(AbaNode),
+}
+
+/// A node running the asynchronous common subset at the core of an epoch.
+///
+/// The batch of every node is reliably broadcast, and a binary agreement per node decides
+/// whether its batch is part of the epoch. We vote for every batch we deliver,
+/// and against the rest once `n - f` batches have been included.
+struct EpochNode {
+    id: NodeId,
+    quorum_info: QuorumInfo,
+    key_set: Arc<PrivateKeySet>,
+    broadcasts: Vec<RbcNode>,
+    agreements: Vec<Agreement>,
+}
+
+fn index(id: NodeId) -> usize {
+    id.0 as usize
+}
+
+/// Hand the messages sent by a sub protocol to the network, tagged with the batch they are about
+fn forward<M, F>(sent: Vec<(NodeId, M)>, outbox: &Outbox<EpochMessage>, tag: F)
+where
+    F: Fn(M) -> EpochMessage,
+{
+    sent.into_iter()
+        .for_each(|(target, message)| outbox.push(target, tag(message)));
+}
+
+impl EpochNode {
+    fn new(
+        id: NodeId,
+        quorum_info: &QuorumInfo,
+        key_set: Arc<PrivateKeySet>,
+        batch: Vec<StoredMessage<MsgType>>,
+    ) -> Self {
+        let members = quorum_info.quorum_members();
+
+        let mut broadcasts = members
+            .iter()
+            .map(|sender| RbcNode::new(*sender, quorum_info, None))
+            .collect::<Vec<_>>();
+
+        broadcasts[index(id)] = RbcNode::new(id, quorum_info, Some(batch));
+
+        Self {
+            id,
+            quorum_info: quorum_info.clone(),
+            key_set,
+            broadcasts,
+            agreements: members
+                .iter()
+                .map(|_| Agreement::Waiting(Vec::new()))
+                .collect(),
+        }
+    }
+
+    /// Give our input to the agreement on the batch of `proposer`, unless we already have
+    fn vote(&mut self, proposer: NodeId, include: bool, outbox: &Outbox<EpochMessage>) {
+        let agreement = &mut self.agreements[index(proposer)];
+
+        let Agreement::Waiting(held) = agreement else {
+            return;
+        };
+
+        let held = std::mem::take(held);
+
+        let mut aba =
+            AbaNode::with_input(include, &self.quorum_info, &self.key_set, index(self.id));
+
+        let sent = Arc::new(Outbox::default());
+
+        aba.start(&sent);
+
+        for message in held {
+            aba.deliver(message, &sent);
+        }
+
+        forward(sent.take(), outbox, |message| {
+            EpochMessage::Agreement(proposer, message)
+        });
+
+        *agreement = Agreement::Running(aba);
+    }
+
+    /// Vote against every batch we have not voted on, once enough batches are included
+    fn close(&mut self, outbox: &Outbox<EpochMessage>) {
+        if self.included().len() < self.quorum_info.quorum_size() {
+            return;
+        }
+
+        for proposer in self.quorum_info.quorum_members().clone() {
+            self.vote(proposer, false, outbox);
+        }
+    }
+
+    fn included(&self) -> Vec<NodeId> {
+        self.agreements
+            .iter()
+            .enumerate()
+            .filter(|(_, agreement)| {
+                matches!(agreement, Agreement::Running(aba) if aba.output() == Some(true))
+            })
+            .map(|(proposer, _)| NodeId::from(proposer))
+            .collect()
+    }
+}
+
+impl SimProtocol for EpochNode {
+    type Message = EpochMessage;
+    type Output = Vec<NodeId>;
+
+    fn start(&mut self, outbox: &Arc<Outbox<Self::Message>>) {
+        for (proposer, broadcast) in self.broadcasts.iter_mut().enumerate() {
+            let sent = Arc::new(Outbox::default());
+
+            broadcast.start(&sent);
+
+            forward(sent.take(), outbox, |message| {
+                EpochMessage::Broadcast(NodeId::from(proposer), message)
+            });
+        }
+    }
+
+    fn deliver(
+        &mut self,
+        message: StoredMessage<Self::Message>,
+        outbox: &Arc<Outbox<Self::Message>>,
+    ) {
+        let (header, message) = message.into_inner();
+        let from = header.from();
+
+        match message {
+            EpochMessage::Broadcast(proposer, message) => {
+                let broadcast = &mut self.broadcasts[index(proposer)];
+                let sent = Arc::new(Outbox::default());
+
+                broadcast.deliver(stored_message(from, self.id, message), &sent);
+
+                forward(sent.take(), outbox, |message| {
+                    EpochMessage::Broadcast(proposer, message)
+                });
+
+                if broadcast.output().is_some() {
+                    self.vote(proposer, true, outbox);
+                }
+            }
+            EpochMessage::Agreement(proposer, message) => {
+                let message = stored_message(from, self.id, message);
+
+                match &mut self.agreements[index(proposer)] {
+                    Agreement::Waiting(held) => held.push(message),
+                    Agreement::Running(aba) => {
+                        let sent = Arc::new(Outbox::default());
+
+                        aba.deliver(message, &sent);
+
+                        forward(sent.take(), outbox, |message| {
+                            EpochMessage::Agreement(proposer, message)
+                        });
+                    }
+                }
+            }
+        }
+
+        self.close(outbox);
+    }
+
+    fn output(&self) -> Option<Self::Output> {
+        let decided = self.agreements.iter().all(|agreement| {
+            matches!(agreement, Agreement::Running(aba) if aba.output().is_some())
+        });
+
+        decided.then(|| self.included())
+    }
+}
+
+/// Run a whole epoch in which every node proposes a batch of `batch_size` requests.
+///
+/// Each request is a single byte, so the throughput reported in MB/s reads as millions of
+/// requests ordered per second. The schedule is the same in every iteration.
+fn bench_epoch(bencher: &mut Bencher, n: usize, batch_size: usize) {
+    let f = (n - 1) / 3;
+    let quorum_info = QuorumInfo::new(n, f, (0..n).map(NodeId::from).collect());
+    let key_set = Arc::new(PrivateKeySet::gen_random(f));
+
+    bencher.bytes = (n * batch_size) as u64;
+
+    bencher.iter(|| {
+        let nodes = (0..n).map(NodeId::from).map(|id| {
+            let batch = (0..batch_size)
+                .map(|request| stored_message(id, id, request as MsgType))
+                .collect();
+
+            (id, EpochNode::new(id, &quorum_info, key_set.clone(), batch))
+        });
+
+        let report = Simulation::new(0, NetworkConfig::default(), nodes).run();
+
+        assert!(report.outputs.values().all(|included| {
+            included
+                .as_ref()
+                .is_some_and(|included| included.len() >= n - f)
+        }));
+
+        report
+    });
+}
+
+#[bench]
+fn bench_epoch_n4_batch_1(bencher: &mut Bencher) {
+    bench_epoch(bencher, 4, 1);
+}
+
+#[bench]
+fn bench_epoch_n4_batch_1000(bencher: &mut Bencher) {
+    bench_epoch(bencher, 4, 1_000);
+}
+
+#[bench]
+fn bench_epoch_n7_batch_1(bencher: &mut Bencher) {
+    bench_epoch(bencher, 7, 1);
+}
+
+#[bench]
+fn bench_epoch_n7_batch_1000(bencher: &mut Bencher) {
+    bench_epoch(bencher, 7, 1_000);
+}
+
+#[bench]
+fn bench_epoch_n10_batch_100(bencher: &mut Bencher) {
+    bench_epoch(bencher, 10, 100);
+}
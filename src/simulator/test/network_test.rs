@@ -0,0 +1,71 @@
+use crate::simulator::network::{NetworkConfig, SimNetwork};
+use atlas_common::node_id::NodeId;
+
+fn deliveries(seed: u64, config: NetworkConfig) -> Vec<(NodeId, NodeId, u32, u64)> {
+    let mut network = SimNetwork::new(seed, config);
+
+    for message in 0..64 {
+        network.send(NodeId(message % 4), NodeId((message + 1) % 4), message);
+    }
+
+    std::iter::from_fn(|| {
+        network
+            .next()
+            .map(|envelope| (envelope.from, envelope.to, envelope.message, network.now()))
+    })
+    .collect()
+}
+
+#[test]
+fn test_same_seed_same_schedule() {
+    assert_eq!(
+        deliveries(3, NetworkConfig::default()),
+        deliveries(3, NetworkConfig::default())
+    );
+}
+
+#[test]
+fn test_messages_are_reordered() {
+    let delivered = deliveries(3, NetworkConfig::default())
+        .into_iter()
+        .map(|(_, _, message, _)| message)
+        .collect::<Vec<_>>();
+
+    assert_eq!(delivered.len(), 64);
+    assert!(delivered.windows(2).any(|pair| pair[0] > pair[1]));
+
+    let mut sorted = delivered.clone();
+    sorted.sort();
+
+    assert_eq!(sorted, (0..64).collect::<Vec<_>>());
+}
+
+#[test]
+fn test_delays_are_within_bounds() {
+    let config = NetworkConfig {
+        min_delay: 5,
+        max_delay: 10,
+    };
+
+    // Every message was sent at time 0
+    assert!(
+        deliveries(11, config)
+            .iter()
+            .all(|(_, _, _, delivered_at)| (5..=10).contains(delivered_at))
+    );
+}
+
+#[test]
+fn test_fifo_without_delay_variance() {
+    let config = NetworkConfig {
+        min_delay: 1,
+        max_delay: 1,
+    };
+
+    let delivered = deliveries(0, config)
+        .into_iter()
+        .map(|(_, _, message, _)| message)
+        .collect::<Vec<_>>();
+
+    assert_eq!(delivered, (0..64).collect::<Vec<_>>());
+}
@@ -0,0 +1,198 @@
+use crate::quorum_info::quorum_info::QuorumInfo;
+use atlas_common::node_id::NodeId;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use std::fmt::Debug;
+use thiserror::Error;
+use threshold_crypto::{Ciphertext, DecryptionShare, PublicKeySet, SecretKeyShare};
+
+/// A trait representing a threshold public key encryption scheme.
+///
+/// Batches are encrypted under the public key of the quorum before they are reliably broadcast,
+/// and can only be decrypted once decryption shares covering `threshold` key shares have been
+/// gathered. Each member is dealt a key share for each unit of its weight, as given by
+/// [`QuorumInfo::key_shares`](crate::quorum_info::quorum_info::QuorumInfo::key_shares),
+/// and its decryption share covers all of them.
+/// This prevents a Byzantine committee from censoring requests based on their content,
+/// as the content is only revealed after the agreement has decided which batches to include.
+///
+/// Ciphertexts and decryption shares are handled as opaque bytes, so that
+/// the scheme can be selected at runtime without changing the message types of the protocol.
+pub trait ThresholdEncryptionScheme: Debug + Send + Sync {
+    /// The amount of key shares the valid decryption shares must cover to decrypt a ciphertext
+    fn threshold(&self) -> usize;
+
+    /// Encrypt the given plaintext under the public key of the quorum
+    fn encrypt(&self, plaintext: &[u8]) -> Result<Vec<u8>, TPKEError>;
+
+    /// Verify that the given ciphertext is well formed
+    fn verify_ciphertext(&self, ciphertext: &[u8]) -> bool;
+
+    /// Produce our decryption share for the given ciphertext
+    fn decryption_share(&self, ciphertext: &[u8]) -> Result<Vec<u8>, TPKEError>;
+
+    /// Verify a decryption share produced by the given node for the given ciphertext
+    fn verify_decryption_share(&self, from: NodeId, ciphertext: &[u8], share: &[u8]) -> bool;
+
+    /// Combine valid decryption shares covering at least `threshold` key shares into the plaintext
+    fn combine_shares(
+        &self,
+        ciphertext: &[u8],
+        shares: &[(NodeId, &[u8])],
+    ) -> Result<Vec<u8>, TPKEError>;
+}
+
+/// The threshold encryption scheme of Baek and Zheng over BLS12-381, which HoneyBadgerBFT
+/// encrypts its batches with.
+///
+/// The key set is dealt over the total weight of the quorum, so the decryption share of a member
+/// holds one share per key share it was dealt, following [`QuorumInfo::key_shares`].
+/// Revealing a ciphertext requires the shares of `t + 1` key shares, where `t` is the threshold
+/// the key set was generated with.
+#[derive(Debug)]
+pub struct BlsThresholdEncryption {
+    quorum_info: QuorumInfo,
+    public_keys: PublicKeySet,
+    // The key shares dealt to us, in the order of their indices
+    key_shares: Vec<SecretKeyShare>,
+}
+
+impl BlsThresholdEncryption {
+    /// The scheme of the given quorum, with the key shares dealt to us under its key set.
+    ///
+    /// Nodes which were dealt no key shares can still encrypt, verify and combine shares,
+    /// but can not produce decryption shares of their own.
+    pub fn new(
+        quorum_info: QuorumInfo,
+        public_keys: PublicKeySet,
+        key_shares: Vec<SecretKeyShare>,
+    ) -> Self {
+        Self {
+            quorum_info,
+            public_keys,
+            key_shares,
+        }
+    }
+}
+
+impl ThresholdEncryptionScheme for BlsThresholdEncryption {
+    fn threshold(&self) -> usize {
+        self.public_keys.threshold() + 1
+    }
+
+    fn encrypt(&self, plaintext: &[u8]) -> Result<Vec<u8>, TPKEError> {
+        let ciphertext = self.public_keys.public_key().encrypt(plaintext);
+
+        encode(&ciphertext).map_err(TPKEError::FailedToEncrypt)
+    }
+
+    fn verify_ciphertext(&self, ciphertext: &[u8]) -> bool {
+        decode::<Ciphertext>(ciphertext).is_some_and(|ciphertext| ciphertext.verify())
+    }
+
+    fn decryption_share(&self, ciphertext: &[u8]) -> Result<Vec<u8>, TPKEError> {
+        let ciphertext = decode::<Ciphertext>(ciphertext).ok_or(TPKEError::InvalidCiphertext)?;
+
+        if self.key_shares.is_empty() {
+            return Err(TPKEError::FailedToDecryptShare(
+                "No key shares were dealt to us".to_string(),
+            ));
+        }
+
+        let shares = self
+            .key_shares
+            .iter()
+            .map(|key_share| key_share.decrypt_share(&ciphertext))
+            .collect::<Option<Vec<_>>>()
+            .ok_or(TPKEError::InvalidCiphertext)?;
+
+        encode(&shares).map_err(TPKEError::FailedToDecryptShare)
+    }
+
+    fn verify_decryption_share(&self, from: NodeId, ciphertext: &[u8], share: &[u8]) -> bool {
+        let (Some(ciphertext), Some(shares)) = (
+            decode::<Ciphertext>(ciphertext),
+            decode::<Vec<DecryptionShare>>(share),
+        ) else {
+            return false;
+        };
+
+        let indices = self.quorum_info.key_shares(from);
+
+        // A member shares each of its key shares, and only those
+        !indices.is_empty()
+            && shares.len() == indices.len()
+            && indices.zip(&shares).all(|(index, share)| {
+                self.public_keys
+                    .public_key_share(index)
+                    .verify_decryption_share(share, &ciphertext)
+            })
+    }
+
+    fn combine_shares(
+        &self,
+        ciphertext: &[u8],
+        shares: &[(NodeId, &[u8])],
+    ) -> Result<Vec<u8>, TPKEError> {
+        let ciphertext = decode::<Ciphertext>(ciphertext).ok_or(TPKEError::InvalidCiphertext)?;
+
+        let decoded = shares
+            .iter()
+            .map(|(node, share)| {
+                decode::<Vec<DecryptionShare>>(share)
+                    .map(|shares| (self.quorum_info.key_shares(*node), shares))
+                    .ok_or_else(|| {
+                        TPKEError::FailedToCombine(format!(
+                            "Malformed decryption share from {node:?}"
+                        ))
+                    })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let key_shares = decoded
+            .iter()
+            .flat_map(|(indices, shares)| indices.clone().zip(shares))
+            .collect::<Vec<_>>();
+
+        if key_shares.len() < self.threshold() {
+            return Err(TPKEError::NotEnoughShares(
+                key_shares.len(),
+                self.threshold(),
+            ));
+        }
+
+        self.public_keys
+            .decrypt(key_shares, &ciphertext)
+            .map_err(|err| TPKEError::FailedToCombine(err.to_string()))
+    }
+}
+
+fn encode<T>(value: &T) -> Result<Vec<u8>, String>
+where
+    T: Serialize,
+{
+    bincode::serde::encode_to_vec(value, bincode::config::standard()).map_err(|err| err.to_string())
+}
+
+fn decode<T>(bytes: &[u8]) -> Option<T>
+where
+    T: DeserializeOwned,
+{
+    bincode::serde::decode_from_slice(bytes, bincode::config::standard())
+        .ok()
+        .map(|(value, _)| value)
+}
+
+#[derive(Error, Debug)]
+pub enum TPKEError {
+    #[error("The ciphertext is malformed")]
+    InvalidCiphertext,
+    #[error("Failed to produce a decryption share: {0}")]
+    FailedToDecryptShare(String),
+    #[error("Not enough decryption shares, have {0} but {1} are required")]
+    NotEnoughShares(usize, usize),
+    #[error("Failed to combine decryption shares: {0}")]
+    FailedToCombine(String),
+    #[error("Failed to encrypt the plaintext: {0}")]
+    FailedToEncrypt(String),
+}